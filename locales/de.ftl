@@ -0,0 +1,55 @@
+# German
+
+popup-muted-title = Stumm
+popup-unmuted-title = Nicht stumm
+popup-mic-off = Mikrofon aus
+popup-mic-on = Mikrofon an
+popup-mic-partial = Mikrofon { $muted }/{ $total } aus
+popup-mic-unavailable = Kein steuerbares Mikrofon
+popup-mic-failed = Stummschalten fehlgeschlagen
+popup-camera-off = Kamera aus
+popup-camera-on = Kamera an: { $names }
+
+tray-mute = Stummschalten
+tray-unmute = Stummschaltung aufheben
+tray-shortcut-unavailable = Kurzbefehl { $shortcut } nicht verfügbar
+tray-use-shortcut-instead = Stattdessen Kurzbefehl verwenden
+tray-cameras = Kameras
+tray-no-cameras = Keine Kameras
+tray-usage = Heute
+tray-usage-mic = Stumm { $muted } · Aktiv { $live }
+tray-usage-toggles = Umschaltungen: { $toggles } · Erneut stummgeschaltet: { $remutes }
+tray-usage-camera = Kamera an { $camera }
+tray-export-usage = Nutzungsbericht exportieren…
+tray-launch-at-login = Bei Anmeldung starten
+tray-show-in-dock = Im Dock anzeigen
+tray-preferences = Einstellungen…
+tray-export-diagnostics = Diagnose exportieren…
+tray-open-logs = Protokolle öffnen
+tray-exit = Beenden
+tray-tooltip = { $name } läuft · { $status }
+
+preferences-title = Mic Mute Einstellungen
+preferences-shortcut = Kurzbefehl
+preferences-shortcut-current = Stummschalten: { $shortcut }
+preferences-shortcut-recording = Neuen Kurzbefehl drücken… (Esc zum Abbrechen)
+preferences-record = Aufnehmen…
+preferences-reset = Zurücksetzen
+preferences-microphones = Stummzuschaltende Mikrofone
+preferences-no-devices = Keine Eingabegeräte gefunden
+preferences-popup = Popup
+preferences-show-camera = Kamerastatus anzeigen
+preferences-session = Sperren und Ruhezustand
+preferences-mute-on-lock = Beim Sperren des Bildschirms stummschalten
+preferences-mute-on-sleep = Vor dem Ruhezustand stummschalten
+preferences-restore-on-unlock = Vorherigen Zustand beim Entsperren wiederherstellen
+preferences-general = Allgemein
+preferences-launch-at-login = Bei Anmeldung starten
+preferences-show-in-dock = Im Dock anzeigen
+preferences-version = Version { $version } · github.com/brettinternet/mic-mute
+preferences-open-settings = Einstellungsdatei öffnen
+
+diagnostics-save-title = Diagnose exportieren
+dialog-cancel = Abbrechen
+dialog-save = Sichern
+usage-save-title = Nutzungsbericht exportieren
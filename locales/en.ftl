@@ -0,0 +1,55 @@
+# English (source locale). Every key here must exist in every other locale.
+
+popup-muted-title = Muted
+popup-unmuted-title = Unmuted
+popup-mic-off = Mic off
+popup-mic-on = Mic on
+popup-mic-partial = Mic { $muted }/{ $total } off
+popup-mic-unavailable = No controllable mic
+popup-mic-failed = Mute failed
+popup-camera-off = Camera off
+popup-camera-on = Camera on: { $names }
+
+tray-mute = Mute
+tray-unmute = Unmute
+tray-shortcut-unavailable = Shortcut { $shortcut } unavailable
+tray-use-shortcut-instead = Use Shortcut Instead
+tray-cameras = Cameras
+tray-no-cameras = No cameras
+tray-usage = Today
+tray-usage-mic = Muted { $muted } · Live { $live }
+tray-usage-toggles = Toggles: { $toggles } · Re-mutes: { $remutes }
+tray-usage-camera = Camera on { $camera }
+tray-export-usage = Export Usage Report…
+tray-launch-at-login = Launch at Login
+tray-show-in-dock = Show in Dock
+tray-preferences = Preferences…
+tray-export-diagnostics = Export Diagnostics…
+tray-open-logs = Open Logs
+tray-exit = Exit
+tray-tooltip = { $name } service is running · { $status }
+
+preferences-title = Mic Mute Preferences
+preferences-shortcut = Shortcut
+preferences-shortcut-current = Mute shortcut: { $shortcut }
+preferences-shortcut-recording = Press a new shortcut… (Esc to cancel)
+preferences-record = Record…
+preferences-reset = Reset
+preferences-microphones = Microphones to mute
+preferences-no-devices = No input devices found
+preferences-popup = Popup
+preferences-show-camera = Show camera status
+preferences-session = Lock and Sleep
+preferences-mute-on-lock = Mute when the screen locks
+preferences-mute-on-sleep = Mute before sleep
+preferences-restore-on-unlock = Restore the previous state on unlock
+preferences-general = General
+preferences-launch-at-login = Launch at Login
+preferences-show-in-dock = Show in Dock
+preferences-version = Version { $version } · github.com/brettinternet/mic-mute
+preferences-open-settings = Open Settings File
+
+diagnostics-save-title = Export Diagnostics
+dialog-cancel = Cancel
+dialog-save = Save
+usage-save-title = Export Usage Report
@@ -0,0 +1,55 @@
+# Spanish
+
+popup-muted-title = Silenciado
+popup-unmuted-title = Activado
+popup-mic-off = Micrófono apagado
+popup-mic-on = Micrófono encendido
+popup-mic-partial = Micrófono { $muted }/{ $total } apagado
+popup-mic-unavailable = Ningún micrófono controlable
+popup-mic-failed = Error al silenciar
+popup-camera-off = Cámara apagada
+popup-camera-on = Cámara encendida: { $names }
+
+tray-mute = Silenciar
+tray-unmute = Activar sonido
+tray-shortcut-unavailable = Atajo { $shortcut } no disponible
+tray-use-shortcut-instead = Usar otro atajo
+tray-cameras = Cámaras
+tray-no-cameras = No hay cámaras
+tray-usage = Hoy
+tray-usage-mic = Silenciado { $muted } · Activo { $live }
+tray-usage-toggles = Cambios: { $toggles } · Re-silenciados: { $remutes }
+tray-usage-camera = Cámara encendida { $camera }
+tray-export-usage = Exportar informe de uso…
+tray-launch-at-login = Abrir al iniciar sesión
+tray-show-in-dock = Mostrar en el Dock
+tray-preferences = Preferencias…
+tray-export-diagnostics = Exportar diagnóstico…
+tray-open-logs = Abrir registros
+tray-exit = Salir
+tray-tooltip = { $name } está en ejecución · { $status }
+
+preferences-title = Preferencias de Mic Mute
+preferences-shortcut = Atajo
+preferences-shortcut-current = Atajo para silenciar: { $shortcut }
+preferences-shortcut-recording = Pulsa un nuevo atajo… (Esc para cancelar)
+preferences-record = Grabar…
+preferences-reset = Restablecer
+preferences-microphones = Micrófonos que silenciar
+preferences-no-devices = No se encontraron dispositivos de entrada
+preferences-popup = Ventana emergente
+preferences-show-camera = Mostrar estado de la cámara
+preferences-session = Bloqueo y suspensión
+preferences-mute-on-lock = Silenciar al bloquear la pantalla
+preferences-mute-on-sleep = Silenciar antes de suspender
+preferences-restore-on-unlock = Restaurar el estado anterior al desbloquear
+preferences-general = General
+preferences-launch-at-login = Abrir al iniciar sesión
+preferences-show-in-dock = Mostrar en el Dock
+preferences-version = Versión { $version } · github.com/brettinternet/mic-mute
+preferences-open-settings = Abrir archivo de ajustes
+
+diagnostics-save-title = Exportar diagnóstico
+dialog-cancel = Cancelar
+dialog-save = Guardar
+usage-save-title = Exportar informe de uso
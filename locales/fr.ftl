@@ -0,0 +1,55 @@
+# French
+
+popup-muted-title = Muet
+popup-unmuted-title = Actif
+popup-mic-off = Micro coupé
+popup-mic-on = Micro actif
+popup-mic-partial = Micro { $muted }/{ $total } coupé
+popup-mic-unavailable = Aucun micro contrôlable
+popup-mic-failed = Échec de la coupure
+popup-camera-off = Caméra éteinte
+popup-camera-on = Caméra allumée : { $names }
+
+tray-mute = Couper le micro
+tray-unmute = Réactiver le micro
+tray-shortcut-unavailable = Raccourci { $shortcut } indisponible
+tray-use-shortcut-instead = Utiliser un autre raccourci
+tray-cameras = Caméras
+tray-no-cameras = Aucune caméra
+tray-usage = Aujourd’hui
+tray-usage-mic = Muet { $muted } · Actif { $live }
+tray-usage-toggles = Basculements : { $toggles } · Remises en sourdine : { $remutes }
+tray-usage-camera = Caméra allumée { $camera }
+tray-export-usage = Exporter le rapport d’utilisation…
+tray-launch-at-login = Ouvrir à la connexion
+tray-show-in-dock = Afficher dans le Dock
+tray-preferences = Préférences…
+tray-export-diagnostics = Exporter les diagnostics…
+tray-open-logs = Ouvrir les journaux
+tray-exit = Quitter
+tray-tooltip = { $name } est en cours d’exécution · { $status }
+
+preferences-title = Préférences de Mic Mute
+preferences-shortcut = Raccourci
+preferences-shortcut-current = Raccourci muet : { $shortcut }
+preferences-shortcut-recording = Appuyez sur un nouveau raccourci… (Échap pour annuler)
+preferences-record = Enregistrer…
+preferences-reset = Réinitialiser
+preferences-microphones = Micros à couper
+preferences-no-devices = Aucun périphérique d’entrée trouvé
+preferences-popup = Fenêtre contextuelle
+preferences-show-camera = Afficher l’état de la caméra
+preferences-session = Verrouillage et veille
+preferences-mute-on-lock = Couper le micro au verrouillage de l’écran
+preferences-mute-on-sleep = Couper le micro avant la mise en veille
+preferences-restore-on-unlock = Rétablir l’état précédent au déverrouillage
+preferences-general = Général
+preferences-launch-at-login = Ouvrir à la connexion
+preferences-show-in-dock = Afficher dans le Dock
+preferences-version = Version { $version } · github.com/brettinternet/mic-mute
+preferences-open-settings = Ouvrir le fichier de réglages
+
+diagnostics-save-title = Exporter les diagnostics
+dialog-cancel = Annuler
+dialog-save = Enregistrer
+usage-save-title = Exporter le rapport d’utilisation
@@ -0,0 +1,1054 @@
+/// What the app does in response to hotkeys, menus, timers, camera and settings
+/// changes, as a reducer from typed inputs to effects. The event loops only turn
+/// native events into `Input`s and carry out the returned `Effect`s.
+use crate::camera::CameraDevice;
+use crate::mic::{AudioBackend, MicController, MicStatus};
+use crate::settings::{LoginMechanism, PopupSettings, PopupVisibility, Settings, ShortcutConfig};
+use crate::sound::Cue;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How long the mic is polled at the active rate after input devices change, since
+/// a new device can come up live.
+const DEVICE_CHANGE_WINDOW: Duration = Duration::from_secs(5);
+/// How often the power source is checked for the battery poll rate.
+const POWER_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// How many mute changes are kept for diagnostics.
+const RECENT_EVENTS: usize = 100;
+
+/// A tray menu item, resolved from its menu id.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MenuAction {
+    ToggleMute,
+    ToggleLaunchAtLogin,
+    ToggleShowInDock,
+    Preferences,
+    ExportDiagnostics,
+    OpenLogs,
+    ExportUsage,
+    Quit,
+    /// Replace the mic shortcut with a suggested free one.
+    SuggestedShortcut(ShortcutConfig),
+}
+
+/// Requests from control interfaces such as the command line or D-Bus.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Mute,
+    Unmute,
+    Toggle,
+    /// Show the popup with the current state, as after a change.
+    ShowStatus,
+}
+
+/// Power and login session changes reported by the platform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionEvent {
+    Sleep,
+    Wake,
+    Lock,
+    Unlock,
+    /// Another user took over the display.
+    SwitchedOut,
+    SwitchedIn,
+}
+
+/// The mic state after a `SetMute` effect was carried out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MicReport {
+    pub muted: bool,
+    pub status: MicStatus,
+    /// Whether the controller keeps re-applying mute.
+    pub enforcing: bool,
+    /// Whether applying the mute state failed.
+    pub failed: bool,
+    pub device_name: Option<String>,
+}
+
+impl MicReport {
+    pub fn from_controller<B: AudioBackend>(controller: &MicController<B>, failed: bool) -> Self {
+        Self {
+            muted: controller.muted,
+            status: controller.status,
+            enforcing: controller.should_enforce_mute(),
+            failed,
+            device_name: controller.active_device_name(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Input {
+    ShortcutPressed,
+    ShortcutReleased,
+    MenuClicked(MenuAction),
+    /// Time passed; runs whatever is due.
+    Tick,
+    /// The outcome of a `SetMute` effect.
+    MicChanged(MicReport),
+    /// The outcome of a `RefreshMic` effect.
+    MicRefreshed(MicReport),
+    CameraChanged {
+        cameras: Vec<CameraDevice>,
+    },
+    /// Settings changed in the app, e.g. in Preferences, and need saving.
+    SettingsEdited(Settings),
+    /// settings.json was edited outside the app.
+    SettingsReloaded(Settings),
+    Command(Command),
+    Session(SessionEvent),
+    /// The outcome of a `CheckPowerSource` effect.
+    PowerSourceChanged {
+        on_battery: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Effect {
+    /// Toggle the mic (None) or set its mute state, then report the outcome as
+    /// `Input::MicChanged`.
+    SetMute(Option<bool>),
+    /// Re-read the mic state without changing it, e.g. to notice a new default
+    /// input, and report it as `Input::MicRefreshed`.
+    RefreshMic,
+    /// Show the mic state in the tray and popup.
+    UpdateMic {
+        muted: bool,
+        status: MicStatus,
+        device_name: Option<String>,
+    },
+    /// Show which cameras are running in the tray and popup.
+    UpdateCamera {
+        cameras: Vec<CameraDevice>,
+    },
+    PlayCue(Cue),
+    SetPopupVisible(bool),
+    /// Move the popup to the cursor's monitor if it follows the cursor.
+    DetectMonitors,
+    /// Check whether the computer runs on battery and report it as
+    /// `Input::PowerSourceChanged`.
+    CheckPowerSource,
+    /// Check settings.json for outside edits and report them as
+    /// `Input::SettingsReloaded`.
+    CheckSettingsFile,
+    /// Write `AppState::settings` to settings.json.
+    SaveSettings,
+    /// Apply `AppState::settings` to the frontend and mic controller.
+    ApplySettings,
+    SetLoginItem {
+        enabled: bool,
+        mechanism: LoginMechanism,
+    },
+    SetDockVisible(bool),
+    OpenPreferences,
+    /// Ask where to save a diagnostics bundle and write it.
+    ExportDiagnostics,
+    /// Open the current log file.
+    OpenLogs,
+    /// Ask where to save a usage report and write it.
+    ExportUsage,
+    /// Restore the mic and exit.
+    Quit,
+}
+
+/// The poll intervals in effect, reported in diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PollIntervals {
+    pub mic: Duration,
+    pub camera: Duration,
+    pub settings: Duration,
+}
+
+/// A change in the mic state, kept for diagnostics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MuteEvent {
+    pub at: Instant,
+    pub muted: bool,
+    pub status: MicStatus,
+    pub failed: bool,
+    pub device_name: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Visibility {
+    Show,
+    ShowThenHide(Duration),
+    Hide,
+    Unchanged,
+}
+
+fn visibility_after_update(settings: &PopupSettings, muted: bool, changed: bool) -> Visibility {
+    if !changed {
+        return if settings.visible_at_rest(muted) {
+            Visibility::Unchanged
+        } else if settings.hide_delay(muted).is_some() {
+            // A pending hide will take care of it
+            Visibility::Unchanged
+        } else {
+            Visibility::Hide
+        };
+    }
+    match settings.visibility {
+        PopupVisibility::Never => Visibility::Hide,
+        PopupVisibility::Always => Visibility::Show,
+        PopupVisibility::OnChange => match settings.hide_delay(muted) {
+            Some(delay) => Visibility::ShowThenHide(delay),
+            None => Visibility::Show,
+        },
+    }
+}
+
+pub struct AppState {
+    settings: Settings,
+    mic_muted: bool,
+    mic_status: MicStatus,
+    enforcing: bool,
+    /// Whether the last attempt to apply the mute state failed.
+    mute_failed: bool,
+    cameras: Vec<CameraDevice>,
+    next_poll: Instant,
+    next_settings_check: Instant,
+    /// When an on-change popup hides, unless it's shown again first.
+    popup_hide_at: Option<Instant>,
+    /// Whether the mic was muted before a lock or sleep muted it.
+    muted_before_away: Option<bool>,
+    /// The default input device, to notice devices changing.
+    device_name: Option<String>,
+    devices_changed_at: Option<Instant>,
+    on_battery: bool,
+    next_power_check: Instant,
+    /// Mute changes, failures and device switches, oldest first.
+    recent_events: VecDeque<MuteEvent>,
+}
+
+impl AppState {
+    pub fn new(
+        settings: Settings,
+        mic: &MicReport,
+        cameras: Vec<CameraDevice>,
+        now: Instant,
+    ) -> Self {
+        Self {
+            mic_muted: mic.muted,
+            mic_status: mic.status,
+            enforcing: mic.enforcing,
+            mute_failed: false,
+            cameras,
+            // Poll straight away
+            next_poll: now,
+            next_settings_check: now + settings.polling.settings(),
+            popup_hide_at: None,
+            muted_before_away: None,
+            device_name: mic.device_name.clone(),
+            devices_changed_at: None,
+            on_battery: false,
+            next_power_check: now,
+            recent_events: VecDeque::new(),
+            settings,
+        }
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn recent_events(&self) -> impl Iterator<Item = &MuteEvent> {
+        self.recent_events.iter()
+    }
+
+    pub fn poll_intervals(&self, now: Instant) -> PollIntervals {
+        PollIntervals {
+            mic: self.mic_poll_interval(now),
+            camera: self.settings.polling.camera(),
+            settings: self.settings.polling.settings(),
+        }
+    }
+
+    /// Fast while mute is enforced or devices just changed, slower on battery and
+    /// slowest while there's nothing to enforce.
+    fn mic_poll_interval(&self, now: Instant) -> Duration {
+        let polling = &self.settings.polling;
+        let devices_changed = self
+            .devices_changed_at
+            .is_some_and(|at| now < at + DEVICE_CHANGE_WINDOW);
+        if !polling.adaptive || devices_changed || (self.enforcing && !self.on_battery) {
+            polling.mic_active()
+        } else if self.enforcing {
+            polling.mic_battery()
+        } else {
+            polling.mic_idle()
+        }
+    }
+
+    /// Bring the next mic poll forward when the interval just got shorter.
+    fn reschedule_poll(&mut self, now: Instant) {
+        self.next_poll = self.next_poll.min(now + self.mic_poll_interval(now));
+    }
+
+    /// When the next timed input is due, so loops sleep rather than spin.
+    pub fn next_deadline(&self) -> Instant {
+        let deadline = self
+            .next_poll
+            .min(self.next_settings_check)
+            .min(self.next_power_check);
+        self.popup_hide_at
+            .map_or(deadline, |hide_at| deadline.min(hide_at))
+    }
+
+    pub fn handle(&mut self, now: Instant, input: Input) -> Vec<Effect> {
+        match input {
+            Input::ShortcutPressed => vec![Effect::SetMute(None)],
+            // Reserved for push-to-talk
+            Input::ShortcutReleased => vec![],
+            Input::MenuClicked(action) => self.menu_clicked(action),
+            Input::Tick => self.tick(now),
+            Input::MicChanged(report) => self.mic_changed(now, report, true),
+            Input::MicRefreshed(report) => self.mic_changed(now, report, false),
+            Input::CameraChanged { cameras } => {
+                if cameras == self.cameras {
+                    return vec![];
+                }
+                self.cameras = cameras.clone();
+                vec![Effect::UpdateCamera { cameras }]
+            }
+            Input::SettingsEdited(settings) => {
+                let mut effects = vec![Effect::SaveSettings];
+                effects.extend(self.replace_settings(now, settings));
+                effects
+            }
+            Input::SettingsReloaded(settings) => self.replace_settings(now, settings),
+            Input::Command(command) => match command {
+                Command::Mute => vec![Effect::SetMute(Some(true))],
+                Command::Unmute => vec![Effect::SetMute(Some(false))],
+                Command::Toggle => vec![Effect::SetMute(None)],
+                Command::ShowStatus => {
+                    let visibility =
+                        visibility_after_update(&self.settings.popup, self.mic_muted, true);
+                    self.set_popup(now, visibility).into_iter().collect()
+                }
+            },
+            Input::Session(event) => self.session_changed(now, event),
+            Input::PowerSourceChanged { on_battery } => {
+                self.on_battery = on_battery;
+                self.reschedule_poll(now);
+                vec![]
+            }
+        }
+    }
+
+    fn menu_clicked(&mut self, action: MenuAction) -> Vec<Effect> {
+        match action {
+            MenuAction::ToggleMute => vec![Effect::SetMute(None)],
+            MenuAction::ToggleLaunchAtLogin => {
+                self.settings.launch_at_login = !self.settings.launch_at_login;
+                vec![
+                    Effect::SaveSettings,
+                    Effect::SetLoginItem {
+                        enabled: self.settings.launch_at_login,
+                        mechanism: self.settings.login_mechanism,
+                    },
+                ]
+            }
+            MenuAction::ToggleShowInDock => {
+                self.settings.show_in_dock = !self.settings.show_in_dock;
+                vec![
+                    Effect::SaveSettings,
+                    Effect::SetDockVisible(self.settings.show_in_dock),
+                ]
+            }
+            MenuAction::Preferences => vec![Effect::OpenPreferences],
+            MenuAction::ExportDiagnostics => vec![Effect::ExportDiagnostics],
+            MenuAction::OpenLogs => vec![Effect::OpenLogs],
+            MenuAction::ExportUsage => vec![Effect::ExportUsage],
+            MenuAction::Quit => vec![Effect::Quit],
+            MenuAction::SuggestedShortcut(shortcut) => {
+                self.settings.mic_shortcut = shortcut;
+                vec![Effect::SaveSettings, Effect::ApplySettings]
+            }
+        }
+    }
+
+    fn tick(&mut self, now: Instant) -> Vec<Effect> {
+        let mut effects = vec![];
+        if self.popup_hide_at.is_some_and(|hide_at| now >= hide_at) {
+            self.popup_hide_at = None;
+            effects.push(Effect::SetPopupVisible(false));
+        }
+        if now >= self.next_settings_check {
+            self.next_settings_check = now + self.settings.polling.settings();
+            effects.push(Effect::CheckSettingsFile);
+        }
+        if now >= self.next_power_check {
+            self.next_power_check = now + POWER_CHECK_INTERVAL;
+            effects.push(Effect::CheckPowerSource);
+        }
+        if now >= self.next_poll {
+            self.next_poll = now + self.mic_poll_interval(now);
+            effects.push(Effect::DetectMonitors);
+            if self.enforcing {
+                effects.push(Effect::SetMute(Some(true)));
+            } else if !self.mute_failed {
+                // A failed toggle stays reported until the next attempt
+                effects.push(Effect::RefreshMic);
+            }
+        }
+        effects
+    }
+
+    /// Record a mic report. `applied` is set when the report follows a `SetMute`, which
+    /// always redraws; a refresh only redraws when something shown changed.
+    fn mic_changed(&mut self, now: Instant, report: MicReport, applied: bool) -> Vec<Effect> {
+        let mut effects = vec![];
+        let mute_changed = report.muted != self.mic_muted;
+        let changed = mute_changed || report.status != self.mic_status;
+        let shown_changed = changed || report.device_name != self.device_name;
+        if shown_changed || report.failed != self.mute_failed {
+            if self.recent_events.len() >= RECENT_EVENTS {
+                self.recent_events.pop_front();
+            }
+            self.recent_events.push_back(MuteEvent {
+                at: now,
+                muted: report.muted,
+                status: report.status,
+                failed: report.failed,
+                device_name: report.device_name.clone(),
+            });
+        }
+        self.mic_muted = report.muted;
+        self.mic_status = report.status;
+        self.enforcing = report.enforcing;
+        if report.device_name != self.device_name {
+            self.device_name = report.device_name.clone();
+            self.devices_changed_at = Some(now);
+        }
+        self.reschedule_poll(now);
+
+        // Sound the failure cue once when failures start rather than on every
+        // enforcement poll
+        if report.failed && !self.mute_failed {
+            effects.push(Effect::PlayCue(Cue::EnforcementFailed));
+        }
+        self.mute_failed = report.failed;
+        if mute_changed {
+            effects.push(Effect::PlayCue(if report.muted {
+                Cue::Mute
+            } else {
+                Cue::Unmute
+            }));
+        }
+
+        if applied || shown_changed {
+            effects.push(Effect::UpdateMic {
+                muted: report.muted,
+                status: report.status,
+                device_name: report.device_name,
+            });
+        }
+        let visibility = visibility_after_update(&self.settings.popup, report.muted, changed);
+        effects.extend(self.set_popup(now, visibility));
+        effects
+    }
+
+    /// Schedule any hide and return the effect that applies `visibility` now.
+    fn set_popup(&mut self, now: Instant, visibility: Visibility) -> Option<Effect> {
+        match visibility {
+            Visibility::Show => {
+                self.popup_hide_at = None;
+                Some(Effect::SetPopupVisible(true))
+            }
+            Visibility::ShowThenHide(delay) => {
+                self.popup_hide_at = Some(now + delay);
+                Some(Effect::SetPopupVisible(true))
+            }
+            Visibility::Hide => {
+                self.popup_hide_at = None;
+                Some(Effect::SetPopupVisible(false))
+            }
+            Visibility::Unchanged => None,
+        }
+    }
+
+    fn session_changed(&mut self, now: Instant, event: SessionEvent) -> Vec<Effect> {
+        match event {
+            SessionEvent::Sleep if self.settings.mute_on_sleep => self.mute_while_away(),
+            SessionEvent::Lock | SessionEvent::SwitchedOut if self.settings.mute_on_lock => {
+                self.mute_while_away()
+            }
+            SessionEvent::Wake => {
+                // USB mics re-enumerate unmuted on wake, so enforce now rather than
+                // at the next poll, and keep polling fast while they settle
+                self.devices_changed_at = Some(now);
+                self.next_poll = now;
+                self.tick(now)
+            }
+            SessionEvent::Unlock | SessionEvent::SwitchedIn => {
+                match self.muted_before_away.take() {
+                    Some(false) if self.settings.restore_on_unlock => {
+                        vec![Effect::SetMute(Some(false))]
+                    }
+                    _ => vec![],
+                }
+            }
+            _ => vec![],
+        }
+    }
+
+    fn mute_while_away(&mut self) -> Vec<Effect> {
+        // A lock that follows sleep keeps the state from before the sleep
+        self.muted_before_away.get_or_insert(self.mic_muted);
+        vec![Effect::SetMute(Some(true))]
+    }
+
+    fn replace_settings(&mut self, now: Instant, settings: Settings) -> Vec<Effect> {
+        let popup_changed = settings.popup != self.settings.popup;
+        self.settings = settings;
+        self.reschedule_poll(now);
+        self.next_settings_check = self
+            .next_settings_check
+            .min(now + self.settings.polling.settings());
+        let mut effects = vec![Effect::ApplySettings];
+        if popup_changed {
+            self.popup_hide_at = None;
+            effects.push(Effect::SetPopupVisible(
+                self.settings.popup.visible_at_rest(self.mic_muted),
+            ));
+        }
+        effects
+    }
+}
+
+/// Carry out a `SetMute` effect, returning the input that reports its outcome.
+pub fn set_mute<B: AudioBackend>(controller: &mut MicController<B>, state: Option<bool>) -> Input {
+    let result = controller.toggle(state).map(|_| ());
+    if let Err(err) = &result {
+        log::error!("Failed to update microphone mute state: {}", err);
+    }
+    Input::MicChanged(MicReport::from_controller(controller, result.is_err()))
+}
+
+/// Carry out a `RefreshMic` effect, returning the input that reports the state.
+/// A failed read shows as `MicStatus::Failed` rather than as a failed mute.
+pub fn refresh_mic<B: AudioBackend>(controller: &mut MicController<B>) -> Input {
+    if let Err(err) = controller.refresh() {
+        log::error!("Failed to read microphone mute state: {}", err);
+    }
+    Input::MicRefreshed(MicReport::from_controller(controller, false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mic::fake::{Device, FakeBackend};
+    use crate::settings::PollingSettings;
+
+    const ACTIVE_POLL: Duration = Duration::from_millis(200);
+
+    /// Drives the state like the event loops do, carrying out `SetMute` and
+    /// `RefreshMic` against the controller and returning every other effect.
+    struct Harness {
+        state: AppState,
+        controller: MicController<FakeBackend>,
+        now: Instant,
+    }
+
+    impl Harness {
+        fn new(devices: Vec<(u32, Device)>, settings: Settings) -> Self {
+            let controller =
+                MicController::with_backend(FakeBackend::with_devices(devices)).unwrap();
+            let now = Instant::now();
+            let report = MicReport::from_controller(&controller, false);
+            Self {
+                state: AppState::new(settings, &report, vec![], now),
+                controller,
+                now,
+            }
+        }
+
+        fn send(&mut self, input: Input) -> Vec<Effect> {
+            let mut effects = vec![];
+            let mut inputs = vec![input];
+            while let Some(input) = inputs.pop() {
+                for effect in self.state.handle(self.now, input) {
+                    match effect {
+                        Effect::SetMute(state) => {
+                            inputs.push(set_mute(&mut self.controller, state))
+                        }
+                        Effect::RefreshMic => inputs.push(refresh_mic(&mut self.controller)),
+                        effect => effects.push(effect),
+                    }
+                }
+            }
+            effects
+        }
+
+        fn advance(&mut self, duration: Duration) -> Vec<Effect> {
+            self.now += duration;
+            self.send(Input::Tick)
+        }
+    }
+
+    fn built_in() -> Vec<(u32, Device)> {
+        vec![(1, Device::native("Built-in", false))]
+    }
+
+    #[test]
+    fn on_change_shows_then_hides_unmuted_popup() {
+        let settings = PopupSettings::default();
+
+        assert_eq!(
+            visibility_after_update(&settings, false, true),
+            Visibility::ShowThenHide(Duration::from_secs(1))
+        );
+        assert_eq!(
+            visibility_after_update(&settings, true, true),
+            Visibility::Show
+        );
+        assert_eq!(
+            visibility_after_update(&settings, true, false),
+            Visibility::Unchanged
+        );
+    }
+
+    #[test]
+    fn never_and_always_ignore_changes() {
+        let never = PopupSettings {
+            visibility: PopupVisibility::Never,
+            ..PopupSettings::default()
+        };
+        let always = PopupSettings {
+            visibility: PopupVisibility::Always,
+            ..PopupSettings::default()
+        };
+
+        assert_eq!(
+            visibility_after_update(&never, true, true),
+            Visibility::Hide
+        );
+        assert_eq!(
+            visibility_after_update(&never, true, false),
+            Visibility::Hide
+        );
+        assert_eq!(
+            visibility_after_update(&always, false, true),
+            Visibility::Show
+        );
+        assert_eq!(
+            visibility_after_update(&always, false, false),
+            Visibility::Unchanged
+        );
+    }
+
+    #[test]
+    fn shortcut_mutes_and_shows_popup() {
+        let mut app = Harness::new(built_in(), Settings::default());
+
+        let effects = app.send(Input::ShortcutPressed);
+
+        assert!(app.controller.muted);
+        assert_eq!(
+            effects,
+            vec![
+                Effect::PlayCue(Cue::Mute),
+                Effect::UpdateMic {
+                    muted: true,
+                    status: MicStatus::Muted,
+                    device_name: Some("Built-in".to_string()),
+                },
+                Effect::SetPopupVisible(true),
+            ]
+        );
+        assert!(app.send(Input::ShortcutReleased).is_empty());
+    }
+
+    #[test]
+    fn unmuted_popup_hides_after_delay() {
+        let mut app = Harness::new(built_in(), Settings::default());
+        app.send(Input::ShortcutPressed);
+
+        let effects = app.send(Input::MenuClicked(MenuAction::ToggleMute));
+        assert!(!app.controller.muted);
+        assert!(effects.contains(&Effect::PlayCue(Cue::Unmute)));
+        assert!(effects.contains(&Effect::SetPopupVisible(true)));
+        assert!(app.state.next_deadline() <= app.now + Duration::from_secs(1));
+
+        assert!(!app
+            .advance(Duration::from_millis(500))
+            .contains(&Effect::SetPopupVisible(false)));
+        assert!(app
+            .advance(Duration::from_millis(500))
+            .contains(&Effect::SetPopupVisible(false)));
+        assert!(!app
+            .advance(Duration::from_secs(1))
+            .contains(&Effect::SetPopupVisible(false)));
+    }
+
+    #[test]
+    fn show_status_flashes_popup_without_changing_mute() {
+        let mut app = Harness::new(built_in(), Settings::default());
+
+        let effects = app.send(Input::Command(Command::ShowStatus));
+
+        assert!(!app.controller.muted);
+        assert_eq!(effects, vec![Effect::SetPopupVisible(true)]);
+        assert!(app
+            .advance(Duration::from_secs(1))
+            .contains(&Effect::SetPopupVisible(false)));
+    }
+
+    #[test]
+    fn muting_again_cancels_pending_hide() {
+        let mut app = Harness::new(built_in(), Settings::default());
+        app.send(Input::Command(Command::Mute));
+        app.send(Input::Command(Command::Unmute));
+        app.advance(Duration::from_millis(500));
+
+        app.send(Input::Command(Command::Mute));
+
+        assert!(!app
+            .advance(Duration::from_secs(2))
+            .contains(&Effect::SetPopupVisible(false)));
+    }
+
+    #[test]
+    fn poll_enforces_mute_only_while_muted() {
+        let mut app = Harness::new(built_in(), Settings::default());
+        assert!(!app
+            .state
+            .handle(app.now, Input::Tick)
+            .contains(&Effect::SetMute(Some(true))));
+
+        app.send(Input::ShortcutPressed);
+        app.now += ACTIVE_POLL;
+        assert!(app
+            .state
+            .handle(app.now, Input::Tick)
+            .contains(&Effect::SetMute(Some(true))));
+        // Not due again until the next interval
+        assert!(!app
+            .state
+            .handle(app.now, Input::Tick)
+            .contains(&Effect::SetMute(Some(true))));
+    }
+
+    #[test]
+    fn failure_cue_sounds_once_until_mute_recovers() {
+        let mut device = Device::native("Built-in", false);
+        device.fail_set_mute = true;
+        let mut app = Harness::new(vec![(1, device)], Settings::default());
+
+        let effects = app.send(Input::ShortcutPressed);
+        assert!(effects.contains(&Effect::PlayCue(Cue::EnforcementFailed)));
+        let effects = app.advance(ACTIVE_POLL);
+        assert!(!effects.contains(&Effect::PlayCue(Cue::EnforcementFailed)));
+        assert!(effects
+            .iter()
+            .any(|effect| matches!(effect, Effect::UpdateMic { muted: false, .. })));
+    }
+
+    #[test]
+    fn mute_changes_and_failures_are_recorded_once() {
+        let mut app = Harness::new(built_in(), Settings::default());
+
+        app.send(Input::ShortcutPressed);
+        app.advance(ACTIVE_POLL);
+        app.advance(ACTIVE_POLL);
+        app.controller
+            .backend_mut()
+            .device_mut(1)
+            .unwrap()
+            .fail_set_mute = true;
+        app.send(Input::ShortcutPressed);
+        app.advance(ACTIVE_POLL);
+
+        let events: Vec<_> = app
+            .state
+            .recent_events()
+            .map(|event| (event.muted, event.failed))
+            .collect();
+        assert_eq!(events, vec![(true, false), (true, true)]);
+    }
+
+    #[test]
+    fn camera_changes_update_only_when_devices_change() {
+        let mut app = Harness::new(built_in(), Settings::default());
+        let camera = |running| CameraDevice {
+            id: "built-in".to_string(),
+            name: "FaceTime HD Camera".to_string(),
+            running,
+        };
+
+        assert!(app
+            .send(Input::CameraChanged { cameras: vec![] })
+            .is_empty());
+        assert_eq!(
+            app.send(Input::CameraChanged {
+                cameras: vec![camera(false)]
+            }),
+            vec![Effect::UpdateCamera {
+                cameras: vec![camera(false)]
+            }]
+        );
+        assert_eq!(
+            app.send(Input::CameraChanged {
+                cameras: vec![camera(true)]
+            }),
+            vec![Effect::UpdateCamera {
+                cameras: vec![camera(true)]
+            }]
+        );
+        assert!(app
+            .send(Input::CameraChanged {
+                cameras: vec![camera(true)]
+            })
+            .is_empty());
+    }
+
+    #[test]
+    fn settings_file_is_checked_every_two_seconds() {
+        let mut app = Harness::new(built_in(), Settings::default());
+
+        assert!(!app
+            .advance(Duration::from_secs(1))
+            .contains(&Effect::CheckSettingsFile));
+        assert!(app
+            .advance(Duration::from_secs(1))
+            .contains(&Effect::CheckSettingsFile));
+        assert!(!app
+            .advance(Duration::from_secs(1))
+            .contains(&Effect::CheckSettingsFile));
+    }
+
+    #[test]
+    fn settings_changes_apply_and_reset_popup_only_when_popup_settings_change() {
+        let mut app = Harness::new(built_in(), Settings::default());
+
+        let mut settings = Settings {
+            excluded_devices: vec!["Capture Card".to_string()],
+            ..Settings::default()
+        };
+        assert_eq!(
+            app.send(Input::SettingsReloaded(settings.clone())),
+            vec![Effect::ApplySettings]
+        );
+        assert_eq!(app.state.settings().excluded_devices, vec!["Capture Card"]);
+
+        settings.popup.visibility = PopupVisibility::Always;
+        assert_eq!(
+            app.send(Input::SettingsEdited(settings)),
+            vec![
+                Effect::SaveSettings,
+                Effect::ApplySettings,
+                Effect::SetPopupVisible(true)
+            ]
+        );
+    }
+
+    #[test]
+    fn menu_toggles_update_and_save_settings() {
+        let mut app = Harness::new(built_in(), Settings::default());
+
+        assert_eq!(
+            app.send(Input::MenuClicked(MenuAction::ToggleLaunchAtLogin)),
+            vec![
+                Effect::SaveSettings,
+                Effect::SetLoginItem {
+                    enabled: true,
+                    mechanism: LoginMechanism::Autostart,
+                },
+            ]
+        );
+        assert_eq!(
+            app.send(Input::MenuClicked(MenuAction::ToggleShowInDock)),
+            vec![Effect::SaveSettings, Effect::SetDockVisible(true)]
+        );
+        let shortcut = ShortcutConfig {
+            modifiers: vec![],
+            key: "F13".to_string(),
+        };
+        assert_eq!(
+            app.send(Input::MenuClicked(MenuAction::SuggestedShortcut(shortcut))),
+            vec![Effect::SaveSettings, Effect::ApplySettings]
+        );
+        assert_eq!(app.state.settings().mic_shortcut.key, "F13");
+        assert_eq!(
+            app.send(Input::MenuClicked(MenuAction::Quit)),
+            vec![Effect::Quit]
+        );
+    }
+
+    #[test]
+    fn commands_set_explicit_states() {
+        let mut app = Harness::new(built_in(), Settings::default());
+
+        app.send(Input::Command(Command::Unmute));
+        assert!(!app.controller.muted);
+        app.send(Input::Command(Command::Mute));
+        app.send(Input::Command(Command::Mute));
+        assert!(app.controller.muted);
+        app.send(Input::Command(Command::Toggle));
+        assert!(!app.controller.muted);
+    }
+
+    fn away_settings(mute_on_lock: bool, mute_on_sleep: bool) -> Settings {
+        Settings {
+            mute_on_lock,
+            mute_on_sleep,
+            restore_on_unlock: true,
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn lock_mutes_and_unlock_restores() {
+        let mut app = Harness::new(built_in(), away_settings(true, false));
+
+        app.send(Input::Session(SessionEvent::Lock));
+        assert!(app.controller.muted);
+        app.send(Input::Session(SessionEvent::Unlock));
+        assert!(!app.controller.muted);
+    }
+
+    #[test]
+    fn unlock_keeps_mic_muted_if_it_was_muted_before_lock() {
+        let mut app = Harness::new(built_in(), away_settings(true, false));
+        app.send(Input::Command(Command::Mute));
+
+        app.send(Input::Session(SessionEvent::SwitchedOut));
+        app.send(Input::Session(SessionEvent::SwitchedIn));
+
+        assert!(app.controller.muted);
+    }
+
+    #[test]
+    fn unlock_restores_state_from_before_sleep() {
+        let mut app = Harness::new(built_in(), away_settings(true, true));
+
+        app.send(Input::Session(SessionEvent::Sleep));
+        app.send(Input::Session(SessionEvent::Lock));
+        app.send(Input::Session(SessionEvent::Wake));
+        assert!(app.controller.muted);
+        app.send(Input::Session(SessionEvent::Unlock));
+
+        assert!(!app.controller.muted);
+    }
+
+    #[test]
+    fn lock_and_sleep_are_ignored_unless_enabled() {
+        let mut app = Harness::new(built_in(), Settings::default());
+
+        assert!(app.send(Input::Session(SessionEvent::Sleep)).is_empty());
+        assert!(app.send(Input::Session(SessionEvent::Lock)).is_empty());
+        assert!(!app.controller.muted);
+    }
+
+    #[test]
+    fn wake_enforces_mute_immediately() {
+        let mut app = Harness::new(built_in(), Settings::default());
+        app.send(Input::ShortcutPressed);
+        app.advance(ACTIVE_POLL);
+        // A re-enumerated device comes back live
+        app.controller.backend_mut().device_mut(1).unwrap().mute = Some(false);
+
+        app.send(Input::Session(SessionEvent::Wake));
+
+        assert_eq!(
+            app.controller.backend_mut().device(1).unwrap().mute,
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn mic_poll_backs_off_when_unmuted_or_on_battery() {
+        let mut app = Harness::new(built_in(), Settings::default());
+        let polling = PollingSettings::default();
+        assert_eq!(app.state.poll_intervals(app.now).mic, polling.mic_idle());
+
+        app.send(Input::Command(Command::Mute));
+        assert_eq!(app.state.poll_intervals(app.now).mic, polling.mic_active());
+
+        app.send(Input::PowerSourceChanged { on_battery: true });
+        assert_eq!(app.state.poll_intervals(app.now).mic, polling.mic_battery());
+    }
+
+    #[test]
+    fn mic_poll_stays_fast_unless_adaptive() {
+        let settings = Settings {
+            polling: PollingSettings {
+                adaptive: false,
+                ..PollingSettings::default()
+            },
+            ..Settings::default()
+        };
+        let app = Harness::new(built_in(), settings);
+
+        assert_eq!(app.state.poll_intervals(app.now).mic, ACTIVE_POLL);
+    }
+
+    #[test]
+    fn mic_poll_speeds_up_after_devices_change() {
+        let mut app = Harness::new(
+            vec![
+                (1, Device::native("Built-in", false)),
+                (2, Device::native("USB Mic", false)),
+            ],
+            Settings::default(),
+        );
+        app.controller.backend_mut().default_input = Some(2);
+
+        app.send(Input::Command(Command::Unmute));
+        assert_eq!(app.state.poll_intervals(app.now).mic, ACTIVE_POLL);
+
+        app.advance(DEVICE_CHANGE_WINDOW);
+        assert_eq!(
+            app.state.poll_intervals(app.now).mic,
+            PollingSettings::default().mic_idle()
+        );
+    }
+
+    #[test]
+    fn idle_poll_notices_default_device_change_and_speeds_up() {
+        let mut app = Harness::new(
+            vec![
+                (1, Device::native("Built-in", false)),
+                (2, Device::native("USB Mic", false)),
+            ],
+            Settings::default(),
+        );
+        let idle = PollingSettings::default().mic_idle();
+        app.advance(idle);
+        assert_eq!(app.state.poll_intervals(app.now).mic, idle);
+
+        // Plugged in while unmuted, with nothing toggled since
+        app.controller.backend_mut().default_input = Some(2);
+        let effects = app.advance(idle);
+
+        assert!(effects.iter().any(|effect| matches!(
+            effect,
+            Effect::UpdateMic { device_name: Some(name), .. } if name == "USB Mic"
+        )));
+        assert_eq!(app.state.poll_intervals(app.now).mic, ACTIVE_POLL);
+    }
+
+    #[test]
+    fn unchanged_idle_refresh_does_not_redraw() {
+        let mut app = Harness::new(built_in(), Settings::default());
+        let idle = PollingSettings::default().mic_idle();
+        app.advance(idle);
+
+        let effects = app.advance(idle);
+
+        assert!(!effects
+            .iter()
+            .any(|effect| matches!(effect, Effect::UpdateMic { .. })));
+    }
+
+    #[test]
+    fn power_source_is_checked_on_start() {
+        let mut app = Harness::new(built_in(), Settings::default());
+
+        assert!(app.send(Input::Tick).contains(&Effect::CheckPowerSource));
+        assert!(!app
+            .advance(Duration::from_secs(1))
+            .contains(&Effect::CheckPowerSource));
+    }
+}
@@ -1,217 +1,453 @@
-use anyhow::Result;
-use cocoa::base::{id, nil};
-use cocoa::foundation::NSString;
+use anyhow::{bail, Result};
 use log::{error, trace};
-use std::ffi::c_void;
-use std::mem;
-
-#[link(name = "AVFoundation", kind = "framework")]
-extern "C" {}
-
-// CMIO constants
-const K_CMIO_OBJECT_PROPERTY_SCOPE_GLOBAL: u32 = 0x676c6f62; // 'glob'
-const K_CMIO_OBJECT_PROPERTY_ELEMENT_MAIN: u32 = 0;
-const K_CMIO_DEVICE_PROPERTY_IS_RUNNING_SOMEWHERE: u32 = 0x676F6E65; // 'gone'
-                                                                     // System object holds the list of all CMIO devices
-const K_CMIO_HARDWARE_OBJECT_SYSTEM: u32 = 1;
-const K_CMIO_HARDWARE_PROPERTY_DEVICES: u32 = 0x64657623; // 'dev#'
-
-type CMIOObjectID = u32;
-
-#[repr(C)]
-struct CMIOObjectPropertyAddress {
-    m_selector: u32,
-    m_scope: u32,
-    m_element: u32,
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub use crate::platform::SystemCameraBackend;
+
+/// A camera and whether any app is using it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraDevice {
+    /// The capture device's unique ID, or the system device ID when it has none.
+    pub id: String,
+    pub name: String,
+    pub running: bool,
+}
+
+/// Names of the cameras in use, in the order they're listed.
+pub fn active_names(cameras: &[CameraDevice]) -> Vec<String> {
+    cameras
+        .iter()
+        .filter(|camera| camera.running)
+        .map(|camera| camera.name.clone())
+        .collect()
 }
 
-#[link(name = "CoreMediaIO", kind = "framework")]
-extern "C" {
-    fn CMIOObjectGetPropertyData(
-        object_id: CMIOObjectID,
-        address: *const CMIOObjectPropertyAddress,
-        qualifier_data_size: u32,
-        qualifier_data: *const c_void,
-        in_data_size: u32,
-        out_data_size: *mut u32,
-        out_data: *mut c_void,
-    ) -> i32;
-
-    fn CMIOObjectGetPropertyDataSize(
-        object_id: CMIOObjectID,
-        address: *const CMIOObjectPropertyAddress,
-        qualifier_data_size: u32,
-        qualifier_data: *const c_void,
-        out_data_size: *mut u32,
-    ) -> i32;
+/// Cameras through two views: the capture API apps record with (AVFoundation on
+/// macOS, V4L2 device nodes on Linux), and a lower-level system device API that
+/// needs no camera permission (CoreMediaIO on macOS, none on Linux).
+pub trait CameraBackend {
+    /// Unique IDs of the capture devices, or None when camera permission is missing.
+    fn capture_device_ids(&self) -> Result<Option<Vec<String>>>;
+    fn capture_device_name(&self, unique_id: &str) -> Result<String>;
+    fn is_in_use_by_another_app(&self, unique_id: &str) -> Result<bool>;
+    fn system_device_ids(&self) -> Result<Vec<u32>>;
+    /// None when the device can't report it.
+    fn is_running_somewhere(&self, device_id: u32) -> Result<Option<bool>>;
+    /// The unique ID of the capture device a system device is, when it reports one.
+    fn system_device_uid(&self, _device_id: u32) -> Result<Option<String>> {
+        Ok(None)
+    }
+    fn system_device_name(&self, _device_id: u32) -> Result<String> {
+        Ok(String::new())
+    }
+    /// Call `on_change`, from any thread, whenever a camera is added, removed,
+    /// starts or stops. Fails when the backend can't notify, so callers poll instead.
+    fn watch(&self, _on_change: Box<dyn Fn() + Send + Sync>) -> Result<()> {
+        bail!("Camera change notifications are unsupported")
+    }
 }
 
-pub struct CameraController {
+pub struct CameraController<B = SystemCameraBackend> {
     pub muted: bool,
+    /// Whether missing camera permission was logged, so it's only logged once.
+    no_permission_logged: AtomicBool,
+    backend: B,
 }
 
-impl CameraController {
+impl CameraController<SystemCameraBackend> {
     pub fn new() -> Result<Self> {
-        let mut controller = Self { muted: false };
+        Self::with_backend(SystemCameraBackend::default())
+    }
+}
+
+impl<B: CameraBackend> CameraController<B> {
+    pub(crate) fn with_backend(backend: B) -> Result<Self> {
+        let mut controller = Self {
+            muted: false,
+            no_permission_logged: AtomicBool::new(false),
+            backend,
+        };
         // muted=false means "camera is active"; muted=true means "camera is idle/off"
         controller.muted = !controller.is_running_anywhere().unwrap_or(false);
         Ok(controller)
     }
 
-    /// Enumerate all CMIO device IDs directly from the CMIO system object.
-    /// This does not require camera TCC permission.
-    fn get_cmio_device_ids_system(&self) -> Vec<CMIOObjectID> {
-        let address = CMIOObjectPropertyAddress {
-            m_selector: K_CMIO_HARDWARE_PROPERTY_DEVICES,
-            m_scope: K_CMIO_OBJECT_PROPERTY_SCOPE_GLOBAL,
-            m_element: K_CMIO_OBJECT_PROPERTY_ELEMENT_MAIN,
-        };
-        let mut data_size: u32 = 0;
-        let status = unsafe {
-            CMIOObjectGetPropertyDataSize(
-                K_CMIO_HARDWARE_OBJECT_SYSTEM,
-                &address,
-                0,
-                std::ptr::null(),
-                &mut data_size,
-            )
-        };
-        if status != 0 || data_size == 0 {
-            trace!(
-                "CMIO system device size query: status={} size={}",
-                status,
-                data_size
-            );
-            return vec![];
-        }
-        let count = data_size as usize / mem::size_of::<CMIOObjectID>();
-        let mut ids = vec![0u32; count];
-        let mut out_size = data_size;
-        let status = unsafe {
-            CMIOObjectGetPropertyData(
-                K_CMIO_HARDWARE_OBJECT_SYSTEM,
-                &address,
-                0,
-                std::ptr::null(),
-                data_size,
-                &mut out_size,
-                ids.as_mut_ptr() as *mut c_void,
-            )
-        };
-        trace!(
-            "CMIO system device enumeration: status={} count={}",
-            status,
-            count
-        );
-        if status != 0 {
-            vec![]
-        } else {
-            ids
-        }
+    /// Returns true if any camera device is actively in use by any process.
+    pub fn is_running_anywhere(&self) -> Result<bool> {
+        Ok(self.devices()?.iter().any(|device| device.running))
     }
 
-    fn is_device_running_somewhere(&self, device_id: CMIOObjectID) -> Option<bool> {
-        let address = CMIOObjectPropertyAddress {
-            m_selector: K_CMIO_DEVICE_PROPERTY_IS_RUNNING_SOMEWHERE,
-            m_scope: K_CMIO_OBJECT_PROPERTY_SCOPE_GLOBAL,
-            m_element: K_CMIO_OBJECT_PROPERTY_ELEMENT_MAIN,
-        };
-        let mut running: u32 = 0;
-        let in_size = mem::size_of::<u32>() as u32;
-        let mut out_size = in_size;
-        let status = unsafe {
-            CMIOObjectGetPropertyData(
-                device_id,
-                &address,
-                0,
-                std::ptr::null(),
-                in_size,
-                &mut out_size,
-                &mut running as *mut u32 as *mut c_void,
-            )
-        };
-        trace!(
-            "CMIO device {} isRunningSomewhere: status={} running={}",
-            device_id,
-            status,
-            running
-        );
-        if status == 0 {
-            Some(running != 0)
-        } else {
-            None
+    /// Every camera and whether it's running. System devices are merged into the
+    /// capture device with the same unique ID, or listed after the capture devices.
+    pub fn devices(&self) -> Result<Vec<CameraDevice>> {
+        let mut devices = vec![];
+        // First: the capture API's in-use flag, which needs camera permission
+        match self.backend.capture_device_ids()? {
+            Some(ids) => {
+                trace!("Capture device count: {}", ids.len());
+                for id in ids {
+                    let running = match self.backend.is_in_use_by_another_app(&id) {
+                        Ok(in_use) => in_use,
+                        // The device may have been unplugged since it was listed
+                        Err(e) => {
+                            trace!("Capture device {} in use unknown: {}", id, e);
+                            continue;
+                        }
+                    };
+                    let name = self.backend.capture_device_name(&id).unwrap_or_default();
+                    devices.push(CameraDevice {
+                        name: if name.is_empty() { id.clone() } else { name },
+                        id,
+                        running,
+                    });
+                }
+            }
+            None => {
+                if !self.no_permission_logged.swap(true, Ordering::Relaxed) {
+                    error!("Capture devices are unavailable — no camera permission?");
+                }
+            }
+        }
+
+        // The in-use flag can be unreliable on newer macOS, so also check the system
+        // devices whatever the capture API reported
+        for system_id in self.backend.system_device_ids()? {
+            let running = self.backend.is_running_somewhere(system_id)? == Some(true);
+            let uid = self
+                .backend
+                .system_device_uid(system_id)
+                .unwrap_or_else(|e| {
+                    trace!("System device {} uid unknown: {}", system_id, e);
+                    None
+                });
+            if let Some(device) = uid
+                .as_ref()
+                .and_then(|uid| devices.iter_mut().find(|device| device.id == *uid))
+            {
+                device.running |= running;
+                continue;
+            }
+            let id = uid.unwrap_or_else(|| system_id.to_string());
+            let name = self
+                .backend
+                .system_device_name(system_id)
+                .unwrap_or_default();
+            devices.push(CameraDevice {
+                name: if name.is_empty() {
+                    format!("Camera {}", id)
+                } else {
+                    name
+                },
+                id,
+                running,
+            });
         }
+        Ok(devices)
     }
 
-    /// Returns true if any camera device is actively in use by any process.
-    pub fn is_running_anywhere(&self) -> Result<bool> {
-        // First: AVFoundation isInUseByAnotherApplication.
-        // Only trusted if devicesWithMediaType: returns at least one device (requires TCC permission).
-        let av_device_count: usize = unsafe {
-            let media_type = NSString::alloc(nil).init_str("vide");
-            let devices: id = msg_send![class!(AVCaptureDevice), devicesWithMediaType: media_type];
-            if devices == nil {
-                error!(
-                    "AVCaptureDevice.devicesWithMediaType: returned nil — no camera permission?"
-                );
-                0
-            } else {
-                msg_send![devices, count]
+    /// Have `on_change` called when cameras change; see `CameraBackend::watch`.
+    pub fn watch(&self, on_change: Box<dyn Fn() + Send + Sync>) -> Result<()> {
+        self.backend.watch(on_change)
+    }
+
+    /// Every camera the backend reports and whether it's in use, for diagnostics.
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = vec![];
+        match self.backend.capture_device_ids() {
+            Ok(Some(ids)) => {
+                lines.push(format!("Capture devices: {}", ids.len()));
+                for id in ids {
+                    let name = self.backend.capture_device_name(&id).unwrap_or_default();
+                    let in_use = match self.backend.is_in_use_by_another_app(&id) {
+                        Ok(in_use) => in_use.to_string(),
+                        Err(e) => format!("unknown ({})", e),
+                    };
+                    lines.push(format!("  {} ({}): in use={}", name, id, in_use));
+                }
             }
-        };
-        trace!("AVFoundation camera device count: {}", av_device_count);
-        if av_device_count > 0 {
-            let active = unsafe {
-                let media_type = NSString::alloc(nil).init_str("vide");
-                let devices: id =
-                    msg_send![class!(AVCaptureDevice), devicesWithMediaType: media_type];
-                let mut any = false;
-                for i in 0..av_device_count {
-                    let device: id = msg_send![devices, objectAtIndex: i];
-                    // isInUseByAnotherApplication returns ObjC BOOL (i8)
-                    let in_use: cocoa::base::BOOL = msg_send![device, isInUseByAnotherApplication];
-                    trace!(
-                        "AVCaptureDevice {} isInUseByAnotherApplication={}",
-                        i,
-                        in_use
-                    );
-                    if in_use != cocoa::base::NO {
-                        any = true;
-                        break;
-                    }
+            Ok(None) => lines.push("Capture devices: unavailable, no camera permission".into()),
+            Err(e) => lines.push(format!("Capture devices: failed ({:#})", e)),
+        }
+        match self.backend.system_device_ids() {
+            Ok(ids) => {
+                lines.push(format!("System devices: {}", ids.len()));
+                for id in ids {
+                    let running = match self.backend.is_running_somewhere(id) {
+                        Ok(Some(running)) => running.to_string(),
+                        Ok(None) => "unknown".to_string(),
+                        Err(e) => format!("unknown ({})", e),
+                    };
+                    lines.push(format!("  device {}: running somewhere={}", id, running));
                 }
-                any
-            };
-            if active {
-                return Ok(true);
             }
-            // AVFoundation found devices but all report not-in-use.
-            // Also check CMIO — isInUseByAnotherApplication can be unreliable on newer macOS.
+            Err(e) => lines.push(format!("System devices: failed ({:#})", e)),
         }
+        lines
+    }
+}
 
-        // CMIO kCMIODevicePropertyDeviceIsRunningSomewhere — lower-level check that
-        // runs regardless of AVFoundation result.
-        let device_ids = self.get_cmio_device_ids_system();
-        if device_ids.is_empty() {
-            trace!("CMIO direct enumeration returned 0 devices");
+/// In-memory cameras for tests of the controller.
+#[cfg(test)]
+pub(crate) mod fake {
+    use super::*;
+    use anyhow::anyhow;
+
+    pub struct CaptureDevice {
+        pub unique_id: String,
+        pub name: String,
+        pub in_use: bool,
+    }
+
+    impl CaptureDevice {
+        pub fn new(unique_id: &str, in_use: bool) -> Self {
+            Self {
+                unique_id: unique_id.to_string(),
+                name: format!("Camera {}", unique_id),
+                in_use,
+            }
         }
-        for id in &device_ids {
-            if let Some(true) = self.is_device_running_somewhere(*id) {
-                return Ok(true);
+    }
+
+    pub struct SystemDevice {
+        pub id: u32,
+        /// The capture device this is, if any.
+        pub uid: Option<String>,
+        pub name: String,
+        /// What the device reports for running somewhere.
+        pub running: Option<bool>,
+    }
+
+    impl SystemDevice {
+        pub fn new(id: u32, running: Option<bool>) -> Self {
+            Self {
+                id,
+                uid: None,
+                name: format!("System camera {}", id),
+                running,
             }
         }
-        Ok(false)
+
+        pub fn with_uid(id: u32, uid: &str, running: Option<bool>) -> Self {
+            Self {
+                uid: Some(uid.to_string()),
+                ..Self::new(id, running)
+            }
+        }
+    }
+
+    /// Defaults to camera permission granted and no cameras.
+    pub struct FakeCameraBackend {
+        pub permission: bool,
+        pub capture: Vec<CaptureDevice>,
+        pub system: Vec<SystemDevice>,
+    }
+
+    impl Default for FakeCameraBackend {
+        fn default() -> Self {
+            Self {
+                permission: true,
+                capture: vec![],
+                system: vec![],
+            }
+        }
+    }
+
+    impl FakeCameraBackend {
+        fn capture_device(&self, unique_id: &str) -> Result<&CaptureDevice> {
+            self.capture
+                .iter()
+                .find(|device| device.unique_id == unique_id)
+                .ok_or_else(|| anyhow!("missing fake camera {}", unique_id))
+        }
+
+        fn system_device(&self, device_id: u32) -> Result<&SystemDevice> {
+            self.system
+                .iter()
+                .find(|device| device.id == device_id)
+                .ok_or_else(|| anyhow!("missing fake system camera {}", device_id))
+        }
+    }
+
+    impl CameraBackend for FakeCameraBackend {
+        fn capture_device_ids(&self) -> Result<Option<Vec<String>>> {
+            Ok(self.permission.then(|| {
+                self.capture
+                    .iter()
+                    .map(|device| device.unique_id.clone())
+                    .collect()
+            }))
+        }
+
+        fn capture_device_name(&self, unique_id: &str) -> Result<String> {
+            Ok(self.capture_device(unique_id)?.name.clone())
+        }
+
+        fn is_in_use_by_another_app(&self, unique_id: &str) -> Result<bool> {
+            Ok(self.capture_device(unique_id)?.in_use)
+        }
+
+        fn system_device_ids(&self) -> Result<Vec<u32>> {
+            Ok(self.system.iter().map(|device| device.id).collect())
+        }
+
+        fn is_running_somewhere(&self, device_id: u32) -> Result<Option<bool>> {
+            Ok(self.system_device(device_id)?.running)
+        }
+
+        fn system_device_uid(&self, device_id: u32) -> Result<Option<String>> {
+            Ok(self.system_device(device_id)?.uid.clone())
+        }
+
+        fn system_device_name(&self, device_id: u32) -> Result<String> {
+            Ok(self.system_device(device_id)?.name.clone())
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::fake::{CaptureDevice, FakeCameraBackend, SystemDevice};
     use super::*;
 
+    fn controller(backend: FakeCameraBackend) -> CameraController<FakeCameraBackend> {
+        CameraController::with_backend(backend).unwrap()
+    }
+
     #[test]
     fn test_camera_controller_new() {
         // CameraController::new() should succeed even with no cameras
         let result = CameraController::new();
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn capture_device_in_use_is_running() {
+        let camera = controller(FakeCameraBackend {
+            capture: vec![
+                CaptureDevice::new("idle", false),
+                CaptureDevice::new("busy", true),
+            ],
+            ..FakeCameraBackend::default()
+        });
+
+        assert!(camera.is_running_anywhere().unwrap());
+        assert!(!camera.muted);
+    }
+
+    #[test]
+    fn idle_capture_devices_fall_back_to_system_devices() {
+        let mut backend = FakeCameraBackend {
+            capture: vec![CaptureDevice::new("built-in", false)],
+            system: vec![SystemDevice::new(40, Some(false))],
+            ..FakeCameraBackend::default()
+        };
+        assert!(!controller(backend).is_running_anywhere().unwrap());
+
+        backend = FakeCameraBackend {
+            capture: vec![CaptureDevice::new("built-in", false)],
+            system: vec![
+                SystemDevice::new(40, Some(false)),
+                SystemDevice::new(41, Some(true)),
+            ],
+            ..FakeCameraBackend::default()
+        };
+        assert!(controller(backend).is_running_anywhere().unwrap());
+    }
+
+    #[test]
+    fn no_permission_uses_system_devices_only() {
+        let idle = controller(FakeCameraBackend {
+            permission: false,
+            capture: vec![CaptureDevice::new("busy", true)],
+            system: vec![
+                SystemDevice::new(40, None),
+                SystemDevice::new(41, Some(false)),
+            ],
+        });
+        let running = controller(FakeCameraBackend {
+            permission: false,
+            system: vec![SystemDevice::new(40, Some(true))],
+            ..FakeCameraBackend::default()
+        });
+
+        assert!(!idle.is_running_anywhere().unwrap());
+        assert!(idle.muted);
+        assert!(running.is_running_anywhere().unwrap());
+    }
+
+    #[test]
+    fn devices_merge_system_devices_by_unique_id() {
+        let camera = controller(FakeCameraBackend {
+            capture: vec![
+                CaptureDevice::new("built-in", false),
+                CaptureDevice::new("usb-1", false),
+            ],
+            system: vec![
+                SystemDevice::with_uid(40, "built-in", Some(true)),
+                SystemDevice::with_uid(41, "usb-1", Some(false)),
+                SystemDevice::new(42, Some(true)),
+            ],
+            ..FakeCameraBackend::default()
+        });
+
+        assert_eq!(
+            camera.devices().unwrap(),
+            vec![
+                CameraDevice {
+                    id: "built-in".to_string(),
+                    name: "Camera built-in".to_string(),
+                    running: true,
+                },
+                CameraDevice {
+                    id: "usb-1".to_string(),
+                    name: "Camera usb-1".to_string(),
+                    running: false,
+                },
+                CameraDevice {
+                    id: "42".to_string(),
+                    name: "System camera 42".to_string(),
+                    running: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn devices_without_permission_come_from_system_devices() {
+        let mut system = SystemDevice::with_uid(40, "built-in", Some(true));
+        system.name = String::new();
+        let camera = controller(FakeCameraBackend {
+            permission: false,
+            system: vec![system],
+            ..FakeCameraBackend::default()
+        });
+
+        assert_eq!(
+            camera.devices().unwrap(),
+            vec![CameraDevice {
+                id: "built-in".to_string(),
+                name: "Camera built-in".to_string(),
+                running: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn describe_lists_both_views() {
+        let camera = controller(FakeCameraBackend {
+            capture: vec![CaptureDevice::new("usb-1", true)],
+            system: vec![SystemDevice::new(40, None)],
+            ..FakeCameraBackend::default()
+        });
+
+        assert_eq!(
+            camera.describe(),
+            vec![
+                "Capture devices: 1",
+                "  Camera usb-1 (usb-1): in use=true",
+                "System devices: 1",
+                "  device 40: running somewhere=unknown",
+            ]
+        );
+    }
 }
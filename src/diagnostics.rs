@@ -0,0 +1,330 @@
+/// Diagnostics bundles for bug reports: a gzipped tarball with the app version,
+/// sanitized settings, audio devices, cameras, recent mute events and recent logs.
+/// The system `tar` builds the archive so no archive library has to be linked.
+use crate::app_state::{AppState, MuteEvent};
+use crate::camera::CameraController;
+use crate::logging;
+use crate::mic::{AudioBackend, DeviceReport, MicController};
+use crate::settings::Settings;
+use crate::utils::{create_runtime_dir, UtcTime};
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Instant, SystemTime};
+
+/// The folder the files are placed in inside the archive.
+const ARCHIVE_DIR: &str = "mic-mute-diagnostics";
+
+/// A file in the bundle and its contents.
+pub type Entry = (&'static str, String);
+
+/// Suggested name for a bundle exported at `time`.
+pub fn file_name(time: SystemTime) -> String {
+    format!(
+        "{}-{}.tar.gz",
+        ARCHIVE_DIR,
+        UtcTime::from_system_time(time).file_stamp()
+    )
+}
+
+/// Gather every file in the bundle. Parts that can't be read are reported in
+/// their file rather than failing the export.
+pub fn collect<B: AudioBackend>(
+    state: &AppState,
+    controller: &MicController<B>,
+    camera: &CameraController,
+) -> Vec<Entry> {
+    let settings = sanitized_settings(state.settings(), dirs::home_dir().as_deref())
+        .unwrap_or_else(|e| format!("Failed to serialize settings: {:#}", e));
+    let devices = match controller.device_table() {
+        Ok(devices) => format_devices(&devices),
+        Err(e) => format!("Failed to list audio devices: {:#}\n", e),
+    };
+    let mut cameras = camera.describe().join("\n");
+    cameras.push('\n');
+    let now = Instant::now();
+    let wall_now = SystemTime::now();
+    let events: Vec<&MuteEvent> = state.recent_events().collect();
+    let mut logs = logging::recent_lines().join("\n");
+    logs.push('\n');
+
+    vec![
+        (
+            "summary.txt",
+            summary(state, controller, camera, now, wall_now),
+        ),
+        ("settings.json", settings),
+        ("devices.txt", devices),
+        ("cameras.txt", cameras),
+        ("mute-events.txt", format_events(&events, now, wall_now)),
+        ("log.txt", logs),
+    ]
+}
+
+fn summary<B: AudioBackend>(
+    state: &AppState,
+    controller: &MicController<B>,
+    camera: &CameraController,
+    now: Instant,
+    wall_now: SystemTime,
+) -> String {
+    let intervals = state.poll_intervals(now);
+    let mut summary = String::new();
+    let _ = writeln!(summary, "Mic Mute {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(
+        summary,
+        "Exported: {}",
+        UtcTime::from_system_time(wall_now).rfc3339()
+    );
+    let _ = writeln!(
+        summary,
+        "Platform: {} {} ({})",
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        os_release().unwrap_or_else(|| "unknown release".to_string())
+    );
+    let _ = writeln!(
+        summary,
+        "Mic: muted={} status={:?} enforcing={} default input={}",
+        controller.muted,
+        controller.status,
+        controller.should_enforce_mute(),
+        controller
+            .active_device_name()
+            .unwrap_or_else(|| "none".to_string())
+    );
+    let _ = writeln!(summary, "Camera: active={}", !camera.muted);
+    let _ = writeln!(
+        summary,
+        "Poll intervals: mic={:?} camera={:?} settings={:?}",
+        intervals.mic, intervals.camera, intervals.settings
+    );
+    summary
+}
+
+/// Kernel name, release and machine from `uname`.
+fn os_release() -> Option<String> {
+    let output = Command::new("uname").arg("-srm").output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Settings as JSON with the home directory in paths replaced by `~`, so the
+/// bundle doesn't reveal the account name, and without the MQTT password.
+fn sanitized_settings(settings: &Settings, home: Option<&Path>) -> Result<String> {
+    let mut value = serde_json::to_value(settings)?;
+    if let Some(password) = value.pointer_mut("/mqtt/password") {
+        if !password.is_null() {
+            *password = Value::from("<redacted>");
+        }
+    }
+    if let Some(home) = home.and_then(Path::to_str).filter(|home| !home.is_empty()) {
+        redact_home(&mut value, home);
+    }
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+fn redact_home(value: &mut Value, home: &str) {
+    match value {
+        Value::String(string) => {
+            // Only whole path components, so /home/janet isn't redacted as /home/jan
+            let rest = string
+                .strip_prefix(home)
+                .filter(|rest| rest.is_empty() || rest.starts_with('/'));
+            if let Some(rest) = rest {
+                *string = format!("~{}", rest);
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|v| redact_home(v, home)),
+        Value::Object(map) => map.values_mut().for_each(|v| redact_home(v, home)),
+        _ => {}
+    }
+}
+
+fn format_optional<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "unknown".to_string(), |value| value.to_string())
+}
+
+fn format_devices(devices: &[DeviceReport]) -> String {
+    let mut out = String::new();
+    for device in devices {
+        let _ = writeln!(out, "Device {}: {}", device.id, device.name);
+        let _ = writeln!(
+            out,
+            "  uid: {}",
+            format_optional(device.details.uid.as_ref())
+        );
+        let _ = writeln!(
+            out,
+            "  transport: {}",
+            format_optional(device.details.transport.as_ref())
+        );
+        let _ = writeln!(
+            out,
+            "  input: {}  excluded: {}  native mute: {}",
+            device.input, device.excluded, device.native_mute
+        );
+        let _ = writeln!(
+            out,
+            "  muted: {}  volume: {}",
+            format_optional(device.muted),
+            format_optional(device.volume)
+        );
+        let _ = writeln!(
+            out,
+            "  volume elements: {}",
+            if device.details.volume_elements.is_empty() {
+                "none".to_string()
+            } else {
+                device.details.volume_elements.join(", ")
+            }
+        );
+    }
+    if devices.is_empty() {
+        out.push_str("No audio devices\n");
+    }
+    out
+}
+
+/// One line per event, dated by how long before `now` it happened.
+fn format_events(events: &[&MuteEvent], now: Instant, wall_now: SystemTime) -> String {
+    let mut out = String::new();
+    for event in events {
+        let at = wall_now
+            .checked_sub(now.saturating_duration_since(event.at))
+            .unwrap_or(wall_now);
+        let _ = writeln!(
+            out,
+            "{} {} status={:?}{} device={}",
+            UtcTime::from_system_time(at).rfc3339(),
+            if event.muted { "muted" } else { "unmuted" },
+            event.status,
+            if event.failed { " failed" } else { "" },
+            event.device_name.as_deref().unwrap_or("none")
+        );
+    }
+    if events.is_empty() {
+        out.push_str("No mute changes since launch\n");
+    }
+    out
+}
+
+/// Write `entries` as a gzipped tarball at `path`. They're staged in the runtime
+/// directory, which only the user can open, since logs and settings are private.
+pub fn write_archive(path: &Path, entries: &[Entry]) -> Result<()> {
+    let staging = create_runtime_dir()?.join(format!(
+        "{}-{}-{}",
+        ARCHIVE_DIR,
+        std::process::id(),
+        UtcTime::from_system_time(SystemTime::now()).file_stamp()
+    ));
+    // Never reuse a folder that's already there
+    fs::create_dir(&staging).with_context(|| format!("Failed to create {}", staging.display()))?;
+    let result = stage_and_archive(&staging, path, entries);
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+fn stage_and_archive(staging: &Path, path: &Path, entries: &[Entry]) -> Result<()> {
+    let root = staging.join(ARCHIVE_DIR);
+    fs::create_dir(&root).with_context(|| format!("Failed to create {}", root.display()))?;
+    for (name, contents) in entries {
+        fs::write(root.join(name), contents)
+            .with_context(|| format!("Failed to write {}", name))?;
+    }
+    let output = Command::new("tar")
+        .arg("-czf")
+        .arg(path)
+        .arg("-C")
+        .arg(staging)
+        .arg(ARCHIVE_DIR)
+        .output()
+        .context("Failed to run tar")?;
+    if !output.status.success() {
+        bail!(
+            "tar failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mic::DeviceDetails;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_sanitized_settings_redacts_home() {
+        let mut settings = Settings::default();
+        settings.sounds.mute_sound = Some(PathBuf::from("/home/jane/sounds/mute.wav"));
+        settings.icons.icon_set = Some(PathBuf::from("minimal"));
+        settings.mqtt.password = Some("hunter2".to_string());
+
+        let json = sanitized_settings(&settings, Some(Path::new("/home/jane"))).unwrap();
+
+        assert!(json.contains("\"~/sounds/mute.wav\""));
+        assert!(json.contains("\"minimal\""));
+        assert!(!json.contains("jane"));
+        assert!(!json.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_redact_home_only_strips_whole_components() {
+        let mut value = serde_json::json!(["/home/jan", "/home/jan/a.wav", "/home/janet/b.wav"]);
+
+        redact_home(&mut value, "/home/jan");
+
+        assert_eq!(
+            value,
+            serde_json::json!(["~", "~/a.wav", "/home/janet/b.wav"])
+        );
+    }
+
+    #[test]
+    fn test_format_devices() {
+        let devices = vec![DeviceReport {
+            id: 73,
+            name: "USB Mic".to_string(),
+            input: true,
+            excluded: false,
+            native_mute: false,
+            muted: Some(true),
+            volume: None,
+            details: DeviceDetails {
+                uid: Some("usb-mic".to_string()),
+                transport: Some("usb".to_string()),
+                volume_elements: vec!["mono".to_string()],
+            },
+        }];
+
+        assert_eq!(
+            format_devices(&devices),
+            "Device 73: USB Mic\n  uid: usb-mic\n  transport: usb\n  \
+             input: true  excluded: false  native mute: false\n  \
+             muted: true  volume: unknown\n  volume elements: mono\n"
+        );
+        assert_eq!(format_devices(&[]), "No audio devices\n");
+    }
+
+    #[test]
+    fn test_write_archive() {
+        let dir = std::env::temp_dir().join("mic-mute-test-diagnostics");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(file_name(SystemTime::now()));
+
+        write_archive(&path, &[("summary.txt", "Mic Mute\n".to_string())]).unwrap();
+
+        let output = Command::new("tar").arg("-tzf").arg(&path).output().unwrap();
+        let listing = String::from_utf8_lossy(&output.stdout);
+        assert!(listing.contains("mic-mute-diagnostics/summary.txt"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,26 +1,65 @@
-use crate::about::show_about;
-use crate::camera::CameraController;
+use crate::app_state::{
+    self, AppState, Command, Effect, Input, MenuAction, MicReport, PollIntervals, SessionEvent,
+};
+use crate::camera::{CameraController, CameraDevice};
+use crate::diagnostics;
+use crate::frontend::Frontend;
+use crate::headless::Headless;
+use crate::i18n::tr;
+use crate::instance;
 use crate::launch_at_login;
+use crate::logging;
 use crate::mic::MicController;
+use crate::mqtt::MqttClient;
+use crate::platform;
 use crate::settings::Settings;
+use crate::status::{self, Status, StatusPublisher};
 use crate::ui::UI;
-use async_std::task;
-use global_hotkey::GlobalHotKeyEvent;
-use log::trace;
+use crate::usage::{self, UsageStore};
+use anyhow::Result;
+use global_hotkey::{GlobalHotKeyEvent, HotKeyState};
+use log::{info, trace};
 use muda::{MenuEvent, MenuId};
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc;
 use std::sync::{Arc, RwLock};
-use std::time::{Duration, Instant};
-use tao::event::Event;
+use std::time::{Duration, Instant, SystemTime};
+use tao::event::{Event, WindowEvent};
 use tao::event_loop::{ControlFlow, EventLoop, EventLoopBuilder};
-use tao::platform::macos::{ActivationPolicy, EventLoopExtMacOS};
-
-const POLL_INTERVAL_MILLIS: u64 = 200;
 
 #[derive(Debug)]
 pub enum Message {
-    HidePopup,
-    CameraStateChanged(bool),
+    CameraStateChanged(Vec<CameraDevice>),
+    /// A Preferences control was clicked; carries the control's tag.
+    PreferencesAction(i64),
+    /// A global hotkey was pressed or released. Only the headless loop receives
+    /// these; the GUI loop reads global-hotkey's channel directly.
+    HotKey(GlobalHotKeyEvent),
+    Session(SessionEvent),
+    /// A mute command from a control interface such as MQTT or D-Bus.
+    Command(Command),
+    /// SIGTERM or SIGINT, e.g. at logout or `systemctl stop`.
+    Quit,
+}
+
+/// Delivers messages to the running loop from background threads.
+type Notify = Arc<dyn Fn(Message) + Send + Sync>;
+
+/// How long to wait for a burst of camera changes to settle before checking, since
+/// a camera starting reports several.
+const CAMERA_CHANGE_SETTLE: Duration = Duration::from_millis(100);
+/// How often today's usage is refreshed in the frontend when nothing changed.
+const USAGE_REFRESH: Duration = Duration::from_secs(60);
+/// How long a signalled quit may take through the loop before the signal thread
+/// restores the mic and exits without it.
+const SIGNAL_QUIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_signal(_: libc::c_int) {
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
 }
 
 pub type EventLoopMessage = EventLoop<Message>;
@@ -34,33 +73,412 @@ pub struct EventIds {
     pub button_toggle_mute: MenuId,
     pub button_launch_at_login: MenuId,
     pub button_show_in_dock: MenuId,
-    pub button_about: MenuId,
+    pub button_preferences: MenuId,
+    pub button_export_diagnostics: MenuId,
+    pub button_open_logs: MenuId,
+    pub button_export_usage: MenuId,
     pub button_quit: MenuId,
-    pub shortcut_mic: Arc<AtomicU32>,
 }
 
-fn update_mic(
-    ui: Arc<RwLock<UI>>,
+/// Effects only the loop itself can carry out, since they need its window target or
+/// end it.
+enum LoopRequest {
+    DetectMonitors,
+    OpenPreferences,
+    ExportDiagnostics,
+    ExportUsage,
+    Quit,
+}
+
+/// Carries out `AppState` effects against the mic, camera, settings file and
+/// frontend. Shared by the GUI and headless loops.
+struct Engine<F> {
+    frontend: F,
+    state: AppState,
     controller: Arc<RwLock<MicController>>,
-    proxy: EventLoopProxyMessage,
-    toggle: bool,
-) {
-    let mut controller = controller.write().unwrap();
-    if toggle || controller.should_enforce_mute() {
-        let state = if toggle { None } else { Some(true) };
-        if let Err(err) = controller.toggle(state) {
-            log::error!("Failed to update microphone mute state: {}", err);
-        }
-        let device_name = controller.active_device_name();
-        let mut ui = ui.write().unwrap();
-        ui.update_mic(controller.muted, device_name.as_deref())
-            .unwrap();
+    camera: Arc<RwLock<CameraController>>,
+    notify: Notify,
+    last_settings_mtime: Option<SystemTime>,
+    /// Read by the camera watcher thread before each check.
+    camera_interval_ms: Arc<AtomicU64>,
+    /// Logged when they change.
+    poll_intervals: Option<PollIntervals>,
+    usage: UsageStore,
+    /// When today's usage was last shown, None to show it at the next chance.
+    usage_shown_at: Option<Instant>,
+    /// None while MQTT is turned off.
+    mqtt: Option<MqttClient>,
+    /// None until `serve_controls`, or when the platform's interface failed.
+    controls: Option<platform::ControlService>,
+    /// Feeds `mic-mute watch` and the status file.
+    status: StatusPublisher,
+}
+
+impl<F: Frontend> Engine<F> {
+    fn new(
+        frontend: F,
+        controller: Arc<RwLock<MicController>>,
+        camera: Arc<RwLock<CameraController>>,
+        settings: Settings,
+        notify: Notify,
+    ) -> Self {
+        let report = MicReport::from_controller(&controller.read().unwrap(), false);
+        let cameras = camera.read().unwrap().devices().unwrap_or_default();
+        let camera_interval = settings.polling.camera().as_millis() as u64;
+        let now = SystemTime::now();
+        let mut usage = UsageStore::open(now);
+        usage.record_mic(now, report.muted, 0);
+        usage.record_camera(now, cameras.iter().any(|camera| camera.running));
+        let status = Status::new(
+            report.muted,
+            report.status,
+            report.device_name.as_deref(),
+            &cameras,
+        );
+        let status = StatusPublisher::new(status, &settings);
+        let mut engine = Self {
+            frontend,
+            state: AppState::new(settings, &report, cameras, Instant::now()),
+            controller,
+            camera,
+            notify,
+            last_settings_mtime: Settings::mtime(),
+            camera_interval_ms: Arc::new(AtomicU64::new(camera_interval)),
+            poll_intervals: None,
+            usage,
+            usage_shown_at: None,
+            mqtt: None,
+            controls: None,
+            status,
+        };
+        engine.apply_mqtt();
+        engine
+    }
+
+    /// Camera detection can run expensive native calls; offload to a background
+    /// thread so it never blocks the loop. The thread checks when the backend
+    /// reports a change, or polls when it can't, and delivers results as
+    /// `Message::CameraStateChanged`.
+    fn spawn_camera_watcher(&self) {
+        let (changed, changes) = mpsc::channel();
+        let on_change = changed.clone();
+        let watching = match self.camera.read().unwrap().watch(Box::new(move || {
+            let _ = on_change.send(());
+        })) {
+            Ok(()) => {
+                trace!("Watching for camera changes");
+                true
+            }
+            Err(e) => {
+                trace!("Polling cameras instead of watching: {:#}", e);
+                false
+            }
+        };
+        let camera = self.camera.clone();
+        let notify = self.notify.clone();
+        let interval_ms = self.camera_interval_ms.clone();
+        std::thread::spawn(move || {
+            // Keeps the channel open if the backend dropped its sender
+            let _changed = changed;
+            loop {
+                let woken = if watching {
+                    changes.recv().is_ok()
+                } else {
+                    let interval = Duration::from_millis(interval_ms.load(Ordering::Relaxed));
+                    changes.recv_timeout(interval).is_ok()
+                };
+                if woken {
+                    std::thread::sleep(CAMERA_CHANGE_SETTLE);
+                    while changes.try_recv().is_ok() {}
+                }
+                match platform::autoreleasepool(|| camera.read().unwrap().devices()) {
+                    Ok(cameras) => notify(Message::CameraStateChanged(cameras)),
+                    Err(e) => trace!("Failed to list cameras: {:#}", e),
+                }
+            }
+        });
+    }
+
+    /// Sleep, wake, lock and user switches, delivered as `Message::Session`.
+    fn watch_session(&self) {
+        let notify = self.notify.clone();
+        platform::watch_session(move |event| notify(Message::Session(event)));
+    }
+
+    /// Quit through the loop on SIGTERM or SIGINT, so usage, status and the mic
+    /// are put away as when quitting from the menu. The handler only sets a flag,
+    /// which a background thread polls.
+    fn watch_signals(&self) {
+        unsafe {
+            libc::signal(
+                libc::SIGTERM,
+                handle_signal as *const () as libc::sighandler_t,
+            );
+            libc::signal(
+                libc::SIGINT,
+                handle_signal as *const () as libc::sighandler_t,
+            );
+        }
+        let notify = self.notify.clone();
+        let controller = self.controller.clone();
+        std::thread::spawn(move || {
+            while !SHUTDOWN_REQUESTED.load(Ordering::SeqCst) {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            info!("Signal received — quitting");
+            notify(Message::Quit);
+            // Only reached when the loop is stuck
+            std::thread::sleep(SIGNAL_QUIT_TIMEOUT);
+            log::error!("Quit timed out — restoring microphone state before exit");
+            restore_microphone_on_exit(&controller);
+            status::remove_files();
+            std::process::exit(0);
+        });
+    }
+
+    /// Save the open usage spans, stop publishing and undo mutes Mic Mute applied.
+    fn shutdown(&mut self) {
+        self.usage.pause(SystemTime::now());
+        self.mqtt = None;
+        self.status.close();
+        restore_microphone_on_exit(&self.controller);
+    }
+
+    /// Take mute commands from later launches and over the platform's control
+    /// interface, D-Bus on Linux, delivered as `Message::Command`, and keep the
+    /// interface's state current. `command` is what this launch was asked to do.
+    fn serve_controls(&mut self, command: Option<Command>) {
+        let notify = self.notify.clone();
+        if let Err(e) = instance::listen(move |command| notify(Message::Command(command))) {
+            log::error!("Later launches can't hand over commands: {:#}", e);
+        }
+        if let Some(command) = command {
+            (self.notify)(Message::Command(command));
+        }
+        let notify = self.notify.clone();
+        match platform::ControlService::start(move |command| notify(Message::Command(command))) {
+            Ok(controls) => {
+                let controller = self.controller.read().unwrap();
+                controls.set_mic(controller.muted, controller.active_device_name().as_deref());
+                controls.set_camera(!self.camera.read().unwrap().muted);
+                self.controls = Some(controls);
+            }
+            Err(e) => log::error!("Control interface unavailable: {:#}", e),
+        }
+    }
+
+    /// Feed an input through the state, carrying out its effects and any inputs
+    /// they report back. Returns the requests left for the loop.
+    fn dispatch(&mut self, input: Input) -> Vec<LoopRequest> {
+        let mut requests = vec![];
+        let mut inputs = VecDeque::from([input]);
+        while let Some(input) = inputs.pop_front() {
+            match input {
+                Input::Session(SessionEvent::Sleep) => self.usage.pause(SystemTime::now()),
+                Input::Session(SessionEvent::Wake) => self.usage.resume(SystemTime::now()),
+                _ => {}
+            }
+            for effect in self.state.handle(Instant::now(), input) {
+                match effect {
+                    Effect::SetMute(state) => {
+                        let mut controller = self.controller.write().unwrap();
+                        inputs.push_back(app_state::set_mute(&mut controller, state));
+                        if self.usage.record_mic(
+                            SystemTime::now(),
+                            controller.muted,
+                            controller.remuted,
+                        ) {
+                            self.usage_shown_at = None;
+                        }
+                    }
+                    Effect::RefreshMic => {
+                        let mut controller = self.controller.write().unwrap();
+                        inputs.push_back(app_state::refresh_mic(&mut controller));
+                    }
+                    Effect::UpdateMic {
+                        muted,
+                        status,
+                        device_name,
+                    } => {
+                        if let Some(mqtt) = &self.mqtt {
+                            mqtt.publish_mic(muted);
+                        }
+                        if let Some(controls) = &self.controls {
+                            controls.set_mic(muted, device_name.as_deref());
+                        }
+                        self.status.set_mic(muted, status, device_name.as_deref());
+                        if let Err(e) =
+                            self.frontend
+                                .update_mic(muted, status, device_name.as_deref())
+                        {
+                            log::error!("{:#}", e);
+                        }
+                    }
+                    Effect::UpdateCamera { cameras } => {
+                        let running = cameras.iter().any(|camera| camera.running);
+                        self.camera.write().unwrap().muted = !running;
+                        if self.usage.record_camera(SystemTime::now(), running) {
+                            self.usage_shown_at = None;
+                        }
+                        if let Some(mqtt) = &self.mqtt {
+                            mqtt.publish_camera(running);
+                        }
+                        if let Some(controls) = &self.controls {
+                            controls.set_camera(running);
+                        }
+                        self.status.set_camera(&cameras);
+                        if let Err(e) = self.frontend.update_camera(&cameras) {
+                            log::error!("{:#}", e);
+                        }
+                    }
+                    Effect::PlayCue(cue) => self.frontend.play_cue(cue),
+                    Effect::SetPopupVisible(visible) => self.frontend.set_popup_visible(visible),
+                    Effect::DetectMonitors => requests.push(LoopRequest::DetectMonitors),
+                    Effect::CheckPowerSource => {
+                        let on_battery = platform::on_battery_power();
+                        inputs.push_back(Input::PowerSourceChanged { on_battery });
+                    }
+                    Effect::CheckSettingsFile => {
+                        let mtime = Settings::mtime();
+                        if mtime != self.last_settings_mtime {
+                            self.last_settings_mtime = mtime;
+                            trace!("settings.json changed on disk — reloading");
+                            inputs.push_back(Input::SettingsReloaded(Settings::load()));
+                        }
+                    }
+                    Effect::SaveSettings => {
+                        if let Err(e) = self.state.settings().save() {
+                            log::error!("Failed to save settings: {}", e);
+                        }
+                        // Our own write isn't an outside edit to reload
+                        self.last_settings_mtime = Settings::mtime();
+                    }
+                    Effect::ApplySettings => self.apply_settings(),
+                    Effect::SetLoginItem { enabled, mechanism } => {
+                        if let Err(e) = launch_at_login::set(enabled, mechanism) {
+                            log::error!("Launch at login error: {}", e);
+                        }
+                    }
+                    Effect::SetDockVisible(visible) => platform::set_dock_visible(visible),
+                    Effect::OpenPreferences => requests.push(LoopRequest::OpenPreferences),
+                    Effect::ExportDiagnostics => requests.push(LoopRequest::ExportDiagnostics),
+                    Effect::OpenLogs => match logging::log_path() {
+                        Some(path) => {
+                            if let Err(e) = platform::open_file(&path) {
+                                log::error!("{:#}", e);
+                            }
+                        }
+                        None => log::error!("No data directory for the log file"),
+                    },
+                    Effect::ExportUsage => requests.push(LoopRequest::ExportUsage),
+                    Effect::Quit => requests.push(LoopRequest::Quit),
+                }
+            }
+        }
+        self.log_poll_intervals();
+        self.show_usage();
+        requests
+    }
+
+    /// Show today's usage when it changed or a while has passed.
+    fn show_usage(&mut self) {
+        let now = Instant::now();
+        if self
+            .usage_shown_at
+            .is_some_and(|shown_at| now < shown_at + USAGE_REFRESH)
+        {
+            return;
+        }
+        self.usage_shown_at = Some(now);
+        let today = self.usage.today(SystemTime::now());
+        if let Err(e) = self.frontend.update_usage(&today) {
+            log::error!("{:#}", e);
+        }
     }
-    if toggle && !controller.muted {
-        task::spawn(async move {
-            task::sleep(Duration::from_secs(1)).await;
-            proxy.send_event(Message::HidePopup).unwrap();
+
+    fn log_poll_intervals(&mut self) {
+        let intervals = self.state.poll_intervals(Instant::now());
+        if self.poll_intervals != Some(intervals) {
+            trace!("Poll intervals {:?}", intervals);
+            self.poll_intervals = Some(intervals);
+        }
+    }
+
+    fn hotkey(&mut self, event: GlobalHotKeyEvent) -> Vec<LoopRequest> {
+        if self.frontend.mic_shortcut_id() != Some(event.id()) {
+            return vec![];
+        }
+        // global-hotkey fires both Pressed and Released
+        match event.state() {
+            HotKeyState::Pressed => {
+                trace!("Toggle mic shortcut activated");
+                self.dispatch(Input::ShortcutPressed)
+            }
+            HotKeyState::Released => self.dispatch(Input::ShortcutReleased),
+        }
+    }
+
+    /// Write a diagnostics bundle to `path`.
+    fn export_diagnostics(&self, path: &Path) -> Result<()> {
+        let entries = platform::autoreleasepool(|| {
+            diagnostics::collect(
+                &self.state,
+                &self.controller.read().unwrap(),
+                &self.camera.read().unwrap(),
+            )
         });
+        diagnostics::write_archive(path, &entries)
+    }
+
+    /// Apply the current settings to the frontend and mic controller.
+    fn apply_settings(&mut self) {
+        let settings = self.state.settings();
+        self.camera_interval_ms.store(
+            settings.polling.camera().as_millis() as u64,
+            Ordering::Relaxed,
+        );
+        if let Err(e) = self
+            .controller
+            .write()
+            .unwrap()
+            .set_excluded_devices(&settings.excluded_devices)
+        {
+            log::error!("Failed to apply excluded devices: {}", e);
+        }
+        if let Err(e) = self.frontend.apply_settings(settings) {
+            log::error!("Failed to apply settings: {}", e);
+        }
+        self.status.apply_settings(settings);
+        self.apply_mqtt();
+    }
+
+    /// Start, restart or stop the MQTT client to match settings.
+    fn apply_mqtt(&mut self) {
+        let settings = &self.state.settings().mqtt;
+        if !settings.enabled {
+            self.mqtt = None;
+            return;
+        }
+        if self
+            .mqtt
+            .as_ref()
+            .is_some_and(|mqtt| mqtt.settings() == settings)
+        {
+            return;
+        }
+        // The old client says it's offline before the new one says it's online
+        self.mqtt = None;
+        let notify = self.notify.clone();
+        match MqttClient::start(settings.clone(), move |command| {
+            notify(Message::Command(command))
+        }) {
+            Ok(mqtt) => {
+                mqtt.publish_mic(self.controller.read().unwrap().muted);
+                mqtt.publish_camera(!self.camera.read().unwrap().muted);
+                self.mqtt = Some(mqtt);
+            }
+            Err(e) => log::error!("Failed to start MQTT: {:#}", e),
+        }
     }
 }
 
@@ -73,74 +491,89 @@ pub fn restore_microphone_on_exit(controller: &Arc<RwLock<MicController>>) {
 pub fn start(
     mut event_loop: EventLoop<Message>,
     event_ids: EventIds,
-    ui: Arc<RwLock<UI>>,
+    ui: UI,
     controller: Arc<RwLock<MicController>>,
     camera: Arc<RwLock<CameraController>>,
-    settings: Arc<RwLock<Settings>>,
+    settings: Settings,
+    command: Option<Command>,
 ) {
     let EventIds {
         button_toggle_mute,
         button_launch_at_login,
         button_show_in_dock,
-        button_about,
+        button_preferences,
+        button_export_diagnostics,
+        button_open_logs,
+        button_export_usage,
         button_quit,
-        shortcut_mic,
     } = event_ids;
 
-    let poll_interval = Duration::from_millis(POLL_INTERVAL_MILLIS);
-    // Start in the past so the first iteration triggers the poll immediately.
-    let mut last_poll = Instant::now() - poll_interval;
-
-    // Poll the settings file for changes every 2 seconds so edits to
-    // settings.json take effect without restarting the app.
-    let settings_poll_interval = Duration::from_secs(2);
-    let mut last_settings_check = Instant::now();
-    let mut last_settings_mtime = Settings::mtime();
-
-    // Camera detection runs expensive Cocoa/CMIO calls; offload to a background
-    // thread so it never blocks the main event loop. Results are delivered back
-    // via a user event.
-    let proxy_camera = event_loop.create_proxy();
-    let camera_bg = camera.clone();
-    std::thread::spawn(move || loop {
-        std::thread::sleep(Duration::from_secs(2));
-        let active = objc::rc::autoreleasepool(|| {
-            camera_bg
-                .read()
-                .unwrap()
-                .is_running_anywhere()
-                .unwrap_or(false)
-        });
-        proxy_camera
-            .send_event(Message::CameraStateChanged(active))
-            .ok();
+    let proxy = event_loop.create_proxy();
+    let notify_proxy = proxy.clone();
+    let notify: Notify = Arc::new(move |message| {
+        notify_proxy.send_event(message).ok();
     });
+    let mut engine = Engine::new(ui, controller, camera, settings, notify);
+    engine.spawn_camera_watcher();
+    engine.watch_session();
+    engine.watch_signals();
+    engine.serve_controls(command);
 
     trace!("Starting event loop");
-    let proxy = event_loop.create_proxy();
-    // Set activation policy based on persisted show_in_dock before the loop starts.
-    let initial_show_in_dock = settings.read().unwrap().show_in_dock;
-    event_loop.set_activation_policy(if initial_show_in_dock {
-        ActivationPolicy::Regular
-    } else {
-        ActivationPolicy::Accessory
-    });
-    event_loop.run(move |event, _, control_flow| {
-        let mut exit_requested = false;
+    let initial_show_in_dock = engine.state.settings().show_in_dock;
+    platform::init_event_loop(&mut event_loop, initial_show_in_dock);
+    event_loop.run(move |event, event_loop_target, control_flow| {
+        let mut requests = vec![];
 
         match event {
-            Event::UserEvent(Message::HidePopup) => {
-                let mic_controller = controller.read().unwrap();
-                if !mic_controller.muted {
-                    let mut ui = ui.write().unwrap();
-                    ui.hide_popup().unwrap();
+            Event::UserEvent(Message::CameraStateChanged(cameras)) => {
+                requests.extend(engine.dispatch(Input::CameraChanged { cameras }));
+            }
+            Event::UserEvent(Message::Session(event)) => {
+                trace!("Session event {:?}", event);
+                requests.extend(engine.dispatch(Input::Session(event)));
+            }
+            Event::UserEvent(Message::Command(command)) => {
+                trace!("Command {:?}", command);
+                requests.extend(engine.dispatch(Input::Command(command)));
+            }
+            Event::UserEvent(Message::Quit) => requests.push(LoopRequest::Quit),
+            // com.brettinternet.mic-mute:// URLs opened on macOS
+            Event::Opened { urls } => {
+                for url in urls {
+                    match instance::parse_command(url.as_str()) {
+                        Some(command) => {
+                            trace!("Opened {} for {:?}", url, command);
+                            requests.extend(engine.dispatch(Input::Command(command)));
+                        }
+                        None => log::error!("Unknown URL {}", url),
+                    }
+                }
+            }
+            Event::UserEvent(Message::PreferencesAction(tag)) => {
+                let mut settings = engine.state.settings().clone();
+                if engine.frontend.preferences_action(tag, &mut settings) {
+                    requests.extend(engine.dispatch(Input::SettingsEdited(settings)));
                 }
             }
-            Event::UserEvent(Message::CameraStateChanged(active)) => {
-                let muted = !active;
-                if muted != camera.read().unwrap().muted {
-                    camera.write().unwrap().muted = muted;
-                    ui.write().unwrap().update_camera(muted).unwrap();
+            Event::WindowEvent {
+                event: WindowEvent::ThemeChanged(theme),
+                ..
+            } => {
+                trace!("System theme changed to {:?}", theme);
+                if let Err(e) = engine.frontend.redraw() {
+                    log::error!("Failed to redraw for theme change: {}", e);
+                }
+            }
+            Event::WindowEvent {
+                window_id, event, ..
+            } => {
+                let mut settings = engine.state.settings().clone();
+                if engine
+                    .frontend
+                    .preferences_window_event(window_id, &event, &mut settings)
+                {
+                    requests.extend(engine.dispatch(Input::SettingsEdited(settings)));
                 }
             }
             _ => {}
@@ -148,101 +581,164 @@ pub fn start(
 
         if let Ok(event) = MenuEvent::receiver().try_recv() {
             trace!("Tray menu event: {:?}", event);
-            if event.id == button_quit {
-                trace!("Exit tray menu item selected");
-                exit_requested = true;
+            let action = if event.id == button_quit {
+                Some(MenuAction::Quit)
             } else if event.id == button_toggle_mute {
-                trace!("Toggle mic tray menu item selected");
-                update_mic(ui.clone(), controller.clone(), proxy.clone(), true);
+                Some(MenuAction::ToggleMute)
             } else if event.id == button_launch_at_login {
-                trace!("Launch at login toggled");
-                let mut s = settings.write().unwrap();
-                s.launch_at_login = !s.launch_at_login;
-                let enabled = s.launch_at_login;
-                if let Err(e) = s.save() {
-                    log::error!("Failed to save settings: {}", e);
-                }
-                drop(s);
-                if let Err(e) = launch_at_login::set(enabled) {
-                    log::error!("Launch at login error: {}", e);
-                }
+                Some(MenuAction::ToggleLaunchAtLogin)
             } else if event.id == button_show_in_dock {
-                trace!("Show in dock toggled");
-                let mut s = settings.write().unwrap();
-                s.show_in_dock = !s.show_in_dock;
-                let visible = s.show_in_dock;
-                if let Err(e) = s.save() {
-                    log::error!("Failed to save settings: {}", e);
-                }
-                drop(s);
-                launch_at_login::set_dock_visible(visible);
-            } else if event.id == button_about {
-                trace!("About tray menu item selected");
-                let mut s = settings.write().unwrap();
-                match show_about(&mut s) {
-                    Ok(true) => {
-                        // Reset to Default clicked — apply all settings immediately
-                        let mut ui = ui.write().unwrap();
-                        if let Err(e) = ui.apply_settings(&s) {
-                            log::error!("Failed to apply settings: {}", e);
-                        } else {
-                            shortcut_mic.store(ui.mic_shortcut_id(), Ordering::Relaxed);
-                        }
-                    }
-                    Ok(false) => {}
-                    Err(e) => log::error!("Preferences error: {}", e),
-                }
+                Some(MenuAction::ToggleShowInDock)
+            } else if event.id == button_preferences {
+                Some(MenuAction::Preferences)
+            } else if event.id == button_export_diagnostics {
+                Some(MenuAction::ExportDiagnostics)
+            } else if event.id == button_open_logs {
+                Some(MenuAction::OpenLogs)
+            } else if event.id == button_export_usage {
+                Some(MenuAction::ExportUsage)
+            } else {
+                engine
+                    .frontend
+                    .suggested_shortcut(&event.id)
+                    .map(MenuAction::SuggestedShortcut)
+            };
+            if let Some(action) = action {
+                trace!("Tray menu action {:?}", action);
+                requests.extend(engine.dispatch(Input::MenuClicked(action)));
             }
         }
 
         if let Ok(event) = GlobalHotKeyEvent::receiver().try_recv() {
-            // Only act on key-down; global-hotkey fires both Pressed and Released
-            if event.state() == global_hotkey::HotKeyState::Pressed {
-                let id = event.id();
-                if shortcut_mic.load(Ordering::Relaxed) == id {
-                    trace!("Toggle mic shortcut activated");
-                    update_mic(ui.clone(), controller.clone(), proxy.clone(), true);
-                }
-            }
+            requests.extend(engine.hotkey(event));
         }
 
-        // Reload settings if the file has been modified since we last checked.
-        if last_settings_check.elapsed() >= settings_poll_interval {
-            last_settings_check = Instant::now();
-            let current_mtime = Settings::mtime();
-            if current_mtime != last_settings_mtime {
-                last_settings_mtime = current_mtime;
-                trace!("settings.json changed on disk — reloading");
-                let new_settings = Settings::load();
-                let mut s = settings.write().unwrap();
-                *s = new_settings.clone();
-                drop(s);
-                let mut ui_w = ui.write().unwrap();
-                if let Err(e) = ui_w.apply_settings(&new_settings) {
-                    log::error!("Failed to apply reloaded settings: {}", e);
-                } else {
-                    shortcut_mic.store(ui_w.mic_shortcut_id(), Ordering::Relaxed);
-                    trace!("Settings reloaded from settings.json");
+        requests.extend(engine.dispatch(Input::Tick));
+
+        let mut exit_requested = false;
+        for request in requests {
+            match request {
+                LoopRequest::DetectMonitors => {
+                    if let Err(e) = engine.frontend.detect(event_loop_target) {
+                        log::error!("{:#}", e);
+                    }
+                }
+                LoopRequest::OpenPreferences => {
+                    let devices = engine
+                        .controller
+                        .read()
+                        .unwrap()
+                        .input_device_names()
+                        .unwrap_or_default();
+                    if let Err(e) = engine.frontend.open_preferences(
+                        event_loop_target,
+                        proxy.clone(),
+                        engine.state.settings(),
+                        devices,
+                    ) {
+                        log::error!("Preferences error: {}", e);
+                    }
+                }
+                LoopRequest::ExportDiagnostics => {
+                    let file_name = diagnostics::file_name(SystemTime::now());
+                    let Some(path) =
+                        platform::choose_save_path(&tr("diagnostics-save-title"), &file_name)
+                    else {
+                        continue;
+                    };
+                    match engine.export_diagnostics(&path) {
+                        Ok(()) => {
+                            trace!("Exported diagnostics to {}", path.display());
+                            if let Err(e) = platform::reveal_file(&path) {
+                                log::error!("{:#}", e);
+                            }
+                        }
+                        Err(e) => log::error!("Failed to export diagnostics: {:#}", e),
+                    }
+                }
+                LoopRequest::ExportUsage => {
+                    let now = SystemTime::now();
+                    let Some(path) = platform::choose_save_path(
+                        &tr("usage-save-title"),
+                        &usage::report_file_name(now),
+                    ) else {
+                        continue;
+                    };
+                    match engine.usage.export(&path, now) {
+                        Ok(()) => {
+                            trace!("Exported usage to {}", path.display());
+                            if let Err(e) = platform::reveal_file(&path) {
+                                log::error!("{:#}", e);
+                            }
+                        }
+                        Err(e) => log::error!("Failed to export usage: {:#}", e),
+                    }
                 }
+                LoopRequest::Quit => exit_requested = true,
             }
         }
 
-        // Poll mic state and cursor-monitor position on a 200 ms interval.
-        if last_poll.elapsed() >= poll_interval {
-            last_poll = Instant::now();
-            update_mic(ui.clone(), controller.clone(), proxy.clone(), false);
-            let mut ui_w = ui.write().unwrap();
-            ui_w.detect().unwrap();
-        }
-
         if exit_requested {
-            restore_microphone_on_exit(&controller);
+            engine.shutdown();
             *control_flow = ControlFlow::Exit;
         } else {
             // Sleep until the next scheduled check rather than spinning.
-            let next_poll = last_poll + poll_interval;
-            let next_settings = last_settings_check + settings_poll_interval;
-            *control_flow = ControlFlow::WaitUntil(next_poll.min(next_settings));
+            *control_flow = ControlFlow::WaitUntil(engine.state.next_deadline());
         }
     });
 }
+
+/// Run the mute engine without a tray, popup or tao event loop, so no display is
+/// needed. Exits only on SIGTERM or SIGINT.
+pub fn start_headless(
+    frontend: Headless,
+    controller: Arc<RwLock<MicController>>,
+    camera: Arc<RwLock<CameraController>>,
+    settings: Settings,
+    command: Option<Command>,
+) -> ! {
+    let (sender, receiver) = mpsc::channel();
+    let hotkey_sender = sender.clone();
+    GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+        hotkey_sender.send(Message::HotKey(event)).ok();
+    }));
+    let notify: Notify = Arc::new(move |message| {
+        sender.send(message).ok();
+    });
+    let mut engine = Engine::new(frontend, controller, camera, settings, notify);
+    engine.spawn_camera_watcher();
+    engine.watch_session();
+    engine.watch_signals();
+    engine.serve_controls(command);
+
+    trace!("Starting headless loop");
+    loop {
+        // Monitors, Preferences and Quit only exist in the GUI
+        engine.dispatch(Input::Tick);
+        let timeout = engine
+            .state
+            .next_deadline()
+            .saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(timeout) {
+            Ok(Message::CameraStateChanged(cameras)) => {
+                engine.dispatch(Input::CameraChanged { cameras });
+            }
+            Ok(Message::Session(event)) => {
+                engine.dispatch(Input::Session(event));
+            }
+            Ok(Message::HotKey(event)) => {
+                engine.hotkey(event);
+            }
+            Ok(Message::Command(command)) => {
+                trace!("Command {:?}", command);
+                engine.dispatch(Input::Command(command));
+            }
+            Ok(Message::Quit) => {
+                engine.shutdown();
+                std::process::exit(0);
+            }
+            Ok(message) => trace!("Ignoring {:?} in headless mode", message),
+            Err(_) => {}
+        }
+    }
+}
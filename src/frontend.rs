@@ -0,0 +1,34 @@
+/// The presentation side of the event loop. The GUI implements it with the tray and
+/// popup; headless mode only logs.
+use crate::camera::CameraDevice;
+use crate::mic::MicStatus;
+use crate::settings::Settings;
+use crate::sound::Cue;
+use crate::usage::Summary;
+use anyhow::Result;
+
+pub trait Frontend {
+    /// Show a new mic state.
+    fn update_mic(
+        &mut self,
+        muted: bool,
+        status: MicStatus,
+        active_device_name: Option<&str>,
+    ) -> Result<()>;
+
+    /// Show every camera and which are running.
+    fn update_camera(&mut self, cameras: &[CameraDevice]) -> Result<()>;
+
+    /// Show today's usage statistics.
+    fn update_usage(&mut self, today: &Summary) -> Result<()>;
+
+    fn play_cue(&mut self, cue: Cue);
+
+    fn set_popup_visible(&mut self, visible: bool);
+
+    /// Apply changed settings. Safe to call with unchanged settings.
+    fn apply_settings(&mut self, settings: &Settings) -> Result<()>;
+
+    /// Id of the registered mic hotkey, or None if no hotkey is registered.
+    fn mic_shortcut_id(&self) -> Option<u32>;
+}
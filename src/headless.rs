@@ -0,0 +1,95 @@
+/// Frontend for `--headless`: no tray, popup or windows, only the mic hotkey and
+/// log lines for state changes.
+use crate::camera::{self, CameraDevice};
+use crate::frontend::Frontend;
+use crate::mic::MicStatus;
+use crate::settings::Settings;
+use crate::shortcuts::Shortcuts;
+use crate::sound::Cue;
+use crate::usage::Summary;
+use anyhow::Result;
+use log::{error, info};
+
+pub struct Headless {
+    /// None when hotkeys aren't available, e.g. without an X11 display.
+    shortcuts: Option<Shortcuts>,
+    mic_muted: Option<bool>,
+    mic_status: Option<MicStatus>,
+    /// Names of the cameras in use, None until the first update.
+    active_cameras: Option<Vec<String>>,
+}
+
+impl Headless {
+    pub fn new(settings: &Settings) -> Self {
+        let shortcuts = Shortcuts::new(settings)
+            .map_err(|e| error!("Running without a mic shortcut: {:#}", e))
+            .ok();
+        Self {
+            shortcuts,
+            mic_muted: None,
+            mic_status: None,
+            active_cameras: None,
+        }
+    }
+}
+
+impl Frontend for Headless {
+    fn update_mic(
+        &mut self,
+        muted: bool,
+        status: MicStatus,
+        active_device_name: Option<&str>,
+    ) -> Result<()> {
+        if self.mic_muted != Some(muted) || self.mic_status != Some(status) {
+            info!(
+                "Mic {} ({:?}), default input {}",
+                if muted { "muted" } else { "unmuted" },
+                status,
+                active_device_name.unwrap_or("unknown")
+            );
+        }
+        self.mic_muted = Some(muted);
+        self.mic_status = Some(status);
+        Ok(())
+    }
+
+    fn update_camera(&mut self, cameras: &[CameraDevice]) -> Result<()> {
+        let active = camera::active_names(cameras);
+        if self.active_cameras.as_ref() != Some(&active) {
+            if active.is_empty() {
+                info!("Camera off");
+            } else {
+                info!("Camera on: {}", active.join(", "));
+            }
+        }
+        self.active_cameras = Some(active);
+        Ok(())
+    }
+
+    /// Usage is only exported from the tray, so there's nothing to show.
+    fn update_usage(&mut self, _today: &Summary) -> Result<()> {
+        Ok(())
+    }
+
+    /// The failure cue is the only one worth a log line; mute changes are already
+    /// logged by `update_mic`.
+    fn play_cue(&mut self, cue: Cue) {
+        if cue == Cue::EnforcementFailed {
+            error!("Mic mute is failing to apply");
+        }
+    }
+
+    fn set_popup_visible(&mut self, _visible: bool) {}
+
+    fn apply_settings(&mut self, settings: &Settings) -> Result<()> {
+        crate::logging::apply_settings(settings);
+        if let Some(shortcuts) = self.shortcuts.as_mut() {
+            shortcuts.reload(settings)?;
+        }
+        Ok(())
+    }
+
+    fn mic_shortcut_id(&self) -> Option<u32> {
+        self.shortcuts.as_ref().and_then(Shortcuts::mic_hotkey_id)
+    }
+}
@@ -0,0 +1,315 @@
+/// Message catalog for user-facing strings.
+/// Locales are .ftl files embedded in the binary, written in the subset of Fluent
+/// syntax the app needs: one-line `key = value` messages with `{ $name }`
+/// placeables, and `#` comments. This isn't a Fluent implementation; multiline
+/// values, attributes, terms, selectors and functions are rejected as parse errors
+/// rather than read wrongly.
+use anyhow::{anyhow, Result};
+use log::{error, trace};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::LazyLock;
+
+/// Bundled locales. The first entry is the source locale and the fallback for
+/// missing keys.
+const LOCALES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.ftl")),
+    ("de", include_str!("../locales/de.ftl")),
+    ("es", include_str!("../locales/es.ftl")),
+    ("fr", include_str!("../locales/fr.ftl")),
+];
+
+type Messages = HashMap<&'static str, &'static str>;
+
+static CATALOGS: LazyLock<Vec<Messages>> = LazyLock::new(|| {
+    LOCALES
+        .iter()
+        .map(|(locale, source)| {
+            parse_ftl(source).unwrap_or_else(|e| {
+                error!("Failed to parse {} locale: {}", locale, e);
+                HashMap::new()
+            })
+        })
+        .collect()
+});
+
+/// Index into `LOCALES` of the active locale.
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+
+fn parse_ftl(source: &'static str) -> Result<Messages> {
+    let mut messages = HashMap::new();
+    for (number, line) in source.lines().enumerate() {
+        if line.starts_with(char::is_whitespace) && !line.trim().is_empty() {
+            return Err(anyhow!(
+                "Unsupported indented line {}; multiline values, attributes and selectors aren't supported",
+                number + 1
+            ));
+        }
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected `key = value` on line {}", number + 1))?;
+        let key = key.trim();
+        if !is_identifier(key) {
+            return Err(anyhow!(
+                "Invalid message key on line {}; terms and attributes aren't supported",
+                number + 1
+            ));
+        }
+        let value = value.trim();
+        if value.is_empty() {
+            return Err(anyhow!(
+                "Empty message on line {}; multiline values aren't supported",
+                number + 1
+            ));
+        }
+        check_placeables(value).map_err(|e| anyhow!("{} on line {}", e, number + 1))?;
+        if messages.insert(key, value).is_some() {
+            return Err(anyhow!(
+                "Duplicate message key {} on line {}",
+                key,
+                number + 1
+            ));
+        }
+    }
+    Ok(messages)
+}
+
+/// A Fluent identifier: a letter followed by letters, digits, `_` or `-`.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Accept only `{ $name }` placeables, the one kind `format_message` fills in.
+fn check_placeables(pattern: &str) -> Result<()> {
+    let mut rest = pattern;
+    while let Some(start) = rest.find(['{', '}']) {
+        let Some(inner) = rest[start..].strip_prefix('{') else {
+            return Err(anyhow!("Unmatched `}}`"));
+        };
+        let end = inner
+            .find('}')
+            .ok_or_else(|| anyhow!("Unclosed placeable"))?;
+        let expression = inner[..end].trim();
+        if !expression.strip_prefix('$').is_some_and(is_identifier) {
+            return Err(anyhow!(
+                "Unsupported placeable `{{{}}}`; only `{{ $name }}` is supported",
+                &inner[..end]
+            ));
+        }
+        rest = &inner[end + 1..];
+    }
+    Ok(())
+}
+
+/// Replace `{ $name }` placeables with their argument values.
+fn format_message(pattern: &str, args: &[(&str, &str)]) -> String {
+    let mut message = pattern.to_string();
+    for (name, value) in args {
+        message = message
+            .replace(&format!("{{ ${name} }}"), value)
+            .replace(&format!("{{${name}}}"), value);
+    }
+    message
+}
+
+/// Pick the bundled locale matching the first supported language tag, e.g.
+/// "de-AT", "pt_BR.UTF-8" or "fr".
+fn negotiate<S: AsRef<str>>(requested: &[S]) -> Option<usize> {
+    requested.iter().find_map(|tag| {
+        let language = tag
+            .as_ref()
+            .split(['-', '_', '.', '@'])
+            .next()?
+            .to_lowercase();
+        LOCALES.iter().position(|(locale, _)| *locale == language)
+    })
+}
+
+/// Preferred languages from the OS, most preferred first.
+fn system_languages() -> Vec<String> {
+    let mut languages = platform_languages();
+    for var in ["LC_ALL", "LC_MESSAGES", "LANG"] {
+        if let Ok(value) = std::env::var(var) {
+            if !value.is_empty() && value != "C" && value != "POSIX" {
+                languages.push(value);
+            }
+        }
+    }
+    languages
+}
+
+#[cfg(target_os = "macos")]
+fn platform_languages() -> Vec<String> {
+    use cocoa::base::id;
+    use cocoa::foundation::NSString;
+
+    let mut languages = vec![];
+    unsafe {
+        let preferred: id = msg_send![class!(NSLocale), preferredLanguages];
+        let count: usize = msg_send![preferred, count];
+        for index in 0..count {
+            let language: id = msg_send![preferred, objectAtIndex: index];
+            let bytes = language.UTF8String();
+            if !bytes.is_null() {
+                let language = std::ffi::CStr::from_ptr(bytes).to_string_lossy();
+                languages.push(language.into_owned());
+            }
+        }
+    }
+    languages
+}
+
+/// GNU gettext's `LANGUAGE` priority list, e.g. `de_AT:de:en`.
+#[cfg(not(target_os = "macos"))]
+fn platform_languages() -> Vec<String> {
+    std::env::var("LANGUAGE")
+        .map(|value| {
+            value
+                .split(':')
+                .filter(|language| !language.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Select the active locale from a `language` override, falling back to the system
+/// languages and then English. Returns true if the active locale changed.
+pub fn set_language(language: Option<&str>) -> bool {
+    let index = language
+        .and_then(|language| {
+            let index = negotiate(&[language]);
+            if index.is_none() {
+                error!("Unsupported language {}, using system language", language);
+            }
+            index
+        })
+        .or_else(|| negotiate(&system_languages()))
+        .unwrap_or(0);
+    trace!("Using {} locale", LOCALES[index].0);
+    CURRENT.swap(index, Ordering::Relaxed) != index
+}
+
+fn lookup(index: usize, key: &str, args: &[(&str, &str)]) -> String {
+    let pattern = CATALOGS[index]
+        .get(key)
+        .or_else(|| CATALOGS[0].get(key))
+        .copied()
+        .unwrap_or(key);
+    format_message(pattern, args)
+}
+
+/// Translate a message in the active locale.
+pub fn tr(key: &str) -> String {
+    lookup(CURRENT.load(Ordering::Relaxed), key, &[])
+}
+
+/// Translate a message with `{ $name }` arguments in the active locale.
+pub fn tr_args(key: &str, args: &[(&str, &str)]) -> String {
+    lookup(CURRENT.load(Ordering::Relaxed), key, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn placeables(pattern: &str) -> BTreeSet<&str> {
+        pattern
+            .split("{ $")
+            .skip(1)
+            .filter_map(|rest| rest.split_once(" }").map(|(name, _)| name))
+            .collect()
+    }
+
+    #[test]
+    fn test_all_locales_parse() {
+        for (locale, source) in LOCALES {
+            assert!(parse_ftl(source).is_ok(), "{} failed to parse", locale);
+        }
+    }
+
+    #[test]
+    fn test_every_key_exists_in_every_locale() {
+        let source = &CATALOGS[0];
+        for (index, (locale, _)) in LOCALES.iter().enumerate().skip(1) {
+            let catalog = &CATALOGS[index];
+            for (key, pattern) in source {
+                let translated = catalog
+                    .get(key)
+                    .unwrap_or_else(|| panic!("{} is missing {}", locale, key));
+                assert_eq!(
+                    placeables(pattern),
+                    placeables(translated),
+                    "{} has different placeables for {}",
+                    locale,
+                    key
+                );
+            }
+            for key in catalog.keys() {
+                assert!(
+                    source.contains_key(key),
+                    "{} has unknown key {}",
+                    locale,
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_negotiate_language_tags() {
+        assert_eq!(negotiate(&["de-AT"]), Some(1));
+        assert_eq!(negotiate(&["fr_CA.UTF-8"]), Some(3));
+        assert_eq!(negotiate(&["ja-JP", "es-419"]), Some(2));
+        assert_eq!(negotiate(&["ja"]), None);
+    }
+
+    #[test]
+    fn test_lookup_formats_arguments() {
+        assert_eq!(
+            lookup(0, "tray-shortcut-unavailable", &[("shortcut", "⇧⌘A")]),
+            "Shortcut ⇧⌘A unavailable"
+        );
+        assert_eq!(lookup(1, "popup-mic-off", &[]), "Mikrofon aus");
+        assert_eq!(lookup(1, "missing-key", &[]), "missing-key");
+    }
+
+    #[test]
+    fn test_parse_ftl_rejects_malformed_lines() {
+        assert!(parse_ftl("key = value\nno separator").is_err());
+        assert!(parse_ftl("key = one\nkey = two").is_err());
+        assert!(parse_ftl("# comment\n\nkey = value").is_ok());
+    }
+
+    #[test]
+    fn test_parse_ftl_rejects_unsupported_fluent_syntax() {
+        // Multiline value
+        assert!(parse_ftl("key =\n    first line\n    second line").is_err());
+        assert!(parse_ftl("key = first line\n    second = line").is_err());
+        // Attribute
+        assert!(parse_ftl("key = value\n    .title = Title").is_err());
+        // Term and term reference
+        assert!(parse_ftl("-brand = Mic Mute").is_err());
+        assert!(parse_ftl("key = About { -brand }").is_err());
+        // Selector
+        assert!(parse_ftl("key = { $count ->\n   *[other] many\n}").is_err());
+        // Function, string literal and unbalanced braces
+        assert!(parse_ftl("key = { NUMBER($count) }").is_err());
+        assert!(parse_ftl("key = { \"{\" }").is_err());
+        assert!(parse_ftl("key = open { $name").is_err());
+        assert!(parse_ftl("key = close }").is_err());
+
+        let messages = parse_ftl("key = { $a }/{$b}").unwrap();
+        assert_eq!(
+            format_message(messages["key"], &[("a", "1"), ("b", "2")]),
+            "1/2"
+        );
+    }
+}
@@ -1,47 +1,303 @@
+use crate::mic::MicStatus;
+use crate::settings::{IconSettings, Settings};
 use anyhow::{Context, Result};
+use log::{error, trace};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, RwLock};
 use tao::window::Theme;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct IconColor {
     pub r: u8,
     pub g: u8,
     pub b: u8,
 }
 
-pub fn popup_icon_color(muted: bool, theme: Theme) -> IconColor {
-    match theme {
-        Theme::Light if muted => IconColor {
-            r: 239,
-            g: 68,
-            b: 68,
-        }, // #ef4444
-        Theme::Light => IconColor { r: 0, g: 0, b: 0 },
-        Theme::Dark if muted => IconColor {
-            r: 248,
-            g: 113,
-            b: 113,
-        }, // #f87171
-        _ => IconColor {
-            r: 255,
-            g: 255,
-            b: 255,
-        },
-    }
-}
-
-pub fn tray_icon_color(muted: bool) -> IconColor {
-    if muted {
-        IconColor {
-            r: 239,
-            g: 68,
-            b: 68,
-        } // #ef4444
-    } else {
-        IconColor {
-            r: 255,
-            g: 255,
-            b: 255,
+const RED_LIGHT: IconColor = IconColor {
+    r: 239,
+    g: 68,
+    b: 68,
+}; // #ef4444
+const RED_DARK: IconColor = IconColor {
+    r: 248,
+    g: 113,
+    b: 113,
+}; // #f87171
+const AMBER_LIGHT: IconColor = IconColor {
+    r: 217,
+    g: 119,
+    b: 6,
+}; // #d97706
+const AMBER_DARK: IconColor = IconColor {
+    r: 251,
+    g: 191,
+    b: 36,
+}; // #fbbf24
+const BLACK: IconColor = IconColor { r: 0, g: 0, b: 0 };
+const WHITE: IconColor = IconColor {
+    r: 255,
+    g: 255,
+    b: 255,
+};
+
+/// Only macOS recolors template tray images to match the menu bar.
+const TEMPLATE_IMAGES: bool = cfg!(target_os = "macos");
+
+impl IconColor {
+    /// Parse "#rrggbb" or "#rgb", with or without the leading "#".
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.trim().trim_start_matches('#');
+        if !hex.is_ascii() {
+            return None;
+        }
+        let channel = |range: std::ops::Range<usize>| u8::from_str_radix(&hex[range], 16).ok();
+        match hex.len() {
+            6 => Some(Self {
+                r: channel(0..2)?,
+                g: channel(2..4)?,
+                b: channel(4..6)?,
+            }),
+            3 => Some(Self {
+                r: channel(0..1)? * 17,
+                g: channel(1..2)? * 17,
+                b: channel(2..3)? * 17,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Which color an icon is drawn in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IconState {
+    Muted,
+    Unmuted,
+    /// Needs attention: partially muted or a failed operation.
+    Warning,
+}
+
+impl IconState {
+    pub fn from_muted(muted: bool) -> Self {
+        if muted {
+            IconState::Muted
+        } else {
+            IconState::Unmuted
+        }
+    }
+
+    pub fn for_status(status: MicStatus) -> Self {
+        match status {
+            MicStatus::Muted => IconState::Muted,
+            MicStatus::Unmuted | MicStatus::NoControllableDevices => IconState::Unmuted,
+            MicStatus::PartiallyMuted { .. } | MicStatus::Failed => IconState::Warning,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Glyph {
+    Mic,
+    MicOff,
+    MicPartial,
+    MicUnavailable,
+    MicAlert,
+    Video,
+    VideoOff,
+}
+
+impl Glyph {
+    const ALL: [Glyph; 7] = [
+        Glyph::Mic,
+        Glyph::MicOff,
+        Glyph::MicPartial,
+        Glyph::MicUnavailable,
+        Glyph::MicAlert,
+        Glyph::Video,
+        Glyph::VideoOff,
+    ];
+
+    pub fn mic(status: MicStatus) -> Self {
+        match status {
+            MicStatus::Muted => Glyph::MicOff,
+            MicStatus::Unmuted => Glyph::Mic,
+            MicStatus::PartiallyMuted { .. } => Glyph::MicPartial,
+            MicStatus::NoControllableDevices => Glyph::MicUnavailable,
+            MicStatus::Failed => Glyph::MicAlert,
+        }
+    }
+
+    pub fn video(muted: bool) -> Self {
+        if muted {
+            Glyph::VideoOff
+        } else {
+            Glyph::Video
+        }
+    }
+
+    fn file_name(self) -> &'static str {
+        match self {
+            Glyph::Mic => "mic.svg",
+            Glyph::MicOff => "mic-off.svg",
+            Glyph::MicPartial => "mic-partial.svg",
+            Glyph::MicUnavailable => "mic-unavailable.svg",
+            Glyph::MicAlert => "mic-alert.svg",
+            Glyph::Video => "video.svg",
+            Glyph::VideoOff => "video-off.svg",
+        }
+    }
+
+    fn builtin(self) -> &'static [u8] {
+        match self {
+            Glyph::Mic => include_bytes!("../assets/mic.svg"),
+            Glyph::MicOff => include_bytes!("../assets/mic-off.svg"),
+            Glyph::MicPartial => include_bytes!("../assets/mic-partial.svg"),
+            Glyph::MicUnavailable => include_bytes!("../assets/mic-unavailable.svg"),
+            Glyph::MicAlert => include_bytes!("../assets/mic-alert.svg"),
+            Glyph::Video => include_bytes!("../assets/video.svg"),
+            Glyph::VideoOff => include_bytes!("../assets/video-off.svg"),
+        }
+    }
+}
+
+/// SVGs from a user icon set. Glyphs that are missing or fail to render use the
+/// built-in icons.
+#[derive(Default)]
+struct IconSet {
+    svgs: HashMap<Glyph, Vec<u8>>,
+}
+
+impl IconSet {
+    fn load(dir: &Path) -> Self {
+        let mut svgs = HashMap::new();
+        for glyph in Glyph::ALL {
+            let path = dir.join(glyph.file_name());
+            if !path.exists() {
+                continue;
+            }
+            let loaded = std::fs::read(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))
+                .and_then(|bytes| {
+                    rasterize_svg(&bytes, &WHITE)
+                        .with_context(|| format!("Invalid icon {}", path.display()))?;
+                    Ok(bytes)
+                });
+            match loaded {
+                Ok(bytes) => {
+                    trace!("Loaded custom {:?} icon", glyph);
+                    svgs.insert(glyph, bytes);
+                }
+                Err(e) => error!("Using built-in {:?} icon: {:#}", glyph, e),
+            }
+        }
+        Self { svgs }
+    }
+
+    fn svg(&self, glyph: Glyph) -> &[u8] {
+        self.svgs
+            .get(&glyph)
+            .map(Vec::as_slice)
+            .unwrap_or_else(|| glyph.builtin())
+    }
+}
+
+fn parse_color(hex: Option<&str>) -> Option<IconColor> {
+    let hex = hex?;
+    let color = IconColor::from_hex(hex);
+    if color.is_none() {
+        error!("Invalid icon color {}, using the default", hex);
+    }
+    color
+}
+
+/// Where a relative `icon_set` lives: an "icons" folder beside settings.json.
+fn icon_set_dir(icon_set: &Path) -> Option<PathBuf> {
+    let config_dir = Settings::config_path()?.parent()?.to_path_buf();
+    Some(config_dir.join("icons").join(icon_set))
+}
+
+struct Icons {
+    settings: IconSettings,
+    set: IconSet,
+    muted: Option<IconColor>,
+    unmuted: Option<IconColor>,
+    warning: Option<IconColor>,
+}
+
+impl Icons {
+    fn new(settings: &IconSettings) -> Self {
+        let set = settings
+            .icon_set
+            .as_deref()
+            .and_then(icon_set_dir)
+            .map(|dir| IconSet::load(&dir))
+            .unwrap_or_default();
+        Self {
+            settings: settings.clone(),
+            set,
+            muted: parse_color(settings.muted_color.as_deref()),
+            unmuted: parse_color(settings.unmuted_color.as_deref()),
+            warning: parse_color(settings.warning_color.as_deref()),
         }
     }
+
+    fn popup_color(&self, state: IconState, theme: Theme) -> IconColor {
+        let light = theme == Theme::Light;
+        match state {
+            IconState::Muted => self
+                .muted
+                .unwrap_or(if light { RED_LIGHT } else { RED_DARK }),
+            IconState::Unmuted => self.unmuted.unwrap_or(if light { BLACK } else { WHITE }),
+            IconState::Warning => {
+                self.warning
+                    .unwrap_or(if light { AMBER_LIGHT } else { AMBER_DARK })
+            }
+        }
+    }
+
+    fn tray_style(&self, state: IconState, theme: Theme) -> (IconColor, bool) {
+        if state == IconState::Muted {
+            (self.muted.unwrap_or(RED_LIGHT), false)
+        } else if state == IconState::Warning {
+            (self.warning.unwrap_or(AMBER_DARK), false)
+        } else if let Some(color) = self.unmuted {
+            (color, false)
+        } else if self.settings.tray_template && TEMPLATE_IMAGES {
+            // Template images only use alpha; macOS picks the color
+            (BLACK, true)
+        } else if theme == Theme::Light {
+            (BLACK, false)
+        } else {
+            (WHITE, false)
+        }
+    }
+}
+
+static ICONS: LazyLock<RwLock<Icons>> =
+    LazyLock::new(|| RwLock::new(Icons::new(&IconSettings::default())));
+
+/// Load colors and the icon set from settings. Returns true if anything changed.
+pub fn apply_settings(settings: &IconSettings) -> bool {
+    let mut icons = ICONS.write().unwrap();
+    if icons.settings == *settings {
+        return false;
+    }
+    *icons = Icons::new(settings);
+    true
+}
+
+/// The SVG for a glyph from the user icon set, or the built-in one.
+pub fn svg(glyph: Glyph) -> Vec<u8> {
+    ICONS.read().unwrap().set.svg(glyph).to_vec()
+}
+
+pub fn popup_icon_color(state: IconState, theme: Theme) -> IconColor {
+    ICONS.read().unwrap().popup_color(state, theme)
+}
+
+/// Tray icon color and whether it should be drawn as a template image.
+pub fn tray_icon_style(state: IconState, theme: Theme) -> (IconColor, bool) {
+    ICONS.read().unwrap().tray_style(state, theme)
 }
 
 /// Rasterizes an SVG with the given stroke color.
@@ -88,3 +344,90 @@ pub fn rasterize_svg(svg_bytes: &[u8], color: &IconColor) -> Result<(Vec<u8>, u3
 
     Ok((straight, w, h))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID_SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="24" viewBox="0 0 24 24"><circle cx="12" cy="12" r="10"/></svg>"#;
+
+    #[test]
+    fn test_color_from_hex() {
+        assert_eq!(IconColor::from_hex("#ef4444"), Some(RED_LIGHT));
+        assert_eq!(IconColor::from_hex("fff"), Some(WHITE));
+        assert_eq!(IconColor::from_hex("#12345"), None);
+        assert_eq!(IconColor::from_hex("#gg0000"), None);
+        assert_eq!(IconColor::from_hex("#ééé"), None);
+    }
+
+    #[test]
+    fn test_builtin_icons_rasterize() {
+        for glyph in Glyph::ALL {
+            let (rgba, w, h) = rasterize_svg(glyph.builtin(), &WHITE).unwrap();
+            assert_eq!(rgba.len(), (w * h * 4) as usize);
+        }
+    }
+
+    #[test]
+    fn test_icon_set_falls_back_for_bad_or_missing_svgs() {
+        let dir = std::env::temp_dir().join("mic-mute-test-icons");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("mic.svg"), "<svg not really").unwrap();
+        std::fs::write(dir.join("mic-off.svg"), VALID_SVG).unwrap();
+
+        let set = IconSet::load(&dir);
+
+        assert_eq!(set.svg(Glyph::Mic), Glyph::Mic.builtin());
+        assert_eq!(set.svg(Glyph::MicOff), VALID_SVG.as_bytes());
+        assert_eq!(set.svg(Glyph::Video), Glyph::Video.builtin());
+        assert_eq!(set.svg(Glyph::MicAlert), Glyph::MicAlert.builtin());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_tray_style() {
+        let icons = Icons::new(&IconSettings::default());
+        assert_eq!(
+            icons.tray_style(IconState::Unmuted, Theme::Light),
+            (BLACK, TEMPLATE_IMAGES)
+        );
+        assert_eq!(
+            icons.tray_style(IconState::Muted, Theme::Dark),
+            (RED_LIGHT, false)
+        );
+        assert_eq!(
+            icons.tray_style(IconState::Warning, Theme::Light),
+            (AMBER_DARK, false)
+        );
+
+        let icons = Icons::new(&IconSettings {
+            tray_template: false,
+            ..IconSettings::default()
+        });
+        assert_eq!(
+            icons.tray_style(IconState::Unmuted, Theme::Light),
+            (BLACK, false)
+        );
+        assert_eq!(
+            icons.tray_style(IconState::Unmuted, Theme::Dark),
+            (WHITE, false)
+        );
+
+        let icons = Icons::new(&IconSettings {
+            unmuted_color: Some("#00ff00".to_string()),
+            ..IconSettings::default()
+        });
+        let green = IconColor { r: 0, g: 255, b: 0 };
+        assert_eq!(
+            icons.tray_style(IconState::Unmuted, Theme::Light),
+            (green, false)
+        );
+        assert_eq!(icons.popup_color(IconState::Unmuted, Theme::Dark), green);
+        assert_eq!(icons.popup_color(IconState::Muted, Theme::Dark), RED_DARK);
+        assert_eq!(
+            icons.popup_color(IconState::Warning, Theme::Light),
+            AMBER_LIGHT
+        );
+    }
+}
@@ -0,0 +1,228 @@
+/// Keeps to one Mic Mute per user. The running app holds a lock on `instance.lock`
+/// in the runtime directory and listens on `instance.sock` beside it; a later
+/// launch hands its command over there and exits. The kernel releases the lock
+/// when its holder exits, crashes included, so a lock file left behind never
+/// blocks a launch, and a socket left behind is replaced.
+use crate::app_state::Command;
+use crate::utils::{create_runtime_dir, runtime_dir};
+use anyhow::{bail, Context, Result};
+use log::{info, trace};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const LOCK_NAME: &str = "instance.lock";
+const SOCKET_NAME: &str = "instance.sock";
+/// The URL scheme registered for the app bundle, as in
+/// `com.brettinternet.mic-mute://toggle`.
+const URL_SCHEME: &str = "com.brettinternet.mic-mute";
+/// How long a later launch waits for the running app to listen and reply, since
+/// it may still be starting up.
+const HANDOFF_TIMEOUT: Duration = Duration::from_secs(5);
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Held for the life of the process by the instance that runs.
+pub struct InstanceLock {
+    _file: File,
+}
+
+/// Take the instance lock, or None when another instance holds it.
+pub fn acquire() -> Result<Option<InstanceLock>> {
+    acquire_at(&create_runtime_dir()?.join(LOCK_NAME))
+}
+
+fn acquire_at(path: &Path) -> Result<Option<InstanceLock>> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let error = io::Error::last_os_error();
+        if error.kind() == io::ErrorKind::WouldBlock {
+            return Ok(None);
+        }
+        return Err(error).with_context(|| format!("Failed to lock {}", path.display()));
+    }
+    // The pid is for people looking at the file; only the lock counts
+    file.set_len(0)?;
+    write!(file, "{}", std::process::id())?;
+    Ok(Some(InstanceLock { _file: file }))
+}
+
+/// Whether `error` from `acquire` only means the runtime directory's file system
+/// can't lock files, e.g. some network mounts. Running without the lock is safe
+/// then; any other failure could let a second instance take over the devices.
+pub fn lock_unsupported(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .and_then(io::Error::raw_os_error)
+        .is_some_and(|code| {
+            code == libc::ENOLCK || code == libc::EOPNOTSUPP || code == libc::ENOTSUP
+        })
+}
+
+/// The command in a launch argument: `show`, `mute`, `unmute` or `toggle`, alone
+/// or as a `com.brettinternet.mic-mute://` URL.
+pub fn parse_command(arg: &str) -> Option<Command> {
+    let name = match arg
+        .strip_prefix(URL_SCHEME)
+        .and_then(|rest| rest.strip_prefix(':'))
+    {
+        Some(rest) => rest.trim_matches('/'),
+        None => arg,
+    };
+    match name.to_ascii_lowercase().as_str() {
+        "show" => Some(Command::ShowStatus),
+        "mute" => Some(Command::Mute),
+        "unmute" => Some(Command::Unmute),
+        "toggle" => Some(Command::Toggle),
+        _ => None,
+    }
+}
+
+fn command_name(command: Command) -> &'static str {
+    match command {
+        Command::ShowStatus => "show",
+        Command::Mute => "mute",
+        Command::Unmute => "unmute",
+        Command::Toggle => "toggle",
+    }
+}
+
+/// Accept commands from later launches, passing each to `on_command` from a
+/// background thread.
+pub fn listen(on_command: impl Fn(Command) + Send + 'static) -> Result<()> {
+    listen_at(&create_runtime_dir()?.join(SOCKET_NAME), on_command)
+}
+
+fn listen_at(path: &Path, on_command: impl Fn(Command) + Send + 'static) -> Result<()> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            bail!("Another Mic Mute is listening on {}", path.display());
+        }
+        fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Failed to listen on {}", path.display()))?;
+    std::thread::Builder::new()
+        .name("instance".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream.and_then(|stream| receive(&stream, &on_command));
+                if let Err(e) = result {
+                    trace!("Failed to take a command from another launch: {}", e);
+                }
+            }
+        })
+        .context("Failed to start the instance thread")?;
+    Ok(())
+}
+
+/// Read one command and reply "ok", or why it was refused.
+fn receive(mut stream: &UnixStream, on_command: &impl Fn(Command)) -> io::Result<()> {
+    stream.set_read_timeout(Some(HANDOFF_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    match parse_command(line.trim()) {
+        Some(command) => {
+            info!("Launched again with {:?}", command);
+            on_command(command);
+            writeln!(stream, "ok")
+        }
+        None => writeln!(stream, "unknown command {:?}", line.trim()),
+    }
+}
+
+/// Hand `command` to the running instance and wait for it to be accepted.
+pub fn forward(command: Command) -> Result<()> {
+    forward_to(&runtime_dir().join(SOCKET_NAME), command)
+}
+
+fn forward_to(path: &Path, command: Command) -> Result<()> {
+    let deadline = Instant::now() + HANDOFF_TIMEOUT;
+    let mut stream = loop {
+        match UnixStream::connect(path) {
+            Ok(stream) => break stream,
+            Err(_) if Instant::now() < deadline => std::thread::sleep(RETRY_DELAY),
+            Err(e) => return Err(e).context("Mic Mute is running but not taking commands"),
+        }
+    };
+    stream.set_read_timeout(Some(HANDOFF_TIMEOUT))?;
+    writeln!(stream, "{}", command_name(command))?;
+    let mut reply = String::new();
+    BufReader::new(stream)
+        .read_line(&mut reply)
+        .context("No reply from the running Mic Mute")?;
+    match reply.trim() {
+        "ok" => Ok(()),
+        reason => bail!("The running Mic Mute refused the command: {}", reason),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("toggle"), Some(Command::Toggle));
+        assert_eq!(parse_command("Mute"), Some(Command::Mute));
+        assert_eq!(
+            parse_command("com.brettinternet.mic-mute://unmute"),
+            Some(Command::Unmute)
+        );
+        assert_eq!(
+            parse_command("com.brettinternet.mic-mute:show/"),
+            Some(Command::ShowStatus)
+        );
+        assert_eq!(parse_command("other-app://toggle"), None);
+        assert_eq!(parse_command("quit"), None);
+    }
+
+    #[test]
+    fn test_lock_is_exclusive_until_released() {
+        let dir = test_dir("mic-mute-test-instance-lock");
+        let path = dir.join(LOCK_NAME);
+
+        let first = acquire_at(&path).unwrap();
+        assert!(first.is_some());
+        assert!(acquire_at(&path).unwrap().is_none());
+        // As when the holder crashes: the file stays, the lock goes
+        drop(first);
+        assert!(acquire_at(&path).unwrap().is_some());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_forwards_commands_and_replaces_stale_socket() {
+        let dir = test_dir("mic-mute-test-instance-socket");
+        let path = dir.join(SOCKET_NAME);
+        // A socket nobody listens on, as left by a crash
+        drop(UnixListener::bind(&path).unwrap());
+        let (commands, received) = mpsc::channel();
+
+        listen_at(&path, move |command| {
+            let _ = commands.send(command);
+        })
+        .unwrap();
+        forward_to(&path, Command::Toggle).unwrap();
+
+        assert_eq!(received.try_recv(), Ok(Command::Toggle));
+        assert!(listen_at(&path, |_| {}).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,264 @@
+/// Logging to stderr, a rotating file in the app's data directory and an in-memory
+/// buffer of recent lines for diagnostics. Levels come from settings and change
+/// live; when `RUST_LOG` is set it decides instead, as with plain env_logger.
+use crate::settings::Settings;
+use crate::utils::UtcTime;
+use env_logger::{Env, Logger};
+use log::{LevelFilter, Log, Metadata, Record};
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::SystemTime;
+
+/// How many of the latest log lines are kept for diagnostics.
+const RECENT_LINES: usize = 5000;
+const FILE_NAME: &str = "mic-mute.log";
+/// Size at which the log file is rotated.
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+/// Rotated files kept beside the current one, as `mic-mute.log.1` and up.
+const ROTATED_FILES: usize = 3;
+
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static LEVELS: RwLock<Levels> = RwLock::new(Levels {
+    default: LevelFilter::Info,
+    modules: Vec::new(),
+});
+static FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+/// Whether `RUST_LOG` overrides the levels from settings.
+static ENV_FILTER: AtomicBool = AtomicBool::new(false);
+
+/// The level in effect for each log target.
+#[derive(Debug, PartialEq)]
+struct Levels {
+    default: LevelFilter,
+    /// Module overrides, longest first so the most specific one wins.
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Levels {
+    fn from_settings(settings: &Settings) -> Self {
+        let mut modules: Vec<_> = settings
+            .log_modules
+            .iter()
+            .map(|(module, level)| (module.clone(), level.filter()))
+            .collect();
+        modules.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+        Self {
+            default: settings.log_level.filter(),
+            modules,
+        }
+    }
+
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                target
+                    .strip_prefix(module.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    fn max(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, file, size })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.size > 0 && self.size + len > MAX_FILE_BYTES {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    /// Shift `mic-mute.log.N` up by one, dropping the oldest, and start a new file.
+    fn rotate(&mut self) -> io::Result<()> {
+        for n in (1..ROTATED_FILES).rev() {
+            let _ = fs::rename(rotated_path(&self.path, n), rotated_path(&self.path, n + 1));
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+struct AppLogger {
+    stderr: Logger,
+}
+
+impl Log for AppLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        if ENV_FILTER.load(Ordering::Relaxed) {
+            self.stderr.enabled(metadata)
+        } else {
+            metadata.level() <= LEVELS.read().unwrap().level_for(metadata.target())
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) || !self.stderr.matches(record) {
+            return;
+        }
+        self.stderr.log(record);
+        let line = format!(
+            "{} {:<5} {}: {}",
+            UtcTime::from_system_time(SystemTime::now()).rfc3339(),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        let mut file = FILE.lock().unwrap();
+        if let Some(log_file) = file.as_mut() {
+            if let Err(e) = log_file.write_line(&line) {
+                eprintln!("Stopped writing {}: {}", log_file.path.display(), e);
+                *file = None;
+            }
+        }
+        drop(file);
+        push_capped(&mut RECENT.lock().unwrap(), line, RECENT_LINES);
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+        if let Some(log_file) = FILE.lock().unwrap().as_mut() {
+            let _ = log_file.file.flush();
+        }
+    }
+}
+
+fn push_capped(lines: &mut VecDeque<String>, line: String, capacity: usize) {
+    if lines.len() >= capacity {
+        lines.pop_front();
+    }
+    lines.push_back(line);
+}
+
+/// The current log file, e.g. `~/.local/share/mic-mute/logs/mic-mute.log`.
+pub fn log_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("mic-mute").join("logs").join(FILE_NAME))
+}
+
+/// Install the logger at the default level until settings are applied.
+pub fn init() {
+    let env_filter = std::env::var_os("RUST_LOG").is_some();
+    ENV_FILTER.store(env_filter, Ordering::Relaxed);
+    let stderr = env_logger::Builder::from_env(Env::default().default_filter_or("trace")).build();
+    log::set_max_level(if env_filter {
+        stderr.filter()
+    } else {
+        LEVELS.read().unwrap().max()
+    });
+    match log_path().map(LogFile::open) {
+        Some(Ok(file)) => *FILE.lock().unwrap() = Some(file),
+        Some(Err(e)) => eprintln!("Failed to open the log file: {}", e),
+        None => eprintln!("No data directory for the log file"),
+    }
+    if let Err(e) = log::set_boxed_logger(Box::new(AppLogger { stderr })) {
+        eprintln!("Failed to install logger: {}", e);
+    }
+}
+
+/// Apply `log_level` and `log_modules`. Safe to call with unchanged settings.
+pub fn apply_settings(settings: &Settings) {
+    let levels = Levels::from_settings(settings);
+    if !ENV_FILTER.load(Ordering::Relaxed) {
+        log::set_max_level(levels.max());
+    }
+    *LEVELS.write().unwrap() = levels;
+}
+
+/// The latest log lines, oldest first.
+pub fn recent_lines() -> Vec<String> {
+    RECENT.lock().unwrap().iter().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::LogLevel;
+
+    #[test]
+    fn test_push_capped_drops_oldest() {
+        let mut lines = VecDeque::new();
+        for i in 0..5 {
+            push_capped(&mut lines, i.to_string(), 3);
+        }
+        assert_eq!(lines, ["2", "3", "4"]);
+    }
+
+    #[test]
+    fn test_module_levels_override_default() {
+        let mut settings = Settings {
+            log_level: LogLevel::Warn,
+            ..Settings::default()
+        };
+        settings
+            .log_modules
+            .insert("mic_mute".to_string(), LogLevel::Info);
+        settings
+            .log_modules
+            .insert("mic_mute::mic".to_string(), LogLevel::Trace);
+        let levels = Levels::from_settings(&settings);
+
+        assert_eq!(levels.level_for("mic_mute::mic"), LevelFilter::Trace);
+        assert_eq!(levels.level_for("mic_mute::mic::fake"), LevelFilter::Trace);
+        assert_eq!(levels.level_for("mic_mute::microphone"), LevelFilter::Info);
+        assert_eq!(levels.level_for("tao::platform"), LevelFilter::Warn);
+        assert_eq!(levels.max(), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn test_log_file_rotates_by_size() {
+        let dir = std::env::temp_dir().join("mic-mute-test-logs");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join(FILE_NAME);
+        let mut file = LogFile::open(path.clone()).unwrap();
+        let line = "x".repeat(MAX_FILE_BYTES as usize / 2);
+
+        for _ in 0..(ROTATED_FILES + 2) * 2 {
+            file.write_line(&line).unwrap();
+        }
+
+        assert!(path.exists());
+        assert!(rotated_path(&path, ROTATED_FILES).exists());
+        assert!(!rotated_path(&path, ROTATED_FILES + 1).exists());
+        assert!(fs::metadata(&path).unwrap().len() <= MAX_FILE_BYTES);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
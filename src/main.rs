@@ -1,92 +1,154 @@
-mod about;
+mod app_state;
 mod camera;
 mod config;
+mod diagnostics;
 mod event_loop;
+mod frontend;
+mod headless;
+mod i18n;
 mod icons;
+mod instance;
 mod launch_at_login;
+mod logging;
 mod mic;
+mod mqtt;
+mod platform;
 mod popup;
 mod popup_content;
+mod preferences;
 mod settings;
 mod shortcuts;
+mod sound;
+mod status;
 mod tray;
 mod ui;
+mod usage;
 mod utils;
 // TODO: Use better Apple logging support? https://lib.rs/crates/oslog
 
+#[cfg(target_os = "macos")]
 #[macro_use]
 extern crate objc;
 
+use crate::app_state::Command;
 use crate::camera::CameraController;
 use crate::config::AppVars;
-use crate::event_loop::{restore_microphone_on_exit, start};
+use crate::event_loop::{start, start_headless};
+use crate::headless::Headless;
 use crate::mic::MicController;
 use crate::settings::Settings;
 use crate::ui::UI;
 use crate::utils::arc_lock;
-use env_logger::{Builder, Env};
 use log::{info, trace};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::Duration;
 
-static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
-
-extern "C" fn handle_signal(_: libc::c_int) {
-    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+/// `mic-mute watch [--format TEMPLATE]`, which follows the running app's state for
+/// status bars. Runs without logging so it doesn't write to the app's log file.
+fn watch(args: &[String]) -> ! {
+    let usage = || -> ! {
+        eprintln!("Usage: mic-mute watch [--format '{{mic_icon}} {{device}}']");
+        std::process::exit(2);
+    };
+    let template = match args {
+        [] => None,
+        [flag, template] if flag == "--format" => Some(template.as_str()),
+        [arg] => Some(arg.strip_prefix("--format=").unwrap_or_else(|| usage())),
+        _ => usage(),
+    };
+    if let Err(e) = status::watch(template) {
+        eprintln!("{:#}", e);
+        std::process::exit(1);
+    }
+    std::process::exit(0);
 }
 
 fn main() {
-    Builder::from_env(Env::default().default_filter_or("trace")).init();
-    info!("Starting app");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "watch") {
+        watch(&args[1..]);
+    }
+    // A command such as `toggle` or a com.brettinternet.mic-mute:// URL. macOS may
+    // add its own `-psn_…` argument.
+    let command = args.iter().find(|arg| !arg.starts_with('-')).map(|arg| {
+        instance::parse_command(arg).unwrap_or_else(|| {
+            eprintln!(
+                "Unknown command {:?}; expected show, mute, unmute, toggle or watch",
+                arg
+            );
+            std::process::exit(2);
+        })
+    });
+    // One instance per user: a later launch hands its command to the running one
+    let instance = match instance::acquire() {
+        Ok(Some(lock)) => Ok(lock),
+        Ok(None) => match instance::forward(command.unwrap_or(Command::ShowStatus)) {
+            Ok(()) => std::process::exit(0),
+            Err(e) => {
+                eprintln!("{:#}", e);
+                std::process::exit(1);
+            }
+        },
+        Err(e) if instance::lock_unsupported(&e) => Err(e),
+        Err(e) => {
+            eprintln!("Not starting: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    logging::init();
+    // Run the mute engine without a tray or popup, e.g. as a service
+    let headless = args.iter().any(|arg| arg == "--headless");
+    info!(
+        "Starting app{}",
+        if headless { " in headless mode" } else { "" }
+    );
+
+    // Held until the process exits
+    let _instance = instance
+        .map_err(|e| log::error!("Running without the single-instance lock: {:#}", e))
+        .ok();
 
     let mut settings = Settings::load();
+    logging::apply_settings(&settings);
+    i18n::set_language(settings.language.as_deref());
+    icons::apply_settings(&settings.icons);
 
     // On first run (or after upgrading from a version without launch_at_login in
-    // settings), adopt the existing plist state so we don't silently disable it.
-    let plist_enabled = launch_at_login::is_enabled();
-    if plist_enabled != settings.launch_at_login {
-        settings.launch_at_login = plist_enabled;
+    // settings), adopt the existing login item state so we don't silently disable it.
+    let login_enabled = launch_at_login::is_enabled();
+    if login_enabled != settings.launch_at_login {
+        settings.launch_at_login = login_enabled;
         let _ = settings.save();
     }
+    if settings.launch_at_login {
+        if let Err(e) = launch_at_login::repair() {
+            log::error!("Failed to repair login item: {:#}", e);
+        }
+    }
 
     let app_vars = AppVars::new();
 
-    let controller = MicController::new().unwrap();
+    let mut controller = MicController::new().unwrap();
+    if let Err(e) = controller.set_excluded_devices(&settings.excluded_devices) {
+        log::error!("Failed to apply excluded devices: {}", e);
+    }
     let mic_muted = controller.muted;
+    let mic_status = controller.status;
     let controller = arc_lock(controller);
     trace!("Mic controller initialized {:?}", controller);
 
-    // Register SIGTERM/SIGINT handlers. The signal handler only sets a flag;
-    // a background thread performs microphone cleanup before exiting.
-    unsafe {
-        libc::signal(
-            libc::SIGTERM,
-            handle_signal as *const () as libc::sighandler_t,
-        );
-        libc::signal(
-            libc::SIGINT,
-            handle_signal as *const () as libc::sighandler_t,
-        );
-    }
-    let shutdown_controller = controller.clone();
-    std::thread::spawn(move || loop {
-        std::thread::sleep(Duration::from_millis(100));
-        if SHUTDOWN_REQUESTED.load(Ordering::SeqCst) {
-            info!("Signal received — restoring microphone state before exit");
-            restore_microphone_on_exit(&shutdown_controller);
-            std::process::exit(0);
-        }
-    });
-
     let camera = CameraController::new().unwrap();
-    let camera_muted = camera.muted;
+    let cameras = camera.devices().unwrap_or_default();
     let camera = arc_lock(camera);
-    trace!("Camera controller initialized, muted={}", camera_muted);
+    trace!("Camera controller initialized, cameras={:?}", cameras);
+
+    if headless {
+        let frontend = Headless::new(&settings);
+        start_headless(frontend, controller, camera, settings, command);
+    }
 
     let (ui, event_loop, event_ids) =
-        UI::new(mic_muted, camera_muted, app_vars, &settings).unwrap();
+        UI::new(mic_muted, mic_status, &cameras, app_vars, &settings).unwrap();
     trace!("UI initialized");
-    let ui = arc_lock(ui);
-    let settings = arc_lock(settings);
-    start(event_loop, event_ids, ui, controller, camera, settings);
+    start(
+        event_loop, event_ids, ui, controller, camera, settings, command,
+    );
 }
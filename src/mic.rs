@@ -1,28 +1,12 @@
-use anyhow::{anyhow, Context, Result};
-use coreaudio::audio_unit::macos_helpers::{get_audio_device_ids, get_device_name};
+use anyhow::{anyhow, Result};
 use log::{error, trace};
-use objc2_core_audio::{
-    kAudioDevicePropertyMute, kAudioDevicePropertyScopeInput,
-    kAudioDevicePropertyStreamConfiguration, kAudioDevicePropertyVolumeScalar,
-    kAudioHardwareNoError, kAudioHardwarePropertyDefaultInputDevice,
-    kAudioHardwareUnknownPropertyError, kAudioObjectPropertyElementMain,
-    kAudioObjectPropertyScopeGlobal, AudioDeviceID, AudioObjectGetPropertyData,
-    AudioObjectGetPropertyDataSize, AudioObjectIsPropertySettable, AudioObjectPropertyAddress,
-    AudioObjectPropertySelector, AudioObjectSetPropertyData,
-};
-use objc2_core_audio_types::{AudioBuffer, AudioBufferList};
-use std::alloc::{alloc_zeroed, dealloc, Layout};
 use std::collections::{HashMap, HashSet};
-use std::ffi::c_void;
 use std::fmt;
 use std::fmt::{Debug, Formatter};
-use std::mem;
-use std::ptr::{null, NonNull};
 
-const SYSTEM_OBJECT_ID: AudioDeviceID = 1;
+pub use crate::platform::{AudioDeviceID, SystemAudioBackend};
+
 const VOLUME_MUTED_EPSILON: f32 = 0.000_001;
-const AUDIO_HARDWARE_SERVICE_DEVICE_PROPERTY_VIRTUAL_MAIN_VOLUME: AudioObjectPropertySelector =
-    0x766d7663; // 'vmvc'
 
 fn is_volume_muted(volume: f32) -> bool {
     volume <= VOLUME_MUTED_EPSILON
@@ -32,46 +16,73 @@ fn target_state(state: Option<bool>, desired_muted: bool) -> bool {
     state.unwrap_or(!desired_muted)
 }
 
-fn status_result(status: i32, operation: &str, audio_device_id: AudioDeviceID) -> Result<()> {
-    if status == kAudioHardwareNoError {
-        Ok(())
-    } else {
-        Err(anyhow!(
-            "{} failed for audio device {} with OSStatus {}",
-            operation,
-            audio_device_id,
-            status
-        ))
-    }
+/// What the mic indicator reports about the input devices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MicStatus {
+    /// Every input device is muted.
+    Muted,
+    /// No input device is muted.
+    Unmuted,
+    /// Some input devices are muted, or some can't be controlled while the rest are.
+    PartiallyMuted { muted: usize, total: usize },
+    /// No input device has a mute or volume control.
+    NoControllableDevices,
+    /// The last attempt to change or read the mute state failed.
+    Failed,
 }
 
-struct AudioBufferListAllocation {
-    ptr: NonNull<u8>,
-    layout: Layout,
+/// Mute state counts across the non-excluded input devices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DeviceCounts {
+    muted: usize,
+    controllable: usize,
+    total: usize,
 }
 
-impl AudioBufferListAllocation {
-    fn new(size: u32) -> Result<Self> {
-        let layout = Layout::from_size_align(size as usize, mem::align_of::<AudioBufferList>())
-            .context("invalid AudioBufferList allocation layout")?;
-        let ptr = NonNull::new(unsafe { alloc_zeroed(layout) })
-            .ok_or_else(|| anyhow!("failed to allocate AudioBufferList"))?;
-        Ok(Self { ptr, layout })
+impl DeviceCounts {
+    /// Muted as far as CoreAudio can control: every controllable device is muted.
+    fn all_muted(&self) -> bool {
+        self.controllable > 0 && self.muted == self.controllable
     }
 
-    fn as_mut_void(&mut self) -> *mut c_void {
-        self.ptr.as_ptr().cast()
+    fn status(&self) -> MicStatus {
+        if self.controllable == 0 {
+            MicStatus::NoControllableDevices
+        } else if self.muted == self.total {
+            MicStatus::Muted
+        } else if self.muted == 0 {
+            MicStatus::Unmuted
+        } else {
+            MicStatus::PartiallyMuted {
+                muted: self.muted,
+                total: self.total,
+            }
+        }
     }
+}
 
-    unsafe fn as_list(&self) -> &AudioBufferList {
-        &*self.ptr.as_ptr().cast::<AudioBufferList>()
-    }
+/// Backend specifics of one device, reported in diagnostics.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceDetails {
+    /// Identifier that survives reconnects, e.g. a CoreAudio UID or PulseAudio source name.
+    pub uid: Option<String>,
+    pub transport: Option<String>,
+    /// The volume controls the device offers.
+    pub volume_elements: Vec<String>,
 }
 
-impl Drop for AudioBufferListAllocation {
-    fn drop(&mut self) {
-        unsafe { dealloc(self.ptr.as_ptr(), self.layout) };
-    }
+/// One row of the device table in diagnostics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceReport {
+    pub id: AudioDeviceID,
+    pub name: String,
+    pub input: bool,
+    pub excluded: bool,
+    /// Whether the device has its own mute control rather than the volume fallback.
+    pub native_mute: bool,
+    pub muted: Option<bool>,
+    pub volume: Option<f32>,
+    pub details: DeviceDetails,
 }
 
 pub trait AudioBackend {
@@ -83,284 +94,59 @@ pub trait AudioBackend {
     fn get_volume(&self, audio_device_id: AudioDeviceID) -> Result<Option<f32>>;
     fn set_volume(&mut self, audio_device_id: AudioDeviceID, volume: f32) -> Result<Option<()>>;
     fn default_input_device(&self) -> Result<Option<AudioDeviceID>>;
-}
-
-#[derive(Default)]
-pub struct CoreAudioBackend;
-
-impl CoreAudioBackend {
-    fn mute_address() -> AudioObjectPropertyAddress {
-        AudioObjectPropertyAddress {
-            mSelector: kAudioDevicePropertyMute,
-            mScope: kAudioDevicePropertyScopeInput,
-            mElement: kAudioObjectPropertyElementMain,
-        }
-    }
-
-    fn volume_addresses() -> [AudioObjectPropertyAddress; 2] {
-        [
-            AudioObjectPropertyAddress {
-                mSelector: kAudioDevicePropertyVolumeScalar,
-                mScope: kAudioDevicePropertyScopeInput,
-                mElement: kAudioObjectPropertyElementMain,
-            },
-            AudioObjectPropertyAddress {
-                mSelector: AUDIO_HARDWARE_SERVICE_DEVICE_PROPERTY_VIRTUAL_MAIN_VOLUME,
-                mScope: kAudioDevicePropertyScopeInput,
-                mElement: kAudioObjectPropertyElementMain,
-            },
-        ]
-    }
-
-    fn is_property_settable(
-        audio_device_id: AudioDeviceID,
-        mut property_address: AudioObjectPropertyAddress,
-    ) -> Result<bool> {
-        let mut is_settable = 0u8;
-        let status = unsafe {
-            AudioObjectIsPropertySettable(
-                audio_device_id,
-                NonNull::new_unchecked(&mut property_address),
-                NonNull::new_unchecked(&mut is_settable),
-            )
-        };
-        if status == kAudioHardwareUnknownPropertyError {
-            return Ok(false);
-        }
-        status_result(status, "check input volume settable", audio_device_id)?;
-        Ok(is_settable != 0)
-    }
-
-    fn get_settable_volume(
-        audio_device_id: AudioDeviceID,
-        mut property_address: AudioObjectPropertyAddress,
-    ) -> Result<Option<f32>> {
-        if !Self::is_property_settable(audio_device_id, property_address)? {
-            return Ok(None);
-        }
 
-        let mut volume = 0_f32;
-        let mut data_size = mem::size_of::<f32>() as u32;
-        let status = unsafe {
-            AudioObjectGetPropertyData(
-                audio_device_id,
-                NonNull::new_unchecked(&mut property_address),
-                0,
-                null(),
-                NonNull::new_unchecked(&mut data_size),
-                NonNull::new_unchecked(&mut volume as *mut f32 as *mut c_void),
-            )
-        };
-        if status == kAudioHardwareUnknownPropertyError {
-            return Ok(None);
-        }
-        status_result(status, "read input volume", audio_device_id)?;
-        Ok(Some(volume))
+    /// Input volume of each channel with its own control, for devices without a
+    /// main volume control.
+    fn get_channel_volumes(&self, _audio_device_id: AudioDeviceID) -> Result<Option<Vec<f32>>> {
+        Ok(None)
     }
 
-    fn set_settable_volume(
-        audio_device_id: AudioDeviceID,
-        mut property_address: AudioObjectPropertyAddress,
-        volume: f32,
+    /// Set each channel's input volume, in the order `get_channel_volumes` reports them.
+    fn set_channel_volumes(
+        &mut self,
+        _audio_device_id: AudioDeviceID,
+        _volumes: &[f32],
     ) -> Result<Option<()>> {
-        if !Self::is_property_settable(audio_device_id, property_address)? {
-            return Ok(None);
-        }
-
-        let data_size = mem::size_of::<f32>() as u32;
-        let status = unsafe {
-            AudioObjectSetPropertyData(
-                audio_device_id,
-                NonNull::new_unchecked(&mut property_address),
-                0,
-                null(),
-                data_size,
-                NonNull::new_unchecked(&volume as *const f32 as *mut c_void),
-            )
-        };
-        if status == kAudioHardwareUnknownPropertyError {
-            return Ok(None);
-        }
-        status_result(status, "set input volume", audio_device_id)?;
-        Ok(Some(()))
-    }
-}
-
-impl AudioBackend for CoreAudioBackend {
-    fn device_ids(&self) -> Result<Vec<AudioDeviceID>> {
-        get_audio_device_ids().map_err(anyhow::Error::msg)
-    }
-
-    fn device_name(&self, audio_device_id: AudioDeviceID) -> Result<String> {
-        get_device_name(audio_device_id).map_err(anyhow::Error::msg)
-    }
-
-    fn has_input_channels(&self, audio_device_id: AudioDeviceID) -> Result<bool> {
-        let mut property_address = AudioObjectPropertyAddress {
-            mSelector: kAudioDevicePropertyStreamConfiguration,
-            mScope: kAudioDevicePropertyScopeInput,
-            mElement: kAudioObjectPropertyElementMain,
-        };
-        let mut data_size = 0u32;
-        let status = unsafe {
-            AudioObjectGetPropertyDataSize(
-                audio_device_id,
-                NonNull::new_unchecked(&mut property_address),
-                0,
-                null(),
-                NonNull::new_unchecked(&mut data_size),
-            )
-        };
-        if status == kAudioHardwareUnknownPropertyError {
-            return Ok(false);
-        }
-        status_result(
-            status,
-            "read input stream configuration size",
-            audio_device_id,
-        )?;
-        if data_size < mem::size_of::<u32>() as u32 {
-            return Ok(false);
-        }
-
-        let mut buffer_list = AudioBufferListAllocation::new(data_size)?;
-        let status = unsafe {
-            AudioObjectGetPropertyData(
-                audio_device_id,
-                NonNull::new_unchecked(&mut property_address),
-                0,
-                null(),
-                NonNull::new_unchecked(&mut data_size),
-                NonNull::new_unchecked(buffer_list.as_mut_void()),
-            )
-        };
-        status_result(status, "read input stream configuration", audio_device_id)?;
-
-        let list = unsafe { buffer_list.as_list() };
-        let buffer_count = list.mNumberBuffers as usize;
-        if buffer_count == 0 {
-            return Ok(false);
-        }
-        let minimum_size = mem::offset_of!(AudioBufferList, mBuffers)
-            + buffer_count * mem::size_of::<AudioBuffer>();
-        if (data_size as usize) < minimum_size {
-            return Err(anyhow!(
-                "input stream configuration for audio device {} was truncated",
-                audio_device_id
-            ));
-        }
-        let buffers = unsafe { std::slice::from_raw_parts(list.mBuffers.as_ptr(), buffer_count) };
-        Ok(buffers.iter().any(|buffer| buffer.mNumberChannels > 0))
-    }
-
-    fn get_mute(&self, audio_device_id: AudioDeviceID) -> Result<Option<bool>> {
-        let mut property_address = Self::mute_address();
-        let mut muted = 0_u32;
-        let mut data_size = mem::size_of::<u32>() as u32;
-        let status = unsafe {
-            AudioObjectGetPropertyData(
-                audio_device_id,
-                NonNull::new_unchecked(&mut property_address),
-                0,
-                null(),
-                NonNull::new_unchecked(&mut data_size),
-                NonNull::new_unchecked(&mut muted as *mut u32 as *mut c_void),
-            )
-        };
-        if status == kAudioHardwareUnknownPropertyError {
-            return Ok(None);
-        }
-        status_result(status, "read mute", audio_device_id)?;
-        Ok(Some(muted == 1))
-    }
-
-    fn set_mute(&mut self, audio_device_id: AudioDeviceID, state: bool) -> Result<Option<()>> {
-        let mut property_address = Self::mute_address();
-        let mut is_settable = 0u8;
-        let status = unsafe {
-            AudioObjectIsPropertySettable(
-                audio_device_id,
-                NonNull::new_unchecked(&mut property_address),
-                NonNull::new_unchecked(&mut is_settable),
-            )
-        };
-        if status == kAudioHardwareUnknownPropertyError {
-            return Ok(None);
-        }
-        status_result(status, "check mute settable", audio_device_id)?;
-        if is_settable == 0 {
-            return Ok(None);
-        }
-        let data = state as u32;
-        let data_size = mem::size_of::<u32>() as u32;
-        let status = unsafe {
-            AudioObjectSetPropertyData(
-                audio_device_id,
-                NonNull::new_unchecked(&mut property_address),
-                0,
-                null(),
-                data_size,
-                NonNull::new_unchecked(&data as *const u32 as *mut c_void),
-            )
-        };
-        if status == kAudioHardwareUnknownPropertyError {
-            return Ok(None);
-        }
-        status_result(status, "set mute", audio_device_id)?;
-        Ok(Some(()))
+        Ok(None)
     }
 
-    fn get_volume(&self, audio_device_id: AudioDeviceID) -> Result<Option<f32>> {
-        for property_address in Self::volume_addresses() {
-            if let Some(volume) = Self::get_settable_volume(audio_device_id, property_address)? {
-                return Ok(Some(volume));
-            }
-        }
+    /// Mute state of each channel with its own mute, for devices without a main mute.
+    fn get_channel_mutes(&self, _audio_device_id: AudioDeviceID) -> Result<Option<Vec<bool>>> {
         Ok(None)
     }
 
-    fn set_volume(&mut self, audio_device_id: AudioDeviceID, volume: f32) -> Result<Option<()>> {
-        for property_address in Self::volume_addresses() {
-            if Self::set_settable_volume(audio_device_id, property_address, volume)?.is_some() {
-                return Ok(Some(()));
-            }
-        }
+    /// Set each channel's mute, in the order `get_channel_mutes` reports them.
+    fn set_channel_mutes(
+        &mut self,
+        _audio_device_id: AudioDeviceID,
+        _mutes: &[bool],
+    ) -> Result<Option<()>> {
         Ok(None)
     }
 
-    fn default_input_device(&self) -> Result<Option<AudioDeviceID>> {
-        let mut property_address = AudioObjectPropertyAddress {
-            mSelector: kAudioHardwarePropertyDefaultInputDevice,
-            mScope: kAudioObjectPropertyScopeGlobal,
-            mElement: kAudioObjectPropertyElementMain,
-        };
-        let mut device_id: AudioDeviceID = 0;
-        let mut data_size = mem::size_of::<AudioDeviceID>() as u32;
-        let status = unsafe {
-            AudioObjectGetPropertyData(
-                SYSTEM_OBJECT_ID,
-                NonNull::new_unchecked(&mut property_address),
-                0,
-                null(),
-                NonNull::new_unchecked(&mut data_size),
-                NonNull::new_unchecked(&mut device_id as *mut AudioDeviceID as *mut c_void),
-            )
-        };
-        if status != kAudioHardwareNoError || device_id == 0 {
-            return Ok(None);
-        }
-        Ok(Some(device_id))
+    fn device_details(&self, _audio_device_id: AudioDeviceID) -> Result<DeviceDetails> {
+        Ok(DeviceDetails::default())
     }
 }
 
-pub struct MicController<B = CoreAudioBackend> {
+pub struct MicController<B = SystemAudioBackend> {
     pub muted: bool,
+    pub status: MicStatus,
+    /// Devices the last mute found unmuted and muted again while mute was already
+    /// enforced, e.g. after another app unmuted them.
+    pub remuted: usize,
     desired_muted: bool,
     /// Saved input volume per device for devices that don't support kAudioDevicePropertyMute.
     /// Keyed by AudioDeviceID; value is the volume scalar (0.0–1.0) before muting.
     saved_volumes: HashMap<AudioDeviceID, f32>,
+    /// Saved input volume of each channel for devices with only per-channel volume.
+    saved_channel_volumes: HashMap<AudioDeviceID, Vec<f32>>,
+    /// Saved mute of each channel for devices with only per-channel mute.
+    saved_channel_mutes: HashMap<AudioDeviceID, Vec<bool>>,
     volume_fallback_devices: HashSet<AudioDeviceID>,
     native_muted_devices: HashSet<AudioDeviceID>,
+    /// Names of input devices the user asked Mic Mute to leave alone.
+    excluded_devices: HashSet<String>,
     backend: B,
 }
 
@@ -368,10 +154,15 @@ impl<B: Default> Default for MicController<B> {
     fn default() -> Self {
         Self {
             muted: false,
+            status: MicStatus::NoControllableDevices,
+            remuted: 0,
             desired_muted: false,
             saved_volumes: HashMap::new(),
+            saved_channel_volumes: HashMap::new(),
+            saved_channel_mutes: HashMap::new(),
             volume_fallback_devices: HashSet::new(),
             native_muted_devices: HashSet::new(),
+            excluded_devices: HashSet::new(),
             backend: B::default(),
         }
     }
@@ -382,31 +173,37 @@ impl<B: AudioBackend> Debug for MicController<B> {
         f.debug_struct("MicController")
             .field("names", &self.names().unwrap_or_default())
             .field("muted", &self.muted)
+            .field("status", &self.status)
             .field("desired_muted", &self.desired_muted)
             .finish()
     }
 }
 
-impl MicController<CoreAudioBackend> {
+impl MicController<SystemAudioBackend> {
     pub fn new() -> Result<Self> {
-        Self::with_backend(CoreAudioBackend)
+        Self::with_backend(SystemAudioBackend::default())
     }
 }
 
 impl<B: AudioBackend> MicController<B> {
-    fn with_backend(backend: B) -> Result<Self> {
+    pub(crate) fn with_backend(backend: B) -> Result<Self> {
         let mut controller = Self {
             muted: false,
+            status: MicStatus::NoControllableDevices,
+            remuted: 0,
             desired_muted: false,
             saved_volumes: HashMap::new(),
+            saved_channel_volumes: HashMap::new(),
+            saved_channel_mutes: HashMap::new(),
             volume_fallback_devices: HashSet::new(),
             native_muted_devices: HashSet::new(),
+            excluded_devices: HashSet::new(),
             backend,
         };
         trace!("Creating audio controller");
         let names = controller.names()?;
         trace!("Found {} devices: {}", names.len(), names.join(", "));
-        controller.muted = controller.is_muted_all()?;
+        controller.refresh_state()?;
         controller.desired_muted = controller.muted;
         Ok(controller)
     }
@@ -430,7 +227,7 @@ impl<B: AudioBackend> MicController<B> {
         );
         let mut input_device_ids = vec![];
         for id in audio_device_ids {
-            if self.backend.has_input_channels(id)? {
+            if self.backend.has_input_channels(id)? && !self.is_excluded(id)? {
                 input_device_ids.push(id);
             }
         }
@@ -443,6 +240,82 @@ impl<B: AudioBackend> MicController<B> {
         Ok(input_device_ids)
     }
 
+    fn is_excluded(&self, audio_device_id: AudioDeviceID) -> Result<bool> {
+        if self.excluded_devices.is_empty() {
+            return Ok(false);
+        }
+        let name = self.backend.device_name(audio_device_id)?;
+        Ok(self.excluded_devices.contains(&name))
+    }
+
+    /// Names of all connected input devices, including excluded ones.
+    pub fn input_device_names(&self) -> Result<Vec<String>> {
+        let mut names = vec![];
+        for id in self.backend.device_ids()? {
+            if self.backend.has_input_channels(id)? {
+                names.push(self.backend.device_name(id)?);
+            }
+        }
+        Ok(names)
+    }
+
+    /// Every audio device with its controls and current state, for diagnostics. A
+    /// device that fails to report is listed with what could be read.
+    pub fn device_table(&self) -> Result<Vec<DeviceReport>> {
+        let mut rows = vec![];
+        for id in self.backend.device_ids()? {
+            let mute = self.backend.get_mute(id).ok().flatten();
+            rows.push(DeviceReport {
+                id,
+                name: self.backend.device_name(id).unwrap_or_default(),
+                input: self.backend.has_input_channels(id).unwrap_or(false),
+                excluded: self.is_excluded(id).unwrap_or(false),
+                native_mute: mute.is_some(),
+                muted: self.is_muted(id).ok().flatten(),
+                volume: self.backend.get_volume(id).ok().flatten().or_else(|| {
+                    let volumes = self.backend.get_channel_volumes(id).ok().flatten()?;
+                    volumes.into_iter().reduce(f32::max)
+                }),
+                details: self.backend.device_details(id).unwrap_or_else(|e| {
+                    trace!("No details for audio device {}: {}", id, e);
+                    DeviceDetails::default()
+                }),
+            });
+        }
+        Ok(rows)
+    }
+
+    /// Replace the set of excluded device names. Devices that become excluded are
+    /// restored if Mic Mute muted them, and are no longer enforced or reported.
+    pub fn set_excluded_devices(&mut self, names: &[String]) -> Result<()> {
+        let excluded: HashSet<String> = names.iter().cloned().collect();
+        if excluded == self.excluded_devices {
+            return Ok(());
+        }
+        trace!("Excluding input devices {:?}", excluded);
+        self.excluded_devices = excluded;
+
+        let mut failures = Vec::new();
+        for id in self.backend.device_ids()? {
+            if self.is_excluded(id)? {
+                if let Err(err) = self.restore_device(id) {
+                    failures.push(format!("{} ({})", id, err));
+                }
+            }
+        }
+        let _ = self.refresh_state();
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "failed to restore {} excluded input device(s): {}",
+                failures.len(),
+                failures.join("; ")
+            ))
+        }
+    }
+
     fn is_muted(&self, audio_device_id: AudioDeviceID) -> Result<Option<bool>> {
         let name = self.backend.device_name(audio_device_id)?;
         trace!(
@@ -451,11 +324,21 @@ impl<B: AudioBackend> MicController<B> {
             name
         );
 
-        match self.backend.get_mute(audio_device_id)? {
-            Some(muted) => Ok(Some(muted)),
-            None => match self.backend.get_volume(audio_device_id)? {
-                Some(volume) => Ok(Some(is_volume_muted(volume))),
-                None => {
+        if let Some(muted) = self.backend.get_mute(audio_device_id)? {
+            return Ok(Some(muted));
+        }
+        if let Some(mutes) = self.backend.get_channel_mutes(audio_device_id)? {
+            if !mutes.is_empty() {
+                return Ok(Some(mutes.into_iter().all(|muted| muted)));
+            }
+        }
+        match self.backend.get_volume(audio_device_id)? {
+            Some(volume) => Ok(Some(is_volume_muted(volume))),
+            None => match self.backend.get_channel_volumes(audio_device_id)? {
+                Some(volumes) if !volumes.is_empty() => {
+                    Ok(Some(volumes.into_iter().all(is_volume_muted)))
+                }
+                _ => {
                     trace!(
                         "Audio device {} has no supported mute or input volume control",
                         audio_device_id
@@ -466,19 +349,24 @@ impl<B: AudioBackend> MicController<B> {
         }
     }
 
-    fn is_muted_all(&self) -> Result<bool> {
-        let mut controllable = false;
+    fn count_devices(&self) -> Result<DeviceCounts> {
+        let mut counts = DeviceCounts {
+            muted: 0,
+            controllable: 0,
+            total: 0,
+        };
         for id in &self.get_input_device_ids()? {
+            counts.total += 1;
             match self.is_muted(*id)? {
                 Some(state) => {
-                    controllable = true;
+                    counts.controllable += 1;
                     trace!(
                         "Input device {} is {}",
                         id,
                         if state { "muted" } else { "unmuted" },
                     );
-                    if !state {
-                        return Ok(false);
+                    if state {
+                        counts.muted += 1;
                     }
                 }
                 None => trace!(
@@ -487,7 +375,24 @@ impl<B: AudioBackend> MicController<B> {
                 ),
             }
         }
-        Ok(controllable)
+        Ok(counts)
+    }
+
+    /// Re-read `muted` and `status` from the devices. A failed read reports unmuted,
+    /// since the mute can't be confirmed.
+    fn refresh_state(&mut self) -> Result<()> {
+        match self.count_devices() {
+            Ok(counts) => {
+                self.muted = counts.all_muted();
+                self.status = counts.status();
+                Ok(())
+            }
+            Err(err) => {
+                self.muted = false;
+                self.status = MicStatus::Failed;
+                Err(err)
+            }
+        }
     }
 
     fn all_devices_match_state(&self, ids: &[AudioDeviceID], state: bool) -> Result<bool> {
@@ -522,10 +427,12 @@ impl<B: AudioBackend> MicController<B> {
         let set_result = self.backend.set_mute(audio_device_id, state)?;
         if set_result.is_none() {
             trace!(
-                "Device {} doesn't support mute property; falling back to input volume",
+                "Device {} doesn't support mute property; falling back to channel mutes or input volume",
                 audio_device_id
             );
-            if !self.mute_via_volume(audio_device_id, state)? {
+            if !self.mute_via_channel_mutes(audio_device_id, state)?
+                && !self.mute_via_volume(audio_device_id, state)?
+            {
                 trace!(
                     "Skipping audio device {} because neither native mute nor input volume is controllable",
                     audio_device_id
@@ -548,6 +455,9 @@ impl<B: AudioBackend> MicController<B> {
             }
         }
 
+        if state && was_muted == Some(false) {
+            self.remuted += 1;
+        }
         let state_text = match self.is_muted(audio_device_id)? {
             Some(true) => "muted",
             Some(false) => "unmuted",
@@ -558,6 +468,9 @@ impl<B: AudioBackend> MicController<B> {
     }
 
     fn mute_via_volume(&mut self, audio_device_id: AudioDeviceID, state: bool) -> Result<bool> {
+        if self.backend.get_volume(audio_device_id)?.is_none() {
+            return self.mute_via_channel_volumes(audio_device_id, state);
+        }
         if state {
             if !self.saved_volumes.contains_key(&audio_device_id) {
                 let Some(current_vol) = self.backend.get_volume(audio_device_id)? else {
@@ -620,8 +533,150 @@ impl<B: AudioBackend> MicController<B> {
         Ok(true)
     }
 
+    /// The volume fallback for devices without a main volume control: each channel
+    /// is muted and later restored to its own saved level.
+    fn mute_via_channel_volumes(
+        &mut self,
+        audio_device_id: AudioDeviceID,
+        state: bool,
+    ) -> Result<bool> {
+        let Some(current) = self.backend.get_channel_volumes(audio_device_id)? else {
+            return Ok(false);
+        };
+        if current.is_empty() {
+            return Ok(false);
+        }
+        let volumes = if state {
+            if !self.saved_channel_volumes.contains_key(&audio_device_id)
+                && !current.iter().copied().all(is_volume_muted)
+            {
+                trace!(
+                    "Saving channel volumes {:?} for device {} before muting",
+                    current,
+                    audio_device_id
+                );
+                self.saved_channel_volumes
+                    .insert(audio_device_id, current.clone());
+            }
+            vec![0.0; current.len()]
+        } else {
+            // Saved levels only fit while the device has the same channels
+            self.saved_channel_volumes
+                .remove(&audio_device_id)
+                .filter(|saved| {
+                    saved.len() == current.len() && !saved.iter().copied().all(is_volume_muted)
+                })
+                .unwrap_or_else(|| vec![1.0; current.len()])
+        };
+        trace!(
+            "Setting channel volumes {:?} for device {}",
+            volumes,
+            audio_device_id
+        );
+        if self
+            .backend
+            .set_channel_volumes(audio_device_id, &volumes)?
+            .is_none()
+        {
+            return Ok(false);
+        }
+        let Some(volumes) = self.backend.get_channel_volumes(audio_device_id)? else {
+            return Ok(false);
+        };
+        if volumes.iter().copied().all(is_volume_muted) != state {
+            return Err(anyhow!(
+                "audio device {} channel volumes remained {:?} after fallback {}",
+                audio_device_id,
+                volumes,
+                if state { "mute" } else { "unmute" }
+            ));
+        }
+        if !state {
+            self.volume_fallback_devices.remove(&audio_device_id);
+        } else if self.saved_channel_volumes.contains_key(&audio_device_id) {
+            self.volume_fallback_devices.insert(audio_device_id);
+        }
+        Ok(true)
+    }
+
+    /// The fallback for devices that only mute each channel: every channel is muted
+    /// and later restored to its own saved state, so channels the user muted stay muted.
+    fn mute_via_channel_mutes(
+        &mut self,
+        audio_device_id: AudioDeviceID,
+        state: bool,
+    ) -> Result<bool> {
+        let Some(current) = self.backend.get_channel_mutes(audio_device_id)? else {
+            return Ok(false);
+        };
+        if current.is_empty() {
+            return Ok(false);
+        }
+        let mutes = if state {
+            if !self.saved_channel_mutes.contains_key(&audio_device_id)
+                && !current.iter().all(|muted| *muted)
+            {
+                trace!(
+                    "Saving channel mutes {:?} for device {} before muting",
+                    current,
+                    audio_device_id
+                );
+                self.saved_channel_mutes
+                    .insert(audio_device_id, current.clone());
+            }
+            vec![true; current.len()]
+        } else {
+            // Saved mutes only fit while the device has the same channels
+            self.saved_channel_mutes
+                .remove(&audio_device_id)
+                .filter(|saved| saved.len() == current.len() && !saved.iter().all(|muted| *muted))
+                .unwrap_or_else(|| vec![false; current.len()])
+        };
+        trace!(
+            "Setting channel mutes {:?} for device {}",
+            mutes,
+            audio_device_id
+        );
+        if self
+            .backend
+            .set_channel_mutes(audio_device_id, &mutes)?
+            .is_none()
+        {
+            return Ok(false);
+        }
+        let Some(mutes) = self.backend.get_channel_mutes(audio_device_id)? else {
+            return Ok(false);
+        };
+        if mutes.iter().all(|muted| *muted) != state {
+            return Err(anyhow!(
+                "audio device {} channel mutes remained {:?} after channel {}",
+                audio_device_id,
+                mutes,
+                if state { "mute" } else { "unmute" }
+            ));
+        }
+        Ok(true)
+    }
+
+    /// Re-read the mute state of every input device without changing it.
+    pub fn refresh(&mut self) -> Result<&Self> {
+        self.refresh_state()?;
+        Ok(self)
+    }
+
+    /// Mute or unmute every input device. Any failure leaves `status` as Failed.
     pub fn mute_all(&mut self, state: bool) -> Result<&Self> {
+        if let Err(err) = self.apply_mute(state) {
+            self.status = MicStatus::Failed;
+            return Err(err);
+        }
+        Ok(self)
+    }
+
+    fn apply_mute(&mut self, state: bool) -> Result<()> {
+        let enforcing = state && self.desired_muted;
         self.desired_muted = state;
+        self.remuted = 0;
         let ids = self.get_input_device_ids()?;
         let mut failures = Vec::new();
         for id in &ids {
@@ -652,7 +707,10 @@ impl<B: AudioBackend> MicController<B> {
             }
         }
 
-        self.muted = self.is_muted_all().unwrap_or(false);
+        if !enforcing {
+            self.remuted = 0;
+        }
+        let _ = self.refresh_state();
         if !failures.is_empty() {
             return Err(anyhow!(
                 "failed to {} {} input device(s): {}",
@@ -662,44 +720,58 @@ impl<B: AudioBackend> MicController<B> {
             ));
         }
         if !self.all_devices_match_state(&ids, state)? {
-            self.muted = self.is_muted_all().unwrap_or(false);
+            let _ = self.refresh_state();
             return Err(anyhow!(
                 "one or more input devices did not reach requested mute state {}",
                 state
             ));
         }
-        self.muted = self.is_muted_all()?;
-        Ok(self)
+        self.refresh_state()
     }
 
-    pub fn restore_on_exit(&mut self) -> Result<()> {
-        let native_devices: Vec<_> = self.native_muted_devices.iter().copied().collect();
-        let volume_devices: Vec<_> = self.saved_volumes.keys().copied().collect();
-        let mut failures = Vec::new();
-
-        for id in native_devices {
-            match self.backend.set_mute(id, false) {
-                Ok(Some(())) => match self.wait_for_device_state(id, false) {
-                    Ok(true) => {
-                        self.native_muted_devices.remove(&id);
-                    }
-                    Ok(false) => failures.push(format!("{} (native mute remained enabled)", id)),
-                    Err(err) => failures.push(format!("{} ({})", id, err)),
-                },
-                Ok(None) => failures.push(format!("{} (native mute unavailable)", id)),
-                Err(err) => failures.push(format!("{} ({})", id, err)),
+    /// Undo any mute Mic Mute applied to a device, leaving pre-existing mutes alone.
+    fn restore_device(&mut self, audio_device_id: AudioDeviceID) -> Result<()> {
+        if self.native_muted_devices.contains(&audio_device_id) {
+            if self.backend.set_mute(audio_device_id, false)?.is_none() {
+                return Err(anyhow!("native mute unavailable"));
+            }
+            if !self.wait_for_device_state(audio_device_id, false)? {
+                return Err(anyhow!("native mute remained enabled"));
             }
+            self.native_muted_devices.remove(&audio_device_id);
         }
+        if self.saved_channel_mutes.contains_key(&audio_device_id)
+            && !self.mute_via_channel_mutes(audio_device_id, false)?
+        {
+            return Err(anyhow!("channel mute unavailable"));
+        }
+        if (self.saved_volumes.contains_key(&audio_device_id)
+            || self.saved_channel_volumes.contains_key(&audio_device_id))
+            && !self.mute_via_volume(audio_device_id, false)?
+        {
+            return Err(anyhow!("input volume unavailable"));
+        }
+        Ok(())
+    }
+
+    pub fn restore_on_exit(&mut self) -> Result<()> {
+        let mut ids: Vec<_> = self.native_muted_devices.iter().copied().collect();
+        ids.extend(
+            self.saved_volumes
+                .keys()
+                .chain(self.saved_channel_volumes.keys())
+                .chain(self.saved_channel_mutes.keys())
+                .filter(|id| !self.native_muted_devices.contains(id)),
+        );
+        let mut failures = Vec::new();
 
-        for id in volume_devices {
-            match self.mute_via_volume(id, false) {
-                Ok(true) => {}
-                Ok(false) => failures.push(format!("{} (input volume unavailable)", id)),
-                Err(err) => failures.push(format!("{} ({})", id, err)),
+        for id in ids {
+            if let Err(err) = self.restore_device(id) {
+                failures.push(format!("{} ({})", id, err));
             }
         }
 
-        self.muted = self.is_muted_all().unwrap_or(false);
+        let _ = self.refresh_state();
         self.desired_muted = self.muted;
 
         if failures.is_empty() {
@@ -733,52 +805,89 @@ impl<B: AudioBackend> MicController<B> {
 }
 
 #[cfg(test)]
-mod tests {
+impl<B: AudioBackend> MicController<B> {
+    pub(crate) fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+}
+
+/// In-memory audio devices for tests of the controller and the code driving it.
+#[cfg(test)]
+pub(crate) mod fake {
     use super::*;
-    use std::collections::{HashMap, HashSet};
+    use anyhow::{anyhow, Result};
+    use std::collections::HashMap;
 
     #[derive(Clone)]
-    struct Device {
-        name: String,
-        input: bool,
-        mute: Option<bool>,
-        volume: Option<f32>,
-        fail_set_mute: bool,
-        fail_set_volume: bool,
-        ignore_set_mute: bool,
+    pub struct Device {
+        pub name: String,
+        pub input: bool,
+        pub mute: Option<bool>,
+        pub volume: Option<f32>,
+        /// Per-channel input volume, for devices without a main volume.
+        pub channel_volumes: Option<Vec<f32>>,
+        /// Per-channel mute, for devices without a main mute.
+        pub channel_mutes: Option<Vec<bool>>,
+        pub fail_set_mute: bool,
+        pub fail_set_volume: bool,
+        pub ignore_set_mute: bool,
     }
 
     impl Device {
-        fn native(name: &str, muted: bool) -> Self {
+        pub fn native(name: &str, muted: bool) -> Self {
             Self {
                 name: name.to_string(),
                 input: true,
                 mute: Some(muted),
                 volume: Some(1.0),
+                channel_volumes: None,
+                channel_mutes: None,
                 fail_set_mute: false,
                 fail_set_volume: false,
                 ignore_set_mute: false,
             }
         }
 
-        fn fallback(name: &str, volume: f32) -> Self {
+        pub fn fallback(name: &str, volume: f32) -> Self {
             Self {
                 name: name.to_string(),
                 input: true,
                 mute: None,
                 volume: Some(volume),
+                channel_volumes: None,
+                channel_mutes: None,
                 fail_set_mute: false,
                 fail_set_volume: false,
                 ignore_set_mute: false,
             }
         }
 
-        fn no_control(name: &str) -> Self {
+        /// A multi-channel device with only per-channel input volume.
+        pub fn channels(name: &str, volumes: &[f32]) -> Self {
+            Self {
+                volume: None,
+                channel_volumes: Some(volumes.to_vec()),
+                ..Self::no_control(name)
+            }
+        }
+
+        /// A multi-channel device with only per-channel mute.
+        pub fn channel_mutes(name: &str, mutes: &[bool]) -> Self {
+            Self {
+                volume: None,
+                channel_mutes: Some(mutes.to_vec()),
+                ..Self::no_control(name)
+            }
+        }
+
+        pub fn no_control(name: &str) -> Self {
             Self {
                 name: name.to_string(),
                 input: true,
                 mute: None,
                 volume: None,
+                channel_volumes: None,
+                channel_mutes: None,
                 fail_set_mute: false,
                 fail_set_volume: false,
                 ignore_set_mute: false,
@@ -787,14 +896,14 @@ mod tests {
     }
 
     #[derive(Default)]
-    struct FakeBackend {
-        devices: HashMap<AudioDeviceID, Device>,
-        ids: Vec<AudioDeviceID>,
-        default_input: Option<AudioDeviceID>,
+    pub struct FakeBackend {
+        pub devices: HashMap<AudioDeviceID, Device>,
+        pub ids: Vec<AudioDeviceID>,
+        pub default_input: Option<AudioDeviceID>,
     }
 
     impl FakeBackend {
-        fn with_devices(devices: Vec<(AudioDeviceID, Device)>) -> Self {
+        pub fn with_devices(devices: Vec<(AudioDeviceID, Device)>) -> Self {
             let default_input = devices.first().map(|(id, _)| *id);
             let ids = devices.iter().map(|(id, _)| *id).collect();
             let devices = devices.into_iter().collect();
@@ -805,13 +914,13 @@ mod tests {
             }
         }
 
-        fn device(&self, id: AudioDeviceID) -> Result<&Device> {
+        pub fn device(&self, id: AudioDeviceID) -> Result<&Device> {
             self.devices
                 .get(&id)
                 .ok_or_else(|| anyhow!("missing fake device {}", id))
         }
 
-        fn device_mut(&mut self, id: AudioDeviceID) -> Result<&mut Device> {
+        pub fn device_mut(&mut self, id: AudioDeviceID) -> Result<&mut Device> {
             self.devices
                 .get_mut(&id)
                 .ok_or_else(|| anyhow!("missing fake device {}", id))
@@ -873,7 +982,65 @@ mod tests {
         fn default_input_device(&self) -> Result<Option<AudioDeviceID>> {
             Ok(self.default_input)
         }
+
+        fn get_channel_volumes(&self, audio_device_id: AudioDeviceID) -> Result<Option<Vec<f32>>> {
+            Ok(self.device(audio_device_id)?.channel_volumes.clone())
+        }
+
+        fn set_channel_volumes(
+            &mut self,
+            audio_device_id: AudioDeviceID,
+            volumes: &[f32],
+        ) -> Result<Option<()>> {
+            let device = self.device_mut(audio_device_id)?;
+            if device.fail_set_volume {
+                return Err(anyhow!("fake volume failure"));
+            }
+            let Some(channel_volumes) = &mut device.channel_volumes else {
+                return Ok(None);
+            };
+            if volumes.len() != channel_volumes.len() {
+                return Err(anyhow!(
+                    "fake device has {} channels",
+                    channel_volumes.len()
+                ));
+            }
+            channel_volumes.copy_from_slice(volumes);
+            Ok(Some(()))
+        }
+
+        fn get_channel_mutes(&self, audio_device_id: AudioDeviceID) -> Result<Option<Vec<bool>>> {
+            Ok(self.device(audio_device_id)?.channel_mutes.clone())
+        }
+
+        fn set_channel_mutes(
+            &mut self,
+            audio_device_id: AudioDeviceID,
+            mutes: &[bool],
+        ) -> Result<Option<()>> {
+            let device = self.device_mut(audio_device_id)?;
+            if device.fail_set_mute {
+                return Err(anyhow!("fake channel mute failure"));
+            }
+            let Some(channel_mutes) = &mut device.channel_mutes else {
+                return Ok(None);
+            };
+            if mutes.len() != channel_mutes.len() {
+                return Err(anyhow!("fake device has {} channels", channel_mutes.len()));
+            }
+            if !device.ignore_set_mute {
+                channel_mutes.copy_from_slice(mutes);
+            }
+            Ok(Some(()))
+        }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fake::{Device, FakeBackend};
+    use super::*;
+    use std::collections::HashSet;
 
     #[test]
     fn test_mic_controller_new() {
@@ -896,20 +1063,6 @@ mod tests {
         assert!(!target_state(Some(false), true));
     }
 
-    #[test]
-    fn core_audio_volume_fallback_checks_virtual_main_volume() {
-        let addresses = CoreAudioBackend::volume_addresses();
-
-        assert_eq!(addresses.len(), 2);
-        assert_eq!(addresses[0].mSelector, kAudioDevicePropertyVolumeScalar);
-        assert_eq!(
-            addresses[1].mSelector,
-            AUDIO_HARDWARE_SERVICE_DEVICE_PROPERTY_VIRTUAL_MAIN_VOLUME
-        );
-        assert_eq!(addresses[1].mScope, kAudioDevicePropertyScopeInput);
-        assert_eq!(addresses[1].mElement, kAudioObjectPropertyElementMain);
-    }
-
     #[test]
     fn native_mute_requires_readback_confirmation() {
         let backend = FakeBackend::with_devices(vec![(1, Device::native("Built-in", false))]);
@@ -921,6 +1074,25 @@ mod tests {
         assert!(controller.should_enforce_mute());
     }
 
+    #[test]
+    fn enforcement_counts_devices_unmuted_behind_its_back() {
+        let backend = FakeBackend::with_devices(vec![
+            (1, Device::native("Built-in", false)),
+            (2, Device::fallback("Continuity", 0.65)),
+        ]);
+        let mut controller = MicController::with_backend(backend).unwrap();
+
+        controller.mute_all(true).unwrap();
+        assert_eq!(controller.remuted, 0);
+        controller.mute_all(true).unwrap();
+        assert_eq!(controller.remuted, 0);
+
+        controller.backend.device_mut(1).unwrap().mute = Some(false);
+        controller.backend.device_mut(2).unwrap().volume = Some(0.5);
+        controller.mute_all(true).unwrap();
+        assert_eq!(controller.remuted, 2);
+    }
+
     #[test]
     fn restore_on_exit_unmutes_native_devices_muted_by_app() {
         let backend = FakeBackend::with_devices(vec![(1, Device::native("Built-in", false))]);
@@ -1066,6 +1238,151 @@ mod tests {
         assert_eq!(controller.backend.device(1).unwrap().volume, Some(1.0));
     }
 
+    #[test]
+    fn multi_channel_device_mutes_and_restores_each_channel() {
+        let backend = FakeBackend::with_devices(vec![(
+            1,
+            Device::channels("Audio Interface", &[0.8, 0.4, 0.0, 0.6]),
+        )]);
+        let mut controller = MicController::with_backend(backend).unwrap();
+        assert!(!controller.muted);
+
+        controller.mute_all(true).unwrap();
+
+        assert!(controller.muted);
+        assert_eq!(
+            controller.backend.device(1).unwrap().channel_volumes,
+            Some(vec![0.0; 4])
+        );
+        assert!(controller.volume_fallback_devices.contains(&1));
+
+        controller.mute_all(false).unwrap();
+
+        assert!(!controller.muted);
+        assert_eq!(
+            controller.backend.device(1).unwrap().channel_volumes,
+            Some(vec![0.8, 0.4, 0.0, 0.6])
+        );
+        assert!(controller.saved_channel_volumes.is_empty());
+        assert!(controller.volume_fallback_devices.is_empty());
+    }
+
+    #[test]
+    fn multi_channel_device_is_muted_only_when_every_channel_is() {
+        let backend = FakeBackend::with_devices(vec![
+            (1, Device::channels("Audio Interface", &[0.0, 0.0])),
+            (2, Device::channels("Field Recorder", &[0.0, 0.3])),
+        ]);
+        let controller = MicController::with_backend(backend).unwrap();
+
+        assert_eq!(
+            controller.status,
+            MicStatus::PartiallyMuted { muted: 1, total: 2 }
+        );
+    }
+
+    #[test]
+    fn restore_on_exit_restores_channel_volumes_muted_by_app() {
+        let backend = FakeBackend::with_devices(vec![
+            (1, Device::channels("Audio Interface", &[0.8, 0.5])),
+            (2, Device::channels("Field Recorder", &[0.0, 0.0])),
+        ]);
+        let mut controller = MicController::with_backend(backend).unwrap();
+        controller.mute_all(true).unwrap();
+
+        controller.restore_on_exit().unwrap();
+
+        assert_eq!(
+            controller.backend.device(1).unwrap().channel_volumes,
+            Some(vec![0.8, 0.5])
+        );
+        assert_eq!(
+            controller.backend.device(2).unwrap().channel_volumes,
+            Some(vec![0.0, 0.0])
+        );
+        assert!(controller.saved_channel_volumes.is_empty());
+    }
+
+    #[test]
+    fn channel_unmute_uses_audible_default_when_channels_changed() {
+        let backend =
+            FakeBackend::with_devices(vec![(1, Device::channels("Audio Interface", &[0.8, 0.5]))]);
+        let mut controller = MicController::with_backend(backend).unwrap();
+        controller.mute_all(true).unwrap();
+        controller.backend.device_mut(1).unwrap().channel_volumes = Some(vec![0.0; 3]);
+
+        controller.mute_all(false).unwrap();
+
+        assert!(!controller.muted);
+        assert_eq!(
+            controller.backend.device(1).unwrap().channel_volumes,
+            Some(vec![1.0; 3])
+        );
+    }
+
+    #[test]
+    fn channel_mute_cycle_keeps_channels_the_user_muted() {
+        let backend = FakeBackend::with_devices(vec![(
+            1,
+            Device::channel_mutes("Audio Interface", &[false, true, false]),
+        )]);
+        let mut controller = MicController::with_backend(backend).unwrap();
+        assert!(!controller.muted);
+
+        controller.mute_all(true).unwrap();
+
+        assert!(controller.muted);
+        assert_eq!(
+            controller.backend.device(1).unwrap().channel_mutes,
+            Some(vec![true; 3])
+        );
+
+        controller.mute_all(false).unwrap();
+
+        assert!(!controller.muted);
+        assert_eq!(
+            controller.backend.device(1).unwrap().channel_mutes,
+            Some(vec![false, true, false])
+        );
+        assert!(controller.saved_channel_mutes.is_empty());
+    }
+
+    #[test]
+    fn restore_on_exit_restores_channel_mutes_muted_by_app() {
+        let backend = FakeBackend::with_devices(vec![
+            (1, Device::channel_mutes("Audio Interface", &[true, false])),
+            (2, Device::channel_mutes("Field Recorder", &[true, true])),
+        ]);
+        let mut controller = MicController::with_backend(backend).unwrap();
+        controller.mute_all(true).unwrap();
+
+        controller.restore_on_exit().unwrap();
+
+        assert_eq!(
+            controller.backend.device(1).unwrap().channel_mutes,
+            Some(vec![true, false])
+        );
+        assert_eq!(
+            controller.backend.device(2).unwrap().channel_mutes,
+            Some(vec![true, true])
+        );
+        assert!(controller.saved_channel_mutes.is_empty());
+    }
+
+    #[test]
+    fn channel_volume_failure_does_not_claim_muted() {
+        let mut device = Device::channels("Audio Interface", &[0.8, 0.5]);
+        device.fail_set_volume = true;
+        let backend = FakeBackend::with_devices(vec![(1, device)]);
+        let mut controller = MicController::with_backend(backend).unwrap();
+
+        let result = controller.mute_all(true);
+
+        assert!(result.is_err());
+        assert!(!controller.muted);
+        assert!(controller.should_enforce_mute());
+    }
+
     #[test]
     fn device_without_native_mute_or_volume_does_not_block_other_devices() {
         let backend = FakeBackend::with_devices(vec![
@@ -1081,6 +1398,72 @@ mod tests {
         assert_eq!(controller.backend.device(2).unwrap().mute, Some(true));
     }
 
+    #[test]
+    fn excluded_devices_are_not_muted_or_reported() {
+        let backend = FakeBackend::with_devices(vec![
+            (1, Device::native("Built-in", false)),
+            (2, Device::native("Capture Card", false)),
+        ]);
+        let mut controller = MicController::with_backend(backend).unwrap();
+        controller
+            .set_excluded_devices(&["Capture Card".to_string()])
+            .unwrap();
+
+        controller.mute_all(true).unwrap();
+
+        assert!(controller.muted);
+        assert_eq!(controller.backend.device(1).unwrap().mute, Some(true));
+        assert_eq!(controller.backend.device(2).unwrap().mute, Some(false));
+        assert_eq!(
+            controller.input_device_names().unwrap(),
+            vec!["Built-in", "Capture Card"]
+        );
+    }
+
+    #[test]
+    fn excluding_a_device_restores_mute_applied_by_app() {
+        let backend = FakeBackend::with_devices(vec![
+            (1, Device::native("Built-in", false)),
+            (2, Device::fallback("Continuity", 0.65)),
+        ]);
+        let mut controller = MicController::with_backend(backend).unwrap();
+        controller.mute_all(true).unwrap();
+
+        controller
+            .set_excluded_devices(&["Built-in".to_string(), "Continuity".to_string()])
+            .unwrap();
+
+        assert!(!controller.muted);
+        assert_eq!(controller.backend.device(1).unwrap().mute, Some(false));
+        assert_eq!(controller.backend.device(2).unwrap().volume, Some(0.65));
+        assert!(controller.native_muted_devices.is_empty());
+        assert!(controller.saved_volumes.is_empty());
+    }
+
+    #[test]
+    fn device_table_reports_controls_and_state() {
+        let backend = FakeBackend::with_devices(vec![
+            (1, Device::native("Built-in", true)),
+            (2, Device::fallback("Continuity", 0.)),
+            (3, Device::no_control("Capture Card")),
+        ]);
+        let mut controller = MicController::with_backend(backend).unwrap();
+        controller
+            .set_excluded_devices(&["Capture Card".to_string()])
+            .unwrap();
+
+        let table = controller.device_table().unwrap();
+
+        assert_eq!(table.len(), 3);
+        assert!(table[0].native_mute);
+        assert_eq!(table[0].muted, Some(true));
+        assert!(!table[1].native_mute);
+        assert_eq!(table[1].muted, Some(true));
+        assert_eq!(table[1].volume, Some(0.));
+        assert!(table[2].excluded);
+        assert_eq!(table[2].muted, None);
+    }
+
     #[test]
     fn only_uncontrollable_devices_do_not_claim_muted() {
         let backend =
@@ -1092,4 +1475,57 @@ mod tests {
         assert!(!controller.muted);
         assert!(controller.should_enforce_mute());
     }
+
+    #[test]
+    fn uncontrollable_device_reports_partial_mute() {
+        let backend = FakeBackend::with_devices(vec![
+            (1, Device::native("Built-in", false)),
+            (2, Device::fallback("USB Mic", 0.65)),
+            (3, Device::no_control("B iPhone Microphone")),
+        ]);
+        let mut controller = MicController::with_backend(backend).unwrap();
+        assert_eq!(controller.status, MicStatus::Unmuted);
+
+        controller.mute_all(true).unwrap();
+
+        assert!(controller.muted);
+        assert_eq!(
+            controller.status,
+            MicStatus::PartiallyMuted { muted: 2, total: 3 }
+        );
+    }
+
+    #[test]
+    fn status_reports_no_controllable_devices_and_failures() {
+        let backend =
+            FakeBackend::with_devices(vec![(1, Device::no_control("B iPhone Microphone"))]);
+        let controller = MicController::with_backend(backend).unwrap();
+        assert_eq!(controller.status, MicStatus::NoControllableDevices);
+
+        let mut device = Device::native("Built-in", false);
+        device.fail_set_mute = true;
+        let backend = FakeBackend::with_devices(vec![(1, device)]);
+        let mut controller = MicController::with_backend(backend).unwrap();
+
+        assert!(controller.mute_all(true).is_err());
+        assert_eq!(controller.status, MicStatus::Failed);
+    }
+
+    #[test]
+    fn device_counts_status() {
+        let counts = |muted, controllable, total| DeviceCounts {
+            muted,
+            controllable,
+            total,
+        };
+        assert_eq!(counts(2, 2, 2).status(), MicStatus::Muted);
+        assert_eq!(counts(0, 2, 2).status(), MicStatus::Unmuted);
+        assert_eq!(
+            counts(1, 2, 2).status(),
+            MicStatus::PartiallyMuted { muted: 1, total: 2 }
+        );
+        assert_eq!(counts(0, 0, 1).status(), MicStatus::NoControllableDevices);
+        assert!(counts(1, 1, 2).all_muted());
+        assert!(!counts(1, 2, 2).all_muted());
+    }
 }
@@ -0,0 +1,887 @@
+/// Publishes mic and camera state to an MQTT broker as retained messages, with
+/// Home Assistant discovery config, and takes mute commands from a command topic.
+/// A minimal MQTT 3.1.1 client over plain TCP so no MQTT library has to be linked;
+/// brokers that require TLS aren't supported.
+use crate::app_state::Command;
+use crate::settings::MqttSettings;
+use anyhow::{bail, Context, Result};
+use log::{info, trace};
+use serde_json::{json, Value};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const CONNECT: u8 = 1;
+const CONNACK: u8 = 2;
+const PUBLISH: u8 = 3;
+const SUBSCRIBE: u8 = 8;
+const PINGREQ: u8 = 12;
+const DISCONNECT: u8 = 14;
+
+const DEFAULT_PORT: u16 = 1883;
+/// The broker drops the connection after 1.5 times this without a packet from us.
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+/// How long resolving the broker's name, connecting and waiting for its reply may
+/// take together.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// A broker that doesn't take a packet for this long is treated as gone.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// We ping at least every half `KEEP_ALIVE`, so hearing nothing back for this long,
+/// not even a PINGRESP, means the connection is dead.
+const READ_TIMEOUT: Duration = Duration::from_secs(KEEP_ALIVE.as_secs() * 3 / 2);
+/// How long dropping the client waits for "offline" to be sent.
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Larger incoming packets are treated as a broken connection.
+const MAX_PACKET_BYTES: usize = 1024 * 1024;
+
+const MIC_MUTED: &str = "muted";
+const MIC_UNMUTED: &str = "unmuted";
+const CAMERA_ON: &str = "on";
+const CAMERA_OFF: &str = "off";
+const ONLINE: &str = "online";
+const OFFLINE: &str = "offline";
+
+type OnCommand = Arc<dyn Fn(Command) + Send + Sync>;
+
+enum Update {
+    Mic {
+        muted: bool,
+    },
+    Camera {
+        active: bool,
+    },
+    /// The reader of the numbered connection lost it.
+    Disconnected(u64),
+    Stop,
+}
+
+/// The worker's open connection, so a stop that times out can cut it.
+#[derive(Default)]
+struct Live {
+    stream: Option<TcpStream>,
+    /// Set when the client gave up waiting; the worker mustn't connect again.
+    abandoned: bool,
+}
+
+/// A connection to the broker kept open on a background thread, reconnecting with
+/// backoff. Dropping it publishes "offline" and disconnects.
+pub struct MqttClient {
+    settings: MqttSettings,
+    updates: Sender<Update>,
+    /// Disconnected once the worker has finished.
+    stopped: Receiver<()>,
+    live: Arc<Mutex<Live>>,
+}
+
+impl MqttClient {
+    /// Start connecting. `on_command` is called from a background thread for each
+    /// command received.
+    pub fn start(
+        settings: MqttSettings,
+        on_command: impl Fn(Command) + Send + Sync + 'static,
+    ) -> Result<Self> {
+        let (host, port) = parse_broker(&settings.broker)?;
+        let (updates, receiver) = mpsc::channel();
+        let (stopped_sender, stopped) = mpsc::channel();
+        let live = Arc::new(Mutex::new(Live::default()));
+        let worker = Worker {
+            host,
+            port,
+            username: settings.username.clone(),
+            password: settings.password.clone(),
+            topics: Topics::new(&settings.topic_prefix),
+            discovery_prefix: settings.discovery_prefix.clone(),
+            on_command: Arc::new(on_command),
+            updates: updates.clone(),
+            receiver,
+            connection: 0,
+            mic: None,
+            camera: None,
+            live: live.clone(),
+            _stopped: stopped_sender,
+        };
+        std::thread::Builder::new()
+            .name("mqtt".to_string())
+            .spawn(move || worker.run())
+            .context("Failed to start the MQTT thread")?;
+        Ok(Self {
+            settings,
+            updates,
+            stopped,
+            live,
+        })
+    }
+
+    pub fn settings(&self) -> &MqttSettings {
+        &self.settings
+    }
+
+    pub fn publish_mic(&self, muted: bool) {
+        let _ = self.updates.send(Update::Mic { muted });
+    }
+
+    pub fn publish_camera(&self, active: bool) {
+        let _ = self.updates.send(Update::Camera { active });
+    }
+}
+
+impl Drop for MqttClient {
+    /// Waits up to `STOP_TIMEOUT` for "offline" to be sent, so a replacement
+    /// client can't be overtaken by it. A broker that stalls is cut off instead,
+    /// since this runs on the UI thread.
+    fn drop(&mut self) {
+        let _ = self.updates.send(Update::Stop);
+        if let Err(RecvTimeoutError::Timeout) = self.stopped.recv_timeout(STOP_TIMEOUT) {
+            log::error!("The MQTT broker didn't take the disconnect in time");
+            let mut live = self.live.lock().unwrap();
+            live.abandoned = true;
+            if let Some(stream) = live.stream.take() {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+}
+
+/// The topics under the configured prefix.
+struct Topics {
+    prefix: String,
+    mic: String,
+    camera: String,
+    availability: String,
+    command: String,
+}
+
+impl Topics {
+    fn new(prefix: &str) -> Self {
+        let prefix = prefix.trim_matches('/');
+        Self {
+            prefix: prefix.to_string(),
+            mic: format!("{}/mic", prefix),
+            camera: format!("{}/camera", prefix),
+            availability: format!("{}/availability", prefix),
+            command: format!("{}/command", prefix),
+        }
+    }
+}
+
+struct Worker {
+    host: String,
+    port: u16,
+    username: Option<String>,
+    password: Option<String>,
+    topics: Topics,
+    discovery_prefix: Option<String>,
+    on_command: OnCommand,
+    updates: Sender<Update>,
+    receiver: Receiver<Update>,
+    /// Numbers connections so a late report from an old reader is ignored.
+    connection: u64,
+    /// The latest state, republished on every connect.
+    mic: Option<bool>,
+    camera: Option<bool>,
+    live: Arc<Mutex<Live>>,
+    /// Dropped with the worker, which tells the client it finished.
+    _stopped: Sender<()>,
+}
+
+impl Worker {
+    fn run(mut self) {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            match self.connect() {
+                Ok(stream) => {
+                    {
+                        let mut live = self.live.lock().unwrap();
+                        if live.abandoned {
+                            let _ = stream.shutdown(Shutdown::Both);
+                            return;
+                        }
+                        live.stream = stream.try_clone().ok();
+                    }
+                    info!("Connected to MQTT broker {}:{}", self.host, self.port);
+                    backoff = MIN_BACKOFF;
+                    let result = self.serve(&stream);
+                    self.live.lock().unwrap().stream = None;
+                    let _ = stream.shutdown(Shutdown::Both);
+                    match result {
+                        Ok(()) => return,
+                        Err(e) => log::error!("MQTT connection lost: {:#}", e),
+                    }
+                }
+                Err(e) => log::error!(
+                    "Failed to connect to MQTT broker {}:{}: {:#}",
+                    self.host,
+                    self.port,
+                    e
+                ),
+            }
+            // Keep up with state changes until it's time to retry
+            let retry_at = Instant::now() + backoff;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+            loop {
+                match self
+                    .receiver
+                    .recv_timeout(retry_at.saturating_duration_since(Instant::now()))
+                {
+                    Ok(Update::Mic { muted }) => self.mic = Some(muted),
+                    Ok(Update::Camera { active }) => self.camera = Some(active),
+                    Ok(Update::Disconnected(_)) => {}
+                    Ok(Update::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+                    Err(RecvTimeoutError::Timeout) => break,
+                }
+            }
+        }
+    }
+
+    fn connect(&self) -> Result<TcpStream> {
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+        let addresses = resolve(&self.host, self.port, CONNECT_TIMEOUT)?;
+        let remaining = || {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            // Zero timeouts are rejected
+            (!remaining.is_zero()).then_some(remaining)
+        };
+        let mut last_error = None;
+        let mut stream = None;
+        for address in addresses {
+            let Some(timeout) = remaining() else {
+                break;
+            };
+            match TcpStream::connect_timeout(&address, timeout) {
+                Ok(connected) => {
+                    stream = Some(connected);
+                    break;
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        let mut stream = match (stream, last_error) {
+            (Some(stream), _) => stream,
+            (None, Some(e)) => return Err(e).context("Failed to connect"),
+            (None, None) if remaining().is_none() => bail!("Timed out connecting"),
+            (None, None) => bail!("The broker's name resolved to no addresses"),
+        };
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        stream.set_read_timeout(Some(remaining().context("Timed out connecting")?))?;
+        let client_id = format!("mic-mute-{}", std::process::id());
+        stream.write_all(&connect_packet(
+            &client_id,
+            self.username.as_deref(),
+            self.password.as_deref(),
+            &self.topics.availability,
+        ))?;
+        let (header, body) = read_packet(&mut stream).context("No reply from the broker")?;
+        if header >> 4 != CONNACK || body.len() < 2 {
+            bail!("Unexpected reply from the broker");
+        }
+        match body[1] {
+            0 => {}
+            1 => bail!("The broker doesn't support MQTT 3.1.1"),
+            4 => bail!("The broker rejected the username or password"),
+            5 => bail!("Not authorized by the broker"),
+            code => bail!("The broker refused the connection (code {})", code),
+        }
+        // Also covers the reader, which shares the socket
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        Ok(stream)
+    }
+
+    /// Announce and publish state on `stream` until told to stop, which returns
+    /// Ok, or the connection fails.
+    fn serve(&mut self, stream: &TcpStream) -> Result<()> {
+        self.connection += 1;
+        let reader = stream.try_clone()?;
+        let command_topic = self.topics.command.clone();
+        let on_command = self.on_command.clone();
+        let updates = self.updates.clone();
+        let connection = self.connection;
+        std::thread::Builder::new()
+            .name("mqtt-reader".to_string())
+            .spawn(move || {
+                read_commands(reader, &command_topic, &*on_command);
+                let _ = updates.send(Update::Disconnected(connection));
+            })?;
+
+        let mut writer = Pinging {
+            stream,
+            last_sent: Instant::now(),
+        };
+        writer.write_all(&subscribe_packet(1, &self.topics.command))?;
+        if let Some(discovery_prefix) = &self.discovery_prefix {
+            for (topic, config) in discovery_configs(discovery_prefix, &self.topics) {
+                writer.write_all(&publish_packet(&topic, config.as_bytes(), true))?;
+            }
+        }
+        writer.write_all(&publish_packet(
+            &self.topics.availability,
+            ONLINE.as_bytes(),
+            true,
+        ))?;
+        if let Some(muted) = self.mic {
+            writer.write_all(&self.mic_packet(muted))?;
+        }
+        if let Some(active) = self.camera {
+            writer.write_all(&self.camera_packet(active))?;
+        }
+
+        loop {
+            let ping_at = writer.last_sent + KEEP_ALIVE / 2;
+            match self
+                .receiver
+                .recv_timeout(ping_at.saturating_duration_since(Instant::now()))
+            {
+                Ok(Update::Mic { muted }) => {
+                    if self.mic != Some(muted) {
+                        self.mic = Some(muted);
+                        writer.write_all(&self.mic_packet(muted))?;
+                    }
+                }
+                Ok(Update::Camera { active }) => {
+                    if self.camera != Some(active) {
+                        self.camera = Some(active);
+                        writer.write_all(&self.camera_packet(active))?;
+                    }
+                }
+                Ok(Update::Disconnected(connection)) => {
+                    if connection == self.connection {
+                        bail!("The broker closed the connection or stopped answering");
+                    }
+                }
+                Ok(Update::Stop) | Err(RecvTimeoutError::Disconnected) => {
+                    // A clean disconnect skips the last will, so say it ourselves
+                    writer.write_all(&publish_packet(
+                        &self.topics.availability,
+                        OFFLINE.as_bytes(),
+                        true,
+                    ))?;
+                    writer.write_all(&[DISCONNECT << 4, 0])?;
+                    trace!("Disconnected from MQTT broker");
+                    return Ok(());
+                }
+                Err(RecvTimeoutError::Timeout) => writer.write_all(&[PINGREQ << 4, 0])?,
+            }
+        }
+    }
+
+    fn mic_packet(&self, muted: bool) -> Vec<u8> {
+        let state = if muted { MIC_MUTED } else { MIC_UNMUTED };
+        publish_packet(&self.topics.mic, state.as_bytes(), true)
+    }
+
+    fn camera_packet(&self, active: bool) -> Vec<u8> {
+        let state = if active { CAMERA_ON } else { CAMERA_OFF };
+        publish_packet(&self.topics.camera, state.as_bytes(), true)
+    }
+}
+
+/// Writes to the broker, noting when, so pings go out on time even while
+/// unchanged state keeps arriving.
+struct Pinging<'a> {
+    stream: &'a TcpStream,
+    last_sent: Instant,
+}
+
+impl Pinging<'_> {
+    fn write_all(&mut self, packet: &[u8]) -> io::Result<()> {
+        self.stream.write_all(packet)?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+}
+
+/// Look up `host` on a helper thread, since the system resolver can block far
+/// longer than `timeout` when DNS is unreachable. A lookup that times out is left
+/// to finish on its own.
+fn resolve(host: &str, port: u16, timeout: Duration) -> Result<Vec<SocketAddr>> {
+    let (sender, receiver) = mpsc::channel();
+    let target = (host.to_string(), port);
+    std::thread::Builder::new()
+        .name("mqtt-resolve".to_string())
+        .spawn(move || {
+            let addresses = (target.0.as_str(), target.1)
+                .to_socket_addrs()
+                .map(Iterator::collect);
+            let _ = sender.send(addresses);
+        })
+        .context("Failed to start resolving the broker")?;
+    match receiver.recv_timeout(timeout) {
+        Ok(addresses) => addresses.context("Failed to resolve the broker"),
+        Err(_) => bail!("Timed out resolving the broker"),
+    }
+}
+
+/// Pass commands published to `command_topic` to `on_command` until the
+/// connection closes.
+fn read_commands(mut stream: TcpStream, command_topic: &str, on_command: &dyn Fn(Command)) {
+    loop {
+        let (header, body) = match read_packet(&mut stream) {
+            Ok(packet) => packet,
+            Err(e) => {
+                trace!("MQTT reader stopped: {}", e);
+                return;
+            }
+        };
+        // Retained commands would be replayed on every connect
+        if header >> 4 != PUBLISH || header & 1 != 0 {
+            continue;
+        }
+        match decode_publish(header, &body) {
+            Some((topic, payload)) if topic == command_topic => match parse_command(&payload) {
+                Some(command) => {
+                    trace!("MQTT command {:?}", command);
+                    on_command(command);
+                }
+                None => trace!(
+                    "Ignoring MQTT command {:?}",
+                    String::from_utf8_lossy(&payload)
+                ),
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Host and port from "mqtt://host:port", "tcp://host" or a bare "host:port".
+fn parse_broker(broker: &str) -> Result<(String, u16)> {
+    let address = match broker.trim().split_once("://") {
+        Some(("mqtt" | "tcp", address)) => address,
+        Some(("mqtts" | "ssl" | "tls", _)) => bail!("TLS MQTT brokers aren't supported"),
+        Some((scheme, _)) => bail!("Unsupported MQTT broker scheme {:?}", scheme),
+        None => broker.trim(),
+    };
+    let address = address.trim_end_matches('/');
+    if address.is_empty() {
+        bail!("No MQTT broker host in {:?}", broker);
+    }
+    // A bracketed IPv6 address may contain colons of its own
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => {
+            let port = port
+                .parse()
+                .with_context(|| format!("Invalid MQTT broker port {:?}", port))?;
+            (host, port)
+        }
+        _ => (address, DEFAULT_PORT),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    Ok((host.to_string(), port))
+}
+
+fn parse_command(payload: &[u8]) -> Option<Command> {
+    let payload = String::from_utf8_lossy(payload);
+    match payload.trim().to_lowercase().as_str() {
+        "mute" => Some(Command::Mute),
+        "unmute" => Some(Command::Unmute),
+        "toggle" => Some(Command::Toggle),
+        _ => None,
+    }
+}
+
+/// A Home Assistant node or object id: letters, digits, `_` and `-` only.
+fn node_id(prefix: &str) -> String {
+    let id: String = prefix
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if id.is_empty() {
+        "mic-mute".to_string()
+    } else {
+        id
+    }
+}
+
+/// Discovery topics and configs for a mic binary sensor that's on while the mic
+/// is live, a camera binary sensor and a mute switch.
+fn discovery_configs(discovery_prefix: &str, topics: &Topics) -> Vec<(String, String)> {
+    let node = node_id(&topics.prefix);
+    let discovery_prefix = discovery_prefix.trim_matches('/');
+    let config = |component: &str, object: &str, mut entity: Value| {
+        entity["unique_id"] = json!(format!("{}_{}", node, object));
+        entity["availability_topic"] = json!(topics.availability);
+        entity["device"] = json!({
+            "identifiers": [node],
+            "name": "Mic Mute",
+            "sw_version": env!("CARGO_PKG_VERSION"),
+        });
+        (
+            format!(
+                "{}/{}/{}/{}/config",
+                discovery_prefix, component, node, object
+            ),
+            entity.to_string(),
+        )
+    };
+    vec![
+        config(
+            "binary_sensor",
+            "mic",
+            json!({
+                "name": "Microphone live",
+                "state_topic": topics.mic,
+                "payload_on": MIC_UNMUTED,
+                "payload_off": MIC_MUTED,
+                "icon": "mdi:microphone",
+            }),
+        ),
+        config(
+            "binary_sensor",
+            "camera",
+            json!({
+                "name": "Camera",
+                "state_topic": topics.camera,
+                "payload_on": CAMERA_ON,
+                "payload_off": CAMERA_OFF,
+                "icon": "mdi:webcam",
+            }),
+        ),
+        config(
+            "switch",
+            "mute",
+            json!({
+                "name": "Mute microphone",
+                "state_topic": topics.mic,
+                "state_on": MIC_MUTED,
+                "state_off": MIC_UNMUTED,
+                "command_topic": topics.command,
+                "payload_on": "mute",
+                "payload_off": "unmute",
+                "icon": "mdi:microphone-off",
+            }),
+        ),
+    ]
+}
+
+/// A fixed header byte, the remaining length and `body`.
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+/// A length-prefixed string or binary field.
+fn push_field(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+/// A clean session with a retained "offline" will on `availability_topic`. The
+/// password is only sent with a username, as MQTT 3.1.1 requires.
+fn connect_packet(
+    client_id: &str,
+    username: Option<&str>,
+    password: Option<&str>,
+    availability_topic: &str,
+) -> Vec<u8> {
+    let password = password.filter(|_| username.is_some());
+    // Clean session, will flag and will retain
+    let mut flags = 0x02 | 0x04 | 0x20;
+    if username.is_some() {
+        flags |= 0x80;
+    }
+    if password.is_some() {
+        flags |= 0x40;
+    }
+    let mut body = vec![];
+    push_field(&mut body, b"MQTT");
+    body.push(4);
+    body.push(flags);
+    body.extend_from_slice(&(KEEP_ALIVE.as_secs() as u16).to_be_bytes());
+    push_field(&mut body, client_id.as_bytes());
+    push_field(&mut body, availability_topic.as_bytes());
+    push_field(&mut body, OFFLINE.as_bytes());
+    for field in [username, password].into_iter().flatten() {
+        push_field(&mut body, field.as_bytes());
+    }
+    packet(CONNECT << 4, &body)
+}
+
+/// A QoS 0 publish.
+fn publish_packet(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = vec![];
+    push_field(&mut body, topic.as_bytes());
+    body.extend_from_slice(payload);
+    packet(PUBLISH << 4 | retain as u8, &body)
+}
+
+/// A QoS 0 subscription to a single topic.
+fn subscribe_packet(packet_id: u16, topic: &str) -> Vec<u8> {
+    let mut body = packet_id.to_be_bytes().to_vec();
+    push_field(&mut body, topic.as_bytes());
+    body.push(0);
+    packet(SUBSCRIBE << 4 | 0x02, &body)
+}
+
+/// Read one packet, returning its fixed header byte and everything after the
+/// remaining length.
+fn read_packet(reader: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    let header = byte[0];
+    let mut length = 0;
+    for shift in (0..4).map(|i| i * 7) {
+        reader.read_exact(&mut byte)?;
+        length |= ((byte[0] & 0x7f) as usize) << shift;
+        if byte[0] & 0x80 == 0 {
+            if length > MAX_PACKET_BYTES {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "MQTT packet too large",
+                ));
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body)?;
+            return Ok((header, body));
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Malformed MQTT packet length",
+    ))
+}
+
+/// Topic and payload of a publish packet.
+fn decode_publish(header: u8, body: &[u8]) -> Option<(String, Vec<u8>)> {
+    let topic_len = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+    let topic = std::str::from_utf8(body.get(2..2 + topic_len)?).ok()?;
+    // QoS 1 and 2 carry a packet id before the payload
+    let payload_start = if (header >> 1) & 0x03 > 0 {
+        2 + topic_len + 2
+    } else {
+        2 + topic_len
+    };
+    Some((topic.to_string(), body.get(payload_start..)?.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn next_publish(broker: &mut TcpStream) -> (String, String) {
+        loop {
+            let (header, body) = read_packet(broker).unwrap();
+            if header >> 4 == PUBLISH {
+                let (topic, payload) = decode_publish(header, &body).unwrap();
+                return (topic, String::from_utf8(payload).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_broker() {
+        assert_eq!(
+            parse_broker("mqtt://homeassistant.local:1884").unwrap(),
+            ("homeassistant.local".to_string(), 1884)
+        );
+        assert_eq!(
+            parse_broker("tcp://broker/").unwrap(),
+            ("broker".to_string(), DEFAULT_PORT)
+        );
+        assert_eq!(
+            parse_broker("[::1]:1885").unwrap(),
+            ("::1".to_string(), 1885)
+        );
+        assert!(parse_broker("mqtts://broker:8883").is_err());
+        assert!(parse_broker("mqtt://broker:port").is_err());
+        assert!(parse_broker("mqtt://").is_err());
+    }
+
+    #[test]
+    fn test_packets_round_trip() {
+        let packet = publish_packet("mic-mute/mic", &[b'x'; 200], true);
+        // 200 bytes of payload and 14 of topic need a two-byte length
+        assert_eq!(&packet[..3], &[0x31, 0x80 | 86, 1]);
+
+        let (header, body) = read_packet(&mut packet.as_slice()).unwrap();
+        let (topic, payload) = decode_publish(header, &body).unwrap();
+        assert_eq!(topic, "mic-mute/mic");
+        assert_eq!(payload, [b'x'; 200]);
+
+        let connect = connect_packet("id", None, Some("secret"), "mic-mute/availability");
+        assert_eq!(connect[9], 0x26);
+        assert!(!connect.windows(6).any(|window| window == b"secret"));
+        assert_eq!(
+            subscribe_packet(1, "a/b"),
+            [0x82, 8, 0, 1, 0, 3, b'a', b'/', b'b', 0]
+        );
+    }
+
+    #[test]
+    fn test_discovery_configs() {
+        let configs = discovery_configs("homeassistant/", &Topics::new("office/mic"));
+        let (topic, config) = &configs[2];
+        let config: Value = serde_json::from_str(config).unwrap();
+
+        assert_eq!(topic, "homeassistant/switch/office_mic/mute/config");
+        assert_eq!(config["unique_id"], "office_mic_mute");
+        assert_eq!(config["command_topic"], "office/mic/command");
+        assert_eq!(config["availability_topic"], "office/mic/availability");
+        assert_eq!(configs.len(), 3);
+        assert_eq!(parse_command(b" Toggle\n"), Some(Command::Toggle));
+        assert_eq!(parse_command(b"on"), None);
+    }
+
+    #[test]
+    fn test_publishes_state_and_forwards_commands() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let settings = MqttSettings {
+            enabled: true,
+            broker: format!("mqtt://127.0.0.1:{}", listener.local_addr().unwrap().port()),
+            discovery_prefix: None,
+            ..MqttSettings::default()
+        };
+        let (commands, received) = mpsc::channel();
+        let client = MqttClient::start(settings, move |command| {
+            let _ = commands.send(command);
+        })
+        .unwrap();
+        client.publish_mic(true);
+
+        let (mut broker, _) = listener.accept().unwrap();
+        broker
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        assert_eq!(read_packet(&mut broker).unwrap().0 >> 4, CONNECT);
+        broker.write_all(&[CONNACK << 4, 2, 0, 0]).unwrap();
+        assert_eq!(read_packet(&mut broker).unwrap().0 >> 4, SUBSCRIBE);
+        assert_eq!(
+            next_publish(&mut broker),
+            ("mic-mute/availability".to_string(), "online".to_string())
+        );
+        assert_eq!(
+            next_publish(&mut broker),
+            ("mic-mute/mic".to_string(), "muted".to_string())
+        );
+
+        broker
+            .write_all(&publish_packet("mic-mute/command", b"toggle", false))
+            .unwrap();
+        assert_eq!(
+            received.recv_timeout(Duration::from_secs(5)).unwrap(),
+            Command::Toggle
+        );
+
+        drop(client);
+        assert_eq!(
+            next_publish(&mut broker),
+            ("mic-mute/availability".to_string(), "offline".to_string())
+        );
+    }
+
+    #[test]
+    fn test_drop_does_not_wait_for_a_stalled_broker() {
+        // Accepts but never answers the CONNECT
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let settings = MqttSettings {
+            enabled: true,
+            broker: format!("127.0.0.1:{}", listener.local_addr().unwrap().port()),
+            ..MqttSettings::default()
+        };
+        let client = MqttClient::start(settings, |_| {}).unwrap();
+        let (_broker, _) = listener.accept().unwrap();
+
+        let started = Instant::now();
+        drop(client);
+
+        assert!(started.elapsed() < CONNECT_TIMEOUT);
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(
+            resolve("127.0.0.1", 1883, CONNECT_TIMEOUT).unwrap(),
+            vec![SocketAddr::from(([127, 0, 0, 1], 1883))]
+        );
+    }
+
+    /// Runs against a real broker, e.g. one started with `mosquitto -p 1883`:
+    /// `MIC_MUTE_TEST_BROKER=mqtt://127.0.0.1:1883 cargo test mosquitto -- --ignored`
+    #[test]
+    #[ignore = "needs an MQTT broker such as mosquitto"]
+    fn test_against_mosquitto() {
+        let broker = std::env::var("MIC_MUTE_TEST_BROKER")
+            .unwrap_or_else(|_| format!("mqtt://127.0.0.1:{}", DEFAULT_PORT));
+        let prefix = format!("mic-mute-test-{}", std::process::id());
+        let (host, port) = parse_broker(&broker).unwrap();
+        let mut observer = TcpStream::connect((host.as_str(), port)).unwrap();
+        observer
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        observer
+            .write_all(&connect_packet(
+                &format!("{}-observer", prefix),
+                None,
+                None,
+                &format!("{}-observer/availability", prefix),
+            ))
+            .unwrap();
+        assert_eq!(read_packet(&mut observer).unwrap().0 >> 4, CONNACK);
+        observer
+            .write_all(&subscribe_packet(1, &format!("{}/#", prefix)))
+            .unwrap();
+
+        let settings = MqttSettings {
+            enabled: true,
+            broker,
+            topic_prefix: prefix.clone(),
+            discovery_prefix: None,
+            ..MqttSettings::default()
+        };
+        let (commands, received) = mpsc::channel();
+        let client = MqttClient::start(settings, move |command| {
+            let _ = commands.send(command);
+        })
+        .unwrap();
+        client.publish_mic(true);
+
+        let mic = (format!("{}/mic", prefix), MIC_MUTED.to_string());
+        while next_publish(&mut observer) != mic {}
+        // The client subscribes before publishing state, so this isn't lost
+        observer
+            .write_all(&publish_packet(
+                &format!("{}/command", prefix),
+                b"toggle",
+                false,
+            ))
+            .unwrap();
+        assert_eq!(
+            received.recv_timeout(Duration::from_secs(5)).unwrap(),
+            Command::Toggle
+        );
+
+        drop(client);
+        let offline = (format!("{}/availability", prefix), OFFLINE.to_string());
+        while next_publish(&mut observer) != offline {}
+
+        // Leave no retained state behind on the broker
+        for topic in ["availability", "mic"] {
+            observer
+                .write_all(&publish_packet(&format!("{}/{}", prefix, topic), b"", true))
+                .unwrap();
+        }
+        observer.write_all(&[DISCONNECT << 4, 0]).unwrap();
+    }
+}
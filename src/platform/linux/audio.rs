@@ -0,0 +1,315 @@
+/// PulseAudio backend for `MicController`, which also covers PipeWire through
+/// pipewire-pulse. Talks to the sound server with `pactl` so no client library has
+/// to be linked; `pactl -f json` needs PulseAudio 16 or PipeWire 0.3.50.
+use crate::mic::{AudioBackend, DeviceDetails};
+use anyhow::{anyhow, bail, Context, Result};
+use log::{error, trace};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Source index as reported by `pactl list sources`.
+pub type AudioDeviceID = u32;
+
+/// Volume value PulseAudio treats as 100%.
+const VOLUME_NORM: f32 = 65536.;
+/// How long a source listing and the default source are reused. One poll queries
+/// every source several times, and each `pactl` call is a round trip to the sound
+/// server.
+const CACHE_TTL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Deserialize)]
+struct ChannelVolume {
+    value: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Source {
+    index: AudioDeviceID,
+    name: String,
+    #[serde(default)]
+    description: String,
+    mute: bool,
+    #[serde(default)]
+    volume: HashMap<String, ChannelVolume>,
+    /// Set on the monitor source PulseAudio creates for every output.
+    #[serde(default)]
+    monitor_of_sink: Option<String>,
+    #[serde(default)]
+    properties: HashMap<String, String>,
+}
+
+impl Source {
+    fn is_monitor(&self) -> bool {
+        matches!(&self.monitor_of_sink, Some(sink) if sink != "n/a")
+    }
+
+    /// Average channel volume as a scalar, where 1.0 is 100%.
+    fn volume(&self) -> Option<f32> {
+        if self.volume.is_empty() {
+            return None;
+        }
+        let total: f32 = self.volume.values().map(|v| v.value as f32).sum();
+        Some(total / self.volume.len() as f32 / VOLUME_NORM)
+    }
+
+    fn details(&self) -> DeviceDetails {
+        let mut channels: Vec<String> = self.volume.keys().cloned().collect();
+        channels.sort();
+        DeviceDetails {
+            uid: Some(self.name.clone()),
+            transport: self.properties.get("device.bus").cloned(),
+            volume_elements: channels,
+        }
+    }
+}
+
+/// Input sources from `pactl -f json list sources`, without output monitors.
+fn parse_sources(json: &str) -> Result<Vec<Source>> {
+    let sources: Vec<Source> =
+        serde_json::from_str(json).context("Failed to parse pactl source list")?;
+    Ok(sources.into_iter().filter(|s| !s.is_monitor()).collect())
+}
+
+fn pactl(args: &[&str]) -> Result<String> {
+    let output = Command::new("pactl")
+        .args(args)
+        .output()
+        .context("Failed to run pactl")?;
+    if !output.status.success() {
+        bail!(
+            "pactl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8(output.stdout).context("pactl output is not UTF-8")
+}
+
+#[derive(Default)]
+struct SourceCache {
+    fetched_at: Option<Instant>,
+    sources: Vec<Source>,
+    /// Name of the default source, fetched along with the listing.
+    default_source: Option<String>,
+    /// Whether the last listing failed, so the failure is only logged once.
+    unreachable: bool,
+}
+
+impl SourceCache {
+    fn default_index(&self) -> Option<AudioDeviceID> {
+        let name = self.default_source.as_deref()?;
+        self.sources
+            .iter()
+            .find(|s| s.name == name)
+            .map(|s| s.index)
+    }
+}
+
+#[derive(Default)]
+pub struct PulseAudioBackend {
+    cache: Mutex<SourceCache>,
+}
+
+impl PulseAudioBackend {
+    fn with_sources<T>(&self, f: impl FnOnce(&[Source]) -> Result<T>) -> Result<T> {
+        self.with_cache(|cache| f(&cache.sources))
+    }
+
+    /// Run `f` against a recent source listing. A missing `pactl` or sound server
+    /// reads as no sources, since PipeWire often starts after the app at login.
+    fn with_cache<T>(&self, f: impl FnOnce(&SourceCache) -> Result<T>) -> Result<T> {
+        let mut cache = self.cache.lock().unwrap();
+        let fresh = cache.fetched_at.is_some_and(|at| at.elapsed() < CACHE_TTL);
+        if !fresh {
+            match pactl(&["-f", "json", "list", "sources"]).and_then(|json| parse_sources(&json)) {
+                Ok(sources) => {
+                    cache.sources = sources;
+                    cache.unreachable = false;
+                    cache.default_source = match pactl(&["get-default-source"]) {
+                        Ok(name) => Some(name.trim().to_string()),
+                        Err(e) => {
+                            trace!("No default source: {:#}", e);
+                            None
+                        }
+                    };
+                }
+                Err(e) => {
+                    if !cache.unreachable {
+                        error!("Failed to list PulseAudio sources: {:#}", e);
+                    }
+                    cache.sources.clear();
+                    cache.default_source = None;
+                    cache.unreachable = true;
+                }
+            }
+            cache.fetched_at = Some(Instant::now());
+        }
+        f(&cache)
+    }
+
+    fn with_source<T>(
+        &self,
+        audio_device_id: AudioDeviceID,
+        f: impl FnOnce(&Source) -> T,
+    ) -> Result<T> {
+        self.with_sources(|sources| {
+            sources
+                .iter()
+                .find(|s| s.index == audio_device_id)
+                .map(f)
+                .ok_or_else(|| anyhow!("No input source with index {}", audio_device_id))
+        })
+    }
+
+    fn invalidate(&self) {
+        self.cache.lock().unwrap().fetched_at = None;
+    }
+}
+
+impl AudioBackend for PulseAudioBackend {
+    fn device_ids(&self) -> Result<Vec<AudioDeviceID>> {
+        self.with_sources(|sources| Ok(sources.iter().map(|s| s.index).collect()))
+    }
+
+    fn device_name(&self, audio_device_id: AudioDeviceID) -> Result<String> {
+        self.with_source(audio_device_id, |s| {
+            if s.description.is_empty() {
+                s.name.clone()
+            } else {
+                s.description.clone()
+            }
+        })
+    }
+
+    fn has_input_channels(&self, audio_device_id: AudioDeviceID) -> Result<bool> {
+        // Monitors are filtered out, so every listed source is an input
+        self.with_source(audio_device_id, |_| true)
+    }
+
+    fn get_mute(&self, audio_device_id: AudioDeviceID) -> Result<Option<bool>> {
+        self.with_source(audio_device_id, |s| Some(s.mute))
+    }
+
+    fn set_mute(&mut self, audio_device_id: AudioDeviceID, state: bool) -> Result<Option<()>> {
+        let result = pactl(&[
+            "set-source-mute",
+            &audio_device_id.to_string(),
+            if state { "1" } else { "0" },
+        ]);
+        self.invalidate();
+        result.map(|_| Some(()))
+    }
+
+    fn get_volume(&self, audio_device_id: AudioDeviceID) -> Result<Option<f32>> {
+        self.with_source(audio_device_id, Source::volume)
+    }
+
+    fn set_volume(&mut self, audio_device_id: AudioDeviceID, volume: f32) -> Result<Option<()>> {
+        let value = (volume.clamp(0., 1.) * VOLUME_NORM).round() as u32;
+        let result = pactl(&[
+            "set-source-volume",
+            &audio_device_id.to_string(),
+            &value.to_string(),
+        ]);
+        self.invalidate();
+        result.map(|_| Some(()))
+    }
+
+    fn default_input_device(&self) -> Result<Option<AudioDeviceID>> {
+        self.with_cache(|cache| Ok(cache.default_index()))
+    }
+
+    fn device_details(&self, audio_device_id: AudioDeviceID) -> Result<DeviceDetails> {
+        self.with_source(audio_device_id, Source::details)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCES: &str = r#"[
+        {
+            "index": 52,
+            "state": "SUSPENDED",
+            "name": "alsa_output.pci-0000_00_1f.3.analog-stereo.monitor",
+            "description": "Monitor of Built-in Audio",
+            "mute": false,
+            "volume": {"front-left": {"value": 65536, "value_percent": "100%", "db": "0.00 dB"}},
+            "monitor_of_sink": "alsa_output.pci-0000_00_1f.3.analog-stereo"
+        },
+        {
+            "index": 53,
+            "state": "RUNNING",
+            "name": "alsa_input.pci-0000_00_1f.3.analog-stereo",
+            "description": "Built-in Audio Analog Stereo",
+            "mute": true,
+            "volume": {
+                "front-left": {"value": 65536, "value_percent": "100%", "db": "0.00 dB"},
+                "front-right": {"value": 32768, "value_percent": "50%", "db": "-18.06 dB"}
+            },
+            "monitor_of_sink": "n/a",
+            "properties": {"device.bus": "pci", "device.api": "alsa"}
+        },
+        {
+            "index": 60,
+            "name": "bluez_input.00_11_22_33_44_55",
+            "description": "",
+            "mute": false,
+            "volume": {"mono": {"value": 0}}
+        }
+    ]"#;
+
+    #[test]
+    fn test_parse_sources_skips_monitors() {
+        let sources = parse_sources(SOURCES).unwrap();
+        let indices: Vec<_> = sources.iter().map(|s| s.index).collect();
+        assert_eq!(indices, vec![53, 60]);
+        assert!(sources[0].mute);
+        assert!(!sources[1].mute);
+    }
+
+    #[test]
+    fn test_source_volume_averages_channels() {
+        let sources = parse_sources(SOURCES).unwrap();
+        assert_eq!(sources[0].volume(), Some(0.75));
+        assert_eq!(sources[1].volume(), Some(0.));
+    }
+
+    #[test]
+    fn test_source_details() {
+        let sources = parse_sources(SOURCES).unwrap();
+        let details = sources[0].details();
+        assert_eq!(
+            details.uid.as_deref(),
+            Some("alsa_input.pci-0000_00_1f.3.analog-stereo")
+        );
+        assert_eq!(details.transport.as_deref(), Some("pci"));
+        assert_eq!(details.volume_elements, vec!["front-left", "front-right"]);
+        assert_eq!(sources[1].details().transport, None);
+    }
+
+    #[test]
+    fn test_cached_default_source() {
+        let mut cache = SourceCache {
+            sources: parse_sources(SOURCES).unwrap(),
+            default_source: Some("bluez_input.00_11_22_33_44_55".to_string()),
+            ..SourceCache::default()
+        };
+        assert_eq!(cache.default_index(), Some(60));
+
+        // The default may be a monitor, which isn't listed
+        cache.default_source = Some("alsa_output.pci-0000_00_1f.3.analog-stereo.monitor".into());
+        assert_eq!(cache.default_index(), None);
+        cache.default_source = None;
+        assert_eq!(cache.default_index(), None);
+    }
+
+    #[test]
+    fn test_parse_sources_rejects_garbage() {
+        assert!(parse_sources("Failed to connect").is_err());
+    }
+}
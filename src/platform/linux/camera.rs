@@ -0,0 +1,177 @@
+/// Camera backend for V4L2 devices.
+/// A camera counts as in use while any process holds a `/dev/video*` node open,
+/// which is visible in `/proc` without extra permissions for the user's own processes.
+use crate::camera::CameraBackend;
+use anyhow::{Context, Result};
+use log::trace;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const VIDEO4LINUX_DIR: &str = "/sys/class/video4linux";
+const PROC_DIR: &str = "/proc";
+
+/// Processes under `proc_dir` and the video devices they hold open. Processes that
+/// exit or can't be inspected mid-scan are skipped.
+fn open_video_devices(proc_dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let entries =
+        fs::read_dir(proc_dir).with_context(|| format!("Failed to read {}", proc_dir.display()))?;
+    let mut open = vec![];
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .filter(|name| name.bytes().all(|b| b.is_ascii_digit()))
+            .map(str::to_string)
+        else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            let Ok(target) = fs::read_link(fd.path()) else {
+                continue;
+            };
+            if target.to_str().is_some_and(|t| t.starts_with("/dev/video")) {
+                trace!("Process {} has {} open", pid, target.display());
+                open.push((pid.clone(), target));
+            }
+        }
+    }
+    Ok(open)
+}
+
+/// Video capture nodes listed in `sysfs_dir`, e.g. `video0`, sorted. UVC cameras
+/// also expose a metadata node with a non-zero `index`, which is skipped so each
+/// camera is listed once.
+fn video_devices(sysfs_dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(sysfs_dir) else {
+        return vec![];
+    };
+    let mut devices: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.starts_with("video"))
+        .filter(|name| is_capture_node(&sysfs_dir.join(name)))
+        .collect();
+    devices.sort();
+    devices
+}
+
+/// Whether a node is the first of its device, which is the capture node. Nodes
+/// without a readable `index` are kept.
+fn is_capture_node(node_dir: &Path) -> bool {
+    fs::read_to_string(node_dir.join("index"))
+        .map(|index| index.trim() == "0")
+        .unwrap_or(true)
+}
+
+/// V4L2 capture devices. Linux has no lower-level device API to fall back to, so
+/// there are no system devices.
+#[derive(Default)]
+pub struct V4l2CameraBackend {
+    /// Device nodes held open, from one walk of `/proc` per listing of the devices
+    /// rather than one per device. None when that walk failed.
+    open_devices: Mutex<Option<HashSet<PathBuf>>>,
+}
+
+impl CameraBackend for V4l2CameraBackend {
+    fn capture_device_ids(&self) -> Result<Option<Vec<String>>> {
+        let open = match open_video_devices(Path::new(PROC_DIR)) {
+            Ok(open) => Some(open.into_iter().map(|(_, path)| path).collect()),
+            Err(e) => {
+                trace!("Checking each camera separately: {:#}", e);
+                None
+            }
+        };
+        *self.open_devices.lock().unwrap() = open;
+        Ok(Some(video_devices(Path::new(VIDEO4LINUX_DIR))))
+    }
+
+    fn capture_device_name(&self, unique_id: &str) -> Result<String> {
+        let path = Path::new(VIDEO4LINUX_DIR).join(unique_id).join("name");
+        let name = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(name.trim().to_string())
+    }
+
+    fn is_in_use_by_another_app(&self, unique_id: &str) -> Result<bool> {
+        let device = Path::new("/dev").join(unique_id);
+        if let Some(open) = &*self.open_devices.lock().unwrap() {
+            return Ok(open.contains(&device));
+        }
+        Ok(open_video_devices(Path::new(PROC_DIR))?
+            .iter()
+            .any(|(_, path)| *path == device))
+    }
+
+    fn system_device_ids(&self) -> Result<Vec<u32>> {
+        Ok(vec![])
+    }
+
+    fn is_running_somewhere(&self, _device_id: u32) -> Result<Option<bool>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn test_open_video_devices() {
+        let proc_dir = std::env::temp_dir().join("mic-mute-test-proc");
+        let _ = fs::remove_dir_all(&proc_dir);
+        let fd_dir = proc_dir.join("1234").join("fd");
+        fs::create_dir_all(&fd_dir).unwrap();
+        fs::create_dir_all(proc_dir.join("self")).unwrap();
+        symlink("/dev/null", fd_dir.join("0")).unwrap();
+        assert!(open_video_devices(&proc_dir).unwrap().is_empty());
+
+        symlink("/dev/video0", fd_dir.join("5")).unwrap();
+        assert_eq!(
+            open_video_devices(&proc_dir).unwrap(),
+            vec![("1234".to_string(), PathBuf::from("/dev/video0"))]
+        );
+
+        // Non-process entries are ignored even if they look like one
+        fs::remove_file(fd_dir.join("5")).unwrap();
+        let self_fd = proc_dir.join("self").join("fd");
+        fs::create_dir_all(&self_fd).unwrap();
+        symlink("/dev/video0", self_fd.join("5")).unwrap();
+        assert!(open_video_devices(&proc_dir).unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(&proc_dir);
+    }
+
+    #[test]
+    fn test_in_use_comes_from_the_listing_scan() {
+        let backend = V4l2CameraBackend {
+            open_devices: Mutex::new(Some(HashSet::from([PathBuf::from("/dev/video7")]))),
+        };
+
+        assert!(backend.is_in_use_by_another_app("video7").unwrap());
+        assert!(!backend.is_in_use_by_another_app("video0").unwrap());
+    }
+
+    #[test]
+    fn test_video_devices() {
+        let sysfs_dir = std::env::temp_dir().join("mic-mute-test-video4linux");
+        let _ = fs::remove_dir_all(&sysfs_dir);
+        for name in ["video2", "video0", "v4l-subdev0"] {
+            fs::create_dir_all(sysfs_dir.join(name)).unwrap();
+        }
+        fs::write(sysfs_dir.join("video0/index"), "0\n").unwrap();
+        // The metadata node of the camera at video0
+        fs::create_dir_all(sysfs_dir.join("video1")).unwrap();
+        fs::write(sysfs_dir.join("video1/index"), "1\n").unwrap();
+
+        assert_eq!(video_devices(&sysfs_dir), vec!["video0", "video2"]);
+        assert!(video_devices(&sysfs_dir.join("missing")).is_empty());
+
+        let _ = fs::remove_dir_all(&sysfs_dir);
+    }
+}
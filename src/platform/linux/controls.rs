@@ -0,0 +1,130 @@
+/// GTK controls for the Preferences window.
+/// Controls are packed top to bottom in the window's default vbox and report clicks
+/// to the event loop as `Message::PreferencesAction` carrying the control's tag.
+use crate::event_loop::{EventLoopProxyMessage, Message};
+use gtk::glib::SignalHandlerId;
+use gtk::prelude::*;
+use gtk::{Align, Orientation};
+use tao::platform::unix::WindowExtUnix;
+use tao::window::Window;
+
+const MARGIN: i32 = 20;
+const ROW_HEIGHT: i32 = 24;
+const ROW_SPACING: i32 = 4;
+const SECTION_GAP: i32 = 12;
+
+/// A text label that can be updated.
+pub struct Label(gtk::Label);
+
+impl Label {
+    pub fn set_text(&self, text: &str) {
+        self.0.set_text(text);
+    }
+}
+
+/// A checkbox whose state can be updated without reporting an action.
+pub struct Checkbox {
+    button: gtk::CheckButton,
+    toggled: SignalHandlerId,
+}
+
+impl Checkbox {
+    pub fn set_checked(&self, checked: bool) {
+        self.button.block_signal(&self.toggled);
+        self.button.set_active(checked);
+        self.button.unblock_signal(&self.toggled);
+    }
+}
+
+/// Packs controls top to bottom in a window's default vbox.
+pub struct Form {
+    vbox: gtk::Box,
+    proxy: EventLoopProxyMessage,
+}
+
+impl Form {
+    /// Height of a form with `rows` rows and `gaps` section gaps. GTK sizes the
+    /// window to fit its controls, so this is only the initial size.
+    pub fn height(rows: usize, gaps: usize) -> f64 {
+        (MARGIN * 2 + rows as i32 * (ROW_HEIGHT + ROW_SPACING) + gaps as i32 * SECTION_GAP) as f64
+    }
+
+    pub fn new(window: &Window, proxy: EventLoopProxyMessage, _width: f64, _height: f64) -> Self {
+        let vbox = gtk::Box::new(Orientation::Vertical, ROW_SPACING);
+        vbox.set_margin_top(MARGIN);
+        vbox.set_margin_bottom(MARGIN);
+        vbox.set_margin_start(MARGIN);
+        vbox.set_margin_end(MARGIN);
+        if let Some(default_vbox) = window.default_vbox() {
+            default_vbox.pack_start(&vbox, true, true, 0);
+        }
+        vbox.show();
+        Self { vbox, proxy }
+    }
+
+    fn add(&self, widget: &impl IsA<gtk::Widget>) {
+        self.vbox.pack_start(widget, false, false, 0);
+        widget.show_all();
+    }
+
+    fn make_label(text: &str) -> gtk::Label {
+        let label = gtk::Label::new(Some(text));
+        label.set_halign(Align::Start);
+        label.set_xalign(0.);
+        label
+    }
+
+    fn make_button(&self, title: &str, tag: i64) -> gtk::Button {
+        let button = gtk::Button::with_label(title);
+        let proxy = self.proxy.clone();
+        button.connect_clicked(move |_| {
+            proxy.send_event(Message::PreferencesAction(tag)).ok();
+        });
+        button
+    }
+
+    pub fn gap(&mut self) {
+        let spacer = gtk::Box::new(Orientation::Vertical, 0);
+        spacer.set_size_request(-1, SECTION_GAP);
+        self.add(&spacer);
+    }
+
+    pub fn heading(&mut self, text: &str) {
+        let label = Self::make_label("");
+        label.set_markup(&format!("<b>{}</b>", gtk::glib::markup_escape_text(text)));
+        self.add(&label);
+    }
+
+    pub fn label(&mut self, text: &str) -> Label {
+        self.label_with_buttons(text, &[])
+    }
+
+    /// A label followed by buttons on the same row.
+    pub fn label_with_buttons(&mut self, text: &str, buttons: &[(&str, i64)]) -> Label {
+        let row = gtk::Box::new(Orientation::Horizontal, ROW_SPACING);
+        let label = Self::make_label(text);
+        row.pack_start(&label, true, true, 0);
+        for (title, tag) in buttons {
+            row.pack_start(&self.make_button(title, *tag), false, false, 0);
+        }
+        self.add(&row);
+        Label(label)
+    }
+
+    pub fn button(&mut self, title: &str, tag: i64) {
+        let button = self.make_button(title, tag);
+        button.set_halign(Align::Start);
+        self.add(&button);
+    }
+
+    pub fn checkbox(&mut self, title: &str, tag: i64, checked: bool) -> Checkbox {
+        let button = gtk::CheckButton::with_label(title);
+        button.set_active(checked);
+        let proxy = self.proxy.clone();
+        let toggled = button.connect_toggled(move |_| {
+            proxy.send_event(Message::PreferencesAction(tag)).ok();
+        });
+        self.add(&button);
+        Checkbox { button, toggled }
+    }
+}
@@ -0,0 +1,320 @@
+/// The `org.brettinternet.MicMute` object on the session bus, for panels and
+/// scripts. Its Mute, Unmute and Toggle methods take the same command path as the
+/// tray, and the Muted, CameraActive and ActiveDevice properties announce changes
+/// with PropertiesChanged. Serving an object needs a real connection rather than
+/// the `gdbus` tool, so this uses GDBus from the GLib that GTK already links. It
+/// runs on its own thread and main context, so headless mode needs no GTK loop.
+use crate::app_state::Command;
+use anyhow::{bail, Context, Result};
+use gtk::gio;
+use gtk::glib::{self, ToVariant, Variant, VariantTy};
+use log::trace;
+use std::collections::HashMap;
+use std::sync::{mpsc, Arc, Mutex};
+
+pub const BUS_NAME: &str = "org.brettinternet.MicMute";
+const OBJECT_PATH: &str = "/org/brettinternet/MicMute";
+const INTERFACE: &str = "org.brettinternet.MicMute";
+const INTROSPECTION: &str = r#"<node>
+  <interface name="org.brettinternet.MicMute">
+    <method name="Mute"/>
+    <method name="Unmute"/>
+    <method name="Toggle"/>
+    <property name="Muted" type="b" access="read"/>
+    <property name="CameraActive" type="b" access="read"/>
+    <property name="ActiveDevice" type="s" access="read"/>
+  </interface>
+</node>"#;
+
+/// RequestName's DBUS_NAME_FLAG_DO_NOT_QUEUE, and its replies for owning the name.
+const DO_NOT_QUEUE: u32 = 4;
+const PRIMARY_OWNER: u32 = 1;
+const ALREADY_OWNER: u32 = 4;
+
+type OnCommand = Box<dyn Fn(Command) + Send + Sync>;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Properties {
+    muted: bool,
+    camera_active: bool,
+    /// Empty when there's no default input.
+    active_device: String,
+}
+
+impl Properties {
+    fn get(&self, name: &str) -> Option<Variant> {
+        match name {
+            "Muted" => Some(self.muted.to_variant()),
+            "CameraActive" => Some(self.camera_active.to_variant()),
+            "ActiveDevice" => Some(self.active_device.to_variant()),
+            _ => None,
+        }
+    }
+
+    /// Names of the properties that differ from `old`.
+    fn changed(&self, old: &Properties) -> Vec<&'static str> {
+        let mut changed = vec![];
+        if self.muted != old.muted {
+            changed.push("Muted");
+        }
+        if self.camera_active != old.camera_active {
+            changed.push("CameraActive");
+        }
+        if self.active_device != old.active_device {
+            changed.push("ActiveDevice");
+        }
+        changed
+    }
+}
+
+struct Shared {
+    properties: Mutex<Properties>,
+    /// Set once the object is registered.
+    connection: Mutex<Option<gio::DBusConnection>>,
+}
+
+/// The D-Bus object, served for the rest of the process.
+pub struct ControlService {
+    shared: Arc<Shared>,
+}
+
+impl ControlService {
+    /// Serve on the session bus. `on_command` is called from the D-Bus thread for
+    /// each method call. Fails when another instance already owns the name.
+    pub fn start(on_command: impl Fn(Command) + Send + Sync + 'static) -> Result<Self> {
+        Self::start_on(None, Box::new(on_command))
+    }
+
+    /// Serve on the bus at `address`, e.g. a private `dbus-daemon`, or the session
+    /// bus when None.
+    fn start_on(address: Option<String>, on_command: OnCommand) -> Result<Self> {
+        let shared = Arc::new(Shared {
+            properties: Mutex::new(Properties::default()),
+            connection: Mutex::new(None),
+        });
+        let (ready, registered) = mpsc::channel();
+        let thread_shared = shared.clone();
+        std::thread::Builder::new()
+            .name("dbus".to_string())
+            .spawn(move || {
+                let context = glib::MainContext::new();
+                let main_loop = glib::MainLoop::new(Some(&context), false);
+                // Method calls are dispatched on the context that was the thread
+                // default when the object was registered
+                let _ = context.with_thread_default(|| {
+                    let result = register(address.as_deref(), &thread_shared, on_command);
+                    let serving = result.is_ok();
+                    let _ = ready.send(result);
+                    if serving {
+                        main_loop.run();
+                    }
+                });
+            })
+            .context("Failed to start the D-Bus thread")?;
+        registered
+            .recv()
+            .context("The D-Bus thread stopped before registering")??;
+        trace!("Serving {} on the session bus", BUS_NAME);
+        Ok(Self { shared })
+    }
+
+    pub fn set_mic(&self, muted: bool, active_device: Option<&str>) {
+        self.change(|properties| {
+            properties.muted = muted;
+            properties.active_device = active_device.unwrap_or_default().to_string();
+        });
+    }
+
+    pub fn set_camera(&self, active: bool) {
+        self.change(|properties| properties.camera_active = active);
+    }
+
+    /// Apply `change` and signal the properties it changed.
+    fn change(&self, change: impl FnOnce(&mut Properties)) {
+        let mut properties = self.shared.properties.lock().unwrap();
+        let old = properties.clone();
+        change(&mut properties);
+        let changed: HashMap<String, Variant> = properties
+            .changed(&old)
+            .into_iter()
+            .filter_map(|name| Some((name.to_string(), properties.get(name)?)))
+            .collect();
+        if changed.is_empty() {
+            return;
+        }
+        let Some(connection) = self.shared.connection.lock().unwrap().clone() else {
+            return;
+        };
+        let parameters = (INTERFACE, changed, Vec::<String>::new()).to_variant();
+        if let Err(e) = connection.emit_signal(
+            None,
+            OBJECT_PATH,
+            "org.freedesktop.DBus.Properties",
+            "PropertiesChanged",
+            Some(&parameters),
+        ) {
+            log::error!("Failed to signal D-Bus property changes: {}", e);
+        }
+    }
+}
+
+/// Connect, register the object and take the bus name.
+fn register(address: Option<&str>, shared: &Arc<Shared>, on_command: OnCommand) -> Result<()> {
+    let connection = match address {
+        Some(address) => gio::DBusConnection::for_address_sync(
+            address,
+            gio::DBusConnectionFlags::AUTHENTICATION_CLIENT
+                | gio::DBusConnectionFlags::MESSAGE_BUS_CONNECTION,
+            None,
+            gio::Cancellable::NONE,
+        ),
+        None => gio::bus_get_sync(gio::BusType::Session, gio::Cancellable::NONE),
+    }
+    .context("Failed to connect to the session bus")?;
+    // GDBus raises SIGTERM when the session bus goes away; keep running instead
+    connection.set_exit_on_close(false);
+
+    let node = gio::DBusNodeInfo::for_xml(INTROSPECTION).context("Invalid introspection XML")?;
+    let interface = node
+        .lookup_interface(INTERFACE)
+        .context("No interface in the introspection XML")?;
+    let properties = shared.clone();
+    connection
+        .register_object(
+            OBJECT_PATH,
+            &interface,
+            move |_, _, _, _, method, _, invocation| {
+                let command = match method {
+                    "Mute" => Command::Mute,
+                    "Unmute" => Command::Unmute,
+                    "Toggle" => Command::Toggle,
+                    _ => {
+                        invocation.return_dbus_error(
+                            "org.freedesktop.DBus.Error.UnknownMethod",
+                            &format!("No method {}", method),
+                        );
+                        return;
+                    }
+                };
+                trace!("D-Bus command {:?}", command);
+                on_command(command);
+                invocation.return_value(None);
+            },
+            move |_, _, _, _, property| {
+                properties
+                    .properties
+                    .lock()
+                    .unwrap()
+                    .get(property)
+                    .unwrap_or_else(|| false.to_variant())
+            },
+            |_, _, _, _, _, _| false,
+        )
+        .context("Failed to register the D-Bus object")?;
+
+    let reply = connection
+        .call_sync(
+            Some("org.freedesktop.DBus"),
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "RequestName",
+            Some(&(BUS_NAME, DO_NOT_QUEUE).to_variant()),
+            Some(VariantTy::new("(u)").unwrap()),
+            gio::DBusCallFlags::NONE,
+            -1,
+            gio::Cancellable::NONE,
+        )
+        .with_context(|| format!("Failed to request {}", BUS_NAME))?;
+    match reply.get::<(u32,)>() {
+        Some((PRIMARY_OWNER | ALREADY_OWNER,)) => {}
+        _ => bail!("{} is owned by another process", BUS_NAME),
+    }
+    *shared.connection.lock().unwrap() = Some(connection);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command as Process, Stdio};
+    use std::time::Duration;
+
+    #[test]
+    fn test_changed_properties() {
+        let old = Properties::default();
+        let new = Properties {
+            muted: true,
+            active_device: "USB Mic".to_string(),
+            ..old.clone()
+        };
+
+        assert_eq!(new.changed(&old), ["Muted", "ActiveDevice"]);
+        assert!(new.changed(&new).is_empty());
+    }
+
+    /// Calls the methods and reads a property over a private `dbus-daemon`.
+    /// Skipped when dbus-daemon isn't installed.
+    #[test]
+    fn test_methods_and_properties_on_private_bus() {
+        let Ok(mut daemon) = Process::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+        else {
+            return;
+        };
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim().to_string();
+        let (commands, received) = mpsc::channel();
+        let service = ControlService::start_on(
+            Some(address.clone()),
+            Box::new(move |command| {
+                let _ = commands.send(command);
+            }),
+        )
+        .unwrap();
+        service.set_mic(true, Some("USB Mic"));
+
+        let client = gio::DBusConnection::for_address_sync(
+            &address,
+            gio::DBusConnectionFlags::AUTHENTICATION_CLIENT
+                | gio::DBusConnectionFlags::MESSAGE_BUS_CONNECTION,
+            None,
+            gio::Cancellable::NONE,
+        )
+        .unwrap();
+        let call = |interface: &str, method: &str, parameters: Option<&Variant>| {
+            client
+                .call_sync(
+                    Some(BUS_NAME),
+                    OBJECT_PATH,
+                    interface,
+                    method,
+                    parameters,
+                    None,
+                    gio::DBusCallFlags::NONE,
+                    5000,
+                    gio::Cancellable::NONE,
+                )
+                .unwrap()
+        };
+        call(INTERFACE, "Toggle", None);
+        assert_eq!(
+            received.recv_timeout(Duration::from_secs(5)).unwrap(),
+            Command::Toggle
+        );
+        let reply = call(
+            "org.freedesktop.DBus.Properties",
+            "Get",
+            Some(&(INTERFACE, "ActiveDevice").to_variant()),
+        );
+        let (value,) = reply.get::<(Variant,)>().unwrap();
+        assert_eq!(value.get::<String>().as_deref(), Some("USB Mic"));
+
+        let _ = daemon.kill();
+    }
+}
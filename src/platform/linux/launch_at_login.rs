@@ -0,0 +1,410 @@
+/// Launch at login through an XDG autostart entry or a systemd user service, both
+/// under `$XDG_CONFIG_HOME` (default `~/.config`).
+use crate::settings::LoginMechanism;
+use anyhow::{anyhow, Context, Result};
+use std::ffi::OsString;
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const APP_ID: &str = "mic-mute";
+/// Target the service is enabled for, so it starts once the desktop is up and
+/// stops with it.
+const SESSION_TARGET: &str = "graphical-session.target";
+
+/// Quote a path as a single Exec argument of a desktop entry. Reserved characters
+/// are backslash-escaped inside double quotes, then backslashes are escaped again
+/// because Exec is also a string value.
+fn desktop_exec(exe: &Path) -> String {
+    let mut quoted = String::from("\"");
+    for c in exe.to_string_lossy().chars() {
+        match c {
+            '"' | '`' | '$' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '%' => quoted.push_str("%%"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted.replace('\\', "\\\\")
+}
+
+/// Quote a path as the command of a systemd ExecStart line, escaping specifiers
+/// and environment variable references.
+fn systemd_exec(exe: &Path) -> String {
+    let mut quoted = String::from("\"");
+    for c in exe.to_string_lossy().chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '%' => quoted.push_str("%%"),
+            '$' => quoted.push_str("$$"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn desktop_entry(exe: &Path) -> String {
+    format!(
+        "[Desktop Entry]
+Type=Application
+Name=Mic Mute
+Comment=System-wide mic mute
+Exec={exec}
+Terminal=false
+X-GNOME-Autostart-enabled=true
+",
+        exec = desktop_exec(exe)
+    )
+}
+
+fn service_unit(exe: &Path) -> String {
+    format!(
+        "[Unit]
+Description=Mic Mute
+PartOf={target}
+After={target}
+
+[Service]
+ExecStart={exec}
+Restart=on-failure
+RestartSec=5
+
+[Install]
+WantedBy={target}
+",
+        target = SESSION_TARGET,
+        exec = systemd_exec(exe)
+    )
+}
+
+/// Whether a desktop entry is switched off by the session's startup settings.
+fn desktop_entry_disabled(contents: &str) -> bool {
+    contents.lines().any(|line| {
+        let line = line.trim();
+        line == "Hidden=true" || line == "X-GNOME-Autostart-enabled=false"
+    })
+}
+
+/// `$XDG_CONFIG_HOME` when it's an absolute path, as the base directory spec
+/// requires, otherwise `~/.config`.
+fn config_dir(xdg_config_home: Option<OsString>, home: Option<PathBuf>) -> Option<PathBuf> {
+    xdg_config_home
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home.map(|home| home.join(".config")))
+}
+
+/// Login item files under one config directory.
+struct LoginItems {
+    config_dir: PathBuf,
+}
+
+impl LoginItems {
+    fn from_env() -> Result<Self> {
+        let config_dir = config_dir(std::env::var_os("XDG_CONFIG_HOME"), dirs::home_dir())
+            .ok_or_else(|| anyhow!("Cannot resolve the config directory"))?;
+        Ok(Self { config_dir })
+    }
+
+    fn autostart_path(&self) -> PathBuf {
+        self.config_dir
+            .join("autostart")
+            .join(format!("{}.desktop", APP_ID))
+    }
+
+    fn units_dir(&self) -> PathBuf {
+        self.config_dir.join("systemd").join("user")
+    }
+
+    fn service_path(&self) -> PathBuf {
+        self.units_dir().join(format!("{}.service", APP_ID))
+    }
+
+    /// The link `systemctl --user enable` would create.
+    fn service_link_path(&self) -> PathBuf {
+        self.units_dir()
+            .join(format!("{}.wants", SESSION_TARGET))
+            .join(format!("{}.service", APP_ID))
+    }
+
+    /// The mechanism currently starting the app at login, if any.
+    fn enabled_mechanism(&self) -> Option<LoginMechanism> {
+        if fs::symlink_metadata(self.service_link_path()).is_ok() {
+            return Some(LoginMechanism::Systemd);
+        }
+        match fs::read_to_string(self.autostart_path()) {
+            Ok(contents) if !desktop_entry_disabled(&contents) => Some(LoginMechanism::Autostart),
+            _ => None,
+        }
+    }
+
+    /// Write `mechanism`'s files for `exe` and remove the other's, so the app is
+    /// never started twice.
+    fn enable(&self, mechanism: LoginMechanism, exe: &Path) -> Result<()> {
+        match mechanism {
+            LoginMechanism::Autostart => {
+                self.remove_service()?;
+                write_file(&self.autostart_path(), &desktop_entry(exe))
+            }
+            LoginMechanism::Systemd => {
+                remove_file(&self.autostart_path())?;
+                let service = self.service_path();
+                write_file(&service, &service_unit(exe))?;
+                let link = self.service_link_path();
+                remove_file(&link)?;
+                if let Some(parent) = link.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                symlink(&service, &link)
+                    .with_context(|| format!("Failed to link {}", link.display()))
+            }
+        }
+    }
+
+    /// Rewrite the enabled mechanism's files if they don't start `exe`, e.g. after
+    /// the binary moved. Returns the mechanism that was rewritten.
+    fn repair(&self, exe: &Path) -> Result<Option<LoginMechanism>> {
+        let Some(mechanism) = self.enabled_mechanism() else {
+            return Ok(None);
+        };
+        let (path, command) = match mechanism {
+            LoginMechanism::Autostart => {
+                (self.autostart_path(), format!("Exec={}", desktop_exec(exe)))
+            }
+            LoginMechanism::Systemd => (
+                self.service_path(),
+                format!("ExecStart={}", systemd_exec(exe)),
+            ),
+        };
+        let current = fs::read_to_string(&path).unwrap_or_default();
+        if current.lines().any(|line| line.trim() == command) {
+            return Ok(None);
+        }
+        log::error!(
+            "Login item {} doesn't start {}. Rewriting it",
+            path.display(),
+            exe.display()
+        );
+        self.enable(mechanism, exe)?;
+        Ok(Some(mechanism))
+    }
+
+    fn remove_service(&self) -> Result<()> {
+        remove_file(&self.service_link_path())?;
+        remove_file(&self.service_path())
+    }
+
+    fn disable(&self) -> Result<()> {
+        remove_file(&self.autostart_path())?;
+        self.remove_service()
+    }
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+    log::trace!("Wrote {}", path.display());
+    Ok(())
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    if fs::symlink_metadata(path).is_ok() {
+        fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+        log::trace!("Removed {}", path.display());
+    }
+    Ok(())
+}
+
+/// Have systemd pick up unit file changes. Desktops without systemd only use
+/// autostart entries, so a failure here is logged rather than returned.
+fn reload_systemd() {
+    match Command::new("systemctl")
+        .args(["--user", "daemon-reload"])
+        .status()
+    {
+        Ok(status) if status.success() => {}
+        Ok(status) => log::error!("systemctl --user daemon-reload exited with {}", status),
+        Err(e) => log::error!("Failed to run systemctl: {}", e),
+    }
+}
+
+pub fn is_enabled() -> bool {
+    LoginItems::from_env()
+        .map(|items| items.enabled_mechanism().is_some())
+        .unwrap_or(false)
+}
+
+pub fn enable(mechanism: LoginMechanism) -> Result<()> {
+    let items = LoginItems::from_env()?;
+    let had_service = items.service_path().exists();
+    let exe = std::env::current_exe()?;
+    items.enable(mechanism, &exe)?;
+    if had_service || mechanism == LoginMechanism::Systemd {
+        reload_systemd();
+    }
+    log::trace!("Launch at login enabled with {:?}", mechanism);
+    Ok(())
+}
+
+pub fn repair() -> Result<()> {
+    let items = LoginItems::from_env()?;
+    if items.repair(&std::env::current_exe()?)? == Some(LoginMechanism::Systemd) {
+        reload_systemd();
+    }
+    Ok(())
+}
+
+pub fn disable() -> Result<()> {
+    let items = LoginItems::from_env()?;
+    let had_service = items.service_path().exists();
+    items.disable()?;
+    if had_service {
+        reload_systemd();
+    }
+    log::trace!("Launch at login disabled");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_home(name: &str) -> PathBuf {
+        let home = std::env::temp_dir().join(format!("mic-mute-test-login-{}", name));
+        let _ = fs::remove_dir_all(&home);
+        fs::create_dir_all(&home).unwrap();
+        home
+    }
+
+    #[test]
+    fn test_config_dir_honors_absolute_xdg_config_home() {
+        let home = Some(PathBuf::from("/home/me"));
+        assert_eq!(
+            config_dir(Some("/tmp/xdg".into()), home.clone()),
+            Some(PathBuf::from("/tmp/xdg"))
+        );
+        assert_eq!(
+            config_dir(Some("relative".into()), home.clone()),
+            Some(PathBuf::from("/home/me/.config"))
+        );
+        assert_eq!(
+            config_dir(None, home),
+            Some(PathBuf::from("/home/me/.config"))
+        );
+        assert_eq!(config_dir(None, None), None);
+    }
+
+    #[test]
+    fn test_exec_quoting() {
+        let exe = Path::new("/opt/Mic Mute/100% \"$HOME\"\\mic-mute");
+        assert_eq!(
+            desktop_exec(exe),
+            r#""/opt/Mic Mute/100%% \\"\\$HOME\\"\\\\mic-mute""#
+        );
+        assert_eq!(
+            systemd_exec(exe),
+            r#""/opt/Mic Mute/100%% \"$$HOME\"\\mic-mute""#
+        );
+    }
+
+    #[test]
+    fn test_autostart_entry() {
+        let home = temp_home("autostart");
+        let items = LoginItems {
+            config_dir: config_dir(None, Some(home.clone())).unwrap(),
+        };
+        assert_eq!(items.enabled_mechanism(), None);
+
+        let exe = Path::new("/usr/bin/mic-mute");
+        items.enable(LoginMechanism::Autostart, exe).unwrap();
+        let path = home.join(".config/autostart/mic-mute.desktop");
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("Exec=\"/usr/bin/mic-mute\"\n"));
+        assert_eq!(items.enabled_mechanism(), Some(LoginMechanism::Autostart));
+
+        // Switched off in the desktop's startup applications
+        fs::write(&path, contents + "Hidden=true\n").unwrap();
+        assert_eq!(items.enabled_mechanism(), None);
+
+        items.disable().unwrap();
+        assert!(!path.exists());
+        let _ = fs::remove_dir_all(&home);
+    }
+
+    #[test]
+    fn test_systemd_service_replaces_autostart_entry() {
+        let home = temp_home("systemd");
+        let items = LoginItems {
+            config_dir: config_dir(Some(home.join("xdg").into()), Some(home.clone())).unwrap(),
+        };
+        let exe = Path::new("/usr/bin/mic-mute");
+        items.enable(LoginMechanism::Autostart, exe).unwrap();
+        items.enable(LoginMechanism::Systemd, exe).unwrap();
+
+        let service = home.join("xdg/systemd/user/mic-mute.service");
+        let link = home.join("xdg/systemd/user/graphical-session.target.wants/mic-mute.service");
+        let unit = fs::read_to_string(&service).unwrap();
+        assert!(unit.contains("ExecStart=\"/usr/bin/mic-mute\"\n"));
+        assert!(unit.contains("Restart=on-failure\n"));
+        assert_eq!(fs::read_link(&link).unwrap(), service);
+        assert!(!home.join("xdg/autostart/mic-mute.desktop").exists());
+        assert!(!home.join(".config").exists());
+        assert_eq!(items.enabled_mechanism(), Some(LoginMechanism::Systemd));
+
+        // Enabling again replaces the existing link
+        items.enable(LoginMechanism::Systemd, exe).unwrap();
+        assert_eq!(items.enabled_mechanism(), Some(LoginMechanism::Systemd));
+
+        items.enable(LoginMechanism::Autostart, exe).unwrap();
+        assert!(!service.exists());
+        assert!(fs::symlink_metadata(&link).is_err());
+        assert_eq!(items.enabled_mechanism(), Some(LoginMechanism::Autostart));
+
+        items.disable().unwrap();
+        assert_eq!(items.enabled_mechanism(), None);
+        let _ = fs::remove_dir_all(&home);
+    }
+
+    #[test]
+    fn test_repair_rewrites_moved_executable() {
+        let home = temp_home("repair");
+        let items = LoginItems {
+            config_dir: config_dir(None, Some(home.clone())).unwrap(),
+        };
+        let old_exe = Path::new("/opt/mic-mute/mic-mute");
+        let new_exe = Path::new("/usr/local/bin/mic-mute");
+        assert_eq!(items.repair(new_exe).unwrap(), None);
+
+        items.enable(LoginMechanism::Systemd, old_exe).unwrap();
+        assert_eq!(items.repair(old_exe).unwrap(), None);
+        assert_eq!(
+            items.repair(new_exe).unwrap(),
+            Some(LoginMechanism::Systemd)
+        );
+        let unit = fs::read_to_string(items.service_path()).unwrap();
+        assert!(unit.contains("ExecStart=\"/usr/local/bin/mic-mute\"\n"));
+
+        // User edits to other keys don't count as stale
+        items.enable(LoginMechanism::Autostart, new_exe).unwrap();
+        let path = items.autostart_path();
+        let contents = fs::read_to_string(&path).unwrap() + "X-GNOME-Autostart-Delay=5\n";
+        fs::write(&path, &contents).unwrap();
+        assert_eq!(items.repair(new_exe).unwrap(), None);
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+        assert_eq!(
+            items.repair(old_exe).unwrap(),
+            Some(LoginMechanism::Autostart)
+        );
+        let _ = fs::remove_dir_all(&home);
+    }
+}
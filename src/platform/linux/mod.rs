@@ -0,0 +1,120 @@
+/// GTK implementation of the platform layer for X11 and Wayland desktops.
+/// tao runs its event loop on GTK, so native widgets are added straight to tao
+/// windows.
+mod audio;
+mod camera;
+mod controls;
+mod dbus;
+pub mod launch_at_login;
+mod popup_content;
+mod power;
+mod session;
+
+pub use audio::{AudioDeviceID, PulseAudioBackend};
+pub use camera::V4l2CameraBackend;
+pub use controls::{Checkbox, Form, Label};
+pub use dbus::ControlService;
+pub use popup_content::PopupContent;
+pub use power::on_battery_power;
+pub use session::watch_session;
+
+use crate::event_loop::EventLoopMessage;
+use crate::i18n::tr;
+use anyhow::{Context, Result};
+use gtk::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tao::platform::unix::{WindowBuilderExtUnix, WindowExtUnix};
+use tao::window::{Window, WindowBuilder};
+
+/// Whether the app can show an icon in a Dock.
+pub const HAS_DOCK: bool = false;
+
+/// The camera backend `CameraController::new` uses.
+pub type SystemCameraBackend = V4l2CameraBackend;
+
+/// The audio backend `MicController::new` uses.
+pub type SystemAudioBackend = PulseAudioBackend;
+
+/// The cursor in GDK's logical screen space, which `Window::monitor_from_point`
+/// uses. Wayland doesn't expose the global pointer position, so this is None there
+/// and the popup falls back to tao's cursor position.
+pub fn cursor_position() -> Option<(f64, f64)> {
+    let display = gtk::gdk::Display::default()?;
+    if display.type_().name() == "GdkWaylandDisplay" {
+        return None;
+    }
+    let pointer = display.default_seat()?.pointer()?;
+    let (_, x, y) = pointer.position_double();
+    Some((x, y))
+}
+
+/// Desktop environments have no activation policy to set.
+pub fn init_event_loop(_event_loop: &mut EventLoopMessage, _show_in_dock: bool) {}
+
+/// Linux panels list windows rather than apps, and the app has no windows at rest.
+pub fn set_dock_visible(_visible: bool) {}
+
+/// Focusing the window is enough to bring it to the front.
+pub fn activate_app() {}
+
+/// GTK has no autorelease pools.
+pub fn autoreleasepool<T>(f: impl FnOnce() -> T) -> T {
+    f()
+}
+
+/// Open a file with the desktop's default application.
+pub fn open_file(path: &Path) -> Result<()> {
+    Command::new("xdg-open")
+        .arg(path)
+        .spawn()
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    Ok(())
+}
+
+/// Open the folder containing a file in the file manager.
+pub fn reveal_file(path: &Path) -> Result<()> {
+    open_file(path.parent().unwrap_or(path))
+}
+
+/// Ask where to save a file, suggesting `file_name`. None if the user cancels.
+pub fn choose_save_path(title: &str, file_name: &str) -> Option<PathBuf> {
+    let (cancel, save) = (tr("dialog-cancel"), tr("dialog-save"));
+    let dialog = gtk::FileChooserDialog::with_buttons(
+        Some(title),
+        None::<&gtk::Window>,
+        gtk::FileChooserAction::Save,
+        &[
+            (cancel.as_str(), gtk::ResponseType::Cancel),
+            (save.as_str(), gtk::ResponseType::Accept),
+        ],
+    );
+    dialog.set_current_name(file_name);
+    dialog.set_do_overwrite_confirmation(true);
+    if let Some(folder) = dirs::download_dir().or_else(dirs::home_dir) {
+        dialog.set_current_folder(folder);
+    }
+    let path = match dialog.run() {
+        gtk::ResponseType::Accept => dialog.filename(),
+        _ => None,
+    };
+    dialog.close();
+    path
+}
+
+/// Window options for the borderless popup.
+pub fn popup_window_builder(builder: WindowBuilder) -> WindowBuilder {
+    builder.with_skip_taskbar(true)
+}
+
+/// Keep the popup from taking focus when it's shown.
+pub fn setup_popup_window(window: &Window) {
+    let gtk_window = window.gtk_window();
+    gtk_window.set_accept_focus(false);
+    gtk_window.set_focus_on_map(false);
+}
+
+/// Show a window above others. The popup is always on top and never takes focus.
+pub fn show_front(window: &Window) {
+    window.set_visible(true);
+}
@@ -0,0 +1,237 @@
+/// GTK popup content: mic and camera icons with their labels in a horizontal box.
+use crate::icons::{popup_icon_color, rasterize_svg, svg, Glyph, IconColor, IconState};
+use crate::mic::MicStatus;
+use crate::popup_content::{get_camera_description_text, get_mic_mute_description_text};
+use anyhow::{Context, Result};
+use gtk::gdk_pixbuf::{Colorspace, InterpType, Pixbuf};
+use gtk::glib::{markup_escape_text, Bytes};
+use gtk::prelude::*;
+use gtk::{Align, Orientation};
+use tao::dpi::LogicalSize;
+use tao::platform::unix::WindowExtUnix;
+use tao::window::{Theme, Window};
+
+/// Sizes at scale 1, matching the Cocoa popup.
+const ICON_HEIGHT: f64 = 16.;
+/// GTK fonts are sized in points at 96 DPI; 12pt is the 16px Cocoa uses.
+const FONT_POINTS: f64 = 12.;
+const SPACING: f64 = 8.;
+
+fn get_pixbuf(glyph: Glyph, state: IconState, theme: Theme, scale: f64) -> Result<Pixbuf> {
+    let color = popup_icon_color(state, theme);
+    let (rgba, w, h) = rasterize_svg(&svg(glyph), &color)?;
+    let pixbuf = Pixbuf::from_bytes(
+        &Bytes::from_owned(rgba),
+        Colorspace::Rgb,
+        true,
+        8,
+        w as i32,
+        h as i32,
+        w as i32 * 4,
+    );
+    let height = (ICON_HEIGHT * scale).round().max(1.);
+    let width = (w as f64 * height / h as f64).round().max(1.);
+    pixbuf
+        .scale_simple(width as i32, height as i32, InterpType::Bilinear)
+        .context("Failed to scale popup icon")
+}
+
+/// Pango markup for a label in its icon's color, so custom colors apply to both.
+fn get_markup(text: &str, color: IconColor, scale: f64) -> String {
+    format!(
+        "<span foreground=\"#{:02x}{:02x}{:02x}\" size=\"{}\">{}</span>",
+        color.r,
+        color.g,
+        color.b,
+        (FONT_POINTS * scale * 1024.).round() as i32,
+        markup_escape_text(text)
+    )
+}
+
+pub struct PopupContent {
+    container: gtk::Box,
+    mic_image: gtk::Image,
+    mic_label: gtk::Label,
+    separator: gtk::Separator,
+    camera_image: gtk::Image,
+    camera_label: gtk::Label,
+    mic_status: MicStatus,
+    active_cameras: Vec<String>,
+    theme: Theme,
+    scale: f64,
+}
+
+impl PopupContent {
+    pub fn new(
+        mic_status: MicStatus,
+        active_cameras: &[String],
+        _size: LogicalSize<f64>,
+        theme: Theme,
+    ) -> Result<Self> {
+        let container = gtk::Box::new(Orientation::Horizontal, SPACING as i32);
+        container.set_halign(Align::Center);
+        container.set_valign(Align::Center);
+
+        let mic_image = gtk::Image::new();
+        let mic_label = gtk::Label::new(None);
+        let separator = gtk::Separator::new(Orientation::Vertical);
+        let camera_image = gtk::Image::new();
+        let camera_label = gtk::Label::new(None);
+        container.pack_start(&mic_image, false, false, 0);
+        container.pack_start(&mic_label, false, false, 0);
+        container.pack_start(&separator, false, false, 0);
+        container.pack_start(&camera_image, false, false, 0);
+        container.pack_start(&camera_label, false, false, 0);
+        // The camera half is shown or hidden on its own, not by show_all()
+        for widget in [
+            separator.upcast_ref::<gtk::Widget>(),
+            camera_image.upcast_ref(),
+            camera_label.upcast_ref(),
+        ] {
+            widget.set_no_show_all(true);
+            widget.set_visible(true);
+        }
+
+        let mut content = Self {
+            container,
+            mic_image,
+            mic_label,
+            separator,
+            camera_image,
+            camera_label,
+            mic_status,
+            active_cameras: active_cameras.to_vec(),
+            theme,
+            scale: 1.,
+        };
+        content.render()?;
+        Ok(content)
+    }
+
+    /// Add the content to a window's default vbox.
+    pub fn attach(&self, window: &Window) {
+        if let Some(vbox) = window.default_vbox() {
+            vbox.pack_start(&self.container, true, true, 0);
+        }
+        self.container.show_all();
+    }
+
+    /// Redraw icons and text at `scale` times their base size.
+    pub fn set_scale(&mut self, _window: &Window, scale: f64) {
+        if scale == self.scale {
+            return;
+        }
+        self.scale = scale;
+        self.container.set_spacing((SPACING * scale).round() as i32);
+        if let Err(e) = self.render() {
+            log::error!("Failed to scale popup content: {:#}", e);
+        }
+    }
+
+    /// Show or hide the camera half of the popup. The box re-centers what's left.
+    pub fn set_camera_visible(&mut self, visible: bool) -> &mut Self {
+        self.separator.set_visible(visible);
+        self.camera_image.set_visible(visible);
+        self.camera_label.set_visible(visible);
+        self
+    }
+
+    pub fn update(
+        &mut self,
+        mic_status: MicStatus,
+        active_cameras: &[String],
+        theme: Theme,
+        _active_device_name: Option<&str>,
+    ) -> Result<&mut Self> {
+        self.mic_status = mic_status;
+        self.active_cameras = active_cameras.to_vec();
+        self.theme = theme;
+        self.render()?;
+        Ok(self)
+    }
+
+    fn render(&mut self) -> Result<()> {
+        let mic_state = IconState::for_status(self.mic_status);
+        let camera_muted = self.active_cameras.is_empty();
+        let camera_state = IconState::from_muted(camera_muted);
+        self.mic_image.set_from_pixbuf(Some(&get_pixbuf(
+            Glyph::mic(self.mic_status),
+            mic_state,
+            self.theme,
+            self.scale,
+        )?));
+        self.mic_label.set_markup(&get_markup(
+            &get_mic_mute_description_text(self.mic_status),
+            popup_icon_color(mic_state, self.theme),
+            self.scale,
+        ));
+        self.camera_image.set_from_pixbuf(Some(&get_pixbuf(
+            Glyph::video(camera_muted),
+            camera_state,
+            self.theme,
+            self.scale,
+        )?));
+        self.camera_label.set_markup(&get_markup(
+            &get_camera_description_text(&self.active_cameras),
+            popup_icon_color(camera_state, self.theme),
+            self.scale,
+        ));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_markup_escapes_text() {
+        let markup = get_markup(
+            "Mic <off>",
+            IconColor {
+                r: 255,
+                g: 0,
+                b: 16,
+            },
+            2.,
+        );
+        assert_eq!(
+            markup,
+            "<span foreground=\"#ff0010\" size=\"24576\">Mic &lt;off&gt;</span>"
+        );
+    }
+
+    /// Needs an X server, e.g. `xvfb-run cargo test`. Skipped without a display.
+    #[test]
+    fn test_popup_content_renders_on_display() {
+        if std::env::var_os("DISPLAY").is_none() || gtk::init().is_err() {
+            return;
+        }
+        let mut content = PopupContent::new(
+            MicStatus::Muted,
+            &[],
+            LogicalSize::new(250., 40.),
+            Theme::Light,
+        )
+        .unwrap();
+        assert_eq!(content.mic_label.text(), "Mic off");
+        assert_eq!(content.camera_label.text(), "Camera off");
+        assert!(content.mic_image.pixbuf().is_some());
+
+        content
+            .update(
+                MicStatus::PartiallyMuted { muted: 1, total: 2 },
+                &["Integrated Camera".to_string()],
+                Theme::Dark,
+                None,
+            )
+            .unwrap();
+        assert_eq!(content.mic_label.text(), "Mic 1/2 off");
+        assert_eq!(content.camera_label.text(), "Camera on: Integrated Camera");
+
+        assert!(content.camera_label.is_visible());
+        content.set_camera_visible(false);
+        assert!(!content.camera_label.is_visible());
+        assert!(!content.separator.is_visible());
+    }
+}
@@ -0,0 +1,60 @@
+/// Power source detection from the kernel's power supply class.
+use std::fs;
+use std::path::Path;
+
+/// Returns true if a system battery under `power_supply_dir` is discharging.
+/// Batteries of peripherals such as wireless mice report a "Device" scope and are
+/// ignored.
+fn is_discharging(power_supply_dir: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(power_supply_dir) else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        let read = |name: &str| fs::read_to_string(entry.path().join(name)).unwrap_or_default();
+        read("type").trim() == "Battery"
+            && read("scope").trim() != "Device"
+            && read("status").trim() == "Discharging"
+    })
+}
+
+/// Returns true if the computer is running on battery power.
+pub fn on_battery_power() -> bool {
+    is_discharging(Path::new("/sys/class/power_supply"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supply(dir: &Path, name: &str, files: &[(&str, &str)]) {
+        let path = dir.join(name);
+        fs::create_dir_all(&path).unwrap();
+        for (file, contents) in files {
+            fs::write(path.join(file), format!("{}\n", contents)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_is_discharging() {
+        let dir = std::env::temp_dir().join("mic-mute-test-power-supply");
+        let _ = fs::remove_dir_all(&dir);
+        supply(&dir, "AC", &[("type", "Mains"), ("online", "1")]);
+        supply(
+            &dir,
+            "hidpp_battery_0",
+            &[
+                ("type", "Battery"),
+                ("scope", "Device"),
+                ("status", "Discharging"),
+            ],
+        );
+        supply(&dir, "BAT0", &[("type", "Battery"), ("status", "Charging")]);
+        assert!(!is_discharging(&dir));
+
+        supply(&dir, "BAT0", &[("status", "Discharging")]);
+        assert!(is_discharging(&dir));
+
+        let _ = fs::remove_dir_all(&dir);
+        assert!(!is_discharging(&dir));
+    }
+}
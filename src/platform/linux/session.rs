@@ -0,0 +1,182 @@
+/// Sleep, wake, screen lock and user switch events from systemd-logind.
+/// Follows logind's D-Bus signals through `gdbus monitor`, the same way the audio
+/// backend uses `pactl`, so no D-Bus library has to be linked. Without a delay
+/// inhibitor the system may suspend before a mute on sleep lands; wake re-enforces.
+use crate::app_state::SessionEvent;
+use log::{error, trace};
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+const LOGIND: &str = "org.freedesktop.login1";
+const SESSION_PATH_PREFIX: &str = "/org/freedesktop/login1/session/";
+/// Delay before following logind again after the monitor exits.
+const RESTART_DELAY: Duration = Duration::from_secs(10);
+
+/// The object path in gdbus's `(objectpath '/org/freedesktop/login1/session/_32',)`.
+fn parse_object_path(reply: &str) -> Option<&str> {
+    let start = reply.find('\'')? + 1;
+    let end = start + reply[start..].find('\'')?;
+    Some(&reply[start..end])
+}
+
+/// Object path of the logind session this process belongs to. None when it's
+/// outside a session, e.g. as a systemd user service.
+fn own_session_path() -> Option<String> {
+    let output = Command::new("gdbus")
+        .args([
+            "call",
+            "--system",
+            "--dest",
+            LOGIND,
+            "--object-path",
+            "/org/freedesktop/login1",
+            "--method",
+            "org.freedesktop.login1.Manager.GetSessionByPID",
+            &std::process::id().to_string(),
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_object_path(&String::from_utf8_lossy(&output.stdout)).map(str::to_string)
+}
+
+/// The event in one line of `gdbus monitor` output. Session signals are only
+/// accepted from `session`, or from any session when it's unknown.
+fn parse_monitor_line(line: &str, session: Option<&str>) -> Option<SessionEvent> {
+    let (path, signal) = line.split_once(": ")?;
+    let (member, args) = signal.split_once(' ').unwrap_or((signal, ""));
+    if member == "org.freedesktop.login1.Manager.PrepareForSleep" {
+        return Some(if args.contains("true") {
+            SessionEvent::Sleep
+        } else {
+            SessionEvent::Wake
+        });
+    }
+    if !path.starts_with(SESSION_PATH_PREFIX) || session.is_some_and(|s| s != path) {
+        return None;
+    }
+    match member {
+        // Requests to the screen locker, e.g. from `loginctl lock-session`
+        "org.freedesktop.login1.Session.Lock" => Some(SessionEvent::Lock),
+        "org.freedesktop.login1.Session.Unlock" => Some(SessionEvent::Unlock),
+        // Lockers that report their state set LockedHint
+        "org.freedesktop.DBus.Properties.PropertiesChanged" => {
+            if args.contains("'LockedHint': <true>") {
+                Some(SessionEvent::Lock)
+            } else if args.contains("'LockedHint': <false>") {
+                Some(SessionEvent::Unlock)
+            } else if args.contains("'Active': <false>") {
+                Some(SessionEvent::SwitchedOut)
+            } else if args.contains("'Active': <true>") {
+                Some(SessionEvent::SwitchedIn)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Follow logind until the monitor exits.
+fn monitor(session: Option<&str>, callback: &impl Fn(SessionEvent)) -> std::io::Result<()> {
+    let mut child = Command::new("gdbus")
+        .args(["monitor", "--system", "--dest", LOGIND])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines() {
+            if let Some(event) = parse_monitor_line(&line?, session) {
+                trace!("Session event {:?}", event);
+                callback(event);
+            }
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// Report session changes to `callback` from a background thread for the rest of
+/// the process.
+pub fn watch_session(callback: impl Fn(SessionEvent) + Send + 'static) {
+    std::thread::spawn(move || {
+        let session = own_session_path();
+        trace!("Watching logind session {:?}", session);
+        loop {
+            if let Err(e) = monitor(session.as_deref(), &callback) {
+                error!("Sleep and lock events are unavailable: {}", e);
+                return;
+            }
+            std::thread::sleep(RESTART_DELAY);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSION: &str = "/org/freedesktop/login1/session/_32";
+
+    #[test]
+    fn test_parse_object_path() {
+        assert_eq!(
+            parse_object_path("(objectpath '/org/freedesktop/login1/session/_32',)\n"),
+            Some(SESSION)
+        );
+        assert_eq!(parse_object_path("Error: GDBus.Error"), None);
+    }
+
+    #[test]
+    fn test_parse_sleep_and_wake() {
+        let sleep =
+            "/org/freedesktop/login1: org.freedesktop.login1.Manager.PrepareForSleep (true,)";
+        let wake =
+            "/org/freedesktop/login1: org.freedesktop.login1.Manager.PrepareForSleep (false,)";
+        assert_eq!(
+            parse_monitor_line(sleep, Some(SESSION)),
+            Some(SessionEvent::Sleep)
+        );
+        assert_eq!(
+            parse_monitor_line(wake, Some(SESSION)),
+            Some(SessionEvent::Wake)
+        );
+    }
+
+    #[test]
+    fn test_parse_session_signals_only_from_own_session() {
+        let lock = format!("{}: org.freedesktop.login1.Session.Lock ()", SESSION);
+        let other = "/org/freedesktop/login1/session/c2: org.freedesktop.login1.Session.Lock ()";
+        assert_eq!(
+            parse_monitor_line(&lock, Some(SESSION)),
+            Some(SessionEvent::Lock)
+        );
+        assert_eq!(parse_monitor_line(other, Some(SESSION)), None);
+        assert_eq!(parse_monitor_line(other, None), Some(SessionEvent::Lock));
+    }
+
+    #[test]
+    fn test_parse_session_properties() {
+        let changed = |properties: &str| {
+            format!(
+                "{}: org.freedesktop.DBus.Properties.PropertiesChanged ('org.freedesktop.login1.Session', {{{}}}, @as [])",
+                SESSION, properties
+            )
+        };
+        assert_eq!(
+            parse_monitor_line(&changed("'LockedHint': <false>"), None),
+            Some(SessionEvent::Unlock)
+        );
+        assert_eq!(
+            parse_monitor_line(&changed("'Active': <false>"), None),
+            Some(SessionEvent::SwitchedOut)
+        );
+        assert_eq!(
+            parse_monitor_line(&changed("'IdleHint': <true>"), None),
+            None
+        );
+    }
+}
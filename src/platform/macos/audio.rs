@@ -0,0 +1,608 @@
+/// CoreAudio backend for the mic controller.
+use crate::mic::{AudioBackend, DeviceDetails};
+use anyhow::{anyhow, Context, Result};
+use cocoa::base::id;
+use cocoa::foundation::NSString;
+use coreaudio::audio_unit::macos_helpers::{get_audio_device_ids, get_device_name};
+pub use objc2_core_audio::AudioDeviceID;
+use objc2_core_audio::{
+    kAudioDevicePropertyMute, kAudioDevicePropertyScopeInput,
+    kAudioDevicePropertyStreamConfiguration, kAudioDevicePropertyVolumeScalar,
+    kAudioHardwareNoError, kAudioHardwarePropertyDefaultInputDevice,
+    kAudioHardwareUnknownPropertyError, kAudioObjectPropertyElementMain,
+    kAudioObjectPropertyScopeGlobal, AudioObjectGetPropertyData, AudioObjectGetPropertyDataSize,
+    AudioObjectIsPropertySettable, AudioObjectPropertyAddress, AudioObjectPropertySelector,
+    AudioObjectSetPropertyData,
+};
+use objc2_core_audio_types::{AudioBuffer, AudioBufferList};
+use std::alloc::{alloc_zeroed, dealloc, Layout};
+use std::ffi::c_void;
+use std::mem;
+use std::ptr::{null, NonNull};
+
+const SYSTEM_OBJECT_ID: AudioDeviceID = 1;
+const AUDIO_HARDWARE_SERVICE_DEVICE_PROPERTY_VIRTUAL_MAIN_VOLUME: AudioObjectPropertySelector =
+    0x766d7663; // 'vmvc'
+const AUDIO_DEVICE_PROPERTY_DEVICE_UID: AudioObjectPropertySelector = 0x75696420; // 'uid '
+const AUDIO_DEVICE_PROPERTY_TRANSPORT_TYPE: AudioObjectPropertySelector = 0x7472616e; // 'tran'
+
+/// A four character code such as a transport type, e.g. `usb` for 'usb '.
+fn fourcc(code: u32) -> String {
+    code.to_be_bytes()
+        .iter()
+        .map(|&byte| {
+            if byte.is_ascii_graphic() {
+                byte as char
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn status_result(status: i32, operation: &str, audio_device_id: AudioDeviceID) -> Result<()> {
+    if status == kAudioHardwareNoError {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} failed for audio device {} with OSStatus {}",
+            operation,
+            audio_device_id,
+            status
+        ))
+    }
+}
+
+struct AudioBufferListAllocation {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+impl AudioBufferListAllocation {
+    fn new(size: u32) -> Result<Self> {
+        let layout = Layout::from_size_align(size as usize, mem::align_of::<AudioBufferList>())
+            .context("invalid AudioBufferList allocation layout")?;
+        let ptr = NonNull::new(unsafe { alloc_zeroed(layout) })
+            .ok_or_else(|| anyhow!("failed to allocate AudioBufferList"))?;
+        Ok(Self { ptr, layout })
+    }
+
+    fn as_mut_void(&mut self) -> *mut c_void {
+        self.ptr.as_ptr().cast()
+    }
+
+    unsafe fn as_list(&self) -> &AudioBufferList {
+        &*self.ptr.as_ptr().cast::<AudioBufferList>()
+    }
+}
+
+impl Drop for AudioBufferListAllocation {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+#[derive(Default)]
+pub struct CoreAudioBackend;
+
+impl CoreAudioBackend {
+    fn mute_address() -> AudioObjectPropertyAddress {
+        AudioObjectPropertyAddress {
+            mSelector: kAudioDevicePropertyMute,
+            mScope: kAudioDevicePropertyScopeInput,
+            mElement: kAudioObjectPropertyElementMain,
+        }
+    }
+
+    fn volume_addresses() -> [AudioObjectPropertyAddress; 2] {
+        [
+            AudioObjectPropertyAddress {
+                mSelector: kAudioDevicePropertyVolumeScalar,
+                mScope: kAudioDevicePropertyScopeInput,
+                mElement: kAudioObjectPropertyElementMain,
+            },
+            AudioObjectPropertyAddress {
+                mSelector: AUDIO_HARDWARE_SERVICE_DEVICE_PROPERTY_VIRTUAL_MAIN_VOLUME,
+                mScope: kAudioDevicePropertyScopeInput,
+                mElement: kAudioObjectPropertyElementMain,
+            },
+        ]
+    }
+
+    /// The input `selector` of one channel; CoreAudio numbers channels from 1, with
+    /// element 0 the main element.
+    fn channel_address(
+        selector: AudioObjectPropertySelector,
+        channel: u32,
+    ) -> AudioObjectPropertyAddress {
+        AudioObjectPropertyAddress {
+            mSelector: selector,
+            mScope: kAudioDevicePropertyScopeInput,
+            mElement: channel,
+        }
+    }
+
+    /// The settable per-channel elements of `selector`, for devices that control
+    /// each input channel rather than the whole device.
+    fn channel_addresses(
+        audio_device_id: AudioDeviceID,
+        selector: AudioObjectPropertySelector,
+    ) -> Result<Vec<AudioObjectPropertyAddress>> {
+        let mut addresses = vec![];
+        for channel in 1..=Self::input_channel_count(audio_device_id)? {
+            let address = Self::channel_address(selector, channel);
+            if Self::is_property_settable(audio_device_id, address)? {
+                addresses.push(address);
+            }
+        }
+        Ok(addresses)
+    }
+
+    fn global_address(selector: AudioObjectPropertySelector) -> AudioObjectPropertyAddress {
+        AudioObjectPropertyAddress {
+            mSelector: selector,
+            mScope: kAudioObjectPropertyScopeGlobal,
+            mElement: kAudioObjectPropertyElementMain,
+        }
+    }
+
+    /// A property holding a CFString, which the caller receives ownership of.
+    fn get_string(
+        audio_device_id: AudioDeviceID,
+        mut property_address: AudioObjectPropertyAddress,
+    ) -> Result<Option<String>> {
+        let mut string: id = std::ptr::null_mut();
+        let mut data_size = mem::size_of::<id>() as u32;
+        let status = unsafe {
+            AudioObjectGetPropertyData(
+                audio_device_id,
+                NonNull::new_unchecked(&mut property_address),
+                0,
+                null(),
+                NonNull::new_unchecked(&mut data_size),
+                NonNull::new_unchecked(&mut string as *mut id as *mut c_void),
+            )
+        };
+        if status == kAudioHardwareUnknownPropertyError || string.is_null() {
+            return Ok(None);
+        }
+        status_result(status, "read string property", audio_device_id)?;
+        // CFString is toll-free bridged to NSString
+        unsafe {
+            let bytes = string.UTF8String();
+            let value = (!bytes.is_null()).then(|| {
+                std::ffi::CStr::from_ptr(bytes)
+                    .to_string_lossy()
+                    .into_owned()
+            });
+            let _: () = msg_send![string, release];
+            Ok(value)
+        }
+    }
+
+    fn get_u32(
+        audio_device_id: AudioDeviceID,
+        mut property_address: AudioObjectPropertyAddress,
+    ) -> Result<Option<u32>> {
+        let mut value = 0_u32;
+        let mut data_size = mem::size_of::<u32>() as u32;
+        let status = unsafe {
+            AudioObjectGetPropertyData(
+                audio_device_id,
+                NonNull::new_unchecked(&mut property_address),
+                0,
+                null(),
+                NonNull::new_unchecked(&mut data_size),
+                NonNull::new_unchecked(&mut value as *mut u32 as *mut c_void),
+            )
+        };
+        if status == kAudioHardwareUnknownPropertyError {
+            return Ok(None);
+        }
+        status_result(status, "read integer property", audio_device_id)?;
+        Ok(Some(value))
+    }
+
+    fn is_property_settable(
+        audio_device_id: AudioDeviceID,
+        mut property_address: AudioObjectPropertyAddress,
+    ) -> Result<bool> {
+        let mut is_settable = 0u8;
+        let status = unsafe {
+            AudioObjectIsPropertySettable(
+                audio_device_id,
+                NonNull::new_unchecked(&mut property_address),
+                NonNull::new_unchecked(&mut is_settable),
+            )
+        };
+        if status == kAudioHardwareUnknownPropertyError {
+            return Ok(false);
+        }
+        status_result(status, "check input property settable", audio_device_id)?;
+        Ok(is_settable != 0)
+    }
+
+    fn get_settable_volume(
+        audio_device_id: AudioDeviceID,
+        mut property_address: AudioObjectPropertyAddress,
+    ) -> Result<Option<f32>> {
+        if !Self::is_property_settable(audio_device_id, property_address)? {
+            return Ok(None);
+        }
+
+        let mut volume = 0_f32;
+        let mut data_size = mem::size_of::<f32>() as u32;
+        let status = unsafe {
+            AudioObjectGetPropertyData(
+                audio_device_id,
+                NonNull::new_unchecked(&mut property_address),
+                0,
+                null(),
+                NonNull::new_unchecked(&mut data_size),
+                NonNull::new_unchecked(&mut volume as *mut f32 as *mut c_void),
+            )
+        };
+        if status == kAudioHardwareUnknownPropertyError {
+            return Ok(None);
+        }
+        status_result(status, "read input volume", audio_device_id)?;
+        Ok(Some(volume))
+    }
+
+    fn set_settable_volume(
+        audio_device_id: AudioDeviceID,
+        mut property_address: AudioObjectPropertyAddress,
+        volume: f32,
+    ) -> Result<Option<()>> {
+        if !Self::is_property_settable(audio_device_id, property_address)? {
+            return Ok(None);
+        }
+
+        let data_size = mem::size_of::<f32>() as u32;
+        let status = unsafe {
+            AudioObjectSetPropertyData(
+                audio_device_id,
+                NonNull::new_unchecked(&mut property_address),
+                0,
+                null(),
+                data_size,
+                NonNull::new_unchecked(&volume as *const f32 as *mut c_void),
+            )
+        };
+        if status == kAudioHardwareUnknownPropertyError {
+            return Ok(None);
+        }
+        status_result(status, "set input volume", audio_device_id)?;
+        Ok(Some(()))
+    }
+
+    fn read_mute(
+        audio_device_id: AudioDeviceID,
+        mut property_address: AudioObjectPropertyAddress,
+    ) -> Result<Option<bool>> {
+        let mut muted = 0_u32;
+        let mut data_size = mem::size_of::<u32>() as u32;
+        let status = unsafe {
+            AudioObjectGetPropertyData(
+                audio_device_id,
+                NonNull::new_unchecked(&mut property_address),
+                0,
+                null(),
+                NonNull::new_unchecked(&mut data_size),
+                NonNull::new_unchecked(&mut muted as *mut u32 as *mut c_void),
+            )
+        };
+        if status == kAudioHardwareUnknownPropertyError {
+            return Ok(None);
+        }
+        status_result(status, "read mute", audio_device_id)?;
+        Ok(Some(muted == 1))
+    }
+
+    fn write_mute(
+        audio_device_id: AudioDeviceID,
+        mut property_address: AudioObjectPropertyAddress,
+        state: bool,
+    ) -> Result<Option<()>> {
+        if !Self::is_property_settable(audio_device_id, property_address)? {
+            return Ok(None);
+        }
+        let data = state as u32;
+        let data_size = mem::size_of::<u32>() as u32;
+        let status = unsafe {
+            AudioObjectSetPropertyData(
+                audio_device_id,
+                NonNull::new_unchecked(&mut property_address),
+                0,
+                null(),
+                data_size,
+                NonNull::new_unchecked(&data as *const u32 as *mut c_void),
+            )
+        };
+        if status == kAudioHardwareUnknownPropertyError {
+            return Ok(None);
+        }
+        status_result(status, "set mute", audio_device_id)?;
+        Ok(Some(()))
+    }
+
+    /// Channels across the device's input streams.
+    fn input_channel_count(audio_device_id: AudioDeviceID) -> Result<u32> {
+        let mut property_address = AudioObjectPropertyAddress {
+            mSelector: kAudioDevicePropertyStreamConfiguration,
+            mScope: kAudioDevicePropertyScopeInput,
+            mElement: kAudioObjectPropertyElementMain,
+        };
+        let mut data_size = 0u32;
+        let status = unsafe {
+            AudioObjectGetPropertyDataSize(
+                audio_device_id,
+                NonNull::new_unchecked(&mut property_address),
+                0,
+                null(),
+                NonNull::new_unchecked(&mut data_size),
+            )
+        };
+        if status == kAudioHardwareUnknownPropertyError {
+            return Ok(0);
+        }
+        status_result(
+            status,
+            "read input stream configuration size",
+            audio_device_id,
+        )?;
+        if data_size < mem::size_of::<u32>() as u32 {
+            return Ok(0);
+        }
+
+        let mut buffer_list = AudioBufferListAllocation::new(data_size)?;
+        let status = unsafe {
+            AudioObjectGetPropertyData(
+                audio_device_id,
+                NonNull::new_unchecked(&mut property_address),
+                0,
+                null(),
+                NonNull::new_unchecked(&mut data_size),
+                NonNull::new_unchecked(buffer_list.as_mut_void()),
+            )
+        };
+        status_result(status, "read input stream configuration", audio_device_id)?;
+
+        let list = unsafe { buffer_list.as_list() };
+        let buffer_count = list.mNumberBuffers as usize;
+        if buffer_count == 0 {
+            return Ok(0);
+        }
+        let minimum_size = mem::offset_of!(AudioBufferList, mBuffers)
+            + buffer_count * mem::size_of::<AudioBuffer>();
+        if (data_size as usize) < minimum_size {
+            return Err(anyhow!(
+                "input stream configuration for audio device {} was truncated",
+                audio_device_id
+            ));
+        }
+        let buffers = unsafe { std::slice::from_raw_parts(list.mBuffers.as_ptr(), buffer_count) };
+        Ok(buffers.iter().map(|buffer| buffer.mNumberChannels).sum())
+    }
+}
+
+impl AudioBackend for CoreAudioBackend {
+    fn device_ids(&self) -> Result<Vec<AudioDeviceID>> {
+        get_audio_device_ids().map_err(anyhow::Error::msg)
+    }
+
+    fn device_name(&self, audio_device_id: AudioDeviceID) -> Result<String> {
+        get_device_name(audio_device_id).map_err(anyhow::Error::msg)
+    }
+
+    fn has_input_channels(&self, audio_device_id: AudioDeviceID) -> Result<bool> {
+        Ok(Self::input_channel_count(audio_device_id)? > 0)
+    }
+
+    fn get_mute(&self, audio_device_id: AudioDeviceID) -> Result<Option<bool>> {
+        Self::read_mute(audio_device_id, Self::mute_address())
+    }
+
+    fn set_mute(&mut self, audio_device_id: AudioDeviceID, state: bool) -> Result<Option<()>> {
+        Self::write_mute(audio_device_id, Self::mute_address(), state)
+    }
+
+    fn get_volume(&self, audio_device_id: AudioDeviceID) -> Result<Option<f32>> {
+        for property_address in Self::volume_addresses() {
+            if let Some(volume) = Self::get_settable_volume(audio_device_id, property_address)? {
+                return Ok(Some(volume));
+            }
+        }
+        Ok(None)
+    }
+
+    fn set_volume(&mut self, audio_device_id: AudioDeviceID, volume: f32) -> Result<Option<()>> {
+        for property_address in Self::volume_addresses() {
+            if Self::set_settable_volume(audio_device_id, property_address, volume)?.is_some() {
+                return Ok(Some(()));
+            }
+        }
+        Ok(None)
+    }
+
+    fn get_channel_volumes(&self, audio_device_id: AudioDeviceID) -> Result<Option<Vec<f32>>> {
+        let addresses = Self::channel_addresses(audio_device_id, kAudioDevicePropertyVolumeScalar)?;
+        if addresses.is_empty() {
+            return Ok(None);
+        }
+        let mut volumes = vec![];
+        for address in addresses {
+            let volume = Self::get_settable_volume(audio_device_id, address)?.ok_or_else(|| {
+                anyhow!(
+                    "input channel {} volume of audio device {} went away",
+                    address.mElement,
+                    audio_device_id
+                )
+            })?;
+            volumes.push(volume);
+        }
+        Ok(Some(volumes))
+    }
+
+    fn set_channel_volumes(
+        &mut self,
+        audio_device_id: AudioDeviceID,
+        volumes: &[f32],
+    ) -> Result<Option<()>> {
+        let addresses = Self::channel_addresses(audio_device_id, kAudioDevicePropertyVolumeScalar)?;
+        if addresses.is_empty() {
+            return Ok(None);
+        }
+        if addresses.len() != volumes.len() {
+            return Err(anyhow!(
+                "audio device {} has {} input channel volumes, not {}",
+                audio_device_id,
+                addresses.len(),
+                volumes.len()
+            ));
+        }
+        for (address, volume) in addresses.into_iter().zip(volumes) {
+            Self::set_settable_volume(audio_device_id, address, *volume)?;
+        }
+        Ok(Some(()))
+    }
+
+    fn get_channel_mutes(&self, audio_device_id: AudioDeviceID) -> Result<Option<Vec<bool>>> {
+        let addresses = Self::channel_addresses(audio_device_id, kAudioDevicePropertyMute)?;
+        if addresses.is_empty() {
+            return Ok(None);
+        }
+        let mut mutes = vec![];
+        for address in addresses {
+            let muted = Self::read_mute(audio_device_id, address)?.ok_or_else(|| {
+                anyhow!(
+                    "input channel {} mute of audio device {} went away",
+                    address.mElement,
+                    audio_device_id
+                )
+            })?;
+            mutes.push(muted);
+        }
+        Ok(Some(mutes))
+    }
+
+    fn set_channel_mutes(
+        &mut self,
+        audio_device_id: AudioDeviceID,
+        mutes: &[bool],
+    ) -> Result<Option<()>> {
+        let addresses = Self::channel_addresses(audio_device_id, kAudioDevicePropertyMute)?;
+        if addresses.is_empty() {
+            return Ok(None);
+        }
+        if addresses.len() != mutes.len() {
+            return Err(anyhow!(
+                "audio device {} has {} input channel mutes, not {}",
+                audio_device_id,
+                addresses.len(),
+                mutes.len()
+            ));
+        }
+        for (address, muted) in addresses.into_iter().zip(mutes) {
+            Self::write_mute(audio_device_id, address, *muted)?;
+        }
+        Ok(Some(()))
+    }
+
+    fn default_input_device(&self) -> Result<Option<AudioDeviceID>> {
+        let mut property_address = AudioObjectPropertyAddress {
+            mSelector: kAudioHardwarePropertyDefaultInputDevice,
+            mScope: kAudioObjectPropertyScopeGlobal,
+            mElement: kAudioObjectPropertyElementMain,
+        };
+        let mut device_id: AudioDeviceID = 0;
+        let mut data_size = mem::size_of::<AudioDeviceID>() as u32;
+        let status = unsafe {
+            AudioObjectGetPropertyData(
+                SYSTEM_OBJECT_ID,
+                NonNull::new_unchecked(&mut property_address),
+                0,
+                null(),
+                NonNull::new_unchecked(&mut data_size),
+                NonNull::new_unchecked(&mut device_id as *mut AudioDeviceID as *mut c_void),
+            )
+        };
+        if status != kAudioHardwareNoError || device_id == 0 {
+            return Ok(None);
+        }
+        Ok(Some(device_id))
+    }
+
+    fn device_details(&self, audio_device_id: AudioDeviceID) -> Result<DeviceDetails> {
+        let uid = Self::get_string(
+            audio_device_id,
+            Self::global_address(AUDIO_DEVICE_PROPERTY_DEVICE_UID),
+        )?;
+        let transport = Self::get_u32(
+            audio_device_id,
+            Self::global_address(AUDIO_DEVICE_PROPERTY_TRANSPORT_TYPE),
+        )?
+        .map(fourcc);
+        let mut volume_elements = vec![];
+        let channel_addresses =
+            Self::channel_addresses(audio_device_id, kAudioDevicePropertyVolumeScalar)?;
+        for address in Self::volume_addresses()
+            .into_iter()
+            .chain(channel_addresses)
+        {
+            if Self::is_property_settable(audio_device_id, address)? {
+                volume_elements.push(format!(
+                    "{} element {}",
+                    fourcc(address.mSelector),
+                    address.mElement
+                ));
+            }
+        }
+        Ok(DeviceDetails {
+            uid,
+            transport,
+            volume_elements,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn core_audio_volume_fallback_checks_virtual_main_volume() {
+        let addresses = CoreAudioBackend::volume_addresses();
+
+        assert_eq!(addresses.len(), 2);
+        assert_eq!(addresses[0].mSelector, kAudioDevicePropertyVolumeScalar);
+        assert_eq!(
+            addresses[1].mSelector,
+            AUDIO_HARDWARE_SERVICE_DEVICE_PROPERTY_VIRTUAL_MAIN_VOLUME
+        );
+        assert_eq!(addresses[1].mScope, kAudioDevicePropertyScopeInput);
+        assert_eq!(addresses[1].mElement, kAudioObjectPropertyElementMain);
+    }
+
+    #[test]
+    fn core_audio_channel_addresses_number_channels_from_one() {
+        let address = CoreAudioBackend::channel_address(kAudioDevicePropertyVolumeScalar, 2);
+
+        assert_eq!(address.mSelector, kAudioDevicePropertyVolumeScalar);
+        assert_eq!(address.mScope, kAudioDevicePropertyScopeInput);
+        assert_eq!(address.mElement, 2);
+        assert_ne!(address.mElement, kAudioObjectPropertyElementMain);
+    }
+
+    #[test]
+    fn fourcc_formats_property_and_transport_codes() {
+        assert_eq!(
+            fourcc(AUDIO_HARDWARE_SERVICE_DEVICE_PROPERTY_VIRTUAL_MAIN_VOLUME),
+            "vmvc"
+        );
+        assert_eq!(fourcc(0x75736220), "usb");
+        assert_eq!(fourcc(0x626c746e), "bltn");
+    }
+}
@@ -0,0 +1,377 @@
+/// Camera backend on AVFoundation and CoreMediaIO.
+use crate::camera::CameraBackend;
+use anyhow::{anyhow, bail, Result};
+use cocoa::base::{id, nil};
+use cocoa::foundation::NSString;
+use log::trace;
+use std::ffi::c_void;
+use std::mem;
+use std::sync::Mutex;
+
+#[link(name = "AVFoundation", kind = "framework")]
+extern "C" {}
+
+// CMIO constants
+const K_CMIO_OBJECT_PROPERTY_SCOPE_GLOBAL: u32 = 0x676c6f62; // 'glob'
+const K_CMIO_OBJECT_PROPERTY_ELEMENT_MAIN: u32 = 0;
+const K_CMIO_DEVICE_PROPERTY_IS_RUNNING_SOMEWHERE: u32 = 0x676F6E65; // 'gone'
+                                                                     // System object holds the list of all CMIO devices
+const K_CMIO_HARDWARE_OBJECT_SYSTEM: u32 = 1;
+const K_CMIO_HARDWARE_PROPERTY_DEVICES: u32 = 0x64657623; // 'dev#'
+                                                          // Matches the AVCaptureDevice uniqueID
+const K_CMIO_DEVICE_PROPERTY_DEVICE_UID: u32 = 0x75696420; // 'uid '
+const K_CMIO_OBJECT_PROPERTY_NAME: u32 = 0x6c6e616d; // 'lnam'
+
+type CMIOObjectID = u32;
+
+#[repr(C)]
+struct CMIOObjectPropertyAddress {
+    m_selector: u32,
+    m_scope: u32,
+    m_element: u32,
+}
+
+type CMIOObjectPropertyListenerProc = extern "C" fn(
+    object_id: CMIOObjectID,
+    number_addresses: u32,
+    addresses: *const CMIOObjectPropertyAddress,
+    client_data: *mut c_void,
+) -> i32;
+
+#[link(name = "CoreMediaIO", kind = "framework")]
+extern "C" {
+    fn CMIOObjectAddPropertyListener(
+        object_id: CMIOObjectID,
+        address: *const CMIOObjectPropertyAddress,
+        listener: CMIOObjectPropertyListenerProc,
+        client_data: *mut c_void,
+    ) -> i32;
+
+    fn CMIOObjectGetPropertyData(
+        object_id: CMIOObjectID,
+        address: *const CMIOObjectPropertyAddress,
+        qualifier_data_size: u32,
+        qualifier_data: *const c_void,
+        in_data_size: u32,
+        out_data_size: *mut u32,
+        out_data: *mut c_void,
+    ) -> i32;
+
+    fn CMIOObjectGetPropertyDataSize(
+        object_id: CMIOObjectID,
+        address: *const CMIOObjectPropertyAddress,
+        qualifier_data_size: u32,
+        qualifier_data: *const c_void,
+        out_data_size: *mut u32,
+    ) -> i32;
+}
+
+/// Enumerate all CMIO device IDs directly from the CMIO system object.
+/// This does not require camera TCC permission.
+fn get_cmio_device_ids_system() -> Vec<CMIOObjectID> {
+    let address = CMIOObjectPropertyAddress {
+        m_selector: K_CMIO_HARDWARE_PROPERTY_DEVICES,
+        m_scope: K_CMIO_OBJECT_PROPERTY_SCOPE_GLOBAL,
+        m_element: K_CMIO_OBJECT_PROPERTY_ELEMENT_MAIN,
+    };
+    let mut data_size: u32 = 0;
+    let status = unsafe {
+        CMIOObjectGetPropertyDataSize(
+            K_CMIO_HARDWARE_OBJECT_SYSTEM,
+            &address,
+            0,
+            std::ptr::null(),
+            &mut data_size,
+        )
+    };
+    if status != 0 || data_size == 0 {
+        trace!(
+            "CMIO system device size query: status={} size={}",
+            status,
+            data_size
+        );
+        return vec![];
+    }
+    let count = data_size as usize / mem::size_of::<CMIOObjectID>();
+    let mut ids = vec![0u32; count];
+    let mut out_size = data_size;
+    let status = unsafe {
+        CMIOObjectGetPropertyData(
+            K_CMIO_HARDWARE_OBJECT_SYSTEM,
+            &address,
+            0,
+            std::ptr::null(),
+            data_size,
+            &mut out_size,
+            ids.as_mut_ptr() as *mut c_void,
+        )
+    };
+    trace!(
+        "CMIO system device enumeration: status={} count={}",
+        status,
+        count
+    );
+    if status != 0 {
+        vec![]
+    } else {
+        ids
+    }
+}
+
+fn is_device_running_somewhere(device_id: CMIOObjectID) -> Option<bool> {
+    let address = CMIOObjectPropertyAddress {
+        m_selector: K_CMIO_DEVICE_PROPERTY_IS_RUNNING_SOMEWHERE,
+        m_scope: K_CMIO_OBJECT_PROPERTY_SCOPE_GLOBAL,
+        m_element: K_CMIO_OBJECT_PROPERTY_ELEMENT_MAIN,
+    };
+    let mut running: u32 = 0;
+    let in_size = mem::size_of::<u32>() as u32;
+    let mut out_size = in_size;
+    let status = unsafe {
+        CMIOObjectGetPropertyData(
+            device_id,
+            &address,
+            0,
+            std::ptr::null(),
+            in_size,
+            &mut out_size,
+            &mut running as *mut u32 as *mut c_void,
+        )
+    };
+    trace!(
+        "CMIO device {} isRunningSomewhere: status={} running={}",
+        device_id,
+        status,
+        running
+    );
+    if status == 0 {
+        Some(running != 0)
+    } else {
+        None
+    }
+}
+
+fn global_address(selector: u32) -> CMIOObjectPropertyAddress {
+    CMIOObjectPropertyAddress {
+        m_selector: selector,
+        m_scope: K_CMIO_OBJECT_PROPERTY_SCOPE_GLOBAL,
+        m_element: K_CMIO_OBJECT_PROPERTY_ELEMENT_MAIN,
+    }
+}
+
+/// A CFString property of a CMIO object, released after it's copied.
+fn get_cmio_string(object_id: CMIOObjectID, selector: u32) -> Result<String> {
+    let address = global_address(selector);
+    let mut string: id = nil;
+    let in_size = mem::size_of::<id>() as u32;
+    let mut out_size = in_size;
+    let status = unsafe {
+        CMIOObjectGetPropertyData(
+            object_id,
+            &address,
+            0,
+            std::ptr::null(),
+            in_size,
+            &mut out_size,
+            &mut string as *mut id as *mut c_void,
+        )
+    };
+    if status != 0 || string == nil {
+        return Err(anyhow!(
+            "CMIO object {} property {:#x} failed: status={}",
+            object_id,
+            selector,
+            status
+        ));
+    }
+    unsafe {
+        let value = ns_string(string);
+        let _: () = msg_send![string, release];
+        Ok(value)
+    }
+}
+
+/// Where the CMIO listeners report to. Leaked by `watch`, since listeners stay
+/// registered for the life of the app.
+struct Listeners {
+    on_change: Box<dyn Fn() + Send + Sync>,
+    /// Devices with a running-somewhere listener, so each gets only one.
+    devices: Mutex<Vec<CMIOObjectID>>,
+}
+
+impl Listeners {
+    /// Listen to devices that were added since the last call.
+    fn listen_to_devices(&'static self) -> Result<()> {
+        let current = get_cmio_device_ids_system();
+        let mut devices = self.devices.lock().unwrap();
+        devices.retain(|id| current.contains(id));
+        for id in current {
+            if !devices.contains(&id) {
+                add_listener(id, K_CMIO_DEVICE_PROPERTY_IS_RUNNING_SOMEWHERE, self)?;
+                devices.push(id);
+            }
+        }
+        trace!("Listening to CMIO devices {:?}", devices);
+        Ok(())
+    }
+}
+
+fn add_listener(
+    object_id: CMIOObjectID,
+    selector: u32,
+    listeners: &'static Listeners,
+) -> Result<()> {
+    let address = global_address(selector);
+    let status = unsafe {
+        CMIOObjectAddPropertyListener(
+            object_id,
+            &address,
+            on_property_changed,
+            listeners as *const Listeners as *mut c_void,
+        )
+    };
+    if status != 0 {
+        bail!(
+            "Failed to listen to CMIO object {} property {:#x}: status={}",
+            object_id,
+            selector,
+            status
+        );
+    }
+    Ok(())
+}
+
+/// Runs on a CoreMediaIO notification thread.
+extern "C" fn on_property_changed(
+    object_id: CMIOObjectID,
+    number_addresses: u32,
+    addresses: *const CMIOObjectPropertyAddress,
+    client_data: *mut c_void,
+) -> i32 {
+    let listeners = unsafe { &*(client_data as *const Listeners) };
+    let addresses = if addresses.is_null() {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(addresses, number_addresses as usize) }
+    };
+    if addresses
+        .iter()
+        .any(|address| address.m_selector == K_CMIO_HARDWARE_PROPERTY_DEVICES)
+    {
+        if let Err(e) = listeners.listen_to_devices() {
+            log::error!("{:#}", e);
+        }
+    }
+    trace!("CMIO object {} changed", object_id);
+    (listeners.on_change)();
+    0
+}
+
+/// All video capture devices, or nil without camera permission.
+unsafe fn capture_devices() -> id {
+    let media_type = NSString::alloc(nil).init_str("vide");
+    let devices: id = msg_send![class!(AVCaptureDevice), devicesWithMediaType: media_type];
+    let _: () = msg_send![media_type, release];
+    devices
+}
+
+unsafe fn capture_device(unique_id: &str) -> Result<id> {
+    let unique_id_string = NSString::alloc(nil).init_str(unique_id);
+    let device: id = msg_send![class!(AVCaptureDevice), deviceWithUniqueID: unique_id_string];
+    let _: () = msg_send![unique_id_string, release];
+    if device == nil {
+        Err(anyhow!("No capture device with unique ID {}", unique_id))
+    } else {
+        Ok(device)
+    }
+}
+
+/// AVFoundation capture devices, with CoreMediaIO as the system devices.
+#[derive(Default)]
+pub struct AvFoundationCameraBackend;
+
+impl CameraBackend for AvFoundationCameraBackend {
+    fn capture_device_ids(&self) -> Result<Option<Vec<String>>> {
+        unsafe {
+            let devices = capture_devices();
+            if devices == nil {
+                return Ok(None);
+            }
+            let count: usize = msg_send![devices, count];
+            let mut ids = Vec::with_capacity(count);
+            for i in 0..count {
+                let device: id = msg_send![devices, objectAtIndex: i];
+                let unique_id: id = msg_send![device, uniqueID];
+                ids.push(ns_string(unique_id));
+            }
+            Ok(Some(ids))
+        }
+    }
+
+    fn capture_device_name(&self, unique_id: &str) -> Result<String> {
+        unsafe {
+            let name: id = msg_send![capture_device(unique_id)?, localizedName];
+            Ok(ns_string(name))
+        }
+    }
+
+    fn is_in_use_by_another_app(&self, unique_id: &str) -> Result<bool> {
+        // isInUseByAnotherApplication returns ObjC BOOL (i8)
+        let in_use: cocoa::base::BOOL =
+            unsafe { msg_send![capture_device(unique_id)?, isInUseByAnotherApplication] };
+        trace!(
+            "AVCaptureDevice {} isInUseByAnotherApplication={}",
+            unique_id,
+            in_use
+        );
+        Ok(in_use != cocoa::base::NO)
+    }
+
+    fn system_device_ids(&self) -> Result<Vec<u32>> {
+        let device_ids = get_cmio_device_ids_system();
+        if device_ids.is_empty() {
+            trace!("CMIO direct enumeration returned 0 devices");
+        }
+        Ok(device_ids)
+    }
+
+    fn is_running_somewhere(&self, device_id: u32) -> Result<Option<bool>> {
+        Ok(is_device_running_somewhere(device_id))
+    }
+
+    fn system_device_uid(&self, device_id: u32) -> Result<Option<String>> {
+        let uid = get_cmio_string(device_id, K_CMIO_DEVICE_PROPERTY_DEVICE_UID)?;
+        Ok(Some(uid).filter(|uid| !uid.is_empty()))
+    }
+
+    fn system_device_name(&self, device_id: u32) -> Result<String> {
+        get_cmio_string(device_id, K_CMIO_OBJECT_PROPERTY_NAME)
+    }
+
+    /// Listens to the device list and each device's running-somewhere property.
+    fn watch(&self, on_change: Box<dyn Fn() + Send + Sync>) -> Result<()> {
+        let listeners: &'static Listeners = Box::leak(Box::new(Listeners {
+            on_change,
+            devices: Mutex::new(vec![]),
+        }));
+        add_listener(
+            K_CMIO_HARDWARE_OBJECT_SYSTEM,
+            K_CMIO_HARDWARE_PROPERTY_DEVICES,
+            listeners,
+        )?;
+        listeners.listen_to_devices()
+    }
+}
+
+unsafe fn ns_string(string: id) -> String {
+    if string == nil {
+        return String::new();
+    }
+    let bytes = string.UTF8String();
+    if bytes.is_null() {
+        return String::new();
+    }
+    std::ffi::CStr::from_ptr(bytes)
+        .to_string_lossy()
+        .into_owned()
+}
@@ -0,0 +1,236 @@
+/// Cocoa controls for the Preferences window.
+/// Controls are laid out top to bottom with fixed frames and report clicks to the
+/// event loop as `Message::PreferencesAction` carrying the control's tag.
+use crate::event_loop::{EventLoopProxyMessage, Message};
+use cocoa::appkit::NSView;
+use cocoa::base::{id, nil, NO};
+use cocoa::foundation::{NSPoint, NSRect, NSSize, NSString};
+use objc::declare::ClassDecl;
+use objc::runtime::{Object, Sel};
+use std::ffi::c_void;
+use std::sync::Once;
+use tao::platform::macos::WindowExtMacOS;
+use tao::window::Window;
+
+const MARGIN: f64 = 20.;
+const ROW_HEIGHT: f64 = 24.;
+const SECTION_GAP: f64 = 12.;
+const BUTTON_WIDTH: f64 = 90.;
+
+const TARGET_CLASS: &str = "MicMutePreferencesTarget";
+const PROXY_IVAR: &str = "proxy";
+
+extern "C" fn on_action(this: &Object, _: Sel, sender: id) {
+    unsafe {
+        let tag: i64 = msg_send![sender, tag];
+        let proxy = *this.get_ivar::<*mut c_void>(PROXY_IVAR) as *const EventLoopProxyMessage;
+        if let Some(proxy) = proxy.as_ref() {
+            proxy.send_event(Message::PreferencesAction(tag)).ok();
+        }
+    }
+}
+
+/// Objective-C class that forwards control actions to the event loop by tag.
+fn target_class() -> &'static objc::runtime::Class {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| {
+        let mut decl = ClassDecl::new(TARGET_CLASS, class!(NSObject))
+            .expect("Preferences target class already registered");
+        decl.add_ivar::<*mut c_void>(PROXY_IVAR);
+        unsafe {
+            decl.add_method(
+                sel!(onAction:),
+                on_action as extern "C" fn(&Object, Sel, id),
+            );
+        }
+        decl.register();
+    });
+    objc::runtime::Class::get(TARGET_CLASS).unwrap()
+}
+
+unsafe fn make_label(text: &str, frame: NSRect, bold: bool) -> id {
+    let label: id = msg_send![class!(NSTextField), alloc];
+    let label: id = msg_send![label, initWithFrame: frame];
+    let text_str = NSString::alloc(nil).init_str(text);
+    let _: () = msg_send![label, setStringValue: text_str];
+    let _: () = msg_send![text_str, release];
+    let _: () = msg_send![label, setBezeled: NO];
+    let _: () = msg_send![label, setEditable: NO];
+    let _: () = msg_send![label, setDrawsBackground: NO];
+    let _: () = msg_send![label, setSelectable: NO];
+    if bold {
+        let ns_font = class!(NSFont);
+        let size: f64 = msg_send![ns_font, systemFontSize];
+        let font: id = msg_send![ns_font, boldSystemFontOfSize: size];
+        let _: () = msg_send![label, setFont: font];
+    }
+    label
+}
+
+unsafe fn make_button(title: &str, frame: NSRect, target: id, tag: i64) -> id {
+    let button: id = msg_send![class!(NSButton), alloc];
+    let button: id = msg_send![button, initWithFrame: frame];
+    let title_str = NSString::alloc(nil).init_str(title);
+    let _: () = msg_send![button, setTitle: title_str];
+    let _: () = msg_send![title_str, release];
+    const NS_BEZEL_STYLE_ROUNDED: u64 = 1;
+    let _: () = msg_send![button, setBezelStyle: NS_BEZEL_STYLE_ROUNDED];
+    let _: () = msg_send![button, setTarget: target];
+    let _: () = msg_send![button, setAction: sel!(onAction:)];
+    let _: () = msg_send![button, setTag: tag];
+    button
+}
+
+/// A text label that can be updated.
+pub struct Label(id);
+
+impl Label {
+    pub fn set_text(&self, text: &str) {
+        unsafe {
+            let text_str = NSString::alloc(nil).init_str(text);
+            let _: () = msg_send![self.0, setStringValue: text_str];
+            let _: () = msg_send![text_str, release];
+        }
+    }
+}
+
+/// A checkbox whose state can be updated without reporting an action.
+pub struct Checkbox(id);
+
+impl Checkbox {
+    pub fn set_checked(&self, checked: bool) {
+        unsafe {
+            let _: () = msg_send![self.0, setState: i64::from(checked)];
+        }
+    }
+}
+
+/// Lays out controls top to bottom in a window's non-flipped NSView.
+pub struct Form {
+    view: id,
+    target: id,
+    width: f64,
+    height: f64,
+    cursor: f64,
+}
+
+impl Form {
+    /// Height of a form with `rows` rows and `gaps` section gaps.
+    pub fn height(rows: usize, gaps: usize) -> f64 {
+        MARGIN * 2. + rows as f64 * ROW_HEIGHT + gaps as f64 * SECTION_GAP
+    }
+
+    pub fn new(window: &Window, proxy: EventLoopProxyMessage, width: f64, height: f64) -> Self {
+        unsafe {
+            let target: id = msg_send![target_class(), new];
+            let proxy = Box::into_raw(Box::new(proxy)) as *mut c_void;
+            (*target).set_ivar(PROXY_IVAR, proxy);
+            Self {
+                view: window.ns_view() as id,
+                target,
+                width,
+                height,
+                cursor: MARGIN,
+            }
+        }
+    }
+
+    fn content_width(&self) -> f64 {
+        self.width - MARGIN * 2.
+    }
+
+    fn row(&mut self) -> f64 {
+        self.cursor += ROW_HEIGHT;
+        self.height - self.cursor
+    }
+
+    fn frame(&self, x: f64, y: f64, width: f64) -> NSRect {
+        NSRect::new(NSPoint::new(x, y), NSSize::new(width, ROW_HEIGHT))
+    }
+
+    unsafe fn add(&self, subview: id) -> id {
+        self.view.addSubview_(subview);
+        let _: () = msg_send![subview, release];
+        subview
+    }
+
+    pub fn gap(&mut self) {
+        self.cursor += SECTION_GAP;
+    }
+
+    pub fn heading(&mut self, text: &str) {
+        let y = self.row();
+        unsafe {
+            self.add(make_label(
+                text,
+                self.frame(MARGIN, y, self.content_width()),
+                true,
+            ));
+        }
+    }
+
+    pub fn label(&mut self, text: &str) -> Label {
+        self.label_with_buttons(text, &[])
+    }
+
+    /// A label followed by buttons on the same row.
+    pub fn label_with_buttons(&mut self, text: &str, buttons: &[(&str, i64)]) -> Label {
+        let y = self.row();
+        let label_width = self.content_width() - BUTTON_WIDTH * buttons.len() as f64;
+        unsafe {
+            let label = self.add(make_label(text, self.frame(MARGIN, y, label_width), false));
+            for (index, (title, tag)) in buttons.iter().enumerate() {
+                let x = MARGIN + label_width + BUTTON_WIDTH * index as f64;
+                self.add(make_button(
+                    title,
+                    self.frame(x, y, BUTTON_WIDTH),
+                    self.target,
+                    *tag,
+                ));
+            }
+            Label(label)
+        }
+    }
+
+    pub fn button(&mut self, title: &str, tag: i64) {
+        let y = self.row();
+        unsafe {
+            self.add(make_button(
+                title,
+                self.frame(MARGIN, y, BUTTON_WIDTH * 2.),
+                self.target,
+                tag,
+            ));
+        }
+    }
+
+    pub fn checkbox(&mut self, title: &str, tag: i64, checked: bool) -> Checkbox {
+        let y = self.row();
+        unsafe {
+            let checkbox = self.add(make_button(
+                title,
+                self.frame(MARGIN, y, self.content_width()),
+                self.target,
+                tag,
+            ));
+            const NS_SWITCH_BUTTON: u64 = 3;
+            let _: () = msg_send![checkbox, setButtonType: NS_SWITCH_BUTTON];
+            let checkbox = Checkbox(checkbox);
+            checkbox.set_checked(checked);
+            checkbox
+        }
+    }
+}
+
+impl Drop for Form {
+    fn drop(&mut self) {
+        unsafe {
+            let proxy = *(*self.target).get_ivar::<*mut c_void>(PROXY_IVAR);
+            (*self.target).set_ivar(PROXY_IVAR, std::ptr::null_mut::<c_void>());
+            if !proxy.is_null() {
+                drop(Box::from_raw(proxy as *mut EventLoopProxyMessage));
+            }
+            let _: () = msg_send![self.target, release];
+        }
+    }
+}
@@ -0,0 +1,321 @@
+/// Launch at login through a LaunchAgent plist in ~/Library/LaunchAgents.
+use crate::settings::LoginMechanism;
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+const PLIST_LABEL: &str = "com.brettinternet.mic-mute";
+
+fn plist_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| {
+        h.join("Library")
+            .join("LaunchAgents")
+            .join(format!("{}.plist", PLIST_LABEL))
+    })
+}
+
+/// Where macOS runs an app opened from a quarantined download, a random read-only
+/// copy that's gone after the next restart, so no login item should point there.
+fn is_translocated(exe: &Path) -> bool {
+    exe.to_string_lossy().contains("/AppTranslocation/")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// The parts of a LaunchAgent plist that decide what launchd starts.
+#[derive(Debug, PartialEq)]
+struct LaunchAgent {
+    label: String,
+    program_arguments: Vec<String>,
+}
+
+impl LaunchAgent {
+    fn for_exe(exe: &Path) -> Self {
+        Self {
+            label: PLIST_LABEL.to_string(),
+            program_arguments: vec![exe.to_string_lossy().into_owned()],
+        }
+    }
+
+    fn to_plist(&self) -> String {
+        let arguments: String = self
+            .program_arguments
+            .iter()
+            .map(|argument| format!("\t\t<string>{}</string>\n", escape_xml(argument)))
+            .collect();
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>Label</key>
+	<string>{label}</string>
+	<key>ProgramArguments</key>
+	<array>
+{arguments}	</array>
+	<key>RunAtLoad</key>
+	<true/>
+	<key>KeepAlive</key>
+	<false/>
+</dict>
+</plist>
+"#,
+            label = escape_xml(&self.label),
+            arguments = arguments,
+        )
+    }
+
+    /// Read the label and program arguments from an XML plist. Other keys are ignored.
+    fn parse(xml: &str) -> Result<Self> {
+        let document = roxmltree::Document::parse_with_options(
+            xml,
+            roxmltree::ParsingOptions {
+                allow_dtd: true,
+                ..Default::default()
+            },
+        )
+        .context("LaunchAgent is not valid XML")?;
+        let dict = document
+            .root_element()
+            .children()
+            .find(|node| node.has_tag_name("dict"))
+            .ok_or_else(|| anyhow!("LaunchAgent has no top-level dict"))?;
+
+        let mut label = None;
+        let mut program_arguments = vec![];
+        let mut entries = dict.children().filter(|node| node.is_element());
+        while let Some(key) = entries.next() {
+            if !key.has_tag_name("key") {
+                bail!(
+                    "LaunchAgent dict has <{}> where a key was expected",
+                    key.tag_name().name()
+                );
+            }
+            let value = entries
+                .next()
+                .ok_or_else(|| anyhow!("LaunchAgent key {:?} has no value", key.text()))?;
+            match key.text() {
+                Some("Label") => label = value.text().map(String::from),
+                Some("ProgramArguments") => {
+                    program_arguments = value
+                        .children()
+                        .filter(|node| node.has_tag_name("string"))
+                        .map(|node| node.text().unwrap_or_default().to_string())
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+        Ok(Self {
+            label: label.ok_or_else(|| anyhow!("LaunchAgent has no Label"))?,
+            program_arguments,
+        })
+    }
+
+    /// Why this plist wouldn't start `expected`, or None if it would. Arguments
+    /// after the program, such as a user's `--headless`, are left out of it.
+    fn mismatch(&self, expected: &LaunchAgent) -> Option<String> {
+        if self.label != expected.label {
+            Some(format!(
+                "label is {:?}, expected {:?}",
+                self.label, expected.label
+            ))
+        } else if self.program_arguments.first() != expected.program_arguments.first() {
+            Some(format!(
+                "it runs {:?}, expected {:?}",
+                self.program_arguments.first(),
+                expected.program_arguments.first()
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// This plist pointed at `exe`, keeping any arguments after the program.
+    fn retargeted(&self, exe: &Path) -> Self {
+        let mut agent = Self::for_exe(exe);
+        agent
+            .program_arguments
+            .extend(self.program_arguments.iter().skip(1).cloned());
+        agent
+    }
+}
+
+pub fn is_enabled() -> bool {
+    plist_path().map(|p| p.exists()).unwrap_or(false)
+}
+
+/// LaunchAgents are the only mechanism on macOS.
+pub fn enable(_mechanism: LoginMechanism) -> Result<()> {
+    let exe = std::env::current_exe()?;
+    if is_translocated(&exe) {
+        bail!("Move Mic Mute to the Applications folder before turning on launch at login");
+    }
+    let path = plist_path().ok_or_else(|| anyhow!("Cannot resolve LaunchAgents directory"))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(&path, LaunchAgent::for_exe(&exe).to_plist())?;
+    log::trace!("Launch at login enabled: wrote {}", path.display());
+    Ok(())
+}
+
+/// Rewrite the LaunchAgent if it no longer starts this executable, e.g. after the
+/// app bundle was moved, renamed or replaced by an update at another path.
+pub fn repair() -> Result<()> {
+    let path = plist_path().ok_or_else(|| anyhow!("Cannot resolve LaunchAgents directory"))?;
+    let xml = match fs::read_to_string(&path) {
+        Ok(xml) => xml,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    let exe = std::env::current_exe()?;
+    if is_translocated(&exe) {
+        log::trace!(
+            "Running from {}; leaving the login item alone",
+            exe.display()
+        );
+        return Ok(());
+    }
+    let expected = LaunchAgent::for_exe(&exe);
+    let (problem, repaired) = match LaunchAgent::parse(&xml) {
+        Ok(existing) => (existing.mismatch(&expected), existing.retargeted(&exe)),
+        Err(e) => (Some(format!("{:#}", e)), expected),
+    };
+    if let Some(problem) = problem {
+        log::error!(
+            "Login item {} is stale: {}. Rewriting it",
+            path.display(),
+            problem
+        );
+        fs::write(&path, repaired.to_plist())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+pub fn disable() -> Result<()> {
+    if let Some(path) = plist_path() {
+        if path.exists() {
+            fs::remove_file(&path)?;
+            log::trace!("Launch at login disabled: removed {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plist_path_is_in_launch_agents() {
+        let path = plist_path().unwrap();
+        assert!(path.to_string_lossy().contains("LaunchAgents"));
+        assert!(path.to_string_lossy().ends_with(".plist"));
+    }
+
+    #[test]
+    fn test_is_enabled_returns_bool() {
+        // Just verify it doesn't panic and returns a bool
+        let _ = is_enabled();
+    }
+
+    #[test]
+    fn test_plist_round_trip() {
+        let agent = LaunchAgent::for_exe(Path::new(
+            "/Applications/Mic & Mute.app/Contents/MacOS/mic-mute",
+        ));
+        let plist = agent.to_plist();
+        assert!(plist
+            .contains("<string>/Applications/Mic &amp; Mute.app/Contents/MacOS/mic-mute</string>"));
+        assert_eq!(LaunchAgent::parse(&plist).unwrap(), agent);
+    }
+
+    #[test]
+    fn test_mismatch_after_app_moves() {
+        let installed = LaunchAgent::for_exe(Path::new(
+            "/Applications/Mic Mute.app/Contents/MacOS/mic-mute",
+        ));
+        let moved = LaunchAgent::for_exe(Path::new(
+            "/Users/me/Apps/Mic Mute.app/Contents/MacOS/mic-mute",
+        ));
+        assert_eq!(installed.mismatch(&installed), None);
+        assert!(installed
+            .mismatch(&moved)
+            .unwrap()
+            .contains("/Users/me/Apps"));
+
+        let relabeled = LaunchAgent {
+            label: "com.example.other".to_string(),
+            ..LaunchAgent::for_exe(Path::new(
+                "/Applications/Mic Mute.app/Contents/MacOS/mic-mute",
+            ))
+        };
+        assert!(relabeled.mismatch(&installed).unwrap().contains("label"));
+    }
+
+    #[test]
+    fn test_parse_ignores_other_keys() {
+        let plist = r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0">
+<dict>
+	<key>KeepAlive</key>
+	<dict><key>SuccessfulExit</key><false/></dict>
+	<key>Label</key>
+	<string>com.brettinternet.mic-mute</string>
+	<key>ProgramArguments</key>
+	<array><string>/opt/mic-mute</string><string>--headless</string></array>
+</dict>
+</plist>"#;
+        let agent = LaunchAgent::parse(plist).unwrap();
+        assert_eq!(agent.label, PLIST_LABEL);
+        assert_eq!(agent.program_arguments, vec!["/opt/mic-mute", "--headless"]);
+    }
+
+    #[test]
+    fn test_repair_keeps_extra_arguments() {
+        let installed = LaunchAgent {
+            label: PLIST_LABEL.to_string(),
+            program_arguments: vec![
+                "/Applications/Mic Mute.app/Contents/MacOS/mic-mute".to_string(),
+                "--headless".to_string(),
+            ],
+        };
+        let current = Path::new("/Applications/Mic Mute.app/Contents/MacOS/mic-mute");
+        assert_eq!(installed.mismatch(&LaunchAgent::for_exe(current)), None);
+
+        let moved = Path::new("/Users/me/Apps/Mic Mute.app/Contents/MacOS/mic-mute");
+        assert!(installed.mismatch(&LaunchAgent::for_exe(moved)).is_some());
+        assert_eq!(
+            installed.retargeted(moved).program_arguments,
+            vec![
+                moved.to_string_lossy().into_owned(),
+                "--headless".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_translocated_paths() {
+        assert!(is_translocated(Path::new(
+            "/private/var/folders/x1/abc/T/AppTranslocation/1234-ABCD/d/Mic Mute.app/Contents/MacOS/mic-mute"
+        )));
+        assert!(!is_translocated(Path::new(
+            "/Applications/Mic Mute.app/Contents/MacOS/mic-mute"
+        )));
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_plists() {
+        assert!(LaunchAgent::parse("not xml").is_err());
+        assert!(LaunchAgent::parse("<plist><dict><key>Label</key></dict></plist>").is_err());
+        assert!(LaunchAgent::parse("<plist><dict></dict></plist>").is_err());
+    }
+}
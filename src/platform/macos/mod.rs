@@ -0,0 +1,203 @@
+/// Cocoa implementation of the platform layer.
+mod audio;
+mod camera;
+mod controls;
+pub mod launch_at_login;
+mod popup_content;
+mod power;
+mod session;
+
+pub use audio::{AudioDeviceID, CoreAudioBackend};
+pub use camera::AvFoundationCameraBackend;
+pub use controls::{Checkbox, Form, Label};
+pub use popup_content::PopupContent;
+pub use power::on_battery_power;
+pub use session::watch_session;
+
+use crate::event_loop::EventLoopMessage;
+use anyhow::{Context, Result};
+use cocoa::appkit::{NSWindow, NSWindowStyleMask, NSWindowTitleVisibility};
+use cocoa::base::{id, nil, YES};
+use cocoa::foundation::NSString;
+use libc::c_void;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tao::platform::macos::{
+    ActivationPolicy, EventLoopExtMacOS, WindowBuilderExtMacOS, WindowExtMacOS,
+};
+use tao::window::{Window, WindowBuilder};
+
+/// Whether the app can show an icon in a Dock.
+pub const HAS_DOCK: bool = true;
+
+/// The camera backend `CameraController::new` uses.
+pub type SystemCameraBackend = AvFoundationCameraBackend;
+
+/// The audio backend `MicController::new` uses.
+pub type SystemAudioBackend = CoreAudioBackend;
+
+/// Stands in for the Linux D-Bus object: macOS has no session bus, so the tray and
+/// shortcut are the only controls.
+pub struct ControlService;
+
+impl ControlService {
+    pub fn start(
+        _on_command: impl Fn(crate::app_state::Command) + Send + Sync + 'static,
+    ) -> Result<Self> {
+        Ok(Self)
+    }
+
+    pub fn set_mic(&self, _muted: bool, _active_device: Option<&str>) {}
+
+    pub fn set_camera(&self, _active: bool) {}
+}
+
+type CGFloat = f64;
+
+#[repr(C)]
+struct CGPoint {
+    pub x: CGFloat,
+    pub y: CGFloat,
+}
+
+extern "C" {
+    fn CFRelease(cf: *const c_void);
+    fn CGEventCreate(r: *const c_void) -> *const c_void;
+    fn CGEventGetLocation(e: *const c_void) -> CGPoint;
+}
+
+/// The cursor in the global display space used by `Window::monitor_from_point`.
+pub fn cursor_position() -> Option<(f64, f64)> {
+    unsafe {
+        let event = CGEventCreate(std::ptr::null());
+        if event.is_null() {
+            return None;
+        }
+
+        let point = CGEventGetLocation(event);
+        CFRelease(event);
+        Some((point.x, point.y))
+    }
+}
+
+/// Set the activation policy from the persisted `show_in_dock` before the loop starts.
+pub fn init_event_loop(event_loop: &mut EventLoopMessage, show_in_dock: bool) {
+    event_loop.set_activation_policy(if show_in_dock {
+        ActivationPolicy::Regular
+    } else {
+        ActivationPolicy::Accessory
+    });
+}
+
+/// Toggle the app's dock icon visibility at runtime.
+///
+/// `true`  → NSApplicationActivationPolicyRegular (shows in Dock + Cmd-Tab)
+/// `false` → NSApplicationActivationPolicyAccessory (no Dock icon, default)
+pub fn set_dock_visible(visible: bool) {
+    // NSApplicationActivationPolicyRegular = 0
+    // NSApplicationActivationPolicyAccessory = 1
+    let policy: i64 = if visible { 0 } else { 1 };
+    unsafe {
+        let app: id = msg_send![class!(NSApplication), sharedApplication];
+        let _: () = msg_send![app, setActivationPolicy: policy];
+    }
+}
+
+/// Bring the app to the front, even when it has no Dock icon.
+pub fn activate_app() {
+    unsafe {
+        let app: id = msg_send![class!(NSApplication), sharedApplication];
+        let _: () = msg_send![app, activateIgnoringOtherApps: YES];
+    }
+}
+
+/// Run `f` in an autorelease pool, for Cocoa calls made off the main thread.
+pub fn autoreleasepool<T>(f: impl FnOnce() -> T) -> T {
+    objc::rc::autoreleasepool(f)
+}
+
+/// Open a file in the default text editor.
+pub fn open_file(path: &Path) -> Result<()> {
+    Command::new("open")
+        .arg("-t")
+        .arg(path)
+        .spawn()
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    Ok(())
+}
+
+/// Select a file in Finder.
+pub fn reveal_file(path: &Path) -> Result<()> {
+    Command::new("open")
+        .arg("-R")
+        .arg(path)
+        .spawn()
+        .with_context(|| format!("Failed to reveal {}", path.display()))?;
+    Ok(())
+}
+
+/// Ask where to save a file, suggesting `file_name`. None if the user cancels.
+pub fn choose_save_path(title: &str, file_name: &str) -> Option<PathBuf> {
+    // NSModalResponseOK
+    const RESPONSE_OK: isize = 1;
+    activate_app();
+    unsafe {
+        let panel: id = msg_send![class!(NSSavePanel), savePanel];
+        let title = NSString::alloc(nil).init_str(title);
+        let _: () = msg_send![panel, setTitle: title];
+        let _: () = msg_send![title, release];
+        let name = NSString::alloc(nil).init_str(file_name);
+        let _: () = msg_send![panel, setNameFieldStringValue: name];
+        let _: () = msg_send![name, release];
+        let response: isize = msg_send![panel, runModal];
+        if response != RESPONSE_OK {
+            return None;
+        }
+        let url: id = msg_send![panel, URL];
+        let path: id = msg_send![url, path];
+        let bytes = path.UTF8String();
+        if bytes.is_null() {
+            return None;
+        }
+        let path = std::ffi::CStr::from_ptr(bytes).to_string_lossy();
+        Some(PathBuf::from(path.as_ref()))
+    }
+}
+
+/// Window options for the borderless popup.
+pub fn popup_window_builder(builder: WindowBuilder) -> WindowBuilder {
+    builder
+        .with_titlebar_hidden(true)
+        .with_movable_by_window_background(true)
+        .with_has_shadow(true)
+}
+
+/// Rounded edges and a hidden, transparent title bar for the popup.
+pub fn setup_popup_window(window: &Window) {
+    unsafe {
+        let window = window.ns_window() as id;
+        window.setHasShadow_(true);
+        // Rounded edges hack: https://stackoverflow.com/a/37418915
+        let mask = window.styleMask();
+        let _: () = msg_send![
+            window,
+            setStyleMask: mask
+                | NSWindowStyleMask::NSTitledWindowMask
+                | NSWindowStyleMask::NSFullSizeContentViewWindowMask
+        ];
+        let _: () = msg_send![
+            window,
+            setTitleVisibility: NSWindowTitleVisibility::NSWindowTitleHidden
+        ];
+        let _: () = msg_send![window, setTitlebarAppearsTransparent: YES];
+    };
+}
+
+/// Show a window above others without activating the app.
+pub fn show_front(window: &Window) {
+    window.set_visible(true);
+    unsafe {
+        let ns_window = window.ns_window() as id;
+        let _: () = msg_send![ns_window, orderFrontRegardless];
+    }
+}
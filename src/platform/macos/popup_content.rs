@@ -0,0 +1,274 @@
+/// Cocoa popup content: mic and camera icons with their labels in an NSStackView.
+use crate::icons::{popup_icon_color, rasterize_svg, svg, Glyph, IconState};
+use crate::mic::MicStatus;
+use crate::popup_content::{get_camera_description_text, get_mic_mute_description_text};
+use anyhow::{Context, Result};
+use cocoa::appkit::{NSColor, NSImage, NSImageView, NSTextField, NSView};
+use cocoa::base::{id, nil, NO, YES};
+use cocoa::foundation::{NSData, NSPoint, NSRect, NSSize, NSString};
+use objc::runtime::Object;
+use tao::dpi::LogicalSize;
+use tao::platform::macos::WindowExtMacOS;
+use tao::window::{Theme, Window};
+
+/// Vertically-centered 18pt-tall rect spanning the full width.
+/// Matches the original layout so the NSStackView stays at a fixed size
+/// and does not activate Auto Layout resizing on the window.
+fn get_frame_rect(size: LogicalSize<f64>) -> NSRect {
+    const LINE_HEIGHT: f64 = 18.;
+    NSRect::new(
+        NSPoint::new(0., (size.height - LINE_HEIGHT) / 2.),
+        NSSize::new(size.width, LINE_HEIGHT),
+    )
+}
+
+/// Text matches its icon's color so custom colors apply to both.
+fn get_text_color(state: IconState, theme: Theme) -> id {
+    let color = popup_icon_color(state, theme);
+    unsafe {
+        NSColor::colorWithRed_green_blue_alpha_(
+            nil,
+            color.r as f64 / 255.,
+            color.g as f64 / 255.,
+            color.b as f64 / 255.,
+            1.,
+        )
+    }
+}
+
+fn get_textfield(text: &str, color: id, frame: NSRect) -> id {
+    unsafe {
+        let label = NSTextField::alloc(nil);
+        let _: () = msg_send![label, initWithFrame: frame];
+        let label_str = NSString::alloc(nil).init_str(text);
+        label.setStringValue_(label_str);
+        let _: () = msg_send![label_str, release];
+        let _: () = msg_send![label, setTextColor: color];
+        let _: () = msg_send![label, setBezeled: NO];
+        let _: () = msg_send![label, setEditable: NO];
+        let _: () = msg_send![label, setDrawsBackground: NO];
+        let _: () = msg_send![label, setSelectable: NO];
+        const NSALIGNMENT_CENTER: i32 = 1;
+        let _: () = msg_send![label, setAlignment: NSALIGNMENT_CENTER];
+        let ns_font = class!(NSFont);
+        let default_size: f64 = msg_send![ns_font, systemFontSize];
+        let custom_font: *mut Object = msg_send![ns_font, systemFontOfSize: default_size + 3.0_f64];
+        let _: () = msg_send![label, setFont: custom_font];
+        label
+    }
+}
+
+/// Rasterizes an SVG and returns PNG-encoded bytes plus source dimensions.
+/// Uses the same NSData→NSImage path as the previous PNG-based approach.
+fn svg_to_png(svg_bytes: &[u8], state: IconState, theme: Theme) -> Result<(Vec<u8>, u32, u32)> {
+    let color = popup_icon_color(state, theme);
+    let (rgba, w, h) = rasterize_svg(svg_bytes, &color)?;
+    let img = image::RgbaImage::from_raw(w, h, rgba).context("Failed to create RgbaImage")?;
+    let mut png = Vec::new();
+    img.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .context("Failed to encode PNG")?;
+    Ok((png, w, h))
+}
+
+fn svg_to_ns_image(svg_bytes: &[u8], state: IconState, theme: Theme) -> Result<id> {
+    let (png, w, h) = svg_to_png(svg_bytes, state, theme)?;
+    const ICON_HEIGHT: f64 = 16.;
+    let icon_width = (w as f64) / (h as f64 / ICON_HEIGHT);
+    let ns_image = unsafe {
+        let nsdata = NSData::dataWithBytes_length_(
+            nil,
+            png.as_ptr() as *const std::os::raw::c_void,
+            png.len() as u64,
+        );
+        let ns_image = NSImage::initWithData_(NSImage::alloc(nil), nsdata);
+        let _: () = msg_send![ns_image, setSize: NSSize::new(icon_width, ICON_HEIGHT)];
+        let _: () = msg_send![ns_image, setTemplate: NO];
+        ns_image
+    };
+    Ok(ns_image)
+}
+
+fn get_mic_image(status: MicStatus, theme: Theme) -> Result<id> {
+    svg_to_ns_image(
+        &svg(Glyph::mic(status)),
+        IconState::for_status(status),
+        theme,
+    )
+}
+
+fn get_camera_image(muted: bool, theme: Theme) -> Result<id> {
+    svg_to_ns_image(
+        &svg(Glyph::video(muted)),
+        IconState::from_muted(muted),
+        theme,
+    )
+}
+
+fn make_image_view(image: id, frame: NSRect) -> id {
+    unsafe {
+        let view = NSImageView::alloc(nil);
+        let _: () = msg_send![view, initWithFrame: frame];
+        view.setImage_(image);
+        view
+    }
+}
+
+/// 1pt-wide vertical separator. Explicit size constraints tell the gravity-based
+/// NSStackView its width without activating the Auto Layout feedback loop that
+/// causes the window to grow.
+unsafe fn make_separator_view(line_height: f64) -> id {
+    let sep: id = msg_send![class!(NSView), alloc];
+    let sep: id = msg_send![sep, initWithFrame: NSRect::new(
+        NSPoint::new(0., 0.),
+        NSSize::new(1., line_height),
+    )];
+    let _: () = msg_send![sep, setWantsLayer: YES];
+    let layer: id = msg_send![sep, layer];
+    let color: id = msg_send![class!(NSColor), colorWithWhite: 0.5_f64 alpha: 0.5_f64];
+    let cg_color: *const std::os::raw::c_void = msg_send![color, CGColor];
+    let _: () = msg_send![layer, setBackgroundColor: cg_color];
+    // Size constraints without position constraints — NSStackView controls position.
+    let _: () = msg_send![sep, setTranslatesAutoresizingMaskIntoConstraints: NO];
+    let w: id = msg_send![class!(NSLayoutConstraint),
+        constraintWithItem: sep attribute: 7i64 relatedBy: 0i64
+        toItem: nil attribute: 0i64 multiplier: 1.0_f64 constant: 1.0_f64];
+    let _: () = msg_send![sep, addConstraint: w];
+    let h: id = msg_send![class!(NSLayoutConstraint),
+        constraintWithItem: sep attribute: 8i64 relatedBy: 0i64
+        toItem: nil attribute: 0i64 multiplier: 1.0_f64 constant: line_height];
+    let _: () = msg_send![sep, addConstraint: h];
+    sep
+}
+
+#[derive(Copy, Clone)]
+pub struct PopupContent {
+    mic_label: id,
+    mic_image: id,
+    separator: id,
+    camera_image: id,
+    camera_label: id,
+    view: id,
+    size: LogicalSize<f64>,
+}
+
+impl PopupContent {
+    pub fn new(
+        mic_status: MicStatus,
+        active_cameras: &[String],
+        size: LogicalSize<f64>,
+        theme: Theme,
+    ) -> Result<Self> {
+        let frame = get_frame_rect(size);
+        let camera_muted = active_cameras.is_empty();
+
+        let mic_label = get_textfield(
+            &get_mic_mute_description_text(mic_status),
+            get_text_color(IconState::for_status(mic_status), theme),
+            frame,
+        );
+        let mic_ns_image = get_mic_image(mic_status, theme)?;
+        let mic_image = make_image_view(mic_ns_image, frame);
+        unsafe {
+            let _: () = msg_send![mic_ns_image, release];
+        }
+        let camera_ns_image = get_camera_image(camera_muted, theme)?;
+        let camera_image = make_image_view(camera_ns_image, frame);
+        unsafe {
+            let _: () = msg_send![camera_ns_image, release];
+        }
+        let camera_label = get_textfield(
+            &get_camera_description_text(active_cameras),
+            get_text_color(IconState::from_muted(camera_muted), theme),
+            frame,
+        );
+
+        let separator = unsafe { make_separator_view(frame.size.height) };
+        let view = unsafe {
+            let stack: *mut Object = msg_send![class!(NSStackView), alloc];
+            let _: () = msg_send![stack, initWithFrame: frame];
+            const GRAVITY_CENTER: i32 = 2;
+            let _: () = msg_send![stack, addView: mic_image inGravity: GRAVITY_CENTER];
+            let _: () = msg_send![mic_image, release];
+            let _: () = msg_send![stack, addView: mic_label inGravity: GRAVITY_CENTER];
+            let _: () = msg_send![mic_label, release];
+            let _: () = msg_send![stack, addView: separator inGravity: GRAVITY_CENTER];
+            let _: () = msg_send![separator, release];
+            let _: () = msg_send![stack, addView: camera_image inGravity: GRAVITY_CENTER];
+            let _: () = msg_send![camera_image, release];
+            let _: () = msg_send![stack, addView: camera_label inGravity: GRAVITY_CENTER];
+            let _: () = msg_send![camera_label, release];
+            stack
+        };
+
+        Ok(Self {
+            mic_label,
+            mic_image,
+            separator,
+            camera_image,
+            camera_label,
+            view,
+            size,
+        })
+    }
+
+    /// Add the content to a window's view, which takes ownership of it.
+    pub fn attach(&self, window: &Window) {
+        unsafe {
+            let ns_view = window.ns_view() as id;
+            ns_view.addSubview_(self.view);
+            let _: () = msg_send![self.view, release];
+        }
+    }
+
+    /// Draw the content at its base size scaled up to fill the window. AppKit scales
+    /// the whole view through its bounds, so the scale itself isn't needed.
+    pub fn set_scale(&mut self, window: &Window, _scale: f64) {
+        unsafe {
+            let ns_view = window.ns_view() as id;
+            let _: () =
+                msg_send![ns_view, setBoundsSize: NSSize::new(self.size.width, self.size.height)];
+        }
+    }
+
+    /// Show or hide the camera half of the popup. Hidden views are detached from
+    /// the NSStackView layout, so the mic status stays centered.
+    pub fn set_camera_visible(&mut self, visible: bool) -> &mut Self {
+        let hidden = if visible { NO } else { YES };
+        unsafe {
+            let _: () = msg_send![self.separator, setHidden: hidden];
+            let _: () = msg_send![self.camera_image, setHidden: hidden];
+            let _: () = msg_send![self.camera_label, setHidden: hidden];
+        }
+        self
+    }
+
+    pub fn update(
+        &mut self,
+        mic_status: MicStatus,
+        active_cameras: &[String],
+        theme: Theme,
+        _active_device_name: Option<&str>,
+    ) -> Result<&mut Self> {
+        let camera_muted = active_cameras.is_empty();
+        let mic_img = get_mic_image(mic_status, theme)?;
+        let mic_color = get_text_color(IconState::for_status(mic_status), theme);
+        let camera_color = get_text_color(IconState::from_muted(camera_muted), theme);
+        let cam_img = get_camera_image(camera_muted, theme)?;
+        unsafe {
+            let mic_str = NSString::alloc(nil).init_str(&get_mic_mute_description_text(mic_status));
+            self.mic_label.setStringValue_(mic_str);
+            let _: () = msg_send![mic_str, release];
+            let _: () = msg_send![self.mic_label, setTextColor: mic_color];
+            self.mic_image.setImage_(mic_img);
+            let _: () = msg_send![mic_img, release];
+            self.camera_image.setImage_(cam_img);
+            let _: () = msg_send![cam_img, release];
+            let cam_str =
+                NSString::alloc(nil).init_str(&get_camera_description_text(active_cameras));
+            self.camera_label.setStringValue_(cam_str);
+            let _: () = msg_send![cam_str, release];
+            let _: () = msg_send![self.camera_label, setTextColor: camera_color];
+        }
+        Ok(self)
+    }
+}
@@ -0,0 +1,36 @@
+/// Power source detection with IOKit's power sources API.
+use cocoa::base::{id, nil, BOOL, NO, YES};
+use cocoa::foundation::NSString;
+use libc::c_void;
+
+#[link(name = "IOKit", kind = "framework")]
+extern "C" {
+    fn IOPSCopyPowerSourcesInfo() -> *const c_void;
+    fn IOPSGetProvidingPowerSourceType(snapshot: *const c_void) -> *const c_void;
+}
+
+extern "C" {
+    fn CFRelease(cf: *const c_void);
+}
+
+/// Returns true if the computer is running on battery power.
+pub fn on_battery_power() -> bool {
+    unsafe {
+        let snapshot = IOPSCopyPowerSourcesInfo();
+        if snapshot.is_null() {
+            return false;
+        }
+        // A CFString, toll-free bridged to NSString and owned by the snapshot
+        let source = IOPSGetProvidingPowerSourceType(snapshot) as id;
+        let on_battery: BOOL = if source.is_null() {
+            NO
+        } else {
+            let battery = NSString::alloc(nil).init_str("Battery Power");
+            let equal: BOOL = msg_send![source, isEqualToString: battery];
+            let _: () = msg_send![battery, release];
+            equal
+        };
+        CFRelease(snapshot);
+        on_battery == YES
+    }
+}
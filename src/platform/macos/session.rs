@@ -0,0 +1,126 @@
+/// Sleep, wake, screen lock and fast user switching notifications.
+/// NSWorkspace posts sleep, wake and session switches; screen lock is only posted to
+/// the distributed notification center. Both deliver on the main run loop.
+use crate::app_state::SessionEvent;
+use cocoa::base::{id, nil};
+use cocoa::foundation::NSString;
+use objc::declare::ClassDecl;
+use objc::runtime::{Object, Sel};
+use std::ffi::c_void;
+use std::sync::Once;
+
+const OBSERVER_CLASS: &str = "MicMuteSessionObserver";
+const CALLBACK_IVAR: &str = "callback";
+
+type Callback = Box<dyn Fn(SessionEvent) + Send>;
+
+/// Notification names and the event each one reports.
+const WORKSPACE_NOTIFICATIONS: [(&str, SessionEvent); 4] = [
+    ("NSWorkspaceWillSleepNotification", SessionEvent::Sleep),
+    ("NSWorkspaceDidWakeNotification", SessionEvent::Wake),
+    (
+        "NSWorkspaceSessionDidResignActiveNotification",
+        SessionEvent::SwitchedOut,
+    ),
+    (
+        "NSWorkspaceSessionDidBecomeActiveNotification",
+        SessionEvent::SwitchedIn,
+    ),
+];
+const SCREEN_NOTIFICATIONS: [(&str, SessionEvent); 2] = [
+    ("com.apple.screenIsLocked", SessionEvent::Lock),
+    ("com.apple.screenIsUnlocked", SessionEvent::Unlock),
+];
+
+unsafe fn report(this: &Object, event: SessionEvent) {
+    let callback = *this.get_ivar::<*mut c_void>(CALLBACK_IVAR) as *const Callback;
+    if let Some(callback) = callback.as_ref() {
+        callback(event);
+    }
+}
+
+extern "C" fn on_sleep(this: &Object, _: Sel, _notification: id) {
+    unsafe { report(this, SessionEvent::Sleep) }
+}
+
+extern "C" fn on_wake(this: &Object, _: Sel, _notification: id) {
+    unsafe { report(this, SessionEvent::Wake) }
+}
+
+extern "C" fn on_lock(this: &Object, _: Sel, _notification: id) {
+    unsafe { report(this, SessionEvent::Lock) }
+}
+
+extern "C" fn on_unlock(this: &Object, _: Sel, _notification: id) {
+    unsafe { report(this, SessionEvent::Unlock) }
+}
+
+extern "C" fn on_switched_out(this: &Object, _: Sel, _notification: id) {
+    unsafe { report(this, SessionEvent::SwitchedOut) }
+}
+
+extern "C" fn on_switched_in(this: &Object, _: Sel, _notification: id) {
+    unsafe { report(this, SessionEvent::SwitchedIn) }
+}
+
+fn selector(event: SessionEvent) -> Sel {
+    match event {
+        SessionEvent::Sleep => sel!(onSleep:),
+        SessionEvent::Wake => sel!(onWake:),
+        SessionEvent::Lock => sel!(onLock:),
+        SessionEvent::Unlock => sel!(onUnlock:),
+        SessionEvent::SwitchedOut => sel!(onSwitchedOut:),
+        SessionEvent::SwitchedIn => sel!(onSwitchedIn:),
+    }
+}
+
+/// Objective-C class that forwards each notification to the callback as an event.
+fn observer_class() -> &'static objc::runtime::Class {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| {
+        let mut decl = ClassDecl::new(OBSERVER_CLASS, class!(NSObject))
+            .expect("Session observer class already registered");
+        decl.add_ivar::<*mut c_void>(CALLBACK_IVAR);
+        type Handler = extern "C" fn(&Object, Sel, id);
+        unsafe {
+            decl.add_method(sel!(onSleep:), on_sleep as Handler);
+            decl.add_method(sel!(onWake:), on_wake as Handler);
+            decl.add_method(sel!(onLock:), on_lock as Handler);
+            decl.add_method(sel!(onUnlock:), on_unlock as Handler);
+            decl.add_method(sel!(onSwitchedOut:), on_switched_out as Handler);
+            decl.add_method(sel!(onSwitchedIn:), on_switched_in as Handler);
+        }
+        decl.register();
+    });
+    objc::runtime::Class::get(OBSERVER_CLASS).unwrap()
+}
+
+unsafe fn observe(center: id, observer: id, notifications: &[(&str, SessionEvent)]) {
+    for (name, event) in notifications {
+        let name = NSString::alloc(nil).init_str(name);
+        let _: () = msg_send![center,
+            addObserver: observer
+            selector: selector(*event)
+            name: name
+            object: nil];
+        let _: () = msg_send![name, release];
+    }
+}
+
+/// Report session changes to `callback` for the rest of the process. Must be called
+/// on the main thread, whose run loop delivers the notifications.
+pub fn watch_session(callback: impl Fn(SessionEvent) + Send + 'static) {
+    unsafe {
+        // The observer and callback live as long as the app, so both are leaked
+        let observer: id = msg_send![observer_class(), new];
+        let callback: Box<Callback> = Box::new(Box::new(callback));
+        (*observer).set_ivar(CALLBACK_IVAR, Box::into_raw(callback) as *mut c_void);
+
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let workspace_center: id = msg_send![workspace, notificationCenter];
+        observe(workspace_center, observer, &WORKSPACE_NOTIFICATIONS);
+        let distributed_center: id =
+            msg_send![class!(NSDistributedNotificationCenter), defaultCenter];
+        observe(distributed_center, observer, &SCREEN_NOTIFICATIONS);
+    }
+}
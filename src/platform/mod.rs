@@ -0,0 +1,12 @@
+/// Platform layer.
+/// Everything that talks to the native toolkit lives here: Cocoa on macOS and GTK on
+/// Linux. The rest of the app works with tao windows and these functions and types.
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "macos")]
+mod macos;
+
+#[cfg(target_os = "linux")]
+pub use linux::*;
+#[cfg(target_os = "macos")]
+pub use macos::*;
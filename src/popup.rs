@@ -1,29 +1,29 @@
-use crate::event_loop::EventLoopMessage;
+use crate::event_loop::Message;
+use crate::i18n::tr;
+use crate::mic::MicStatus;
+use crate::platform;
 use crate::popup_content::PopupContent;
-use crate::utils::get_cursor_pos;
+use crate::settings::{PopupAnchor, PopupMonitors, PopupSettings};
 use anyhow::{Context, Result};
-use cocoa::{
-    appkit::{NSView, NSWindow, NSWindowStyleMask, NSWindowTitleVisibility},
-    base::{id, YES},
-};
 use log::trace;
 use tao::{
     dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize},
+    event_loop::EventLoopWindowTarget,
     monitor::MonitorHandle,
-    platform::macos::{WindowBuilderExtMacOS, WindowExtMacOS},
     window::{Theme, Window, WindowBuilder},
 };
 
-const MUTED_TITLE: &str = "Muted";
-const UNMUTED_TITLE: &str = "Unmuted";
+/// Unscaled popup size. Content is laid out at this size and scaled with the window.
+const BASE_WIDTH: f64 = 250.;
+const BASE_HEIGHT: f64 = 40.;
 
 pub type WindowSize<T = f64> = LogicalSize<T>;
 
-fn get_mute_title_text(muted: bool) -> &'static str {
+fn get_mute_title_text(muted: bool) -> String {
     if muted {
-        MUTED_TITLE
+        tr("popup-muted-title")
     } else {
-        UNMUTED_TITLE
+        tr("popup-unmuted-title")
     }
 }
 
@@ -38,43 +38,70 @@ fn monitor_contains_physical_position(
         && position.y < monitor_position.y + monitor_size.height
 }
 
-fn setup_window(window: id) {
-    unsafe {
-        window.setHasShadow_(true);
-        // Rounded edges hack: https://stackoverflow.com/a/37418915
-        let mask = window.styleMask();
-        let _: () = msg_send![
-            window,
-            setStyleMask: mask
-                | NSWindowStyleMask::NSTitledWindowMask
-                | NSWindowStyleMask::NSFullSizeContentViewWindowMask
-        ];
-        let _: () = msg_send![
-            window,
-            setTitleVisibility: NSWindowTitleVisibility::NSWindowTitleHidden
-        ];
-        let _: () = msg_send![window, setTitlebarAppearsTransparent: YES];
+fn get_size(scale: f64) -> WindowSize {
+    LogicalSize::new(BASE_WIDTH * scale, BASE_HEIGHT * scale)
+}
+
+/// Place a window of `window_size` at `anchor` within a monitor's logical bounds.
+/// Edges are inset by the window height, which keeps the default bottom anchor where
+/// the popup has always been.
+fn anchored_position(
+    monitor_position: LogicalPosition<f64>,
+    monitor_size: LogicalSize<f64>,
+    window_size: WindowSize,
+    settings: &PopupSettings,
+) -> LogicalPosition<f64> {
+    let margin = window_size.height;
+    let left = monitor_position.x + margin;
+    let center_x = monitor_position.x + (monitor_size.width - window_size.width) / 2.;
+    let right = monitor_position.x + monitor_size.width - window_size.width - margin;
+    let top = monitor_position.y + margin;
+    let center_y = monitor_position.y + (monitor_size.height - window_size.height) / 2.;
+    let bottom = monitor_position.y + monitor_size.height - window_size.height - margin;
+
+    let (x, y) = match settings.anchor {
+        PopupAnchor::TopLeft => (left, top),
+        PopupAnchor::Top => (center_x, top),
+        PopupAnchor::TopRight => (right, top),
+        PopupAnchor::Left => (left, center_y),
+        PopupAnchor::Right => (right, center_y),
+        PopupAnchor::BottomLeft => (left, bottom),
+        PopupAnchor::Bottom => (center_x, bottom),
+        PopupAnchor::BottomRight => (right, bottom),
     };
+    LogicalPosition::new(x + settings.offset_x, y + settings.offset_y)
 }
 
-pub struct Popup {
+fn get_position(
+    monitor: &MonitorHandle,
+    window_size: WindowSize,
+    settings: &PopupSettings,
+) -> LogicalPosition<f64> {
+    let scale = monitor.scale_factor();
+    let monitor_position = monitor.position().to_logical::<f64>(scale);
+    let monitor_size = monitor.size().to_logical::<f64>(scale);
+    anchored_position(monitor_position, monitor_size, window_size, settings)
+}
+
+/// One popup window and its content, pinned to a monitor.
+struct PopupWindow {
     window: Window,
     content: PopupContent,
-    current_monitor: Option<MonitorHandle>,
+    monitor: Option<MonitorHandle>,
 }
 
-impl Popup {
-    pub fn new(event_loop: &EventLoopMessage, mic_muted: bool) -> Result<Self> {
-        let camera_muted = false;
-        let initial_monitor = Popup::get_initial_monitor(event_loop);
-        let size = Popup::get_size();
-        let scale = initial_monitor
-            .as_ref()
-            .map_or(1.0, MonitorHandle::scale_factor);
-        let mut builder = WindowBuilder::new()
+impl PopupWindow {
+    fn new(
+        event_loop: &EventLoopWindowTarget<Message>,
+        monitor: Option<MonitorHandle>,
+        mic_muted: bool,
+        mic_status: MicStatus,
+        active_cameras: &[String],
+        settings: &PopupSettings,
+    ) -> Result<Self> {
+        let size = get_size(settings.clamped_scale());
+        let mut builder = platform::popup_window_builder(WindowBuilder::new())
             .with_title(get_mute_title_text(mic_muted))
-            .with_titlebar_hidden(true)
-            .with_movable_by_window_background(true)
             .with_always_on_top(true)
             .with_closable(false)
             .with_content_protection(true)
@@ -84,10 +111,10 @@ impl Popup {
             .with_minimizable(false)
             .with_resizable(false)
             .with_visible_on_all_workspaces(true)
-            .with_visible(false)
-            .with_has_shadow(true);
-        if let Some(monitor) = initial_monitor.as_ref() {
-            builder = builder.with_position(Popup::get_position(monitor, size));
+            .with_visible(false);
+        if let Some(monitor) = monitor.as_ref() {
+            trace!("Window scale factor {}", monitor.scale_factor());
+            builder = builder.with_position(get_position(monitor, size, settings));
         }
         let window = builder
             .build(event_loop)
@@ -95,98 +122,211 @@ impl Popup {
         window.set_visible(false);
         window.set_ignore_cursor_events(true)?;
 
-        trace!("Window scale factor {}", scale);
-        let content = PopupContent::new(mic_muted, camera_muted, size, window.theme())?;
-        unsafe {
-            let ns_view = window.ns_view() as id;
-            ns_view.addSubview_(content.view);
-            let _: () = msg_send![content.view, release];
-            let ns_window = window.ns_window() as id;
-            setup_window(ns_window);
-        };
+        let base_size = get_size(1.);
+        let mut content = PopupContent::new(mic_status, active_cameras, base_size, window.theme())?;
+        content.set_camera_visible(settings.show_camera);
+        content.attach(&window);
+        platform::setup_popup_window(&window);
 
-        let popup = Self {
+        let mut popup_window = Self {
             window,
             content,
-            current_monitor: initial_monitor,
+            monitor,
         };
-        Ok(popup)
+        popup_window.scale_content(settings);
+        Ok(popup_window)
+    }
+
+    /// Draw the content at its base size scaled up to the window's size.
+    fn scale_content(&mut self, settings: &PopupSettings) {
+        self.content
+            .set_scale(&self.window, settings.clamped_scale());
+    }
+
+    fn place(&mut self, monitor: MonitorHandle, settings: &PopupSettings) {
+        let monitor_changed = self.monitor.as_ref() != Some(&monitor);
+        let was_visible = monitor_changed && self.window.is_visible();
+        if was_visible {
+            self.window.set_visible(false);
+        }
+
+        let size = get_size(settings.clamped_scale());
+        self.window.set_inner_size(size);
+        self.scale_content(settings);
+        self.window
+            .set_outer_position(get_position(&monitor, size, settings));
+        self.monitor = Some(monitor);
+
+        if was_visible {
+            self.show_front();
+        }
     }
 
-    fn get_size() -> WindowSize {
-        LogicalSize::new(250., 40.)
+    fn show_front(&self) {
+        platform::show_front(&self.window);
+    }
+}
+
+pub struct Popup {
+    windows: Vec<PopupWindow>,
+    settings: PopupSettings,
+    mic_muted: bool,
+    mic_status: MicStatus,
+    /// Names of the cameras in use.
+    active_cameras: Vec<String>,
+    visible: bool,
+}
+
+impl Popup {
+    pub fn new(
+        event_loop: &EventLoopWindowTarget<Message>,
+        mic_muted: bool,
+        mic_status: MicStatus,
+        active_cameras: &[String],
+        settings: &PopupSettings,
+    ) -> Result<Self> {
+        let initial_monitor = event_loop.primary_monitor();
+        let window = PopupWindow::new(
+            event_loop,
+            initial_monitor,
+            mic_muted,
+            mic_status,
+            active_cameras,
+            settings,
+        )?;
+        let mut popup = Self {
+            windows: vec![window],
+            settings: settings.clone(),
+            mic_muted,
+            mic_status,
+            active_cameras: active_cameras.to_vec(),
+            visible: false,
+        };
+        popup.set_visible(settings.visible_at_rest(mic_muted));
+        Ok(popup)
     }
 
     pub fn get_theme(&self) -> Theme {
-        self.window.theme()
+        self.windows[0].window.theme()
     }
 
+    /// Refresh the popup content. Visibility is left to `set_visible`.
     pub fn update_with_camera(
         &mut self,
         mic_muted: bool,
-        camera_muted: bool,
+        mic_status: MicStatus,
+        active_cameras: &[String],
         active_device_name: Option<&str>,
     ) -> Result<&mut Self> {
-        self.window.set_title(get_mute_title_text(mic_muted));
+        self.mic_muted = mic_muted;
+        self.mic_status = mic_status;
+        self.active_cameras = active_cameras.to_vec();
         self.update_placement()?;
-        self.content.update(
-            mic_muted,
-            camera_muted,
-            self.get_theme(),
-            active_device_name,
-        )?;
-        if mic_muted {
-            self.show_front();
+        let theme = self.get_theme();
+        for popup_window in &mut self.windows {
+            popup_window
+                .window
+                .set_title(&get_mute_title_text(mic_muted));
+            popup_window
+                .content
+                .update(mic_status, active_cameras, theme, active_device_name)?;
         }
         Ok(self)
     }
 
-    pub fn hide(&mut self) -> Result<&mut Self> {
-        self.window.set_visible(false);
+    pub fn apply_settings(&mut self, settings: &PopupSettings) -> Result<&mut Self> {
+        if *settings == self.settings {
+            return Ok(self);
+        }
+        self.settings = settings.clone();
+        for popup_window in &mut self.windows {
+            popup_window
+                .content
+                .set_camera_visible(settings.show_camera);
+        }
+        if settings.monitors == PopupMonitors::FollowCursor {
+            self.windows.truncate(1);
+        }
+        // Force placement to recompute size and position for every window
+        for popup_window in &mut self.windows {
+            if let Some(monitor) = popup_window.monitor.take() {
+                popup_window.place(monitor, settings);
+            }
+        }
         Ok(self)
     }
 
-    pub fn update_placement(&mut self) -> Result<&mut Self> {
-        if let Some(monitor) = self.get_current_monitor()? {
-            let monitor_changed = self.current_monitor.as_ref() != Some(&monitor);
-            let was_visible = monitor_changed && self.window.is_visible();
-            if was_visible {
-                self.window.set_visible(false);
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+        for popup_window in &self.windows {
+            if visible {
+                popup_window.show_front();
+            } else {
+                popup_window.window.set_visible(false);
             }
+        }
+    }
 
-            let size = Popup::get_size();
-            self.window.set_inner_size(size);
-            self.window
-                .set_outer_position(Popup::get_position(&monitor, size));
-            self.current_monitor = Some(monitor);
-
-            if was_visible {
-                self.show_front();
+    pub fn update_placement(&mut self) -> Result<&mut Self> {
+        match self.settings.monitors {
+            PopupMonitors::FollowCursor => {
+                if let Some(monitor) = self.get_current_monitor()? {
+                    self.windows[0].place(monitor, &self.settings);
+                }
+            }
+            PopupMonitors::All => {
+                let monitors: Vec<_> = self.windows[0].window.available_monitors().collect();
+                for (popup_window, monitor) in self.windows.iter_mut().zip(monitors) {
+                    popup_window.place(monitor, &self.settings);
+                }
             }
         }
         Ok(self)
     }
 
-    pub fn detect_cursor_monitor(&mut self) -> Result<&mut Self> {
+    /// Track monitor changes: follow the cursor, or keep one window per monitor.
+    pub fn detect_monitors(
+        &mut self,
+        event_loop: &EventLoopWindowTarget<Message>,
+    ) -> Result<&mut Self> {
+        if self.settings.monitors == PopupMonitors::All {
+            let monitor_count = event_loop.available_monitors().count().max(1);
+            self.windows.truncate(monitor_count);
+            while self.windows.len() < monitor_count {
+                trace!("Adding popup window for monitor {}", self.windows.len());
+                let popup_window = PopupWindow::new(
+                    event_loop,
+                    None,
+                    self.mic_muted,
+                    self.mic_status,
+                    &self.active_cameras,
+                    &self.settings,
+                )?;
+                if self.visible {
+                    popup_window.show_front();
+                }
+                self.windows.push(popup_window);
+            }
+        }
         self.update_placement()
     }
 
     fn get_current_monitor(&self) -> Result<Option<MonitorHandle>> {
-        // CoreGraphics and `Window::monitor_from_point` both use the same global
-        // display coordinate space on macOS. Prefer this path over
+        let window = &self.windows[0].window;
+        // The platform cursor position and `Window::monitor_from_point` share the
+        // same global display coordinate space. Prefer this path over
         // `Window::cursor_position`, which converts through the primary display's
         // scale factor and can misclassify points near monitor boundaries.
-        if let Some((x, y)) = get_cursor_pos() {
-            if let Some(monitor) = self.window.monitor_from_point(x, y) {
+        if let Some((x, y)) = platform::cursor_position() {
+            if let Some(monitor) = window.monitor_from_point(x, y) {
                 return Ok(Some(monitor));
             }
         }
 
-        let position = self
-            .window
+        let position = window
             .cursor_position()
             .context("Failed to read cursor position")?;
-        if let Some(monitor) = self.window.monitor_from_point(position.x, position.y) {
+        if let Some(monitor) = window.monitor_from_point(position.x, position.y) {
             return Ok(Some(monitor));
         }
 
@@ -197,7 +337,7 @@ impl Popup {
         &self,
         position: PhysicalPosition<f64>,
     ) -> Option<MonitorHandle> {
-        self.window.available_monitors().find(|monitor| {
+        self.windows[0].window.available_monitors().find(|monitor| {
             monitor_contains_physical_position(
                 position,
                 monitor.position().cast::<f64>(),
@@ -205,27 +345,6 @@ impl Popup {
             )
         })
     }
-
-    fn get_initial_monitor(event_loop: &EventLoopMessage) -> Option<MonitorHandle> {
-        event_loop.primary_monitor()
-    }
-
-    fn show_front(&self) {
-        self.window.set_visible(true);
-        unsafe {
-            let ns_window = self.window.ns_window() as id;
-            let _: () = msg_send![ns_window, orderFrontRegardless];
-        }
-    }
-
-    fn get_position(monitor: &MonitorHandle, window_size: WindowSize) -> LogicalPosition<f64> {
-        let scale = monitor.scale_factor();
-        let monitor_position = monitor.position().to_logical::<f64>(scale);
-        let monitor_size = monitor.size().to_logical::<f64>(scale);
-        let x: f64 = (monitor_position.x + (monitor_size.width / 2.)) - (window_size.width / 2.);
-        let y: f64 = (monitor_position.y + monitor_size.height) - (window_size.height * 2.);
-        LogicalPosition::new(x, y)
-    }
 }
 
 #[cfg(test)]
@@ -265,4 +384,34 @@ mod tests {
             monitor_size
         ));
     }
+
+    #[test]
+    fn default_anchor_is_bottom_center_one_popup_height_above_the_edge() {
+        let position = anchored_position(
+            LogicalPosition::new(0.0, 0.0),
+            LogicalSize::new(1440.0, 900.0),
+            get_size(1.),
+            &PopupSettings::default(),
+        );
+
+        assert_eq!(position, LogicalPosition::new(595.0, 820.0));
+    }
+
+    #[test]
+    fn anchors_apply_offsets_and_monitor_origin() {
+        let settings = PopupSettings {
+            anchor: PopupAnchor::TopRight,
+            offset_x: -10.0,
+            offset_y: 5.0,
+            ..PopupSettings::default()
+        };
+        let position = anchored_position(
+            LogicalPosition::new(-1440.0, 0.0),
+            LogicalSize::new(1440.0, 900.0),
+            get_size(2.),
+            &settings,
+        );
+
+        assert_eq!(position, LogicalPosition::new(-590.0, 85.0));
+    }
 }
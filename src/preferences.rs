@@ -0,0 +1,466 @@
+/// Preferences window for the app.
+/// Edits the same settings as settings.json through native controls hosted in a tao
+/// window, so nobody needs to hand-edit the file.
+use crate::event_loop::{EventLoopProxyMessage, Message};
+use crate::i18n::{tr, tr_args};
+use crate::platform::{self, Checkbox, Form, Label};
+use crate::settings::{Settings, ShortcutConfig};
+use anyhow::{Context, Result};
+use global_hotkey::hotkey::HotKey;
+use log::trace;
+use tao::dpi::LogicalSize;
+use tao::event::{ElementState, WindowEvent};
+use tao::event_loop::EventLoopWindowTarget;
+use tao::keyboard::{KeyCode, ModifiersState};
+use tao::window::{Window, WindowBuilder, WindowId};
+
+const WINDOW_WIDTH: f64 = 420.;
+
+const TAG_RECORD_SHORTCUT: i64 = 1;
+const TAG_RESET_SHORTCUT: i64 = 2;
+const TAG_SHOW_CAMERA: i64 = 3;
+const TAG_LAUNCH_AT_LOGIN: i64 = 4;
+const TAG_SHOW_IN_DOCK: i64 = 5;
+const TAG_OPEN_SETTINGS_FILE: i64 = 6;
+const TAG_MUTE_ON_LOCK: i64 = 7;
+const TAG_MUTE_ON_SLEEP: i64 = 8;
+const TAG_RESTORE_ON_UNLOCK: i64 = 9;
+/// Device checkboxes are tagged with this base plus their index in the device list.
+const TAG_DEVICE_BASE: i64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    RecordShortcut,
+    ResetShortcut,
+    ToggleDevice(usize),
+    ToggleShowCamera,
+    ToggleMuteOnLock,
+    ToggleMuteOnSleep,
+    ToggleRestoreOnUnlock,
+    ToggleLaunchAtLogin,
+    ToggleShowInDock,
+    OpenSettingsFile,
+}
+
+fn action_from_tag(tag: i64) -> Option<Action> {
+    match tag {
+        TAG_RECORD_SHORTCUT => Some(Action::RecordShortcut),
+        TAG_RESET_SHORTCUT => Some(Action::ResetShortcut),
+        TAG_SHOW_CAMERA => Some(Action::ToggleShowCamera),
+        TAG_MUTE_ON_LOCK => Some(Action::ToggleMuteOnLock),
+        TAG_MUTE_ON_SLEEP => Some(Action::ToggleMuteOnSleep),
+        TAG_RESTORE_ON_UNLOCK => Some(Action::ToggleRestoreOnUnlock),
+        TAG_LAUNCH_AT_LOGIN => Some(Action::ToggleLaunchAtLogin),
+        TAG_SHOW_IN_DOCK => Some(Action::ToggleShowInDock),
+        TAG_OPEN_SETTINGS_FILE => Some(Action::OpenSettingsFile),
+        tag if tag >= TAG_DEVICE_BASE => {
+            Some(Action::ToggleDevice((tag - TAG_DEVICE_BASE) as usize))
+        }
+        _ => None,
+    }
+}
+
+/// Flip a device between included (muted by Mic Mute) and excluded.
+fn toggle_device(settings: &mut Settings, name: &str) {
+    if let Some(index) = settings.excluded_devices.iter().position(|d| d == name) {
+        settings.excluded_devices.remove(index);
+    } else {
+        settings.excluded_devices.push(name.to_string());
+    }
+}
+
+/// Connected devices followed by excluded devices that aren't currently connected,
+/// so a disconnected device can still be re-included.
+fn device_list(connected: Vec<String>, excluded: &[String]) -> Vec<String> {
+    let mut devices = connected;
+    for name in excluded {
+        if !devices.contains(name) {
+            devices.push(name.clone());
+        }
+    }
+    devices
+}
+
+/// Convert a recorded key press into a shortcut. Returns None for bare modifier keys
+/// and keys global-hotkey can't register.
+fn shortcut_from_key(code: KeyCode, modifiers: ModifiersState) -> Option<ShortcutConfig> {
+    if matches!(
+        code,
+        KeyCode::ShiftLeft
+            | KeyCode::ShiftRight
+            | KeyCode::ControlLeft
+            | KeyCode::ControlRight
+            | KeyCode::AltLeft
+            | KeyCode::AltRight
+            | KeyCode::SuperLeft
+            | KeyCode::SuperRight
+            | KeyCode::CapsLock
+            | KeyCode::Fn
+    ) {
+        return None;
+    }
+    let name = code.to_string();
+    let key = name
+        .strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+        .unwrap_or(&name)
+        .to_string();
+    key.parse::<HotKey>().ok()?;
+
+    let mut mods = vec![];
+    if modifiers.shift_key() {
+        mods.push("shift".to_string());
+    }
+    if modifiers.control_key() {
+        mods.push("ctrl".to_string());
+    }
+    if modifiers.alt_key() {
+        mods.push("alt".to_string());
+    }
+    if modifiers.super_key() {
+        mods.push("meta".to_string());
+    }
+    Some(ShortcutConfig {
+        modifiers: mods,
+        key,
+    })
+}
+
+fn shortcut_text(shortcut: &ShortcutConfig, recording: bool) -> String {
+    if recording {
+        tr("preferences-shortcut-recording")
+    } else {
+        tr_args(
+            "preferences-shortcut-current",
+            &[("shortcut", &shortcut.to_string())],
+        )
+    }
+}
+
+pub struct Preferences {
+    window: Window,
+    /// Owns the native control callbacks for as long as the window is open.
+    _form: Form,
+    shortcut_label: Label,
+    show_camera: Checkbox,
+    mute_on_lock: Checkbox,
+    mute_on_sleep: Checkbox,
+    restore_on_unlock: Checkbox,
+    launch_at_login: Checkbox,
+    show_in_dock: Option<Checkbox>,
+    device_checkboxes: Vec<Checkbox>,
+    devices: Vec<String>,
+    recording: bool,
+    modifiers: ModifiersState,
+}
+
+impl Preferences {
+    pub fn new(
+        event_loop: &EventLoopWindowTarget<Message>,
+        proxy: EventLoopProxyMessage,
+        settings: &Settings,
+        connected_devices: Vec<String>,
+    ) -> Result<Self> {
+        let devices = device_list(connected_devices, &settings.excluded_devices);
+        // Shortcut, microphones, popup, lock and general sections plus the footer.
+        let general_rows = if platform::HAS_DOCK { 3 } else { 2 };
+        let rows = 2 + 1 + devices.len().max(1) + 2 + 4 + general_rows + 2;
+        let height = Form::height(rows, 5);
+
+        let window = WindowBuilder::new()
+            .with_title(tr("preferences-title"))
+            .with_inner_size(LogicalSize::new(WINDOW_WIDTH, height))
+            .with_resizable(false)
+            .with_minimizable(false)
+            .with_maximized(false)
+            .build(event_loop)
+            .context("Failed to build preferences window")?;
+
+        let mut form = Form::new(&window, proxy, WINDOW_WIDTH, height);
+
+        form.heading(&tr("preferences-shortcut"));
+        let shortcut_label = form.label_with_buttons(
+            &shortcut_text(&settings.mic_shortcut, false),
+            &[
+                (&tr("preferences-record"), TAG_RECORD_SHORTCUT),
+                (&tr("preferences-reset"), TAG_RESET_SHORTCUT),
+            ],
+        );
+        form.gap();
+
+        form.heading(&tr("preferences-microphones"));
+        let device_checkboxes = devices
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                form.checkbox(
+                    name,
+                    TAG_DEVICE_BASE + index as i64,
+                    !settings.excluded_devices.contains(name),
+                )
+            })
+            .collect();
+        if devices.is_empty() {
+            form.label(&tr("preferences-no-devices"));
+        }
+        form.gap();
+
+        form.heading(&tr("preferences-popup"));
+        let show_camera = form.checkbox(
+            &tr("preferences-show-camera"),
+            TAG_SHOW_CAMERA,
+            settings.popup.show_camera,
+        );
+        form.gap();
+
+        form.heading(&tr("preferences-session"));
+        let mute_on_lock = form.checkbox(
+            &tr("preferences-mute-on-lock"),
+            TAG_MUTE_ON_LOCK,
+            settings.mute_on_lock,
+        );
+        let mute_on_sleep = form.checkbox(
+            &tr("preferences-mute-on-sleep"),
+            TAG_MUTE_ON_SLEEP,
+            settings.mute_on_sleep,
+        );
+        let restore_on_unlock = form.checkbox(
+            &tr("preferences-restore-on-unlock"),
+            TAG_RESTORE_ON_UNLOCK,
+            settings.restore_on_unlock,
+        );
+        form.gap();
+
+        form.heading(&tr("preferences-general"));
+        let launch_at_login = form.checkbox(
+            &tr("preferences-launch-at-login"),
+            TAG_LAUNCH_AT_LOGIN,
+            settings.launch_at_login,
+        );
+        let show_in_dock = platform::HAS_DOCK.then(|| {
+            form.checkbox(
+                &tr("preferences-show-in-dock"),
+                TAG_SHOW_IN_DOCK,
+                settings.show_in_dock,
+            )
+        });
+        form.gap();
+
+        let version = env!("CARGO_PKG_VERSION");
+        form.label(&tr_args("preferences-version", &[("version", version)]));
+        form.button(&tr("preferences-open-settings"), TAG_OPEN_SETTINGS_FILE);
+
+        let preferences = Self {
+            window,
+            _form: form,
+            shortcut_label,
+            show_camera,
+            mute_on_lock,
+            mute_on_sleep,
+            restore_on_unlock,
+            launch_at_login,
+            show_in_dock,
+            device_checkboxes,
+            devices,
+            recording: false,
+            modifiers: ModifiersState::empty(),
+        };
+        preferences.focus();
+        trace!("Preferences window opened");
+        Ok(preferences)
+    }
+
+    pub fn window_id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    /// Bring the window to the front, activating the app even when it has no Dock icon.
+    pub fn focus(&self) {
+        self.window.set_visible(true);
+        self.window.set_focus();
+        platform::activate_app();
+    }
+
+    /// Refresh controls from settings, e.g. after settings.json changed on disk.
+    pub fn sync(&mut self, settings: &Settings) {
+        if !self.recording {
+            self.shortcut_label
+                .set_text(&shortcut_text(&settings.mic_shortcut, false));
+        }
+        self.show_camera.set_checked(settings.popup.show_camera);
+        self.mute_on_lock.set_checked(settings.mute_on_lock);
+        self.mute_on_sleep.set_checked(settings.mute_on_sleep);
+        self.restore_on_unlock
+            .set_checked(settings.restore_on_unlock);
+        self.launch_at_login.set_checked(settings.launch_at_login);
+        if let Some(show_in_dock) = &self.show_in_dock {
+            show_in_dock.set_checked(settings.show_in_dock);
+        }
+        for (checkbox, name) in self.device_checkboxes.iter().zip(&self.devices) {
+            checkbox.set_checked(!settings.excluded_devices.contains(name));
+        }
+    }
+
+    /// Handle a control action. Returns true if settings changed and should be saved.
+    pub fn handle_action(&mut self, tag: i64, settings: &mut Settings) -> bool {
+        let Some(action) = action_from_tag(tag) else {
+            return false;
+        };
+        trace!("Preferences action {:?}", action);
+        match action {
+            Action::RecordShortcut => {
+                self.set_recording(true, settings);
+                false
+            }
+            Action::ResetShortcut => {
+                self.set_recording(false, settings);
+                settings.mic_shortcut = ShortcutConfig::default();
+                true
+            }
+            Action::ToggleDevice(index) => match self.devices.get(index) {
+                Some(name) => {
+                    toggle_device(settings, name);
+                    true
+                }
+                None => false,
+            },
+            Action::ToggleShowCamera => {
+                settings.popup.show_camera = !settings.popup.show_camera;
+                true
+            }
+            Action::ToggleMuteOnLock => {
+                settings.mute_on_lock = !settings.mute_on_lock;
+                true
+            }
+            Action::ToggleMuteOnSleep => {
+                settings.mute_on_sleep = !settings.mute_on_sleep;
+                true
+            }
+            Action::ToggleRestoreOnUnlock => {
+                settings.restore_on_unlock = !settings.restore_on_unlock;
+                true
+            }
+            Action::ToggleLaunchAtLogin => {
+                settings.launch_at_login = !settings.launch_at_login;
+                true
+            }
+            Action::ToggleShowInDock => {
+                settings.show_in_dock = !settings.show_in_dock;
+                true
+            }
+            Action::OpenSettingsFile => {
+                if let Some(path) = Settings::config_path() {
+                    if let Err(e) = platform::open_file(&path) {
+                        log::error!("{:#}", e);
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    /// Handle a window event for this window. Returns true if settings changed.
+    pub fn handle_window_event(&mut self, event: &WindowEvent, settings: &mut Settings) -> bool {
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+                false
+            }
+            WindowEvent::KeyboardInput { event, .. }
+                if self.recording && event.state == ElementState::Pressed =>
+            {
+                if event.physical_key == KeyCode::Escape {
+                    self.set_recording(false, settings);
+                    return false;
+                }
+                match shortcut_from_key(event.physical_key, self.modifiers) {
+                    Some(shortcut) => {
+                        trace!("Recorded shortcut {}", shortcut);
+                        settings.mic_shortcut = shortcut;
+                        self.set_recording(false, settings);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    fn set_recording(&mut self, recording: bool, settings: &Settings) {
+        self.recording = recording;
+        self.shortcut_label
+            .set_text(&shortcut_text(&settings.mic_shortcut, recording));
+    }
+}
+
+impl Drop for Preferences {
+    fn drop(&mut self) {
+        self.window.set_visible(false);
+        trace!("Preferences window closed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_from_tag() {
+        assert_eq!(
+            action_from_tag(TAG_SHOW_IN_DOCK),
+            Some(Action::ToggleShowInDock)
+        );
+        assert_eq!(
+            action_from_tag(TAG_DEVICE_BASE + 2),
+            Some(Action::ToggleDevice(2))
+        );
+        assert_eq!(action_from_tag(0), None);
+    }
+
+    #[test]
+    fn test_toggle_device() {
+        let mut settings = Settings::default();
+
+        toggle_device(&mut settings, "Capture Card");
+        assert_eq!(settings.excluded_devices, vec!["Capture Card"]);
+
+        toggle_device(&mut settings, "Capture Card");
+        assert!(settings.excluded_devices.is_empty());
+    }
+
+    #[test]
+    fn test_device_list_keeps_disconnected_excluded_devices() {
+        let devices = device_list(
+            vec!["Built-in".to_string(), "Capture Card".to_string()],
+            &["Capture Card".to_string(), "USB Mic".to_string()],
+        );
+        assert_eq!(devices, vec!["Built-in", "Capture Card", "USB Mic"]);
+    }
+
+    #[test]
+    fn test_shortcut_from_key() {
+        let shortcut =
+            shortcut_from_key(KeyCode::KeyM, ModifiersState::SHIFT | ModifiersState::SUPER)
+                .unwrap();
+        assert_eq!(shortcut.key, "M");
+        assert_eq!(shortcut.modifiers, vec!["shift", "meta"]);
+
+        let shortcut = shortcut_from_key(KeyCode::F13, ModifiersState::empty()).unwrap();
+        assert_eq!(shortcut.key, "F13");
+        assert!(shortcut.modifiers.is_empty());
+
+        assert_eq!(
+            shortcut_from_key(KeyCode::Digit1, ModifiersState::CONTROL)
+                .unwrap()
+                .key,
+            "1"
+        );
+    }
+
+    #[test]
+    fn test_shortcut_from_modifier_key_is_ignored() {
+        assert!(shortcut_from_key(KeyCode::ShiftLeft, ModifiersState::SHIFT).is_none());
+        assert!(shortcut_from_key(KeyCode::SuperRight, ModifiersState::SUPER).is_none());
+    }
+}
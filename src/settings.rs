@@ -1,8 +1,11 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::path::PathBuf;
+use std::time::Duration;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ShortcutConfig {
     #[serde(default)]
     pub modifiers: Vec<String>, // ["shift", "meta", "ctrl", "alt"]
@@ -18,6 +21,315 @@ impl Default for ShortcutConfig {
     }
 }
 
+/// Formats the shortcut with macOS modifier glyphs, e.g. "⇧⌘A".
+impl fmt::Display for ShortcutConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for modifier in &self.modifiers {
+            match modifier.as_str() {
+                "shift" => write!(f, "⇧")?,
+                "meta" | "cmd" | "command" => write!(f, "⌘")?,
+                "ctrl" | "control" => write!(f, "⌃")?,
+                "alt" | "option" => write!(f, "⌥")?,
+                _ => {}
+            }
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+/// Where the popup sits on its monitor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PopupAnchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Right,
+    BottomLeft,
+    #[default]
+    Bottom,
+    BottomRight,
+}
+
+/// When the popup is shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PopupVisibility {
+    /// Always visible, muted or not.
+    Always,
+    /// Shown when the mic state changes, then hidden after the state's delay.
+    #[default]
+    OnChange,
+    /// Never shown.
+    Never,
+}
+
+/// Which monitors show the popup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PopupMonitors {
+    /// A single popup on the monitor containing the cursor.
+    #[default]
+    FollowCursor,
+    /// One popup on every monitor.
+    All,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PopupSettings {
+    /// Show camera status next to the mic status in the popup.
+    pub show_camera: bool,
+    pub anchor: PopupAnchor,
+    /// Logical pixels added to the anchored position; positive moves right and down.
+    pub offset_x: f64,
+    pub offset_y: f64,
+    /// Size multiplier, clamped to 0.5–3.
+    pub scale: f64,
+    pub visibility: PopupVisibility,
+    /// Hide this long after muting; null keeps the popup visible while muted.
+    pub muted_hide_delay_ms: Option<u64>,
+    /// Hide this long after unmuting; null keeps the popup visible while unmuted.
+    pub unmuted_hide_delay_ms: Option<u64>,
+    pub monitors: PopupMonitors,
+}
+
+impl Default for PopupSettings {
+    fn default() -> Self {
+        Self {
+            show_camera: true,
+            anchor: PopupAnchor::default(),
+            offset_x: 0.,
+            offset_y: 0.,
+            scale: 1.,
+            visibility: PopupVisibility::default(),
+            muted_hide_delay_ms: None,
+            unmuted_hide_delay_ms: Some(1000),
+            monitors: PopupMonitors::default(),
+        }
+    }
+}
+
+impl PopupSettings {
+    pub fn clamped_scale(&self) -> f64 {
+        if self.scale.is_finite() {
+            self.scale.clamp(0.5, 3.)
+        } else {
+            1.
+        }
+    }
+
+    /// How long the popup stays up after changing to `muted`, or None to stay visible.
+    pub fn hide_delay(&self, muted: bool) -> Option<Duration> {
+        let delay_ms = if muted {
+            self.muted_hide_delay_ms
+        } else {
+            self.unmuted_hide_delay_ms
+        };
+        delay_ms.map(Duration::from_millis)
+    }
+
+    /// Whether the popup should be showing for `muted` once any hide delay has passed.
+    pub fn visible_at_rest(&self, muted: bool) -> bool {
+        match self.visibility {
+            PopupVisibility::Always => true,
+            PopupVisibility::OnChange => self.hide_delay(muted).is_none(),
+            PopupVisibility::Never => false,
+        }
+    }
+}
+
+/// Shortest interval any poll runs at, so a typo can't spin the CPU.
+const MIN_POLL_MS: u64 = 50;
+
+/// How often the mic, camera and settings file are checked.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PollingSettings {
+    /// Slow the mic poll down while mute isn't enforced or on battery power. When
+    /// false the mic is always polled at `mic_active_ms`.
+    pub adaptive: bool,
+    /// Mic poll while mute is enforced, and just after input devices change.
+    pub mic_active_ms: u64,
+    /// Mic poll while mute is enforced on battery power.
+    pub mic_battery_ms: u64,
+    /// Mic poll while the mic is on and nothing is enforced.
+    pub mic_idle_ms: u64,
+    pub camera_ms: u64,
+    /// Checks of settings.json for edits made outside the app.
+    pub settings_ms: u64,
+}
+
+impl Default for PollingSettings {
+    fn default() -> Self {
+        Self {
+            adaptive: true,
+            mic_active_ms: 200,
+            mic_battery_ms: 500,
+            mic_idle_ms: 1000,
+            camera_ms: 2000,
+            settings_ms: 2000,
+        }
+    }
+}
+
+fn poll_interval(ms: u64) -> Duration {
+    Duration::from_millis(ms.max(MIN_POLL_MS))
+}
+
+impl PollingSettings {
+    pub fn mic_active(&self) -> Duration {
+        poll_interval(self.mic_active_ms)
+    }
+
+    pub fn mic_battery(&self) -> Duration {
+        poll_interval(self.mic_battery_ms)
+    }
+
+    pub fn mic_idle(&self) -> Duration {
+        poll_interval(self.mic_idle_ms)
+    }
+
+    pub fn camera(&self) -> Duration {
+        poll_interval(self.camera_ms)
+    }
+
+    pub fn settings(&self) -> Duration {
+        poll_interval(self.settings_ms)
+    }
+}
+
+/// How much is written to the log file and stderr.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub fn filter(self) -> log::LevelFilter {
+        match self {
+            LogLevel::Off => log::LevelFilter::Off,
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
+/// How launch at login is registered on Linux. macOS always uses a LaunchAgent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LoginMechanism {
+    /// An XDG autostart entry, started by the desktop session.
+    #[default]
+    Autostart,
+    /// A systemd user service that restarts the app if it crashes.
+    Systemd,
+}
+
+/// Audible feedback on mic state changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundSettings {
+    pub enabled: bool,
+    /// Playback volume from 0 to 1.
+    pub volume: f32,
+    /// WAV files replacing the built-in tones; null uses the built-in tone.
+    pub mute_sound: Option<PathBuf>,
+    pub unmute_sound: Option<PathBuf>,
+    pub failure_sound: Option<PathBuf>,
+}
+
+impl Default for SoundSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            volume: 0.5,
+            mute_sound: None,
+            unmute_sound: None,
+            failure_sound: None,
+        }
+    }
+}
+
+impl SoundSettings {
+    pub fn clamped_volume(&self) -> f32 {
+        if self.volume.is_finite() {
+            self.volume.clamp(0., 1.)
+        } else {
+            0.5
+        }
+    }
+}
+
+/// Publishing mic and camera state to an MQTT broker, e.g. for Home Assistant.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttSettings {
+    pub enabled: bool,
+    /// Such as "mqtt://homeassistant.local:1883". TLS brokers aren't supported.
+    pub broker: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// State is published under this, e.g. "mic-mute/mic", and commands are read
+    /// from "mic-mute/command".
+    pub topic_prefix: String,
+    /// Where Home Assistant reads discovery config; null publishes none.
+    pub discovery_prefix: Option<String>,
+}
+
+impl Default for MqttSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            broker: "mqtt://localhost:1883".to_string(),
+            username: None,
+            password: None,
+            topic_prefix: "mic-mute".to_string(),
+            discovery_prefix: Some("homeassistant".to_string()),
+        }
+    }
+}
+
+/// Tray and popup icon appearance.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IconSettings {
+    /// Draw the unmuted tray icon as a template image that macOS tints to match the
+    /// menu bar. Ignored when `unmuted_color` is set.
+    pub tray_template: bool,
+    /// Hex colors such as "#ef4444" for each state; null uses the theme's colors.
+    pub muted_color: Option<String>,
+    pub unmuted_color: Option<String>,
+    /// Partially muted or failed states.
+    pub warning_color: Option<String>,
+    /// Directory of replacement SVGs (mic.svg, mic-off.svg, video.svg, video-off.svg).
+    /// Relative paths are resolved against the "icons" folder beside settings.json.
+    pub icon_set: Option<PathBuf>,
+}
+
+impl Default for IconSettings {
+    fn default() -> Self {
+        Self {
+            tray_template: true,
+            muted_color: None,
+            unmuted_color: None,
+            warning_color: None,
+            icon_set: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Settings {
     #[serde(default)]
@@ -26,6 +338,43 @@ pub struct Settings {
     pub show_in_dock: bool,
     #[serde(default)]
     pub launch_at_login: bool,
+    #[serde(default)]
+    pub login_mechanism: LoginMechanism,
+    /// Input device names that are never muted or enforced.
+    #[serde(default)]
+    pub excluded_devices: Vec<String>,
+    /// Mute when the screen locks or the user switches away.
+    #[serde(default)]
+    pub mute_on_lock: bool,
+    /// Mute before the computer sleeps.
+    #[serde(default)]
+    pub mute_on_sleep: bool,
+    /// Put the mic back the way it was before a lock or sleep muted it, once the
+    /// session is unlocked.
+    #[serde(default)]
+    pub restore_on_unlock: bool,
+    #[serde(default)]
+    pub popup: PopupSettings,
+    /// Language tag such as "de" or "fr-CA"; null follows the system language.
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub sounds: SoundSettings,
+    #[serde(default)]
+    pub icons: IconSettings,
+    #[serde(default)]
+    pub polling: PollingSettings,
+    #[serde(default)]
+    pub log_level: LogLevel,
+    /// Levels for individual modules that override `log_level`, keyed by log target
+    /// such as `mic_mute::mic`.
+    #[serde(default)]
+    pub log_modules: BTreeMap<String, LogLevel>,
+    #[serde(default)]
+    pub mqtt: MqttSettings,
+    /// Keep `status.json` in the runtime directory up to date for status bars.
+    #[serde(default)]
+    pub status_file: bool,
 }
 
 impl Settings {
@@ -33,7 +382,7 @@ impl Settings {
         Self::load_from_file().unwrap_or_default()
     }
 
-    fn config_path() -> Option<PathBuf> {
+    pub fn config_path() -> Option<PathBuf> {
         dirs::config_dir().map(|d| d.join("mic-mute").join("settings.json"))
     }
 
@@ -74,6 +423,16 @@ mod tests {
         assert!(sc.modifiers.contains(&"meta".to_string()));
     }
 
+    #[test]
+    fn test_shortcut_display() {
+        assert_eq!(ShortcutConfig::default().to_string(), "⇧⌘A");
+        let sc = ShortcutConfig {
+            modifiers: vec![],
+            key: "F13".to_string(),
+        };
+        assert_eq!(sc.to_string(), "F13");
+    }
+
     #[test]
     fn test_settings_json_round_trip() {
         let s = Settings::default();
@@ -98,6 +457,104 @@ mod tests {
         assert!(loaded.mic_shortcut.modifiers.is_empty());
     }
 
+    #[test]
+    fn test_settings_json_missing_popup_and_devices() {
+        let loaded: Settings = serde_json::from_str(r#"{ "popup": {} }"#).unwrap();
+        assert_eq!(loaded.language, None);
+        assert!(!loaded.sounds.enabled);
+        assert!(loaded.icons.tray_template);
+
+        assert!(loaded.excluded_devices.is_empty());
+        assert!(!loaded.mute_on_lock);
+        assert!(loaded.popup.show_camera);
+    }
+
+    #[test]
+    fn test_popup_settings_defaults_match_original_behavior() {
+        let popup = PopupSettings::default();
+
+        assert_eq!(popup.anchor, PopupAnchor::Bottom);
+        assert!(popup.visible_at_rest(true));
+        assert!(!popup.visible_at_rest(false));
+        assert_eq!(popup.hide_delay(false), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_popup_settings_json() {
+        let loaded: Settings = serde_json::from_str(
+            r#"{
+                "popup": {
+                    "anchor": "top-right",
+                    "visibility": "always",
+                    "monitors": "all",
+                    "muted_hide_delay_ms": 2000,
+                    "unmuted_hide_delay_ms": null,
+                    "scale": 10
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(loaded.popup.anchor, PopupAnchor::TopRight);
+        assert_eq!(loaded.popup.visibility, PopupVisibility::Always);
+        assert_eq!(loaded.popup.monitors, PopupMonitors::All);
+        assert_eq!(loaded.popup.hide_delay(true), Some(Duration::from_secs(2)));
+        assert_eq!(loaded.popup.hide_delay(false), None);
+        assert_eq!(loaded.popup.clamped_scale(), 3.);
+        assert!(loaded.popup.show_camera);
+    }
+
+    #[test]
+    fn test_polling_settings_json() {
+        let loaded: Settings =
+            serde_json::from_str(r#"{ "polling": { "mic_active_ms": 0, "camera_ms": 5000 } }"#)
+                .unwrap();
+
+        assert_eq!(
+            loaded.polling.mic_active(),
+            Duration::from_millis(MIN_POLL_MS)
+        );
+        assert_eq!(loaded.polling.camera(), Duration::from_secs(5));
+        assert_eq!(loaded.polling.settings(), Duration::from_secs(2));
+        assert!(loaded.polling.adaptive);
+    }
+
+    #[test]
+    fn test_log_settings_json() {
+        let loaded: Settings = serde_json::from_str(
+            r#"{ "log_level": "warn", "log_modules": { "mic_mute::mic": "trace" } }"#,
+        )
+        .unwrap();
+
+        assert_eq!(loaded.log_level, LogLevel::Warn);
+        assert_eq!(loaded.log_modules["mic_mute::mic"], LogLevel::Trace);
+        assert_eq!(Settings::default().log_level, LogLevel::Info);
+    }
+
+    #[test]
+    fn test_mqtt_settings_json() {
+        let loaded: Settings = serde_json::from_str(
+            r#"{ "mqtt": { "enabled": true, "broker": "mqtt://broker:1884", "discovery_prefix": null } }"#,
+        )
+        .unwrap();
+
+        assert!(loaded.mqtt.enabled);
+        assert_eq!(loaded.mqtt.broker, "mqtt://broker:1884");
+        assert_eq!(loaded.mqtt.topic_prefix, "mic-mute");
+        assert_eq!(loaded.mqtt.discovery_prefix, None);
+        assert!(!Settings::default().mqtt.enabled);
+    }
+
+    #[test]
+    fn test_popup_never_visible_at_rest() {
+        let popup = PopupSettings {
+            visibility: PopupVisibility::Never,
+            ..PopupSettings::default()
+        };
+        assert!(!popup.visible_at_rest(true));
+        assert!(!popup.visible_at_rest(false));
+    }
+
     #[test]
     fn test_settings_save_and_load() {
         use std::fs;
@@ -115,6 +572,31 @@ mod tests {
             },
             show_in_dock: false,
             launch_at_login: false,
+            login_mechanism: LoginMechanism::Systemd,
+            excluded_devices: vec!["Capture Card".to_string()],
+            mute_on_lock: true,
+            mute_on_sleep: false,
+            restore_on_unlock: true,
+            popup: PopupSettings::default(),
+            language: Some("de".to_string()),
+            sounds: SoundSettings {
+                enabled: true,
+                mute_sound: Some(PathBuf::from("/tmp/mute.wav")),
+                ..SoundSettings::default()
+            },
+            icons: IconSettings {
+                muted_color: Some("#ff0000".to_string()),
+                icon_set: Some(PathBuf::from("outline")),
+                ..IconSettings::default()
+            },
+            polling: PollingSettings {
+                mic_idle_ms: 3000,
+                ..PollingSettings::default()
+            },
+            log_level: LogLevel::Debug,
+            log_modules: BTreeMap::from([("mic_mute::mic".to_string(), LogLevel::Trace)]),
+            mqtt: MqttSettings::default(),
+            status_file: true,
         };
 
         let json = serde_json::to_string_pretty(&s).unwrap();
@@ -123,6 +605,18 @@ mod tests {
         let loaded: Settings =
             serde_json::from_str(&fs::read_to_string(&tmp_path).unwrap()).unwrap();
         assert_eq!(loaded.mic_shortcut.key, "M");
+        assert_eq!(loaded.language.as_deref(), Some("de"));
+        assert_eq!(loaded.sounds, s.sounds);
+        assert_eq!(loaded.icons, s.icons);
+        assert_eq!(loaded.polling, s.polling);
+        assert_eq!(loaded.log_level, LogLevel::Debug);
+        assert_eq!(loaded.log_modules, s.log_modules);
+        assert_eq!(loaded.excluded_devices, vec!["Capture Card"]);
+        assert_eq!(loaded.login_mechanism, LoginMechanism::Systemd);
+        assert!(loaded.mute_on_lock);
+        assert!(!loaded.mute_on_sleep);
+        assert!(loaded.restore_on_unlock);
+        assert!(loaded.status_file);
 
         let _ = fs::remove_file(&tmp_path);
     }
@@ -4,11 +4,29 @@ use global_hotkey::{
     hotkey::{Code, HotKey, Modifiers},
     GlobalHotKeyManager,
 };
+use log::{error, trace};
+
+/// Modifier combinations tried, in order, when suggesting a replacement shortcut.
+const SUGGESTED_MODIFIERS: &[&[&str]] = &[
+    &["shift", "meta"],
+    &["ctrl", "meta"],
+    &["alt", "meta"],
+    &["ctrl", "alt"],
+    &["shift", "alt", "meta"],
+    &["ctrl", "shift", "meta"],
+];
+
+/// Keys rarely bound by other apps, suggested without modifiers.
+const SUGGESTED_KEYS: &[&str] = &["F13", "F14", "F15", "F16", "F17", "F18", "F19"];
 
 #[allow(dead_code)]
 pub struct Shortcuts {
     hotkeys_manager: GlobalHotKeyManager,
     pub mic_hotkey: HotKey,
+    /// The shortcut behind `mic_hotkey`, or None when no mic hotkey is registered.
+    pub mic_shortcut: Option<ShortcutConfig>,
+    /// The most recently requested shortcut that could not be registered.
+    pub mic_unavailable: Option<ShortcutConfig>,
 }
 
 fn modifiers_from_config(config: &ShortcutConfig) -> Modifiers {
@@ -37,33 +55,129 @@ fn hotkey_from_config(config: &ShortcutConfig) -> HotKey {
     HotKey::new(Some(mods), code)
 }
 
+/// Replacement candidates for a shortcut that could not be registered: the same key
+/// under other modifier combinations, then unmodified function keys.
+fn candidate_shortcuts(config: &ShortcutConfig) -> Vec<ShortcutConfig> {
+    let same_key = SUGGESTED_MODIFIERS.iter().map(|modifiers| ShortcutConfig {
+        modifiers: modifiers.iter().map(|m| m.to_string()).collect(),
+        key: config.key.clone(),
+    });
+    let function_keys = SUGGESTED_KEYS.iter().map(|key| ShortcutConfig {
+        modifiers: vec![],
+        key: key.to_string(),
+    });
+
+    let mut seen = vec![hotkey_from_config(config)];
+    let mut candidates = vec![];
+    for candidate in same_key.chain(function_keys) {
+        let hotkey = hotkey_from_config(&candidate);
+        if !seen.contains(&hotkey) {
+            seen.push(hotkey);
+            candidates.push(candidate);
+        }
+    }
+    candidates
+}
+
 impl Shortcuts {
+    /// Registration failures are logged and recorded in `mic_unavailable` rather than
+    /// returned, so a shortcut owned by another app doesn't stop the app from starting.
+    /// Id of the registered mic hotkey, or None if no mic hotkey is registered.
+    pub fn mic_hotkey_id(&self) -> Option<u32> {
+        self.mic_shortcut.as_ref().map(|_| self.mic_hotkey.id())
+    }
+
     pub fn new(settings: &Settings) -> Result<Self> {
-        let hotkeys_manager = GlobalHotKeyManager::new().unwrap();
+        let hotkeys_manager =
+            GlobalHotKeyManager::new().context("Failed to create hotkey manager")?;
 
         let mic_hotkey = hotkey_from_config(&settings.mic_shortcut);
 
-        hotkeys_manager
-            .register(mic_hotkey)
-            .context("Failed to register mic hotkey")?;
+        let (mic_shortcut, mic_unavailable) = match hotkeys_manager.register(mic_hotkey) {
+            Ok(()) => (Some(settings.mic_shortcut.clone()), None),
+            Err(err) => {
+                error!(
+                    "Failed to register mic hotkey {}: {}",
+                    settings.mic_shortcut, err
+                );
+                (None, Some(settings.mic_shortcut.clone()))
+            }
+        };
 
         Ok(Self {
             hotkeys_manager,
             mic_hotkey,
+            mic_shortcut,
+            mic_unavailable,
         })
     }
 
     /// Unregister the current hotkeys and register new ones from updated settings.
+    /// If the new shortcut can't be registered, the previous working one is restored.
     pub fn reload(&mut self, settings: &Settings) -> Result<()> {
-        let _ = self.hotkeys_manager.unregister(self.mic_hotkey);
+        let hotkey = hotkey_from_config(&settings.mic_shortcut);
+        if self.mic_shortcut.is_some() && hotkey == self.mic_hotkey {
+            self.mic_unavailable = None;
+            return Ok(());
+        }
+
+        if self.mic_shortcut.is_some() {
+            let _ = self.hotkeys_manager.unregister(self.mic_hotkey);
+        }
 
-        self.mic_hotkey = hotkey_from_config(&settings.mic_shortcut);
+        match self.hotkeys_manager.register(hotkey) {
+            Ok(()) => {
+                self.mic_hotkey = hotkey;
+                self.mic_shortcut = Some(settings.mic_shortcut.clone());
+                self.mic_unavailable = None;
+                Ok(())
+            }
+            Err(err) => {
+                if self.mic_shortcut.is_some() {
+                    if let Err(rollback_err) = self.hotkeys_manager.register(self.mic_hotkey) {
+                        error!("Failed to restore previous mic hotkey: {}", rollback_err);
+                        self.mic_shortcut = None;
+                    } else {
+                        trace!("Restored previous mic hotkey {}", self.mic_hotkey);
+                    }
+                } else {
+                    self.mic_hotkey = hotkey;
+                }
+                self.mic_unavailable = Some(settings.mic_shortcut.clone());
+                Err(err).context(format!(
+                    "Failed to register mic hotkey {}",
+                    settings.mic_shortcut
+                ))
+            }
+        }
+    }
 
-        self.hotkeys_manager
-            .register(self.mic_hotkey)
-            .context("Failed to register mic hotkey")?;
+    /// Suggest up to `limit` shortcuts that can currently be registered in place of
+    /// the unavailable mic shortcut. Empty when the mic shortcut is registered.
+    pub fn suggest_alternatives(&self, limit: usize) -> Vec<ShortcutConfig> {
+        let Some(unavailable) = &self.mic_unavailable else {
+            return vec![];
+        };
+        candidate_shortcuts(unavailable)
+            .into_iter()
+            .filter(|candidate| self.is_available(candidate))
+            .take(limit)
+            .collect()
+    }
 
-        Ok(())
+    /// Probe a shortcut by registering and immediately unregistering it.
+    fn is_available(&self, config: &ShortcutConfig) -> bool {
+        let hotkey = hotkey_from_config(config);
+        if self.mic_shortcut.is_some() && hotkey == self.mic_hotkey {
+            return false;
+        }
+        match self.hotkeys_manager.register(hotkey) {
+            Ok(()) => {
+                let _ = self.hotkeys_manager.unregister(hotkey);
+                true
+            }
+            Err(_) => false,
+        }
     }
 }
 
@@ -130,4 +244,38 @@ mod tests {
         assert!(mods.contains(Modifiers::ALT));
         assert!(mods.contains(Modifiers::META));
     }
+
+    #[test]
+    fn test_candidate_shortcuts_exclude_unavailable_shortcut() {
+        let config = ShortcutConfig::default();
+        let taken = hotkey_from_config(&config);
+
+        let candidates = candidate_shortcuts(&config);
+
+        assert!(!candidates.is_empty());
+        assert!(candidates.iter().all(|c| hotkey_from_config(c) != taken));
+        assert_eq!(candidates[0].key, "A");
+        assert_eq!(candidates[0].modifiers, vec!["ctrl", "meta"]);
+    }
+
+    #[test]
+    fn test_candidate_shortcuts_are_unique() {
+        let config = ShortcutConfig {
+            modifiers: vec![],
+            key: "F13".to_string(),
+        };
+
+        let candidates = candidate_shortcuts(&config);
+        let hotkeys: Vec<_> = candidates.iter().map(hotkey_from_config).collect();
+
+        for (i, hotkey) in hotkeys.iter().enumerate() {
+            assert!(!hotkeys[i + 1..].contains(hotkey));
+        }
+        assert!(!candidates
+            .iter()
+            .any(|c| c.modifiers.is_empty() && c.key == "F13"));
+        assert!(candidates
+            .iter()
+            .any(|c| c.modifiers.is_empty() && c.key == "F14"));
+    }
 }
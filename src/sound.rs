@@ -0,0 +1,538 @@
+/// Audible feedback for mute, unmute and enforcement failures.
+/// Built-in tones are synthesized and user WAV files are decoded in Rust, then handed
+/// to the system as in-memory 16-bit PCM WAV so playback never blocks the event loop.
+use crate::settings::SoundSettings;
+use anyhow::{anyhow, bail, Context, Result};
+use log::{error, trace};
+use std::collections::HashMap;
+use std::path::Path;
+
+const SAMPLE_RATE: u32 = 44_100;
+/// Peak amplitude of generated tones, leaving headroom before the volume setting.
+const TONE_AMPLITUDE: f32 = 0.6;
+/// Fade in and out of each tone to avoid clicks.
+const FADE_MILLIS: u32 = 5;
+/// User sounds are meant to be short cues; larger files are rejected.
+const MAX_WAV_BYTES: u64 = 5 * 1024 * 1024;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cue {
+    Mute,
+    Unmute,
+    EnforcementFailed,
+}
+
+impl Cue {
+    /// Built-in tone as (frequency in Hz, duration in ms) notes; 0 Hz is silence.
+    fn notes(self) -> &'static [(f32, u32)] {
+        match self {
+            Cue::Mute => &[(660., 70), (440., 110)],
+            Cue::Unmute => &[(440., 70), (660., 110)],
+            Cue::EnforcementFailed => &[(220., 90), (0., 50), (220., 90), (0., 50), (220., 90)],
+        }
+    }
+
+    fn user_sound(self, settings: &SoundSettings) -> Option<&Path> {
+        match self {
+            Cue::Mute => settings.mute_sound.as_deref(),
+            Cue::Unmute => settings.unmute_sound.as_deref(),
+            Cue::EnforcementFailed => settings.failure_sound.as_deref(),
+        }
+    }
+}
+
+/// Interleaved audio samples in the range -1 to 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Samples {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub data: Vec<f32>,
+}
+
+/// Synthesize mono sine notes, each faded in and out.
+fn generate_tone(notes: &[(f32, u32)], sample_rate: u32) -> Samples {
+    let mut data = vec![];
+    for &(frequency, millis) in notes {
+        let length = (sample_rate as u64 * millis as u64 / 1000) as usize;
+        let fade = ((sample_rate * FADE_MILLIS / 1000) as usize).min(length / 2);
+        for i in 0..length {
+            if frequency <= 0. {
+                data.push(0.);
+                continue;
+            }
+            let envelope = if i < fade {
+                i as f32 / fade as f32
+            } else if length - i <= fade {
+                (length - i - 1) as f32 / fade as f32
+            } else {
+                1.
+            };
+            let phase = 2. * std::f32::consts::PI * frequency * i as f32 / sample_rate as f32;
+            data.push(phase.sin() * envelope * TONE_AMPLITUDE);
+        }
+    }
+    Samples {
+        sample_rate,
+        channels: 1,
+        data,
+    }
+}
+
+/// Encode samples as a 16-bit PCM WAV file.
+fn encode_wav(samples: &Samples) -> Vec<u8> {
+    let data_len = (samples.data.len() * 2) as u32;
+    let block_align = samples.channels * 2;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&WAVE_FORMAT_PCM.to_le_bytes());
+    wav.extend_from_slice(&samples.channels.to_le_bytes());
+    wav.extend_from_slice(&samples.sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(samples.sample_rate * block_align as u32).to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in &samples.data {
+        let value = (sample.clamp(-1., 1.) * i16::MAX as f32).round() as i16;
+        wav.extend_from_slice(&value.to_le_bytes());
+    }
+    wav
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16> {
+    let slice = bytes
+        .get(offset..offset + 2)
+        .ok_or_else(|| anyhow!("Unexpected end of WAV data"))?;
+    Ok(u16::from_le_bytes([slice[0], slice[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    let slice = bytes
+        .get(offset..offset + 4)
+        .ok_or_else(|| anyhow!("Unexpected end of WAV data"))?;
+    Ok(u32::from_le_bytes([slice[0], slice[1], slice[2], slice[3]]))
+}
+
+/// Decode an integer PCM (8, 16, 24 or 32-bit) or 32-bit float WAV file.
+fn decode_wav(bytes: &[u8]) -> Result<Samples> {
+    if bytes.get(0..4) != Some(b"RIFF") || bytes.get(8..12) != Some(b"WAVE") {
+        bail!("Not a RIFF/WAVE file");
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let len = read_u32(bytes, offset + 4)? as usize;
+        let start = offset + 8;
+        let end = start
+            .checked_add(len)
+            .filter(|end| *end <= bytes.len())
+            .ok_or_else(|| anyhow!("WAV chunk extends past end of file"))?;
+        match id {
+            b"fmt " => {
+                let mut tag = read_u16(bytes, start)?;
+                let channels = read_u16(bytes, start + 2)?;
+                let sample_rate = read_u32(bytes, start + 4)?;
+                let bits = read_u16(bytes, start + 14)?;
+                if tag == WAVE_FORMAT_EXTENSIBLE {
+                    // The sub-format GUID starts with the actual format tag
+                    tag = read_u16(bytes, start + 24)?;
+                }
+                format = Some((tag, channels, sample_rate, bits));
+            }
+            b"data" => data = Some(&bytes[start..end]),
+            _ => {}
+        }
+        // Chunks are padded to an even length
+        offset = end + (len & 1);
+    }
+
+    let (tag, channels, sample_rate, bits) =
+        format.ok_or_else(|| anyhow!("WAV file has no fmt chunk"))?;
+    let data = data.ok_or_else(|| anyhow!("WAV file has no data chunk"))?;
+    if channels == 0 || sample_rate == 0 {
+        bail!("WAV file has no channels or sample rate");
+    }
+
+    let decoded: Vec<f32> = match (tag, bits) {
+        (WAVE_FORMAT_PCM, 8) => data.iter().map(|&b| (b as f32 - 128.) / 128.).collect(),
+        (WAVE_FORMAT_PCM, 16) => data
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32_768.)
+            .collect(),
+        (WAVE_FORMAT_PCM, 24) => data
+            .chunks_exact(3)
+            .map(|b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.)
+            .collect(),
+        (WAVE_FORMAT_PCM, 32) => data
+            .chunks_exact(4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.)
+            .collect(),
+        (WAVE_FORMAT_IEEE_FLOAT, 32) => data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+        _ => bail!("Unsupported WAV format {} with {} bits", tag, bits),
+    };
+
+    Ok(Samples {
+        sample_rate,
+        channels,
+        data: decoded,
+    })
+}
+
+fn load_wav(path: &Path) -> Result<Samples> {
+    let size = std::fs::metadata(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .len();
+    if size > MAX_WAV_BYTES {
+        bail!("{} is larger than {} bytes", path.display(), MAX_WAV_BYTES);
+    }
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    decode_wav(&bytes).with_context(|| format!("Failed to decode {}", path.display()))
+}
+
+/// Plays cues according to the sound settings, caching the encoded WAV for each cue.
+pub struct SoundPlayer {
+    settings: SoundSettings,
+    cache: HashMap<Cue, Vec<u8>>,
+    output: platform::Output,
+}
+
+impl SoundPlayer {
+    pub fn new(settings: &SoundSettings) -> Self {
+        Self {
+            settings: settings.clone(),
+            cache: HashMap::new(),
+            output: platform::Output::new(),
+        }
+    }
+
+    pub fn apply_settings(&mut self, settings: &SoundSettings) {
+        if *settings != self.settings {
+            self.settings = settings.clone();
+            self.cache.clear();
+        }
+    }
+
+    /// Start playing a cue and return immediately. Does nothing when sounds are off.
+    pub fn play(&mut self, cue: Cue) {
+        let Self {
+            settings,
+            cache,
+            output,
+        } = self;
+        if !settings.enabled {
+            return;
+        }
+        let wav = cached_wav(cache, settings, cue);
+        trace!("Playing {:?} sound", cue);
+        if let Err(e) = output.play(wav, settings.clamped_volume()) {
+            error!("Failed to play {:?} sound: {}", cue, e);
+        }
+    }
+}
+
+/// The cue's user sound, or its built-in tone when none is set or it can't be loaded.
+fn cached_wav<'a>(
+    cache: &'a mut HashMap<Cue, Vec<u8>>,
+    settings: &SoundSettings,
+    cue: Cue,
+) -> &'a [u8] {
+    cache.entry(cue).or_insert_with(|| {
+        let samples = match cue.user_sound(settings).map(load_wav) {
+            Some(Ok(samples)) => samples,
+            Some(Err(e)) => {
+                error!("Using built-in sound: {:#}", e);
+                generate_tone(cue.notes(), SAMPLE_RATE)
+            }
+            None => generate_tone(cue.notes(), SAMPLE_RATE),
+        };
+        encode_wav(&samples)
+    })
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use anyhow::{anyhow, Result};
+    use cocoa::base::{id, nil, NO};
+
+    /// Keeps the playing NSSound alive until the next cue replaces it.
+    pub struct Output {
+        sound: id,
+    }
+
+    impl Output {
+        pub fn new() -> Self {
+            Self { sound: nil }
+        }
+
+        /// NSSound plays asynchronously on the current default output device.
+        pub fn play(&mut self, wav: &[u8], volume: f32) -> Result<()> {
+            unsafe {
+                self.stop();
+                let data: id =
+                    msg_send![class!(NSData), dataWithBytes: wav.as_ptr() length: wav.len()];
+                let sound: id = msg_send![class!(NSSound), alloc];
+                let sound: id = msg_send![sound, initWithData: data];
+                if sound == nil {
+                    return Err(anyhow!("NSSound rejected the WAV data"));
+                }
+                let _: () = msg_send![sound, setVolume: volume];
+                let started: cocoa::base::BOOL = msg_send![sound, play];
+                self.sound = sound;
+                if started == NO {
+                    return Err(anyhow!("NSSound failed to start"));
+                }
+            }
+            Ok(())
+        }
+
+        unsafe fn stop(&mut self) {
+            if self.sound != nil {
+                let _: () = msg_send![self.sound, stop];
+                let _: () = msg_send![self.sound, release];
+                self.sound = nil;
+            }
+        }
+    }
+
+    impl Drop for Output {
+        fn drop(&mut self) {
+            unsafe { self.stop() }
+        }
+    }
+}
+
+/// Plays through the PulseAudio or PipeWire command line players with the WAV on
+/// stdin, like the pactl calls in the Linux audio backend.
+#[cfg(not(target_os = "macos"))]
+mod platform {
+    use anyhow::{bail, Context, Result};
+    use std::io::{ErrorKind, Write};
+    use std::process::{Child, Command, Stdio};
+
+    pub struct Output {
+        /// The cue playing, stopped when the next one starts.
+        child: Option<Child>,
+        /// Set once neither player was found, which has been logged.
+        unavailable: bool,
+    }
+
+    impl Output {
+        pub fn new() -> Self {
+            Output {
+                child: None,
+                unavailable: false,
+            }
+        }
+
+        pub fn play(&mut self, wav: &[u8], volume: f32) -> Result<()> {
+            if self.unavailable {
+                return Ok(());
+            }
+            self.stop();
+            let mut child = match spawn_player(volume)? {
+                Some(child) => child,
+                None => {
+                    self.unavailable = true;
+                    bail!("Neither paplay nor pw-play is installed; sounds are off");
+                }
+            };
+            let mut stdin = child.stdin.take().context("No stdin for the player")?;
+            let wav = wav.to_vec();
+            // The pipe only takes part of a longer sound until the player reads it
+            std::thread::Builder::new()
+                .name("sound".to_string())
+                .spawn(move || {
+                    if let Err(e) = stdin.write_all(&wav) {
+                        log::trace!("Sound player stopped reading: {}", e);
+                    }
+                })
+                .context("Failed to start the sound thread")?;
+            self.child = Some(child);
+            Ok(())
+        }
+
+        fn stop(&mut self) {
+            if let Some(mut child) = self.child.take() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+    }
+
+    impl Drop for Output {
+        fn drop(&mut self) {
+            self.stop();
+        }
+    }
+
+    /// paplay, or pw-play where PipeWire runs without the Pulse tools. None when
+    /// neither is installed.
+    fn spawn_player(volume: f32) -> Result<Option<Child>> {
+        // paplay's 65536 is 100%
+        let players = [
+            (
+                "paplay",
+                vec![format!("--volume={}", (volume * 65536.) as u32)],
+            ),
+            (
+                "pw-play",
+                vec![format!("--volume={}", volume), "-".to_string()],
+            ),
+        ];
+        for (player, args) in players {
+            let spawned = Command::new(player)
+                .args(&args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+            match spawned {
+                Ok(child) => return Ok(Some(child)),
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e).with_context(|| format!("Failed to run {}", player)),
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_tone_length_and_fades() {
+        let samples = generate_tone(&[(440., 100), (0., 50)], 8_000);
+
+        assert_eq!(samples.channels, 1);
+        assert_eq!(samples.data.len(), 800 + 400);
+        assert_eq!(samples.data[0], 0.);
+        assert!(samples.data[799].abs() < 1e-6);
+        assert!(samples.data[800..].iter().all(|s| *s == 0.));
+        assert!(samples
+            .data
+            .iter()
+            .all(|s| s.abs() <= TONE_AMPLITUDE + 1e-6));
+        assert!(samples.data.iter().any(|s| s.abs() > TONE_AMPLITUDE * 0.9));
+    }
+
+    #[test]
+    fn test_builtin_cues_are_distinct() {
+        let mute = generate_tone(Cue::Mute.notes(), SAMPLE_RATE);
+        let unmute = generate_tone(Cue::Unmute.notes(), SAMPLE_RATE);
+        assert_eq!(mute.data.len(), unmute.data.len());
+        assert_ne!(mute, unmute);
+    }
+
+    #[test]
+    fn test_wav_round_trip() {
+        let samples = Samples {
+            sample_rate: 22_050,
+            channels: 2,
+            data: vec![0., 0.5, -0.5, 1., -1., 0.25],
+        };
+
+        let decoded = decode_wav(&encode_wav(&samples)).unwrap();
+
+        assert_eq!(decoded.sample_rate, 22_050);
+        assert_eq!(decoded.channels, 2);
+        for (a, b) in decoded.data.iter().zip(&samples.data) {
+            assert!((a - b).abs() < 1e-3, "{} != {}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_decode_skips_unknown_chunks_and_reads_8_bit() {
+        let mut wav = encode_wav(&Samples {
+            sample_rate: 8_000,
+            channels: 1,
+            data: vec![],
+        });
+        // Rewrite as 8-bit, then insert an odd-length LIST chunk before the data
+        wav[32..34].copy_from_slice(&1u16.to_le_bytes());
+        wav[34..36].copy_from_slice(&8u16.to_le_bytes());
+        let data_chunk = wav.split_off(36);
+        wav.extend_from_slice(b"LIST");
+        wav.extend_from_slice(&3u32.to_le_bytes());
+        wav.extend_from_slice(&[1, 2, 3, 0]);
+        wav.extend_from_slice(&data_chunk[..4]);
+        wav.extend_from_slice(&2u32.to_le_bytes());
+        wav.extend_from_slice(&[0, 255]);
+
+        let decoded = decode_wav(&wav).unwrap();
+
+        assert_eq!(decoded.data, vec![-1., 127. / 128.]);
+    }
+
+    #[test]
+    fn test_decode_rejects_invalid_files() {
+        assert!(decode_wav(b"not a wav file").is_err());
+
+        let mut truncated = encode_wav(&Samples {
+            sample_rate: 8_000,
+            channels: 1,
+            data: vec![0.; 10],
+        });
+        truncated.truncate(50);
+        assert!(decode_wav(&truncated).is_err());
+
+        let mut adpcm = encode_wav(&Samples {
+            sample_rate: 8_000,
+            channels: 1,
+            data: vec![0.; 10],
+        });
+        adpcm[20..22].copy_from_slice(&2u16.to_le_bytes());
+        assert!(decode_wav(&adpcm).is_err());
+    }
+
+    #[test]
+    fn test_user_sound_falls_back_to_builtin_tone() {
+        let settings = SoundSettings {
+            enabled: true,
+            mute_sound: Some("/nonexistent/mute.wav".into()),
+            ..SoundSettings::default()
+        };
+        let mut cache = HashMap::new();
+
+        let wav = cached_wav(&mut cache, &settings, Cue::Mute).to_vec();
+
+        let expected = encode_wav(&generate_tone(Cue::Mute.notes(), SAMPLE_RATE));
+        assert_eq!(wav, expected);
+    }
+
+    #[test]
+    fn test_user_sound_is_decoded_from_file() {
+        let path = std::env::temp_dir().join("mic-mute-test-unmute.wav");
+        let samples = Samples {
+            sample_rate: 8_000,
+            channels: 1,
+            data: vec![0., 0.5, -0.5],
+        };
+        std::fs::write(&path, encode_wav(&samples)).unwrap();
+        let settings = SoundSettings {
+            unmute_sound: Some(path.clone()),
+            ..SoundSettings::default()
+        };
+        let mut cache = HashMap::new();
+
+        let wav = cached_wav(&mut cache, &settings, Cue::Unmute).to_vec();
+
+        assert_eq!(wav, encode_wav(&samples));
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,372 @@
+/// Mic and camera state for status bars such as sketchybar, tmux, waybar and
+/// polybar. The running app streams each change as a JSON line over a socket in
+/// the per-user runtime directory, which `mic-mute watch` prints as-is or through
+/// a template, and can also keep `status.json` beside it up to date.
+use crate::camera::{self, CameraDevice};
+use crate::mic::MicStatus;
+use crate::settings::Settings;
+use crate::utils::{create_runtime_dir, runtime_dir};
+use anyhow::{bail, Context, Result};
+use log::trace;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const SOCKET_NAME: &str = "status.sock";
+const FILE_NAME: &str = "status.json";
+/// A watcher that stops reading is dropped after this rather than holding up the app.
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+/// How often `mic-mute watch` looks for the app while it isn't running.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// One line of `mic-mute watch` output and the contents of the status file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    pub muted: bool,
+    /// `muted`, `unmuted`, `partially-muted`, `no-controllable-devices` or `failed`.
+    pub status: String,
+    /// The default input device.
+    pub device: Option<String>,
+    /// Whether any camera is running.
+    pub camera: bool,
+    /// Names of the cameras running.
+    pub cameras: Vec<String>,
+}
+
+impl Status {
+    pub fn new(
+        muted: bool,
+        status: MicStatus,
+        device: Option<&str>,
+        cameras: &[CameraDevice],
+    ) -> Self {
+        let mut new = Self::default();
+        new.set_mic(muted, status, device);
+        new.set_camera(cameras);
+        new
+    }
+
+    fn set_mic(&mut self, muted: bool, status: MicStatus, device: Option<&str>) {
+        self.muted = muted;
+        self.status = status_name(status).to_string();
+        self.device = device.map(str::to_string);
+    }
+
+    fn set_camera(&mut self, cameras: &[CameraDevice]) {
+        self.cameras = camera::active_names(cameras);
+        self.camera = !self.cameras.is_empty();
+    }
+}
+
+fn status_name(status: MicStatus) -> &'static str {
+    match status {
+        MicStatus::Muted => "muted",
+        MicStatus::Unmuted => "unmuted",
+        MicStatus::PartiallyMuted { .. } => "partially-muted",
+        MicStatus::NoControllableDevices => "no-controllable-devices",
+        MicStatus::Failed => "failed",
+    }
+}
+
+/// Fill `{name}` placeholders in `template`: `mic` (muted or unmuted), `mic_icon`,
+/// `status`, `device`, `camera` (on or off), `camera_icon` (empty while off) and
+/// `cameras`. Unknown placeholders are left as they are.
+pub fn render(template: &str, status: &Status) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        match placeholder(&rest[start + 1..end], status) {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn placeholder(name: &str, status: &Status) -> Option<String> {
+    let value = match name {
+        "mic" => (if status.muted { "muted" } else { "unmuted" }).to_string(),
+        "mic_icon" => (if status.muted { "🔇" } else { "🎙️" }).to_string(),
+        "status" => status.status.clone(),
+        "device" => status.device.clone().unwrap_or_default(),
+        "camera" => (if status.camera { "on" } else { "off" }).to_string(),
+        "camera_icon" => (if status.camera { "📷" } else { "" }).to_string(),
+        "cameras" => status.cameras.join(", "),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Replace the file at `path` in one step, so readers never see it half written.
+fn write_atomic(path: &std::path::Path, contents: &str) -> io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    fs::write(&temp, contents)?;
+    fs::rename(&temp, path)
+}
+
+/// Accepts `mic-mute watch` connections and sends each the latest status, then
+/// every change.
+struct Server {
+    path: PathBuf,
+    clients: Arc<Mutex<Vec<UnixStream>>>,
+    latest: Arc<Mutex<String>>,
+}
+
+impl Server {
+    fn start(line: String) -> Result<Self> {
+        let path = create_runtime_dir()?.join(SOCKET_NAME);
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                bail!("Another Mic Mute is serving {}", path.display());
+            }
+            // Left behind by a process that didn't exit cleanly
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("Failed to listen on {}", path.display()))?;
+        let clients = Arc::new(Mutex::new(vec![]));
+        let latest = Arc::new(Mutex::new(line));
+        let accepted = clients.clone();
+        let accepted_latest = latest.clone();
+        std::thread::Builder::new()
+            .name("status".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = match stream {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            trace!("Failed to accept a status watcher: {}", e);
+                            continue;
+                        }
+                    };
+                    let _ = stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT));
+                    // Holding `latest` keeps a change from slipping in between
+                    let latest = accepted_latest.lock().unwrap();
+                    if writeln!(stream, "{}", latest).is_ok() {
+                        trace!("Status watcher connected");
+                        accepted.lock().unwrap().push(stream);
+                    }
+                }
+            })
+            .context("Failed to start the status thread")?;
+        Ok(Self {
+            path,
+            clients,
+            latest,
+        })
+    }
+
+    fn send(&self, line: &str) {
+        let mut latest = self.latest.lock().unwrap();
+        line.clone_into(&mut latest);
+        self.clients
+            .lock()
+            .unwrap()
+            .retain_mut(|client| writeln!(client, "{}", line).is_ok());
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Sends state changes to watchers and the status file.
+pub struct StatusPublisher {
+    status: Status,
+    server: Option<Server>,
+    /// Set while `status_file` is on.
+    file: Option<PathBuf>,
+}
+
+impl StatusPublisher {
+    pub fn new(status: Status, settings: &Settings) -> Self {
+        let line = serde_json::to_string(&status).unwrap_or_default();
+        let server = Server::start(line)
+            .map_err(|e| log::error!("mic-mute watch is unavailable: {:#}", e))
+            .ok();
+        let mut publisher = Self {
+            status,
+            server,
+            file: None,
+        };
+        publisher.apply_settings(settings);
+        publisher
+    }
+
+    pub fn set_mic(&mut self, muted: bool, status: MicStatus, device: Option<&str>) {
+        let mut changed = self.status.clone();
+        changed.set_mic(muted, status, device);
+        self.publish(changed);
+    }
+
+    pub fn set_camera(&mut self, cameras: &[CameraDevice]) {
+        let mut changed = self.status.clone();
+        changed.set_camera(cameras);
+        self.publish(changed);
+    }
+
+    /// Start or stop keeping the status file.
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        if settings.status_file == self.file.is_some() {
+            return;
+        }
+        if settings.status_file {
+            match create_runtime_dir() {
+                Ok(dir) => {
+                    let path = dir.join(FILE_NAME);
+                    trace!("Writing status to {}", path.display());
+                    self.file = Some(path);
+                    self.write_file();
+                }
+                Err(e) => log::error!("Failed to keep the status file: {:#}", e),
+            }
+        } else if let Some(path) = self.file.take() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    /// Stop serving watchers and remove the status file, which would otherwise
+    /// outlive the app with a stale state.
+    pub fn close(&mut self) {
+        self.server = None;
+        self.file = None;
+        remove_files();
+    }
+
+    fn publish(&mut self, status: Status) {
+        if status == self.status {
+            return;
+        }
+        self.status = status;
+        if let Some(server) = &self.server {
+            match serde_json::to_string(&self.status) {
+                Ok(line) => server.send(&line),
+                Err(e) => log::error!("Failed to serialize status: {}", e),
+            }
+        }
+        self.write_file();
+    }
+
+    fn write_file(&self) {
+        let Some(path) = &self.file else {
+            return;
+        };
+        let result = serde_json::to_string_pretty(&self.status)
+            .map_err(io::Error::from)
+            .and_then(|json| write_atomic(path, &json));
+        if let Err(e) = result {
+            log::error!("Failed to write {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Remove the socket and status file, for exits that skip `StatusPublisher::close`.
+pub fn remove_files() {
+    let dir = runtime_dir();
+    let _ = fs::remove_file(dir.join(SOCKET_NAME));
+    let _ = fs::remove_file(dir.join(FILE_NAME));
+}
+
+/// `mic-mute watch`: print the running app's status and every change, as JSON
+/// lines or through `template`, waiting for the app when it isn't running.
+pub fn watch(template: Option<&str>) -> Result<()> {
+    let path = runtime_dir().join(SOCKET_NAME);
+    let mut waiting = false;
+    loop {
+        if let Ok(stream) = UnixStream::connect(&path) {
+            waiting = false;
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                let output = match template {
+                    Some(template) => match serde_json::from_str(&line) {
+                        Ok(status) => render(template, &status),
+                        Err(e) => {
+                            eprintln!("Unreadable status {:?}: {}", line, e);
+                            continue;
+                        }
+                    },
+                    None => line,
+                };
+                // Fails once the reader goes away, e.g. the bar restarted
+                let mut stdout = io::stdout().lock();
+                writeln!(stdout, "{}", output)?;
+                stdout.flush()?;
+            }
+        } else if !waiting {
+            eprintln!("Waiting for Mic Mute to start");
+            waiting = true;
+        }
+        std::thread::sleep(RECONNECT_DELAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status() -> Status {
+        let cameras = [CameraDevice {
+            id: "0x8020000005ac8514".to_string(),
+            name: "FaceTime HD Camera".to_string(),
+            running: true,
+        }];
+        Status::new(
+            true,
+            MicStatus::PartiallyMuted { muted: 1, total: 2 },
+            Some("USB Mic"),
+            &cameras,
+        )
+    }
+
+    #[test]
+    fn test_status_json() {
+        assert_eq!(
+            serde_json::to_string(&status()).unwrap(),
+            r#"{"muted":true,"status":"partially-muted","device":"USB Mic","camera":true,"cameras":["FaceTime HD Camera"]}"#
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let status = status();
+
+        assert_eq!(render("{mic_icon} {device}", &status), "🔇 USB Mic");
+        assert_eq!(
+            render("{mic}/{camera} {cameras}{camera_icon}", &status),
+            "muted/on FaceTime HD Camera📷"
+        );
+        assert_eq!(render("{unknown} {status", &status), "{unknown} {status");
+        assert_eq!(render("", &status), "");
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_file() {
+        let dir = std::env::temp_dir().join("mic-mute-test-status");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(FILE_NAME);
+
+        write_atomic(&path, "first").unwrap();
+        write_atomic(&path, "second").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,37 +1,59 @@
+use crate::camera::CameraDevice;
 use crate::config::AppVars;
-use crate::icons::{rasterize_svg, tray_icon_color};
+use crate::i18n::{tr, tr_args};
+use crate::icons::{rasterize_svg, svg, tray_icon_style, Glyph, IconState};
+use crate::mic::MicStatus;
+use crate::platform;
+use crate::popup_content::get_mic_mute_description_text;
 use crate::settings::ShortcutConfig;
+use crate::usage::{format_duration, Summary};
 use anyhow::{Context, Result};
 use log::trace;
-use muda::{accelerator::Accelerator, CheckMenuItem, Menu, MenuId, MenuItem, PredefinedMenuItem};
+use muda::{
+    accelerator::Accelerator, CheckMenuItem, Menu, MenuId, MenuItem, PredefinedMenuItem, Submenu,
+};
 use std::fmt;
 use tao::window::Theme;
 use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
 
-const MUTE_TEXT: &str = "Mute";
-const UNMUTE_TEXT: &str = "Unmute";
+pub fn get_shortcut_unavailable_text(shortcut: &ShortcutConfig) -> String {
+    tr_args(
+        "tray-shortcut-unavailable",
+        &[("shortcut", &shortcut.to_string())],
+    )
+}
 
-pub fn get_mute_menu_text(muted: bool) -> &'static str {
+pub fn get_mute_menu_text(muted: bool) -> String {
     if muted {
-        UNMUTE_TEXT
+        tr("tray-unmute")
     } else {
-        MUTE_TEXT
+        tr("tray-mute")
     }
 }
 
-fn get_image(muted: bool, _theme: Theme) -> Result<(Vec<u8>, u32, u32)> {
-    const MIC_ON: &[u8] = include_bytes!("../assets/mic.svg");
-    const MIC_OFF: &[u8] = include_bytes!("../assets/mic-off.svg");
-    let svg = if muted { MIC_OFF } else { MIC_ON };
-    rasterize_svg(svg, &tray_icon_color(muted))
+fn get_tooltip_text(app_vars: &AppVars, status: MicStatus) -> String {
+    tr_args(
+        "tray-tooltip",
+        &[
+            ("name", &app_vars.name),
+            ("status", &get_mic_mute_description_text(status)),
+        ],
+    )
+}
+
+fn get_image(status: MicStatus, theme: Theme) -> Result<(Vec<u8>, u32, u32, bool)> {
+    let (color, template) = tray_icon_style(IconState::for_status(status), theme);
+    let (rgba, width, height) = rasterize_svg(&svg(Glyph::mic(status)), &color)?;
+    Ok((rgba, width, height, template))
 }
 
-fn get_icon(muted: bool, theme: Theme) -> Result<Icon> {
+/// The tray icon and whether it's a template image.
+fn get_icon(status: MicStatus, theme: Theme) -> Result<(Icon, bool)> {
     trace!("Fetching icons");
-    let (icon_rgba, icon_width, icon_height) = get_image(muted, theme)?;
+    let (icon_rgba, icon_width, icon_height, template) = get_image(status, theme)?;
     let icon =
         Icon::from_rgba(icon_rgba, icon_width, icon_height).context("Failed to open icon")?;
-    Ok(icon)
+    Ok((icon, template))
 }
 
 fn accelerator_from_config(config: &ShortcutConfig) -> Accelerator {
@@ -63,95 +85,301 @@ impl fmt::Debug for Tray {
 
 pub struct Tray {
     pub systray: TrayIcon,
+    app_vars: AppVars,
+    status: MicStatus,
+    menu: Menu,
     pub toggle_mute: MenuItem,
+    shortcut_unavailable: MenuItem,
+    shortcut_suggestions: Submenu,
+    suggestion_items: Vec<(MenuItem, ShortcutConfig)>,
+    cameras: Submenu,
+    /// One disabled item per camera, checked while it's running.
+    camera_items: Vec<CheckMenuItem>,
+    no_cameras: MenuItem,
+    usage: Submenu,
+    usage_mic: MenuItem,
+    usage_toggles: MenuItem,
+    usage_camera: MenuItem,
+    pub export_usage: MenuItem,
+    /// Shown again when the language changes.
+    usage_today: Summary,
     pub launch_at_login: CheckMenuItem,
     pub show_in_dock: CheckMenuItem,
-    pub about: MenuItem,
+    pub preferences: MenuItem,
+    pub export_diagnostics: MenuItem,
+    pub open_logs: MenuItem,
     pub quit: MenuItem,
 }
 
 impl Tray {
     pub fn new(
         muted: bool,
+        status: MicStatus,
         theme: Theme,
         app_vars: AppVars,
         login_enabled: bool,
         dock_visible: bool,
-        mic_shortcut: &ShortcutConfig,
+        mic_shortcut: Option<&ShortcutConfig>,
     ) -> Result<Self> {
         trace!("Creating tray icon");
-        let icon = get_icon(muted, theme)?;
+        let (icon, template) = get_icon(status, theme)?;
         let tray_menu = Menu::new();
         let toggle_mute = MenuItem::new(
             get_mute_menu_text(muted),
             true,
-            Some(accelerator_from_config(mic_shortcut)),
+            mic_shortcut.map(accelerator_from_config),
         );
-        let launch_at_login = CheckMenuItem::new("Launch at Login", true, login_enabled, None);
-        let show_in_dock = CheckMenuItem::new("Show in Dock", true, dock_visible, None);
-        let about = MenuItem::new("About", true, None);
-        let quit = MenuItem::new("Exit", true, None);
+        let shortcut_unavailable = MenuItem::new("", false, None);
+        let shortcut_suggestions = Submenu::new(tr("tray-use-shortcut-instead"), true);
+        let cameras = Submenu::new(tr("tray-cameras"), true);
+        let no_cameras = MenuItem::new(tr("tray-no-cameras"), false, None);
+        let usage = Submenu::new(tr("tray-usage"), true);
+        let usage_mic = MenuItem::new("", false, None);
+        let usage_toggles = MenuItem::new("", false, None);
+        let usage_camera = MenuItem::new("", false, None);
+        let export_usage = MenuItem::new(tr("tray-export-usage"), true, None);
+        usage
+            .append_items(&[
+                &usage_mic,
+                &usage_toggles,
+                &usage_camera,
+                &PredefinedMenuItem::separator(),
+                &export_usage,
+            ])
+            .context("Failed to append usage menu items")?;
+        let launch_at_login =
+            CheckMenuItem::new(tr("tray-launch-at-login"), true, login_enabled, None);
+        let show_in_dock = CheckMenuItem::new(tr("tray-show-in-dock"), true, dock_visible, None);
+        let preferences = MenuItem::new(tr("tray-preferences"), true, None);
+        let export_diagnostics = MenuItem::new(tr("tray-export-diagnostics"), true, None);
+        let open_logs = MenuItem::new(tr("tray-open-logs"), true, None);
+        let quit = MenuItem::new(tr("tray-exit"), true, None);
 
         tray_menu
             .append_items(&[
                 &toggle_mute,
+                &cameras,
+                &usage,
                 &PredefinedMenuItem::separator(),
                 &launch_at_login,
-                &show_in_dock,
-                &about,
+            ])
+            .context("Failed to append menu items")?;
+        if platform::HAS_DOCK {
+            tray_menu
+                .append(&show_in_dock)
+                .context("Failed to append dock menu item")?;
+        }
+        tray_menu
+            .append_items(&[
+                &preferences,
+                &export_diagnostics,
+                &open_logs,
                 &PredefinedMenuItem::separator(),
                 &quit,
             ])
             .context("Failed to append menu items")?;
 
         let systray = TrayIconBuilder::new()
-            .with_menu(Box::new(tray_menu))
-            .with_tooltip(format!("{} service is running", app_vars.name))
+            .with_menu(Box::new(tray_menu.clone()))
+            .with_tooltip(get_tooltip_text(&app_vars, status))
             .with_icon(icon)
+            .with_icon_as_template(template)
             .with_menu_on_left_click(true)
             .build()
             .context("Failed to create tray icon")?;
 
         trace!("Tray item created");
-        let tray = Self {
+        let mut tray = Self {
             systray,
+            app_vars,
+            status,
+            menu: tray_menu,
             toggle_mute,
+            shortcut_unavailable,
+            shortcut_suggestions,
+            suggestion_items: vec![],
+            cameras,
+            camera_items: vec![],
+            no_cameras,
+            usage,
+            usage_mic,
+            usage_toggles,
+            usage_camera,
+            export_usage,
+            usage_today: Summary::default(),
             launch_at_login,
             show_in_dock,
-            about,
+            preferences,
+            export_diagnostics,
+            open_logs,
             quit,
         };
+        tray.update_usage(&Summary::default());
         Ok(tray)
     }
 
-    pub fn update(&mut self, muted: bool, theme: Theme) -> Result<()> {
-        trace!("Updating tray with {} state", get_mute_menu_text(muted));
-        self.update_icon(muted, theme)?;
+    pub fn update(&mut self, muted: bool, status: MicStatus, theme: Theme) -> Result<()> {
+        trace!("Updating tray with {:?} state", status);
+        self.status = status;
+        self.update_icon(theme)?;
         self.update_menu(muted)?;
+        self.update_tooltip()?;
         Ok(())
     }
 
-    fn update_icon(&mut self, muted: bool, theme: Theme) -> Result<()> {
-        let icon = get_icon(muted, theme)?;
-        self.systray.set_icon(Some(icon))?;
+    fn update_icon(&mut self, theme: Theme) -> Result<()> {
+        let (icon, template) = get_icon(self.status, theme)?;
+        // Template images are macOS only; elsewhere the icon is drawn in color
+        #[cfg(target_os = "macos")]
+        self.systray
+            .set_icon_with_as_template(Some(icon), template)?;
+        #[cfg(not(target_os = "macos"))]
+        {
+            let _ = template;
+            self.systray.set_icon(Some(icon))?;
+        }
         trace!("Updated tray icon");
         Ok(())
     }
 
+    fn update_tooltip(&mut self) -> Result<()> {
+        self.systray
+            .set_tooltip(Some(get_tooltip_text(&self.app_vars, self.status)))
+            .context("Failed to update tray tooltip")
+    }
+
     fn update_menu(&mut self, muted: bool) -> Result<()> {
         self.toggle_mute.set_text(get_mute_menu_text(muted));
         trace!("Updated tray menu");
         Ok(())
     }
 
+    /// List the cameras in their submenu, or a disabled placeholder without any.
+    pub fn update_cameras(&mut self, cameras: &[CameraDevice]) -> Result<()> {
+        let _ = self.cameras.remove(&self.no_cameras);
+        for item in self.camera_items.drain(..) {
+            let _ = self.cameras.remove(&item);
+        }
+        if cameras.is_empty() {
+            self.cameras
+                .append(&self.no_cameras)
+                .context("Failed to append no cameras item")?;
+        }
+        for camera in cameras {
+            let item = CheckMenuItem::new(&camera.name, false, camera.running, None);
+            self.cameras
+                .append(&item)
+                .context("Failed to append camera item")?;
+            self.camera_items.push(item);
+        }
+        trace!("Updated tray cameras");
+        Ok(())
+    }
+
+    /// Show today's time muted and live, toggles, re-mutes and camera time.
+    pub fn update_usage(&mut self, today: &Summary) {
+        self.usage_mic.set_text(tr_args(
+            "tray-usage-mic",
+            &[
+                ("muted", &format_duration(today.muted_secs)),
+                ("live", &format_duration(today.live_secs)),
+            ],
+        ));
+        self.usage_toggles.set_text(tr_args(
+            "tray-usage-toggles",
+            &[
+                ("toggles", &today.toggles.to_string()),
+                ("remutes", &today.remutes.to_string()),
+            ],
+        ));
+        self.usage_camera.set_text(tr_args(
+            "tray-usage-camera",
+            &[("camera", &format_duration(today.camera_secs))],
+        ));
+        self.usage_today = today.clone();
+    }
+
+    /// Re-translate every label after the language changes.
+    pub fn update_language(&mut self, muted: bool) -> Result<()> {
+        self.update_menu(muted)?;
+        self.shortcut_suggestions
+            .set_text(tr("tray-use-shortcut-instead"));
+        self.cameras.set_text(tr("tray-cameras"));
+        self.no_cameras.set_text(tr("tray-no-cameras"));
+        self.usage.set_text(tr("tray-usage"));
+        let today = self.usage_today.clone();
+        self.update_usage(&today);
+        self.export_usage.set_text(tr("tray-export-usage"));
+        self.launch_at_login.set_text(tr("tray-launch-at-login"));
+        self.show_in_dock.set_text(tr("tray-show-in-dock"));
+        self.preferences.set_text(tr("tray-preferences"));
+        self.export_diagnostics
+            .set_text(tr("tray-export-diagnostics"));
+        self.open_logs.set_text(tr("tray-open-logs"));
+        self.quit.set_text(tr("tray-exit"));
+        self.update_tooltip()?;
+        trace!("Updated tray language");
+        Ok(())
+    }
+
     /// Update the displayed keyboard shortcuts after settings change.
-    pub fn update_accelerators(&mut self, mic_shortcut: &ShortcutConfig) -> Result<()> {
+    /// `None` clears the accelerator when no mic hotkey is registered.
+    pub fn update_accelerators(&mut self, mic_shortcut: Option<&ShortcutConfig>) -> Result<()> {
         self.toggle_mute
-            .set_accelerator(Some(accelerator_from_config(mic_shortcut)))
+            .set_accelerator(mic_shortcut.map(accelerator_from_config))
             .context("Failed to update mic accelerator")?;
         Ok(())
     }
 
+    /// Show or clear the "shortcut unavailable" notice below the mute item, with a
+    /// submenu of suggested replacements when any are free.
+    pub fn update_shortcut_status(
+        &mut self,
+        unavailable: Option<&ShortcutConfig>,
+        suggestions: Vec<ShortcutConfig>,
+    ) -> Result<()> {
+        // Removing an item that isn't in the menu is a no-op error; ignore it.
+        let _ = self.menu.remove(&self.shortcut_unavailable);
+        let _ = self.menu.remove(&self.shortcut_suggestions);
+        for (item, _) in self.suggestion_items.drain(..) {
+            let _ = self.shortcut_suggestions.remove(&item);
+        }
+
+        let Some(shortcut) = unavailable else {
+            trace!("Cleared shortcut unavailable notice");
+            return Ok(());
+        };
+
+        self.shortcut_unavailable
+            .set_text(get_shortcut_unavailable_text(shortcut));
+        self.menu
+            .insert(&self.shortcut_unavailable, 1)
+            .context("Failed to insert shortcut unavailable item")?;
+
+        if !suggestions.is_empty() {
+            for suggestion in suggestions {
+                let item = MenuItem::new(suggestion.to_string(), true, None);
+                self.shortcut_suggestions
+                    .append(&item)
+                    .context("Failed to append shortcut suggestion")?;
+                self.suggestion_items.push((item, suggestion));
+            }
+            self.menu
+                .insert(&self.shortcut_suggestions, 2)
+                .context("Failed to insert shortcut suggestions")?;
+        }
+        trace!("Marked shortcut {} unavailable", shortcut);
+        Ok(())
+    }
+
+    /// Look up the suggested shortcut behind a clicked menu item.
+    pub fn suggested_shortcut(&self, id: &MenuId) -> Option<&ShortcutConfig> {
+        self.suggestion_items
+            .iter()
+            .find(|(item, _)| item.id() == id)
+            .map(|(_, shortcut)| shortcut)
+    }
+
     pub fn toggle_mute_id(&self) -> &MenuId {
         self.toggle_mute.id()
     }
@@ -164,8 +392,20 @@ impl Tray {
         self.show_in_dock.id()
     }
 
-    pub fn about_id(&self) -> &MenuId {
-        self.about.id()
+    pub fn preferences_id(&self) -> &MenuId {
+        self.preferences.id()
+    }
+
+    pub fn export_diagnostics_id(&self) -> &MenuId {
+        self.export_diagnostics.id()
+    }
+
+    pub fn open_logs_id(&self) -> &MenuId {
+        self.open_logs.id()
+    }
+
+    pub fn export_usage_id(&self) -> &MenuId {
+        self.export_usage.id()
     }
 
     pub fn quit_id(&self) -> &MenuId {
@@ -186,4 +426,12 @@ mod tests {
     fn test_get_mute_menu_text_unmuted() {
         assert_eq!(get_mute_menu_text(false), "Mute");
     }
+
+    #[test]
+    fn test_get_shortcut_unavailable_text() {
+        assert_eq!(
+            get_shortcut_unavailable_text(&ShortcutConfig::default()),
+            "Shortcut ⇧⌘A unavailable"
+        );
+    }
 }
@@ -1,13 +1,24 @@
+use crate::camera::{self, CameraDevice};
 use crate::config::AppVars;
-use crate::event_loop::{create, EventIds, EventLoopMessage};
+use crate::event_loop::{create, EventIds, EventLoopMessage, EventLoopProxyMessage, Message};
+use crate::frontend::Frontend;
+use crate::mic::MicStatus;
 use crate::popup::Popup;
-use crate::settings::Settings;
+use crate::preferences::Preferences;
+use crate::settings::{Settings, ShortcutConfig};
 use crate::shortcuts::Shortcuts;
+use crate::sound::{Cue, SoundPlayer};
 use crate::tray::Tray;
+use crate::usage::Summary;
 use anyhow::{Context, Result};
 use log::trace;
-use std::sync::atomic::AtomicU32;
-use std::sync::Arc;
+use muda::MenuId;
+use tao::event::WindowEvent;
+use tao::event_loop::EventLoopWindowTarget;
+use tao::window::WindowId;
+
+/// Number of free alternatives offered when the mic shortcut can't be registered.
+const SHORTCUT_SUGGESTION_COUNT: usize = 3;
 
 /// Event loop must remain on the main thread and doesn't implement Copy
 #[allow(dead_code)]
@@ -15,8 +26,12 @@ pub struct UI {
     tray: Tray,
     popup: Popup,
     shortcuts: Shortcuts,
+    preferences: Option<Preferences>,
+    sounds: SoundPlayer,
     mic_muted: bool,
-    camera_muted: bool,
+    mic_status: MicStatus,
+    /// Names of the cameras in use.
+    active_cameras: Vec<String>,
 }
 
 unsafe impl Send for UI {}
@@ -25,105 +40,238 @@ unsafe impl Sync for UI {}
 impl UI {
     pub fn new(
         mic_muted: bool,
-        camera_muted: bool,
+        mic_status: MicStatus,
+        cameras: &[CameraDevice],
         app_vars: AppVars,
         settings: &Settings,
     ) -> Result<(Self, EventLoopMessage, EventIds)> {
         let event_loop = create();
-        let popup = Popup::new(&event_loop, mic_muted).context("Failed to setup popup window")?;
+        let active_cameras = camera::active_names(cameras);
+        let popup = Popup::new(
+            &event_loop,
+            mic_muted,
+            mic_status,
+            &active_cameras,
+            &settings.popup,
+        )
+        .context("Failed to setup popup window")?;
         let theme = popup.get_theme();
-        let tray = Tray::new(
+        let shortcuts = Shortcuts::new(settings).context("Failed to setup shortcuts")?;
+        let mut tray = Tray::new(
             mic_muted,
+            mic_status,
             theme,
             app_vars,
             settings.launch_at_login,
             settings.show_in_dock,
-            &settings.mic_shortcut,
+            shortcuts.mic_shortcut.as_ref(),
         )
         .context("Failed to create system tray")?;
-        let shortcuts = Shortcuts::new(settings).context("Failed to setup shortcuts")?;
+        tray.update_cameras(cameras)?;
 
         let event_ids = EventIds {
             button_toggle_mute: tray.toggle_mute_id().clone(),
             button_launch_at_login: tray.launch_at_login_id().clone(),
             button_show_in_dock: tray.show_in_dock_id().clone(),
-            button_about: tray.about_id().clone(),
+            button_preferences: tray.preferences_id().clone(),
+            button_export_diagnostics: tray.export_diagnostics_id().clone(),
+            button_open_logs: tray.open_logs_id().clone(),
+            button_export_usage: tray.export_usage_id().clone(),
             button_quit: tray.quit_id().clone(),
-            shortcut_mic: Arc::new(AtomicU32::new(shortcuts.mic_hotkey.id())),
         };
 
-        let ui = Self {
+        let mut ui = Self {
             tray,
             popup,
             shortcuts,
+            preferences: None,
+            sounds: SoundPlayer::new(&settings.sounds),
             mic_muted,
-            camera_muted,
+            mic_status,
+            active_cameras,
         };
+        ui.update_shortcut_status()?;
         Ok((ui, event_loop, event_ids))
     }
 
-    pub fn update_mic(
+    /// Redraw the tray icon and popup for the current state, e.g. after the system
+    /// theme changes.
+    pub fn redraw(&mut self) -> Result<()> {
+        self.tray
+            .update(self.mic_muted, self.mic_status, self.popup.get_theme())
+            .context("Failed to redraw UI tray")?;
+        self.popup
+            .update_with_camera(self.mic_muted, self.mic_status, &self.active_cameras, None)
+            .context("Failed to redraw UI popup")?;
+        Ok(())
+    }
+
+    /// Open the Preferences window, or focus it if it's already open.
+    pub fn open_preferences(
+        &mut self,
+        event_loop: &EventLoopWindowTarget<Message>,
+        proxy: EventLoopProxyMessage,
+        settings: &Settings,
+        devices: Vec<String>,
+    ) -> Result<()> {
+        match &self.preferences {
+            Some(preferences) => preferences.focus(),
+            None => {
+                let preferences = Preferences::new(event_loop, proxy, settings, devices)
+                    .context("Failed to open preferences")?;
+                self.preferences = Some(preferences);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if the action changed settings.
+    pub fn preferences_action(&mut self, tag: i64, settings: &mut Settings) -> bool {
+        self.preferences
+            .as_mut()
+            .is_some_and(|preferences| preferences.handle_action(tag, settings))
+    }
+
+    /// Route a window event to the Preferences window. Returns true if settings changed.
+    pub fn preferences_window_event(
+        &mut self,
+        window_id: WindowId,
+        event: &WindowEvent,
+        settings: &mut Settings,
+    ) -> bool {
+        let Some(preferences) = self.preferences.as_mut() else {
+            return false;
+        };
+        if preferences.window_id() != window_id {
+            return false;
+        }
+        if let WindowEvent::CloseRequested = event {
+            self.preferences = None;
+            return false;
+        }
+        preferences.handle_window_event(event, settings)
+    }
+
+    /// The suggested replacement shortcut behind a tray menu item, if any.
+    pub fn suggested_shortcut(&self, id: &MenuId) -> Option<ShortcutConfig> {
+        self.tray.suggested_shortcut(id).cloned()
+    }
+
+    fn update_shortcut_status(&mut self) -> Result<()> {
+        self.tray
+            .update_accelerators(self.shortcuts.mic_shortcut.as_ref())
+            .context("Failed to update tray accelerators")?;
+        let suggestions = self
+            .shortcuts
+            .suggest_alternatives(SHORTCUT_SUGGESTION_COUNT);
+        self.tray
+            .update_shortcut_status(self.shortcuts.mic_unavailable.as_ref(), suggestions)
+            .context("Failed to update tray shortcut status")?;
+        Ok(())
+    }
+
+    pub fn detect(&mut self, event_loop: &EventLoopWindowTarget<Message>) -> Result<&mut Self> {
+        self.popup
+            .detect_monitors(event_loop)
+            .context("Failed to update UI popup placement")?;
+        Ok(self)
+    }
+}
+
+impl Frontend for UI {
+    fn update_mic(
         &mut self,
         muted: bool,
+        status: MicStatus,
         active_device_name: Option<&str>,
-    ) -> Result<&mut Self> {
-        trace!("Updating UI mic state {}", muted);
+    ) -> Result<()> {
+        trace!("Updating UI mic state {:?}", status);
         self.mic_muted = muted;
+        self.mic_status = status;
         self.tray
-            .update(muted, self.popup.get_theme())
+            .update(muted, status, self.popup.get_theme())
             .context("Failed to update UI tray")?;
         self.popup
-            .update_with_camera(muted, self.camera_muted, active_device_name)
+            .update_with_camera(muted, status, &self.active_cameras, active_device_name)
             .context("Failed to update UI popup")?;
-        Ok(self)
+        Ok(())
     }
 
-    pub fn update_camera(&mut self, muted: bool) -> Result<&mut Self> {
-        trace!("Updating UI camera state {}", muted);
-        self.camera_muted = muted;
+    fn update_camera(&mut self, cameras: &[CameraDevice]) -> Result<()> {
+        trace!("Updating UI cameras {:?}", cameras);
+        self.active_cameras = camera::active_names(cameras);
+        self.tray.update_cameras(cameras)?;
         self.popup
-            .update_with_camera(self.mic_muted, muted, None)
+            .update_with_camera(self.mic_muted, self.mic_status, &self.active_cameras, None)
             .context("Failed to update UI popup for camera")?;
-        Ok(self)
+        Ok(())
     }
 
-    pub fn hide_popup(&mut self) -> Result<&mut Self> {
-        self.popup.hide().context("Failed to hide UI popup")?;
-        Ok(self)
+    fn update_usage(&mut self, today: &Summary) -> Result<()> {
+        self.tray.update_usage(today);
+        Ok(())
     }
 
-    /// Apply all settings to the live app state.
-    /// Safe to call whenever settings change — all operations are idempotent.
-    pub fn apply_settings(&mut self, settings: &Settings) -> Result<()> {
-        // Re-register hotkeys and update tray accelerator labels
-        self.shortcuts.reload(settings)?;
-        self.tray
-            .update_accelerators(&settings.mic_shortcut)
-            .context("Failed to update tray accelerators")?;
+    fn play_cue(&mut self, cue: Cue) {
+        self.sounds.play(cue);
+    }
+
+    fn set_popup_visible(&mut self, visible: bool) {
+        self.popup.set_visible(visible);
+    }
+
+    /// All operations are idempotent.
+    fn apply_settings(&mut self, settings: &Settings) -> Result<()> {
+        crate::logging::apply_settings(settings);
+
+        // Re-translate labels when the language override changes
+        if crate::i18n::set_language(settings.language.as_deref()) {
+            self.tray
+                .update_language(self.mic_muted)
+                .context("Failed to update tray language")?;
+            self.redraw()?;
+        }
+
+        // Redraw icons with new colors or icon set
+        if crate::icons::apply_settings(&settings.icons) {
+            self.redraw()?;
+        }
+
+        // Re-register hotkeys and update tray accelerator labels. A shortcut that can't
+        // be registered keeps the previous binding and is reported in the tray instead.
+        if let Err(e) = self.shortcuts.reload(settings) {
+            log::error!("{:#}", e);
+        }
+        self.update_shortcut_status()?;
 
         // Sync dock visibility and its tray checkbox
         self.tray.show_in_dock.set_checked(settings.show_in_dock);
-        crate::launch_at_login::set_dock_visible(settings.show_in_dock);
+        crate::platform::set_dock_visible(settings.show_in_dock);
 
-        // Sync launch-at-login plist and its tray checkbox
+        // Sync the login item and its tray checkbox
         self.tray
             .launch_at_login
             .set_checked(settings.launch_at_login);
-        if let Err(e) = crate::launch_at_login::set(settings.launch_at_login) {
+        if let Err(e) =
+            crate::launch_at_login::set(settings.launch_at_login, settings.login_mechanism)
+        {
             log::error!("Failed to apply launch_at_login setting: {}", e);
         }
 
-        Ok(())
-    }
+        self.popup
+            .apply_settings(&settings.popup)
+            .context("Failed to apply popup settings")?;
+        self.sounds.apply_settings(&settings.sounds);
+
+        // Keep an open Preferences window in step with edits made elsewhere
+        if let Some(preferences) = self.preferences.as_mut() {
+            preferences.sync(settings);
+        }
 
-    pub fn mic_shortcut_id(&self) -> u32 {
-        self.shortcuts.mic_hotkey.id()
+        Ok(())
     }
 
-    pub fn detect(&mut self) -> Result<&mut Self> {
-        self.popup
-            .detect_cursor_monitor()
-            .context("Failed to update UI popup placement")?;
-        Ok(self)
+    fn mic_shortcut_id(&self) -> Option<u32> {
+        self.shortcuts.mic_hotkey_id()
     }
 }
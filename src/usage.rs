@@ -0,0 +1,548 @@
+/// Usage statistics: time spent muted and live, mute toggles, re-mutes by
+/// enforcement and time with a camera on. Records are appended to `usage.jsonl` in
+/// the app's data directory as they happen and summed per local day or week.
+use crate::utils::{civil_from_days, local_offset, UtcTime};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const FILE_NAME: &str = "usage.jsonl";
+/// Records older than this are dropped when the store is opened.
+const RETENTION_DAYS: i64 = 90;
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// One line of the store. Times are seconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Record {
+    /// Time spent in one mic state.
+    Mic {
+        muted: bool,
+        start: i64,
+        end: i64,
+    },
+    /// Time with at least one camera running.
+    Camera {
+        start: i64,
+        end: i64,
+    },
+    Toggle {
+        at: i64,
+    },
+    /// Enforcement found devices unmuted and muted them again.
+    Remute {
+        at: i64,
+        devices: usize,
+    },
+}
+
+impl Record {
+    fn end(&self) -> i64 {
+        match self {
+            Record::Mic { end, .. } | Record::Camera { end, .. } => *end,
+            Record::Toggle { at } | Record::Remute { at, .. } => *at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    /// Weeks start on Monday.
+    Week,
+}
+
+/// Totals for one day or week.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Summary {
+    /// The local date the period starts on, e.g. `2024-03-01`.
+    pub start: String,
+    pub muted_secs: i64,
+    pub live_secs: i64,
+    pub camera_secs: i64,
+    pub toggles: usize,
+    pub remutes: usize,
+}
+
+/// The store, plus the mic and camera states whose spans are still open.
+pub struct UsageStore {
+    /// None when there's no data directory or writing failed.
+    path: Option<PathBuf>,
+    records: Vec<Record>,
+    mic: Option<(bool, i64)>,
+    camera_since: Option<i64>,
+    /// While asleep nothing is counted; open spans restart on resume.
+    paused: bool,
+}
+
+fn unix_secs(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+/// The usage store, e.g. `~/.local/share/mic-mute/usage.jsonl`.
+fn store_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("mic-mute").join(FILE_NAME))
+}
+
+/// Read the records ending after `cutoff`, rewriting the file without older ones.
+/// Lines that don't parse are skipped.
+fn load(path: &Path, cutoff: i64) -> Result<Vec<Record>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    let mut expired = false;
+    let mut records = vec![];
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        match serde_json::from_str::<Record>(line) {
+            Ok(record) if record.end() >= cutoff => records.push(record),
+            Ok(_) => expired = true,
+            Err(e) => log::trace!("Skipping usage record {:?}: {}", line, e),
+        }
+    }
+    if expired {
+        let mut contents = String::new();
+        for record in &records {
+            contents.push_str(&serde_json::to_string(record)?);
+            contents.push('\n');
+        }
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(records)
+}
+
+impl UsageStore {
+    pub fn open(now: SystemTime) -> Self {
+        Self::open_at(store_path(), unix_secs(now))
+    }
+
+    fn open_at(path: Option<PathBuf>, now: i64) -> Self {
+        let records = match &path {
+            Some(path) => load(path, now - RETENTION_DAYS * SECONDS_PER_DAY).unwrap_or_else(|e| {
+                log::error!("Starting usage statistics afresh: {:#}", e);
+                vec![]
+            }),
+            None => vec![],
+        };
+        Self {
+            path,
+            records,
+            mic: None,
+            camera_since: None,
+            paused: false,
+        }
+    }
+
+    fn push(&mut self, record: Record) {
+        if let Some(path) = &self.path {
+            if let Err(e) = append(path, &record) {
+                log::error!("Stopped writing usage statistics: {:#}", e);
+                self.path = None;
+            }
+        }
+        self.records.push(record);
+    }
+
+    /// Note the mic state after a mute or enforcement poll. Returns whether it
+    /// changed anything shown in a summary.
+    pub fn record_mic(&mut self, now: SystemTime, muted: bool, remuted: usize) -> bool {
+        let now = unix_secs(now);
+        let mut changed = false;
+        if remuted > 0 {
+            self.push(Record::Remute {
+                at: now,
+                devices: remuted,
+            });
+            changed = true;
+        }
+        match self.mic {
+            Some((current, _)) if current == muted => {}
+            previous => {
+                if let Some((previous, start)) = previous {
+                    self.push(Record::Toggle { at: now });
+                    if !self.paused {
+                        self.push(Record::Mic {
+                            muted: previous,
+                            start,
+                            end: now,
+                        });
+                    }
+                }
+                self.mic = Some((muted, now));
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Note whether any camera is running. Returns whether that changed.
+    pub fn record_camera(&mut self, now: SystemTime, on: bool) -> bool {
+        let now = unix_secs(now);
+        match (self.camera_since, on) {
+            (None, true) => self.camera_since = Some(now),
+            (Some(start), false) => {
+                if !self.paused {
+                    self.push(Record::Camera { start, end: now });
+                }
+                self.camera_since = None;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Stop counting, e.g. before sleep or quitting, closing the open spans at `now`.
+    pub fn pause(&mut self, now: SystemTime) {
+        if self.paused {
+            return;
+        }
+        let now = unix_secs(now);
+        if let Some((muted, start)) = self.mic {
+            self.push(Record::Mic {
+                muted,
+                start,
+                end: now,
+            });
+        }
+        if let Some(start) = self.camera_since {
+            self.push(Record::Camera { start, end: now });
+        }
+        self.paused = true;
+    }
+
+    /// Start counting again from `now` in the states from before the pause.
+    pub fn resume(&mut self, now: SystemTime) {
+        if !self.paused {
+            return;
+        }
+        let now = unix_secs(now);
+        if let Some((_, start)) = self.mic.as_mut() {
+            *start = now;
+        }
+        if let Some(start) = self.camera_since.as_mut() {
+            *start = now;
+        }
+        self.paused = false;
+    }
+
+    /// Every record, with the open spans ending at `now`.
+    fn records_until(&self, now: i64) -> Vec<Record> {
+        let mut records = self.records.clone();
+        if !self.paused {
+            if let Some((muted, start)) = self.mic {
+                records.push(Record::Mic {
+                    muted,
+                    start,
+                    end: now,
+                });
+            }
+            if let Some(start) = self.camera_since {
+                records.push(Record::Camera { start, end: now });
+            }
+        }
+        records
+    }
+
+    /// Totals per local day or week, oldest first.
+    pub fn summaries(&self, now: SystemTime, period: Period) -> Vec<Summary> {
+        summarize(
+            &self.records_until(unix_secs(now)),
+            local_offset(now),
+            period,
+        )
+    }
+
+    /// Totals for the local day `now` falls on.
+    pub fn today(&self, now: SystemTime) -> Summary {
+        let offset = local_offset(now);
+        let today = format_date(local_day(unix_secs(now), offset));
+        self.summaries(now, Period::Day)
+            .into_iter()
+            .find(|summary| summary.start == today)
+            .unwrap_or(Summary {
+                start: today,
+                ..Summary::default()
+            })
+    }
+
+    /// Write daily and weekly totals to `path`, as JSON when it ends in `.json`
+    /// and CSV otherwise.
+    pub fn export(&self, path: &Path, now: SystemTime) -> Result<()> {
+        let daily = self.summaries(now, Period::Day);
+        let weekly = self.summaries(now, Period::Week);
+        let contents = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::to_string_pretty(&serde_json::json!({
+                "daily": daily,
+                "weekly": weekly,
+            }))?
+        } else {
+            to_csv(&daily, &weekly)
+        };
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+fn append(path: &Path, record: &Record) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// Suggested name for a report exported at `time`.
+pub fn report_file_name(time: SystemTime) -> String {
+    format!(
+        "mic-mute-usage-{}.csv",
+        UtcTime::from_system_time(time).file_stamp()
+    )
+}
+
+fn local_day(secs: i64, offset: i64) -> i64 {
+    (secs + offset).div_euclid(SECONDS_PER_DAY)
+}
+
+fn period_start(day: i64, period: Period) -> i64 {
+    match period {
+        Period::Day => day,
+        // 1970-01-01 was a Thursday
+        Period::Week => day - (day + 3).rem_euclid(7),
+    }
+}
+
+fn format_date(day: i64) -> String {
+    let (year, month, day) = civil_from_days(day);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Sum `records` per period in a time zone `offset` seconds from UTC. Spans that
+/// cross midnight are split between the days.
+fn summarize(records: &[Record], offset: i64, period: Period) -> Vec<Summary> {
+    let mut periods = BTreeMap::new();
+    for record in records {
+        match *record {
+            Record::Mic { muted, start, end } => {
+                for (at, secs) in split_by_day(start, end, offset) {
+                    let summary = summary_at(&mut periods, at, offset, period);
+                    if muted {
+                        summary.muted_secs += secs;
+                    } else {
+                        summary.live_secs += secs;
+                    }
+                }
+            }
+            Record::Camera { start, end } => {
+                for (at, secs) in split_by_day(start, end, offset) {
+                    summary_at(&mut periods, at, offset, period).camera_secs += secs;
+                }
+            }
+            Record::Toggle { at } => summary_at(&mut periods, at, offset, period).toggles += 1,
+            Record::Remute { at, devices } => {
+                summary_at(&mut periods, at, offset, period).remutes += devices;
+            }
+        }
+    }
+    periods.into_values().collect()
+}
+
+/// The summary for the period `secs` falls in, keyed by the period's first day.
+fn summary_at(
+    periods: &mut BTreeMap<i64, Summary>,
+    secs: i64,
+    offset: i64,
+    period: Period,
+) -> &mut Summary {
+    let start = period_start(local_day(secs, offset), period);
+    periods.entry(start).or_insert_with(|| Summary {
+        start: format_date(start),
+        ..Summary::default()
+    })
+}
+
+/// The parts of `start..end` on each local day, as each part's start and length.
+fn split_by_day(start: i64, end: i64, offset: i64) -> Vec<(i64, i64)> {
+    let mut parts = vec![];
+    let mut at = start;
+    while at < end {
+        let next_day = (local_day(at, offset) + 1) * SECONDS_PER_DAY - offset;
+        let until = end.min(next_day);
+        parts.push((at, until - at));
+        at = until;
+    }
+    parts
+}
+
+fn to_csv(daily: &[Summary], weekly: &[Summary]) -> String {
+    let mut csv = String::from("period,start,muted_secs,live_secs,camera_secs,toggles,remutes\n");
+    for (period, summaries) in [("day", daily), ("week", weekly)] {
+        for summary in summaries {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{}",
+                period,
+                summary.start,
+                summary.muted_secs,
+                summary.live_secs,
+                summary.camera_secs,
+                summary.toggles,
+                summary.remutes
+            );
+        }
+    }
+    csv
+}
+
+/// Hours and minutes, e.g. `1h 05m`, or minutes alone under an hour.
+pub fn format_duration(secs: i64) -> String {
+    let minutes = secs.max(0) / 60;
+    if minutes >= 60 {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// 2024-03-01 00:00:00 UTC, a Friday.
+    const MARCH_1: i64 = 1_709_251_200;
+    const HOUR: i64 = 3600;
+
+    fn at(secs: i64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs as u64)
+    }
+
+    #[test]
+    fn test_summarize_splits_spans_at_local_midnight() {
+        let records = vec![
+            Record::Mic {
+                muted: true,
+                start: MARCH_1 - HOUR,
+                end: MARCH_1 + 2 * HOUR,
+            },
+            Record::Toggle {
+                at: MARCH_1 + 2 * HOUR,
+            },
+            Record::Mic {
+                muted: false,
+                start: MARCH_1 + 2 * HOUR,
+                end: MARCH_1 + 3 * HOUR,
+            },
+            Record::Remute {
+                at: MARCH_1 + HOUR,
+                devices: 2,
+            },
+        ];
+
+        let utc = summarize(&records, 0, Period::Day);
+        assert_eq!(utc.len(), 2);
+        assert_eq!(utc[0].start, "2024-02-29");
+        assert_eq!(utc[0].muted_secs, HOUR);
+        assert_eq!(
+            utc[1],
+            Summary {
+                start: "2024-03-01".to_string(),
+                muted_secs: 2 * HOUR,
+                live_secs: HOUR,
+                camera_secs: 0,
+                toggles: 1,
+                remutes: 2,
+            }
+        );
+
+        // Three hours ahead of UTC everything falls on March 1st
+        let ahead = summarize(&records, 3 * HOUR, Period::Day);
+        assert_eq!(ahead.len(), 1);
+        assert_eq!(ahead[0].muted_secs, 3 * HOUR);
+    }
+
+    #[test]
+    fn test_summarize_weeks_start_on_monday() {
+        let records = vec![
+            // Friday and the following Monday
+            Record::Toggle { at: MARCH_1 },
+            Record::Toggle {
+                at: MARCH_1 + 3 * SECONDS_PER_DAY,
+            },
+        ];
+
+        let weeks = summarize(&records, 0, Period::Week);
+
+        assert_eq!(
+            weeks
+                .iter()
+                .map(|week| (week.start.as_str(), week.toggles))
+                .collect::<Vec<_>>(),
+            vec![("2024-02-26", 1), ("2024-03-04", 1)]
+        );
+    }
+
+    #[test]
+    fn test_store_records_spans_and_reloads() {
+        let dir = std::env::temp_dir().join("mic-mute-test-usage");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join(FILE_NAME);
+        let mut store = UsageStore::open_at(Some(path.clone()), MARCH_1);
+
+        assert!(store.record_mic(at(MARCH_1), false, 0));
+        assert!(!store.record_mic(at(MARCH_1 + 60), false, 0));
+        assert!(store.record_mic(at(MARCH_1 + 600), true, 0));
+        assert!(store.record_mic(at(MARCH_1 + 700), true, 1));
+        assert!(store.record_camera(at(MARCH_1 + 100), true));
+        store.pause(at(MARCH_1 + 900));
+        // Asleep for an hour
+        store.resume(at(MARCH_1 + 900 + HOUR));
+        store.pause(at(MARCH_1 + 1000 + HOUR));
+
+        let totals = summarize(&store.records_until(MARCH_1 + 2 * HOUR), 0, Period::Day);
+        assert_eq!(totals[0].live_secs, 600);
+        assert_eq!(totals[0].muted_secs, 400);
+        assert_eq!(totals[0].camera_secs, 900);
+        assert_eq!(totals[0].toggles, 1);
+        assert_eq!(totals[0].remutes, 1);
+
+        let reloaded = UsageStore::open_at(Some(path.clone()), MARCH_1 + 2 * HOUR);
+        assert_eq!(reloaded.records, store.records);
+        // Everything expires after the retention period
+        let expired = UsageStore::open_at(
+            Some(path.clone()),
+            MARCH_1 + (RETENTION_DAYS + 1) * SECONDS_PER_DAY,
+        );
+        assert!(expired.records.is_empty());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_csv_and_duration_formats() {
+        let day = Summary {
+            start: "2024-03-01".to_string(),
+            muted_secs: 3900,
+            live_secs: 60,
+            camera_secs: 0,
+            toggles: 3,
+            remutes: 0,
+        };
+
+        assert_eq!(
+            to_csv(std::slice::from_ref(&day), std::slice::from_ref(&day)),
+            "period,start,muted_secs,live_secs,camera_secs,toggles,remutes\n\
+             day,2024-03-01,3900,60,0,3,0\n\
+             week,2024-03-01,3900,60,0,3,0\n"
+        );
+        assert_eq!(format_duration(3900), "1h 05m");
+        assert_eq!(format_duration(59), "0m");
+    }
+}
@@ -1,34 +1,175 @@
-use libc::c_void;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-type CGFloat = f64;
+pub fn arc_lock<T>(value: T) -> Arc<RwLock<T>> {
+    let rwlock = RwLock::new(value);
+    Arc::new(rwlock)
+}
+
+/// Per-user folder for sockets and state that only last while the app runs:
+/// `$XDG_RUNTIME_DIR/mic-mute`, or a folder named for the user in the temp
+/// directory where there's none, as on macOS.
+pub fn runtime_dir() -> PathBuf {
+    match dirs::runtime_dir() {
+        Some(dir) => dir.join("mic-mute"),
+        None => std::env::temp_dir().join(format!("mic-mute-{}", unsafe { libc::getuid() })),
+    }
+}
+
+/// `runtime_dir`, created readable by the user only. A folder that already
+/// exists is used only if it's a real folder that the user owns and only they
+/// can open, since the temp directory fallback can be created first by anyone.
+pub fn create_runtime_dir() -> Result<PathBuf> {
+    let dir = runtime_dir();
+    if let Err(e) = fs::DirBuilder::new().mode(0o700).create(&dir) {
+        if e.kind() != io::ErrorKind::AlreadyExists {
+            return Err(e).with_context(|| format!("Failed to create {}", dir.display()));
+        }
+    }
+    check_private_dir(&dir)?;
+    Ok(dir)
+}
 
-#[repr(C)]
-struct CGPoint {
-    pub x: CGFloat,
-    pub y: CGFloat,
+fn check_private_dir(dir: &Path) -> Result<()> {
+    let metadata =
+        fs::symlink_metadata(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    if !metadata.file_type().is_dir() {
+        bail!("{} is not a directory", dir.display());
+    }
+    if metadata.uid() != unsafe { libc::getuid() } {
+        bail!("{} is owned by another user", dir.display());
+    }
+    if metadata.mode() & 0o777 != 0o700 {
+        bail!(
+            "{} is open to other users (mode {:o})",
+            dir.display(),
+            metadata.mode() & 0o777
+        );
+    }
+    Ok(())
 }
 
-extern "C" {
-    fn CFRelease(cf: *const c_void);
-    fn CGEventCreate(r: *const c_void) -> *const c_void;
-    fn CGEventGetLocation(e: *const c_void) -> CGPoint;
+/// A UTC date and time split into fields, precise to the millisecond.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UtcTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub millisecond: u32,
 }
 
-pub fn get_cursor_pos() -> Option<(f64, f64)> {
-    unsafe {
-        let event = CGEventCreate(std::ptr::null());
-        if event.is_null() {
-            return None;
+impl UtcTime {
+    /// Times before 1970 are clamped to the epoch.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs = since_epoch.as_secs() as i64;
+        let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+        let secs_of_day = secs.rem_euclid(86_400) as u32;
+        Self {
+            year,
+            month,
+            day,
+            hour: secs_of_day / 3600,
+            minute: secs_of_day % 3600 / 60,
+            second: secs_of_day % 60,
+            millisecond: since_epoch.subsec_millis(),
         }
+    }
 
-        let point = CGEventGetLocation(event);
-        CFRelease(event);
-        Some((point.x, point.y))
+    /// RFC 3339, e.g. `2024-03-01T09:30:00.250Z`.
+    pub fn rfc3339(&self) -> String {
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, self.millisecond
+        )
+    }
+
+    /// Compact and safe in file names, e.g. `20240301-093000`.
+    pub fn file_stamp(&self) -> String {
+        format!(
+            "{:04}{:02}{:02}-{:02}{:02}{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
     }
 }
 
-pub fn arc_lock<T>(value: T) -> Arc<RwLock<T>> {
-    let rwlock = RwLock::new(value);
-    Arc::new(rwlock)
+/// The local time zone's offset from UTC at `time`, in seconds. UTC when the
+/// time zone can't be read.
+pub fn local_offset(time: SystemTime) -> i64 {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&secs, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+/// Year, month and day of a count of days since 1970-01-01, from Howard Hinnant's
+/// `civil_from_days`.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Duration;
+
+    #[test]
+    fn test_utc_time() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_285_400_250);
+        let utc = UtcTime::from_system_time(time);
+        assert_eq!(utc.rfc3339(), "2024-03-01T09:30:00.250Z");
+        assert_eq!(utc.file_stamp(), "20240301-093000");
+        assert_eq!(
+            UtcTime::from_system_time(UNIX_EPOCH).rfc3339(),
+            "1970-01-01T00:00:00.000Z"
+        );
+        // Leap day
+        let leap = UNIX_EPOCH + Duration::from_secs(951_782_400);
+        assert_eq!(
+            UtcTime::from_system_time(leap).file_stamp(),
+            "20000229-000000"
+        );
+    }
+
+    #[test]
+    fn test_private_dir_must_be_owned_and_closed() {
+        let dir = std::env::temp_dir().join("mic-mute-test-private-dir");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(check_private_dir(&dir).is_err());
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)).unwrap();
+        assert!(check_private_dir(&dir).is_ok());
+
+        let link = dir.with_extension("link");
+        let _ = fs::remove_file(&link);
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+        assert!(check_private_dir(&link).is_err());
+        let _ = fs::remove_file(&link);
+        let _ = fs::remove_dir_all(&dir);
+    }
 }